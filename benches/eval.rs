@@ -0,0 +1,89 @@
+//! Times evaluation of long expressions. Run with `cargo bench`; there are no external dependencies,
+//! so this is a plain timing loop rather than a statistical harness.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use shunting_yard::{eval_postfix, AngleMode, Context, Lexer, Locale};
+
+fn long_expression(terms: usize) -> String {
+	let mut expression = String::from("1");
+	for i in 0 .. terms {
+		let op = ['+', '-', '*', '/'][i % 4];
+		expression.push_str(&format!(" {} {}.5", op, i % 97 + 1));
+	}
+	expression
+}
+
+fn bench(name: &str, iterations: u32, mut run: impl FnMut()) {
+	run();
+	let start = Instant::now();
+	for _ in 0 .. iterations {
+		run();
+	}
+	let per_iteration = start.elapsed() / iterations;
+	println!("{:<32} {:>10.2?} per iteration", name, per_iteration);
+}
+
+fn main() {
+	let context = Context::new();
+	for terms in [10, 1000, 10_000] {
+		let expression = long_expression(terms);
+		let iterations = (100_000 / terms as u32).max(20);
+		bench(&format!("eval, {} terms", terms), iterations, || {
+			black_box(context.eval(black_box(&expression)).unwrap());
+		});
+		bench(&format!("to_postfix, {} terms", terms), iterations, || {
+			black_box(context.to_postfix(black_box(&expression)).unwrap());
+		});
+		let rpn = context.to_postfix(&expression).unwrap();
+		bench(&format!("eval_postfix, {} terms", terms), iterations, || {
+			black_box(eval_postfix(black_box(rpn.clone())).unwrap());
+		});
+	}
+	let mut degrees = Context::new();
+	degrees.set_angle_mode(AngleMode::Degrees);
+	let trigonometry = vec!["sin(30) * cos(60) + tan(45)"; 1000].join(" + ");
+	bench("eval, 1000 calls in degrees", 100, || {
+		black_box(degrees.eval(black_box(&trigonometry)).unwrap());
+	});
+	// The same sums with and without a budget, which should cost nothing measurable
+	let sum = "sum_over(k, 1, 1000, k^2 / (k + 1))";
+	bench("sum_over, 1000 terms", 100, || {
+		black_box(context.eval(black_box(sum)).unwrap());
+	});
+	let mut budgeted = Context::new();
+	budgeted.set_budget(Some(1_000_000));
+	bench("sum_over, 1000 terms, budgeted", 100, || {
+		black_box(budgeted.eval(black_box(sum)).unwrap());
+	});
+	let literals = long_expression(50_000);
+	bench("lex, 50000 literals", 20, || {
+		for lexeme in Lexer::new(black_box(&literals), Locale::default()) {
+			black_box(lexeme.unwrap());
+		}
+	});
+	bench("to_postfix, 50000 literals", 20, || {
+		black_box(context.to_postfix(black_box(&literals)).unwrap());
+	});
+	// One formula evaluated a million times with a variable that changes every time: by the token
+	// walker, which parses it each time, and compiled once
+	let formula = "3 * x^2 + 2 * x - sqrt(x) / 4 + max(x, 10)";
+	let mut variables = Context::new();
+	variables.set_var("x", 0.0);
+	let mut x = 0.0;
+	bench("token walker, changing x", 1_000_000, || {
+		x += 1.0;
+		variables.set_var("x", x);
+		black_box(variables.eval(black_box(formula)).unwrap());
+	});
+	let ast = variables.to_ast(formula).unwrap();
+	bench("Expr::eval_in, fixed x", 1_000_000, || {
+		black_box(black_box(&ast).eval_in(&variables));
+	});
+	let mut program = variables.compile(formula).unwrap();
+	bench("Program::eval, changing x", 1_000_000, || {
+		x += 1.0;
+		black_box(program.eval(&[black_box(x)]).unwrap());
+	});
+}
@@ -0,0 +1,159 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::budget::Budget;
+use crate::calculus::{arguments, span_of};
+use crate::{Context, EvalError, Lexeme, LexemeKind, Span};
+
+/// The digits of an integer in `base`, 2 to 36, in lowercase. Every finite integer is written out
+/// exactly, however large.
+pub fn to_digits(value: f64, base: u32) -> String {
+	// An integer from 2^53 up is even, so halving it stays exact
+	let (mut mantissa, mut doublings) = (value.abs(), 0);
+	while mantissa >= 9007199254740992.0 {
+		mantissa /= 2.0;
+		doublings += 1;
+	}
+	// Least significant first
+	let mut digits = Vec::new();
+	let mut rest = mantissa as u64;
+	while rest > 0 || digits.is_empty() {
+		digits.push((rest % base as u64) as u32);
+		rest /= base as u64;
+	}
+	for _ in 0 .. doublings {
+		let mut carry = 0;
+		for digit in digits.iter_mut() {
+			let doubled = *digit * 2 + carry;
+			*digit = doubled % base;
+			carry = doubled / base;
+		}
+		if carry > 0 {
+			digits.push(carry);
+		}
+	}
+	let sign = if value < 0.0 { "-" } else { "" };
+	sign.chars().chain(digits.iter().rev().filter_map(|digit| char::from_digit(*digit, base))).collect()
+}
+
+/// Evaluates the base argument of `tobase` or `frombase`.
+fn base(call: &str, arg: (usize, usize), context: &Context, budget: &Budget) -> Result<u32, EvalError> {
+	match context.eval_within(&call[arg.0 .. arg.1], budget).map_err(|err| err.offset(arg.0))? {
+		base if base.fract() == 0.0 && (2.0 ..= 36.0).contains(&base) => Ok(base as u32),
+		_ => Err(EvalError::InvalidBase(span_of(call, arg)))
+	}
+}
+
+/// `frombase("ff", 16)`, the value of digits in quotes. Spans of errors are relative to `call`.
+pub(crate) fn from_base(call: &str, context: &Context, budget: &Budget) -> Result<f64, EvalError> {
+	let args = arguments(call, context.display().locale.argument_separator());
+	if args.len() != 2 {
+		return Err(EvalError::ArgumentCount { name: "frombase", expected: 2, found: args.len(), span: Span::new(0, call.len()) });
+	}
+	let base = base(call, args[1], context, budget)?;
+	let span = span_of(call, args[0]);
+	let quoted = &call[span.start .. span.end];
+	let Some(digits) = quoted.strip_prefix('"').and_then(|quoted| quoted.strip_suffix('"')) else {
+		return Err(EvalError::ExpectedDigits(span));
+	};
+	let (sign, unsigned) = match digits.strip_prefix('-') {
+		Some(unsigned) => (-1.0, unsigned),
+		None => (1.0, digits)
+	};
+	if unsigned.is_empty() {
+		return Err(EvalError::ExpectedDigits(span));
+	}
+	let start = span.end - 1 - unsigned.len();
+	let mut value = 0.0;
+	for (i, c) in unsigned.char_indices() {
+		let Some(digit) = c.to_digit(base) else {
+			return Err(EvalError::InvalidDigit { digit: c, base, span: Span::at(start + i, c) });
+		};
+		value = value * base as f64 + digit as f64;
+	}
+	Ok(sign * value)
+}
+
+/// `tobase(255, 16)`, the value and the base to show it in. Spans of errors are relative to `call`.
+pub(crate) fn to_base(call: &str, context: &Context, budget: &Budget) -> Result<(f64, u32), EvalError> {
+	let args = arguments(call, context.display().locale.argument_separator());
+	if args.len() != 2 {
+		return Err(EvalError::ArgumentCount { name: "tobase", expected: 2, found: args.len(), span: Span::new(0, call.len()) });
+	}
+	let (arg, base) = (args[0], base(call, args[1], context, budget)?);
+	match context.eval_within(&call[arg.0 .. arg.1], budget).map_err(|err| err.offset(arg.0))? {
+		value if value.fract() == 0.0 => Ok((value, base)),
+		_ => Err(EvalError::ExpectedInteger(span_of(call, arg)))
+	}
+}
+
+/// The base to show the result of `expression` in, when it is a call of `tobase` and nothing else.
+pub(crate) fn result_base(expression: &str, context: &Context, budget: &Budget) -> Result<Option<u32>, EvalError> {
+	let mut lexemes = context.lexer(expression);
+	match (lexemes.next(), lexemes.next()) {
+		(Some(Ok(Lexeme { kind: LexemeKind::Form, span })), None) if expression[span.start ..].starts_with("tobase") => {
+			let call = &expression[span.start .. span.end];
+			to_base(call, context, budget).map(|(_, base)| Some(base)).map_err(|err| err.offset(span.start))
+		}
+		_ => Ok(None)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::EvalOutcome;
+
+	#[test]
+	fn digits_are_written_in_each_base() {
+		let cases = [(10.0, 2, "1010"), (511.0, 8, "777"), (255.0, 16, "ff"), (1295.0, 36, "zz"), (0.0, 16, "0"), (-255.0, 16, "-ff")];
+		for (value, base, digits) in cases {
+			assert_eq!(to_digits(value, base), digits, "{} in base {}", value, base);
+		}
+		assert_eq!(to_digits(2f64.powi(64), 16), "10000000000000000");
+		assert_eq!(to_digits(1e20, 10), "100000000000000000000");
+	}
+
+	#[test]
+	fn digits_are_read_back_mid_expression() {
+		let context = Context::new();
+		let cases = [
+			("frombase(\"1010\", 2)", 10.0),
+			("frombase(\"777\", 8)", 511.0),
+			("frombase(\"ff\", 16) + 1", 256.0),
+			("frombase(\"FF\", 16)", 255.0),
+			("frombase(\"zz\", 36)", 1295.0),
+			("frombase(\"-101\", 2) * 2", -10.0)
+		];
+		for (expression, value) in cases {
+			assert_eq!(context.eval(expression).unwrap(), value, "{}", expression);
+		}
+	}
+
+	#[test]
+	fn tobase_results_are_shown_as_digits() {
+		let mut context = Context::new();
+		for (expression, shown) in [("tobase(255, 16)", "ff"), ("tobase(255, 2)", "11111111"), ("tobase(64, 8)", "100"), ("tobase(1295, 36)", "zz")] {
+			let Ok(EvalOutcome::Value(value)) = context.execute(expression) else { panic!("{}", expression) };
+			assert_eq!(context.format_result(value), shown, "{}", expression);
+		}
+		let Ok(EvalOutcome::Value(value)) = context.execute("ans + 1") else { panic!() };
+		assert_eq!(context.format_result(value), "1296");
+	}
+
+	#[test]
+	fn digits_and_bases_are_checked() {
+		let context = Context::new();
+		let err = context.eval("frombase(\"12\", 2)").unwrap_err();
+		assert!(matches!(err, EvalError::InvalidDigit { digit: '2', base: 2, span } if span == Span::new(11, 12)), "{}", err);
+		assert!(matches!(context.eval("frombase(\"g\", 16)"), Err(EvalError::InvalidDigit { digit: 'g', base: 16, .. })));
+		assert!(matches!(context.eval("frombase(ff, 16)"), Err(EvalError::ExpectedDigits(_))));
+		assert!(matches!(context.eval("frombase(\"\", 16)"), Err(EvalError::ExpectedDigits(_))));
+		assert!(matches!(context.eval("tobase(2.5, 2)"), Err(EvalError::ExpectedInteger(span)) if span == Span::new(7, 10)));
+		assert!(matches!(context.eval("tobase(10, 37)"), Err(EvalError::InvalidBase(_))));
+		assert!(matches!(context.eval("tobase(10, 1)"), Err(EvalError::InvalidBase(_))));
+		assert!(matches!(context.eval("tobase(255, 16) + 1"), Err(EvalError::DigitsInExpression(_))));
+	}
+}
@@ -0,0 +1,362 @@
+use std::io::{self, BufRead, BufWriter, Write};
+use std::num::NonZeroUsize;
+use std::panic;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::{assigned_name, Context, EvalError, EvalOutcome};
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct BatchOptions {
+	/// Stop at the first line that fails.
+	pub fail_fast: bool,
+	/// Write a progress line to stderr after every this many lines.
+	pub progress: Option<usize>,
+	/// Measure every line and the whole batch into [`BatchStats`]. The clock is only read when
+	/// timing, it is not available on every target.
+	pub timed: bool,
+	/// Record the timed lines and batch as taking no time, for output that does not change between
+	/// runs.
+	pub frozen_clock: bool
+}
+
+/// How many of the slowest lines [`BatchStats`] keeps.
+pub const SLOWEST_LINES: usize = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineTime {
+	/// 1-based.
+	pub number: usize,
+	pub line: String,
+	pub time: Duration
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct BatchStats {
+	/// Every line read, including blank ones.
+	pub lines: usize,
+	pub ok: usize,
+	pub errors: usize,
+	/// The smallest and largest result of the lines that succeeded, `None` before any did.
+	pub min: Option<f64>,
+	pub max: Option<f64>,
+	/// The sum of the results of the lines that succeeded.
+	pub sum: f64,
+	/// The whole batch, when timed.
+	pub elapsed: Option<Duration>,
+	/// The slowest timed lines, slowest first, failed ones included.
+	pub slowest: Vec<LineTime>
+}
+impl BatchStats {
+	/// Counts a line, with its result if it succeeded and how long it took if it was timed.
+	pub fn record(&mut self, number: usize, line: &str, result: Option<f64>, time: Option<Duration>) {
+		match result {
+			Some(value) => {
+				self.ok += 1;
+				self.min = Some(self.min.map_or(value, |min| min.min(value)));
+				self.max = Some(self.max.map_or(value, |max| max.max(value)));
+				self.sum += value;
+			}
+			None => self.errors += 1
+		}
+		let Some(time) = time else {
+			return;
+		};
+		let index = self.slowest.iter().position(|slow| slow.time < time).unwrap_or(self.slowest.len());
+		if index < SLOWEST_LINES {
+			self.slowest.insert(index, LineTime { number, line: line.to_string(), time });
+			self.slowest.truncate(SLOWEST_LINES);
+		}
+	}
+	/// The mean result of the lines that succeeded.
+	pub fn mean(&self) -> Option<f64> {
+		(self.ok > 0).then(|| self.sum / self.ok as f64)
+	}
+}
+
+/// Evaluates one expression per line, writing one output line for each value: the formatted result
+/// or `error: message`. Assignments and blank lines produce no output, a `solve` gives `x = 2`.
+pub fn eval_lines<R: BufRead, W: Write>(
+	reader: R, writer: W, context: &mut Context, options: &BatchOptions
+) -> io::Result<BatchStats> {
+	eval_lines_with(reader, writer, context, options, |out, context, _, line| {
+		match context.execute(line) {
+			Ok(EvalOutcome::Value(value)) => writeln!(out, "{}", context.format_result(value))?,
+			Ok(EvalOutcome::Solved(name, value)) => writeln!(out, "{} = {}", name, context.format(value))?,
+			Ok(EvalOutcome::Multiple(values)) => writeln!(out, "{}", context.format_list(values))?,
+			Ok(EvalOutcome::Assigned(..) | EvalOutcome::AssignedMultiple(_)) => {}
+			Err(err) => {
+				writeln!(out, "error: {}", err)?;
+				return Ok(false);
+			}
+		}
+		Ok(true)
+	})
+}
+
+/// Reads `reader` line by line into a single reused buffer and calls `evaluate` with the 1-based line
+/// number of every non-blank line, which reports whether it succeeded. The result of a line that
+/// succeeded is taken from `ans`. Output is buffered and flushed before returning.
+pub fn eval_lines_with<R, W, F>(
+	mut reader: R, writer: W, context: &mut Context, options: &BatchOptions, mut evaluate: F
+) -> io::Result<BatchStats>
+where
+	R: BufRead,
+	W: Write,
+	F: FnMut(&mut BufWriter<W>, &mut Context, usize, &str) -> io::Result<bool>
+{
+	let mut out = BufWriter::new(writer);
+	let mut stats = BatchStats::default();
+	let mut line = String::new();
+	let start = options.timed.then(Instant::now);
+	let elapsed = |start: Instant| if options.frozen_clock { Duration::ZERO } else { start.elapsed() };
+	loop {
+		line.clear();
+		if reader.read_line(&mut line)? == 0 {
+			break;
+		}
+		stats.lines += 1;
+		let text = clean_line(&line, stats.lines == 1);
+		if !text.trim().is_empty() {
+			let line_start = options.timed.then(Instant::now);
+			let success = evaluate(&mut out, context, stats.lines, text)?;
+			let time = line_start.map(elapsed);
+			let result = if success { Some(context.answer().unwrap_or(f64::NAN)) } else { None };
+			stats.record(stats.lines, text.trim(), result, time);
+		}
+		if options.progress.is_some_and(|every| every > 0 && stats.lines % every == 0) {
+			eprintln!("{} lines, {} errors", stats.lines, stats.errors);
+		}
+		if stats.errors > 0 && options.fail_fast {
+			break;
+		}
+	}
+	out.flush()?;
+	stats.elapsed = start.map(elapsed);
+	Ok(stats)
+}
+
+/// A line as read, without its line ending, `\n` or `\r\n`, and on the first line of a file
+/// without the byte order mark some Windows tools write.
+pub fn clean_line(line: &str, first: bool) -> &str {
+	let line = line.strip_suffix('\n').map_or(line, |line| line.strip_suffix('\r').unwrap_or(line));
+	if first {
+		return line.strip_prefix('\u{feff}').unwrap_or(line);
+	}
+	line
+}
+
+/// Evaluates independent expressions on every available core. Results are in input order.
+pub fn eval_batch_parallel(expressions: &[&str], context: &Context) -> Vec<Result<f64, EvalError>> {
+	let jobs = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+	eval_batch_jobs(expressions, context, jobs)
+}
+
+/// Evaluates independent expressions in `jobs` contiguous chunks, one thread each. The context is
+/// only read, so an assignment is not evaluated, its result is
+/// [`EvalError::UnexpectedAssignment`], and `ans` keeps its value throughout.
+pub fn eval_batch_jobs(expressions: &[&str], context: &Context, jobs: usize) -> Vec<Result<f64, EvalError>> {
+	execute_batch_jobs(expressions, context, jobs, |scratch, _, expression| match assigned_name(expression, scratch) {
+		Some(name) => Err(EvalError::UnexpectedAssignment(name.to_string())),
+		None => scratch.execute(expression).map(|outcome| outcome.value())
+	})
+}
+
+/// Calls `run` with every statement and its index in `jobs` contiguous chunks, one thread each, for
+/// executing it in every mode as [`Context::execute`] does. Each statement is given its own copy of
+/// `context`, so none sees what another did. Results are in input order.
+pub fn execute_batch_jobs<T, F>(statements: &[&str], context: &Context, jobs: usize, run: F) -> Vec<T>
+where
+	T: Send,
+	F: Fn(&mut Context, usize, &str) -> T + Sync
+{
+	// Every statement starts from the same state, without the tape and cache it would copy
+	let mut base = context.clone();
+	base.tape_mut().clear();
+	base.cache = None;
+	let execute = |(index, statement): (usize, &&str)| run(&mut base.clone(), index, statement);
+	let chunk_size = statements.len().div_ceil(jobs.max(1)).max(1);
+	if chunk_size >= statements.len() {
+		return statements.iter().enumerate().map(execute).collect();
+	}
+	let execute = &execute;
+	thread::scope(|scope| {
+		let workers: Vec<_> = statements.chunks(chunk_size).enumerate().map(|(index, chunk)| {
+			let offset = index * chunk_size;
+			scope.spawn(move || chunk.iter().enumerate().map(|(index, statement)| execute((offset + index, statement))).collect::<Vec<_>>())
+		}).collect();
+		let mut results = Vec::with_capacity(statements.len());
+		for worker in workers {
+			results.extend(worker.join().unwrap_or_else(|payload| panic::resume_unwind(payload)));
+		}
+		results
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Expressions that do not depend on each other, from a fixed pattern of operands and operators.
+	fn generated(count: usize) -> Vec<String> {
+		const OPERATORS: [&str; 5] = ["+", "-", "*", "/", "^"];
+		(0 .. count).map(|i| {
+			let (a, b, c) = (i % 17, i % 5 + 1, i % 3);
+			match i % 4 {
+				0 => format!("{} {} {}", a, OPERATORS[i % 5], b),
+				1 => format!("sqrt({}) * x - {}", a, c),
+				2 => format!("max({}, {}) / ({} - {})", a, b, c, c),
+				_ => format!("({} + ans) {} {}%", a, OPERATORS[(i / 4) % 5], b * 10)
+			}
+		}).collect()
+	}
+
+	fn same(serial: &Result<f64, EvalError>, parallel: &Result<f64, EvalError>) -> bool {
+		match (serial, parallel) {
+			(Ok(a), Ok(b)) => a == b || (a.is_nan() && b.is_nan()),
+			(Err(a), Err(b)) => a.to_string() == b.to_string(),
+			_ => false
+		}
+	}
+
+	#[test]
+	fn parallel_results_match_serial_ones_in_order() {
+		let mut context = Context::new();
+		context.set_var("x", 2.5);
+		context.set_answer(7.0);
+		let expressions = generated(3000);
+		let expressions: Vec<&str> = expressions.iter().map(String::as_str).collect();
+		let serial: Vec<_> = expressions.iter().map(|expression| context.eval(expression)).collect();
+		for jobs in [1, 3, 8, 5000] {
+			let parallel = eval_batch_jobs(&expressions, &context, jobs);
+			assert_eq!(parallel.len(), serial.len());
+			for (index, (serial, parallel)) in serial.iter().zip(&parallel).enumerate() {
+				assert!(same(serial, parallel), "{} with {} jobs: {:?} != {:?}", expressions[index], jobs, serial, parallel);
+			}
+		}
+		assert_eq!(context.answer(), Some(7.0));
+		assert!(eval_batch_parallel(&[], &context).is_empty());
+	}
+
+	#[test]
+	fn assignments_are_refused() {
+		let context = Context::new();
+		let results = eval_batch_parallel(&["1 + 1", "x = 2", "y, z = 3, 4", "x"], &context);
+		assert_eq!(*results[0].as_ref().unwrap(), 2.0);
+		assert!(matches!(&results[1], Err(EvalError::UnexpectedAssignment(name)) if name == "x"));
+		assert!(matches!(&results[2], Err(EvalError::UnexpectedAssignment(name)) if name == "y"));
+		assert!(matches!(&results[3], Err(EvalError::UnknownIdentifier(name, _)) if name == "x"));
+		assert_eq!(assigned_name("x^2 = 4", &context), None);
+		assert_eq!(assigned_name("2 + 2", &context), None);
+	}
+
+	#[test]
+	fn statements_are_executed_in_the_mode() {
+		let mut context = Context::new();
+		context.set_money(true);
+		assert_eq!(*eval_batch_jobs(&["1.005 * 3", "1"], &context, 2)[0].as_ref().unwrap(), 3.03);
+		let mut context = Context::new();
+		context.set_uncertainty(true);
+		let shown = execute_batch_jobs(&["12.30 ± 0.20", "1h + 30m", "5 [kg] * 2", "2"], &context, 4, |context, index, statement| {
+			let value = context.execute(statement).unwrap().value();
+			(index, context.format_result(value))
+		});
+		let expected = [(0, "12.30 ± 0.20"), (1, "1h 30m"), (2, "10 [kg]"), (3, "2")];
+		assert_eq!(shown, expected.map(|(index, text)| (index, text.to_string())));
+	}
+
+	#[test]
+	fn large_inputs_stream_through_with_their_stats() {
+		let mut input = String::new();
+		for i in 0 .. 100_000 {
+			match i % 1000 {
+				0 => input.push_str("1 +\n"),
+				1 => input.push('\n'),
+				_ => input.push_str(&format!("{} * 2\r\n", i % 10))
+			}
+		}
+		let mut out = Vec::new();
+		let stats = eval_lines(input.as_bytes(), &mut out, &mut Context::new(), &BatchOptions::default()).unwrap();
+		assert_eq!((stats.lines, stats.ok, stats.errors), (100_000, 99_800, 100));
+		assert_eq!((stats.min, stats.max), (Some(0.0), Some(18.0)));
+		let out = String::from_utf8(out).unwrap();
+		assert_eq!(out.lines().count(), 99_900);
+		assert!(out.starts_with("error: incomplete expression: expected a value after '+'\n4\n6\n"));
+		let options = BatchOptions { fail_fast: true, ..BatchOptions::default() };
+		let stats = eval_lines("2\n3\n1 +\n4\n".as_bytes(), io::sink(), &mut Context::new(), &options).unwrap();
+		assert_eq!((stats.lines, stats.ok, stats.errors, stats.sum), (3, 2, 1, 5.0));
+	}
+
+	#[test]
+	fn timed_batches_keep_the_slowest_lines() {
+		let options = BatchOptions { timed: true, frozen_clock: true, ..BatchOptions::default() };
+		let stats = eval_lines("\u{feff}x = 3\nx * 2\nx +\n".as_bytes(), io::sink(), &mut Context::new(), &options).unwrap();
+		assert_eq!(stats.elapsed, Some(Duration::ZERO));
+		assert_eq!(stats.slowest.iter().map(|slow| slow.number).collect::<Vec<_>>(), [1, 2, 3]);
+		assert_eq!(stats.slowest[0].line, "x = 3");
+		assert_eq!(stats.mean(), Some(4.5));
+	}
+
+	#[test]
+	fn one_job_and_untimed_batches_need_no_threads_or_clock() {
+		let context = Context::new();
+		let caller = thread::current().id();
+		let threads = execute_batch_jobs(&["1", "2", "3"], &context, 1, |_, _, _| thread::current().id());
+		assert!(threads.iter().all(|thread| *thread == caller));
+		let threads = execute_batch_jobs(&["1"], &context, 8, |_, _, _| thread::current().id());
+		assert_eq!(threads, [caller]);
+		let stats = eval_lines("1\n2 +\n".as_bytes(), io::sink(), &mut Context::new(), &BatchOptions::default()).unwrap();
+		assert_eq!((stats.elapsed, stats.slowest.len()), (None, 0));
+		assert_eq!(crate::trace::Stopwatch::start(false).elapsed(), Duration::ZERO);
+	}
+
+	#[test]
+	fn windows_line_endings_and_blank_lines_read_cleanly() {
+		assert_eq!(clean_line("\u{feff}1 + 1\r\n", true), "1 + 1");
+		assert_eq!(clean_line("\u{feff}1 + 1\r\n", false), "\u{feff}1 + 1");
+		assert_eq!(clean_line("2\r", true), "2\r");
+		assert_eq!(clean_line("2\r\r\n", false), "2\r");
+		let mut out = Vec::new();
+		let input = "\u{feff}1 + 1\r\n   \r\n\t\r\n\r\n2\t*\t3\r\n";
+		let stats = eval_lines(input.as_bytes(), &mut out, &mut Context::new(), &BatchOptions::default()).unwrap();
+		assert_eq!(String::from_utf8(out).unwrap(), "2\n6\n");
+		assert_eq!((stats.ok, stats.errors), (2, 0));
+	}
+
+	#[test]
+	fn control_characters_other_than_tab_are_invalid() {
+		let context = Context::new();
+		assert_eq!(context.eval("1\t+\t2\r").unwrap(), 3.0);
+		for (expression, c, at) in [("1 +\u{0} 2", '\u{0}', 3), ("\u{1b}1", '\u{1b}', 0), ("1\u{7f}", '\u{7f}', 1), ("2 \u{feff}", '\u{feff}', 2)] {
+			let err = context.eval(expression).unwrap_err();
+			assert!(matches!(err, EvalError::InvalidCharacter(found, span) if found == c && span.start == at), "{:?}: {}", expression, err);
+		}
+	}
+
+	#[test]
+	fn the_stats_count_every_line_and_summarize_only_the_successes() {
+		let input = "10\n1 +\nx = -5\nsqrt(\n\n2 * 3\n1e300 * nope\n";
+		let mut out = Vec::new();
+		let stats = eval_lines(input.as_bytes(), &mut out, &mut Context::new(), &BatchOptions::default()).unwrap();
+		assert_eq!((stats.lines, stats.ok, stats.errors), (7, 3, 3));
+		assert_eq!((stats.min, stats.max, stats.sum), (Some(-5.0), Some(10.0), 11.0));
+		assert_eq!(stats.mean(), Some(11.0 / 3.0));
+		assert_eq!(String::from_utf8(out).unwrap().lines().filter(|line| !line.starts_with("error")).collect::<Vec<_>>(), ["10", "6"]);
+		let stats = eval_lines("1 +\n\n".as_bytes(), io::sink(), &mut Context::new(), &BatchOptions::default()).unwrap();
+		assert_eq!((stats.ok, stats.errors, stats.min, stats.mean()), (0, 1, None, None));
+	}
+
+	#[test]
+	fn only_the_slowest_lines_are_kept_slowest_first() {
+		let mut stats = BatchStats::default();
+		for (number, millis) in [(1, 5), (2, 9), (3, 1), (4, 7), (5, 9)] {
+			let result = (number != 2).then_some(number as f64);
+			stats.record(number, &format!("line {}", number), result, Some(Duration::from_millis(millis)));
+		}
+		stats.record(6, "untimed", Some(1.0), None);
+		let slowest: Vec<(usize, u128)> = stats.slowest.iter().map(|slow| (slow.number, slow.time.as_millis())).collect();
+		assert_eq!(slowest, [(2, 9), (5, 9), (4, 7)]);
+		assert_eq!(stats.slowest[0].line, "line 2");
+		assert_eq!((stats.ok, stats.errors, stats.min, stats.max), (5, 1, Some(1.0), Some(5.0)));
+	}
+}
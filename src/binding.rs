@@ -0,0 +1,138 @@
+use alloc::string::ToString;
+
+use crate::budget::Budget;
+use crate::{is_identifier, Context, EvalError};
+
+/// Byte ranges of the parts of `let NAME = VALUE in BODY`, which evaluates BODY with the variable
+/// NAME set to VALUE. The lexer reads it as one lexeme, like a calculus form.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub(crate) struct Let {
+	pub(crate) name: (usize, usize),
+	pub(crate) value: (usize, usize),
+	pub(crate) body: (usize, usize)
+}
+
+/// The `let` at the start of `input`, or `None` if it does not start with one. The value ends at
+/// the `in` outside parentheses that is not the one of a `let` within it, and the body reaches as
+/// far as it can: to the end of `input` or of the parentheses or argument the `let` is in.
+pub(crate) fn split(input: &str, separator: char) -> Option<Let> {
+	let rest = input.strip_prefix("let")?;
+	let name_start = input.len() - rest.trim_start().len();
+	let name_end = input[name_start ..].find(|c: char| !is_word(c)).map_or(input.len(), |end| name_start + end);
+	if name_start == 3 || !is_identifier(&input[name_start .. name_end]) {
+		return None;
+	}
+	let value_start = name_end + input[name_end ..].find(|c: char| !c.is_whitespace())?;
+	if !input[value_start ..].starts_with('=') {
+		return None;
+	}
+	let value_start = value_start + 1;
+	let (mut depth, mut quoted, mut lets) = (0, false, 1);
+	// The end of the value and the start of the body, once the `in` is found
+	let mut split = None;
+	let mut chars = input[value_start ..].char_indices().map(|(i, c)| (value_start + i, c)).peekable();
+	while let Some((i, c)) = chars.next() {
+		match c {
+			'"' => quoted = !quoted,
+			_ if quoted => {}
+			'(' => depth += 1,
+			_ if depth == 0 && (c == ')' || c == separator) => {
+				return split.map(|(value_end, body_start)| Let { name: (name_start, name_end), value: (value_start, value_end), body: (body_start, i) });
+			}
+			')' => depth -= 1,
+			_ if is_word(c) => {
+				let mut end = i + c.len_utf8();
+				while let Some((j, c)) = chars.next_if(|&(_, c)| is_word(c)) {
+					end = j + c.len_utf8();
+				}
+				match &input[i .. end] {
+					_ if depth > 0 || split.is_some() => {}
+					"let" => lets += 1,
+					"in" if lets == 1 => split = Some((i, end)),
+					"in" => lets -= 1,
+					_ => {}
+				}
+			}
+			_ => {}
+		}
+	}
+	split.map(|(value_end, body_start)| Let { name: (name_start, name_end), value: (value_start, value_end), body: (body_start, input.len()) })
+}
+
+fn is_word(c: char) -> bool {
+	c.is_alphanumeric() || c == '_'
+}
+
+/// Evaluates `text`, a `let` as the lexer found it, within `budget`. The name may not be
+/// [protected](Context::protect).
+pub(crate) fn evaluate(text: &str, context: &Context, budget: &Budget) -> Result<f64, EvalError> {
+	let Some(Let { name, value, body }) = split(text, context.display().locale.argument_separator()) else {
+		unreachable!()
+	};
+	let name = &text[name.0 .. name.1];
+	if context.is_protected(name) {
+		return Err(EvalError::Protected(name.to_string()));
+	}
+	let value = context.eval_within(&text[value.0 .. value.1], budget).map_err(|err| err.offset(value.0))?;
+	let mut scope = context.clone();
+	scope.set_var(name, value);
+	scope.eval_within(&text[body.0 .. body.1], budget).map_err(|err| err.offset(body.0))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Span;
+
+	/// The name, value and body of the `let` at the start of `input`.
+	fn parts(input: &str) -> Option<[&str; 3]> {
+		split(input, ',').map(|Let { name, value, body }| [name, value, body].map(|(start, end)| input[start .. end].trim()))
+	}
+
+	#[test]
+	fn the_value_ends_at_its_in_and_the_body_at_its_group() {
+		assert_eq!(parts("let x = 2 in x * x"), Some(["x", "2", "x * x"]));
+		assert_eq!(parts("let r = (1 + 2) in min(r, 1), 5"), Some(["r", "(1 + 2)", "min(r, 1)"]));
+		assert_eq!(parts("let a = let b = 1 in b + 1 in a * 2) + 3"), Some(["a", "let b = 1 in b + 1", "a * 2"]));
+		assert_eq!(parts("let v = convert(x, in, cm) in v"), Some(["v", "convert(x, in, cm)", "v"]));
+		assert_eq!(parts("let d = frombase(\"in\", 36) in d"), Some(["d", "frombase(\"in\", 36)", "d"]));
+		assert_eq!(parts("let w = 2in in w"), Some(["w", "2in", "w"]));
+		for input in ["let x = 2", "let = 2 in 1", "letx = 1 in x", "let 2 = 1 in 3", "let x in x", "(let x = 1) in x", "lets"] {
+			assert_eq!(parts(input), None, "{}", input);
+		}
+	}
+
+	#[test]
+	fn the_body_sees_the_name_and_nothing_else_does() {
+		let mut context = Context::new();
+		context.set_var("x", 10.0);
+		assert_eq!(context.eval("let x = 2 in x * x").unwrap(), 4.0);
+		assert_eq!(context.eval("(let x = 2 in x * x) + x").unwrap(), 14.0);
+		assert_eq!(context.eval("let y = x + 1 in let x = y * 2 in x + y").unwrap(), 33.0);
+		assert_eq!(context.eval("max(let a = 3 in a ^ 2, 5) - 1").unwrap(), 8.0);
+		assert_eq!(context.eval("sum_over(k, 1, 3, let j = 2 * k in j + 1)").unwrap(), 15.0);
+		assert_eq!(context.get_var("y"), None);
+		assert_eq!(context.execute("z = let t = 4 in sqrt(t)").unwrap().value(), 2.0);
+		assert_eq!(context.get_var("z"), Some(2.0));
+	}
+
+	#[test]
+	fn errors_point_into_the_let() {
+		let context = Context::new();
+		assert!(matches!(context.eval("1 + (let a = 2 in a * b)"), Err(EvalError::UnknownIdentifier(name, span)) if name == "b" && span == Span::new(22, 23)));
+		assert!(matches!(context.eval("let a = 2 * in a"), Err(EvalError::IncompleteExpression('*', span)) if span.start == 10));
+		assert!(matches!(context.eval("let x = 1"), Err(EvalError::UnknownIdentifier(name, _)) if name == "let"));
+		let mut sandbox = Context::sandboxed();
+		assert!(matches!(sandbox.execute("let pi = 3 in pi"), Err(EvalError::Protected(name)) if name == "pi"));
+	}
+
+	#[test]
+	fn a_let_is_kept_whole_when_written_back() {
+		let context = Context::new();
+		let ast = context.to_ast("(let x = 2 in x * x) * 3").unwrap();
+		assert_eq!(crate::to_infix(&ast), "(let x = 2 in x * x)*3");
+		assert_eq!(context.eval(&crate::to_infix(&ast)).unwrap(), 12.0);
+		assert_eq!(context.to_canonical_string("( let x=2 in x*x )*3").unwrap(), "(let x = 2 in x * x) * 3");
+		assert_eq!(context.to_canonical_string("let a=(let b=1 in b)in a+b").unwrap(), "let a = (let b = 1 in b) in a + b");
+	}
+}
@@ -0,0 +1,89 @@
+use core::cell::Cell;
+
+use crate::{EvalError, Span};
+
+/// The operations one evaluation may spend, shared with the expressions it evaluates in turn such
+/// as the terms of a `sum_over`. Each RPN token reduced costs one. It also counts how deep those
+/// evaluations within evaluations are.
+pub(crate) struct Budget {
+	limit: Option<u64>,
+	spent: Cell<u64>,
+	depth: Cell<usize>
+}
+impl Budget {
+	pub(crate) fn new(limit: Option<u64>) -> Self {
+		Self { limit, spent: Cell::new(0), depth: Cell::new(0) }
+	}
+	/// Goes `levels` deeper until the returned guard is dropped, failing with
+	/// [`EvalError::TooDeep`] at `span` beyond `limit`.
+	pub(crate) fn nest(&self, levels: usize, limit: usize, span: Span) -> Result<Nested<'_>, EvalError> {
+		let depth = self.depth.get() + levels;
+		if depth > limit {
+			return Err(EvalError::TooDeep { limit, span });
+		}
+		self.depth.set(depth);
+		Ok(Nested { budget: self, levels })
+	}
+	pub(crate) fn spend(&self, operations: usize) -> Result<(), EvalError> {
+		let spent = self.spent.get() + operations as u64;
+		self.spent.set(spent);
+		match self.limit {
+			Some(limit) if spent > limit => Err(EvalError::BudgetExceeded { spent, limit }),
+			_ => Ok(())
+		}
+	}
+}
+
+pub(crate) struct Nested<'a> {
+	budget: &'a Budget,
+	levels: usize
+}
+impl Drop for Nested<'_> {
+	fn drop(&mut self) {
+		self.budget.depth.set(self.budget.depth.get() - self.levels);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Context;
+
+	#[test]
+	fn spending_past_the_limit_fails_with_what_was_spent() {
+		let budget = Budget::new(Some(10));
+		assert!(budget.spend(4).is_ok() && budget.spend(6).is_ok());
+		assert!(matches!(budget.spend(3), Err(EvalError::BudgetExceeded { spent: 13, limit: 10 })));
+		let unlimited = Budget::new(None);
+		assert!(unlimited.spend(usize::MAX).is_ok());
+	}
+
+	#[test]
+	fn nesting_is_given_back_when_the_guard_drops() {
+		let budget = Budget::new(None);
+		let outer = budget.nest(3, 4, Span::new(0, 1)).unwrap();
+		assert!(matches!(budget.nest(2, 4, Span::new(0, 1)), Err(EvalError::TooDeep { limit: 4, .. })));
+		drop(outer);
+		assert!(budget.nest(4, 4, Span::new(0, 1)).is_ok());
+		assert_eq!(budget.depth.get(), 0);
+	}
+
+	#[test]
+	fn a_huge_sum_runs_out_of_budget_and_normal_expressions_do_not() {
+		let mut context = Context::new();
+		context.set_term_limit(u64::MAX);
+		context.set_budget(Some(100_000));
+		let err = context.eval("sum_over(k, 1, 1e12, k^2)").unwrap_err();
+		assert!(matches!(err, EvalError::BudgetExceeded { spent, limit: 100_000 } if spent > 100_000), "{}", err);
+		assert_eq!(context.eval("sum_over(k, 1, 100, k^2)").unwrap(), 338350.0);
+		// Each evaluation starts with the whole budget
+		for _ in 0 .. 3 {
+			assert_eq!(context.eval("(1 + 2) * 3 - 4 / 2").unwrap(), 7.0);
+			assert!(context.eval("integrate(x^2, x, 0, 1)").is_ok());
+		}
+		context.set_budget(Some(4));
+		assert!(matches!(context.eval("1 + 2 * 3"), Err(EvalError::BudgetExceeded { spent: 5, limit: 4 })));
+		context.set_budget(None);
+		assert_eq!(context.eval("sum_over(k, 1, 200000, 1)").unwrap(), 200000.0);
+	}
+}
@@ -0,0 +1,339 @@
+//! Compiling an expression to a [`Program`], a flat list of instructions over a stack. Only
+//! arithmetic, operators, conversions and built-in functions are compiled. Calculus forms, `let`
+//! and calls of functions defined with [`Context::register_fn`] are not: each is evaluated once
+//! when compiling and becomes a constant, so one that reads a variable of the context is refused as
+//! [`EvalError::Uncompilable`]. So is anything in money mode, whose arithmetic is in cents.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::calculus::form_name;
+use crate::expr::{is_percent_change, parse};
+use crate::{dependencies_with, duration, strict, AngleMode, BinOp, Context, EvalError, Expr, Expression, Function, Lexeme, LexemeKind, Rounding, RuleLevel, StrictRule, Unit};
+
+/// One step of a [`Program`], working on a stack of values.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Instr {
+	/// Pushes an entry of the constant pool.
+	PushConst(u32),
+	/// Pushes the value given for a variable slot.
+	LoadVar(u32),
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Pow,
+	Neg,
+	/// `x%`, the value over 100.
+	Percent,
+	/// `a + b%` or `a - b%`, `b` already over 100.
+	PercentChange(BinOp),
+	/// Any other operator, such as `±`, which without uncertainties keeps its value.
+	Apply(BinOp),
+	/// A function of [`Function::MAP`] by position, with its number of arguments.
+	CallBuiltin(u8, u8),
+	Round,
+	/// A conversion of the program's list by position.
+	Convert(u32)
+}
+
+/// An expression compiled to a flat list of instructions, for evaluating it many times with other
+/// values of its variables. The variables become slots when it is compiled, so evaluating looks
+/// nothing up by name, and the stack is kept between evaluations.
+#[derive(Clone, Debug)]
+pub struct Program {
+	code: Vec<Instr>,
+	consts: Vec<f64>,
+	slots: Vec<String>,
+	conversions: Vec<(&'static Unit, &'static Unit)>,
+	angle_mode: AngleMode,
+	rounding: Rounding,
+	/// The level of [`StrictRule::NonFinite`], the one rule about the value.
+	non_finite: RuleLevel,
+	stack: Vec<f64>
+}
+impl Program {
+	/// The variables by slot, the order [`Program::eval`] takes their values in.
+	pub fn slots(&self) -> &[String] {
+		&self.slots
+	}
+	pub fn code(&self) -> &[Instr] {
+		&self.code
+	}
+	/// Evaluates with `values` for the slots, giving what [`Context::eval`] gives for the expression
+	/// with the variables set to them, errors of function domains and strict mode included. Missing
+	/// values are NaN.
+	pub fn eval(&mut self, values: &[f64]) -> Result<f64, EvalError> {
+		let stack = &mut self.stack;
+		stack.clear();
+		for instr in &self.code {
+			let value = match *instr {
+				Instr::PushConst(index) => self.consts[index as usize],
+				Instr::LoadVar(slot) => values.get(slot as usize).copied().unwrap_or(f64::NAN),
+				Instr::Neg => -stack.pop().unwrap(),
+				Instr::Percent => stack.pop().unwrap() / 100.0,
+				Instr::Round => self.rounding.round(stack.pop().unwrap()),
+				Instr::Convert(index) => {
+					let (from, to) = self.conversions[index as usize];
+					from.convert(stack.pop().unwrap(), to)
+				}
+				Instr::CallBuiltin(index, argc) => {
+					let base = stack.len() - argc as usize;
+					let func = Function::MAP[index as usize].1;
+					func.check_domain(&stack[base ..])?;
+					let value = func.resolve_in(&stack[base ..], self.angle_mode);
+					stack.truncate(base);
+					value
+				}
+				Instr::Add | Instr::Sub | Instr::Mul | Instr::Div | Instr::Pow | Instr::PercentChange(_) | Instr::Apply(_) => {
+					let rhs = stack.pop().unwrap();
+					let lhs = stack.pop().unwrap();
+					match *instr {
+						Instr::Add => lhs + rhs,
+						Instr::Sub => lhs - rhs,
+						Instr::Mul => lhs * rhs,
+						Instr::Div => lhs / rhs,
+						Instr::Pow => lhs.powf(rhs),
+						Instr::PercentChange(op) => op.apply(lhs, lhs * rhs),
+						Instr::Apply(op) => op.apply(lhs, rhs),
+						_ => unreachable!()
+					}
+				}
+			};
+			stack.push(value);
+		}
+		let value = stack.pop().ok_or(EvalError::NoResult)?;
+		if !value.is_finite() && self.non_finite == RuleLevel::Error {
+			return Err(EvalError::Strict { rule: StrictRule::NonFinite, span: None });
+		}
+		Ok(value)
+	}
+}
+
+impl Context {
+	/// Compiles `expression` into a [`Program`]. Every variable of this context that it names becomes
+	/// a slot; constants, `ans` and tape entries keep the values they have now, as do the angle mode
+	/// and rounding. A form, `let` or call of a user function is evaluated once here, so one that
+	/// reads a variable cannot be compiled, and neither can anything in money mode.
+	pub fn compile(&self, expression: &str) -> Result<Program, EvalError> {
+		if self.money() {
+			return Err(EvalError::Uncompilable("an expression in money mode".to_string()));
+		}
+		for lexeme in self.lexer(expression) {
+			let Lexeme { kind, span } = lexeme?;
+			if kind != LexemeKind::Form {
+				continue;
+			}
+			let call = &expression[span.start .. span.end];
+			let read = dependencies_with(call, self)?.into_iter().find(|name| self.get_var(name).is_some());
+			if let Some(var) = read {
+				let name = form_name(call);
+				return Err(EvalError::Uncompilable(format!("'{}' reading '{}'", name, var)));
+			}
+		}
+		let (rpn, ast) = parse(expression, self)?;
+		duration::check(&rpn)?;
+		strict::check_input(expression, 0, &rpn, self, &mut Vec::new())?;
+		let mut program = Program {
+			code: Vec::new(),
+			consts: Vec::new(),
+			slots: Vec::new(),
+			conversions: Vec::new(),
+			angle_mode: self.angle_mode(),
+			rounding: self.rounding(),
+			non_finite: self.rule(StrictRule::NonFinite),
+			stack: Vec::new()
+		};
+		emit(&ast, self, &mut program);
+		Ok(program)
+	}
+}
+
+impl Expression {
+	/// The expression as a [`Program`] over the variables of `context`, see [`Context::compile`].
+	pub fn compile(&self, context: &Context) -> Result<Program, EvalError> {
+		context.compile(self.as_str())
+	}
+}
+
+/// A node of the tree on the way down, to push its operands, or on the way back up, to apply it.
+enum Visit<'a> {
+	Enter(&'a Expr),
+	Exit(&'a Expr)
+}
+
+/// Emits the instructions of `ast` in postfix order. The tree is walked with a stack of its own,
+/// so however deep it is the walk does not recurse.
+fn emit(ast: &Expr, context: &Context, program: &mut Program) {
+	let mut visits = vec![Visit::Enter(ast)];
+	while let Some(visit) = visits.pop() {
+		let expr = match visit {
+			Visit::Enter(expr) => {
+				visits.push(Visit::Exit(expr));
+				match expr {
+					Expr::Number(_) | Expr::Name(..) | Expr::Var(_) => {}
+					Expr::Unary(_, value) | Expr::Percent(value) | Expr::Convert { value, .. } => visits.push(Visit::Enter(value)),
+					Expr::Binary(_, lhs, rhs) => {
+						visits.push(Visit::Enter(rhs));
+						visits.push(Visit::Enter(lhs));
+					}
+					Expr::Call(_, args) => visits.extend(args.iter().rev().map(Visit::Enter))
+				}
+				continue;
+			}
+			Visit::Exit(expr) => expr
+		};
+		let instr = match expr {
+			Expr::Name(name, _) if context.get_var(name).is_some() => Instr::LoadVar(slot(name, program)),
+			Expr::Var(name) => Instr::LoadVar(slot(name, program)),
+			Expr::Number(value) | Expr::Name(_, value) => {
+				program.consts.push(*value);
+				Instr::PushConst(program.consts.len() as u32 - 1)
+			}
+			Expr::Unary(op, _) if op.op != BinOp::Sub => continue,
+			Expr::Unary(..) => Instr::Neg,
+			Expr::Binary(op, lhs, rhs) => match op.op {
+				_ if is_percent_change(op, lhs, rhs) => Instr::PercentChange(op.op),
+				BinOp::Add => Instr::Add,
+				BinOp::Sub => Instr::Sub,
+				BinOp::Mul => Instr::Mul,
+				BinOp::Div => Instr::Div,
+				BinOp::Pow => Instr::Pow,
+				other => Instr::Apply(other)
+			},
+			Expr::Call(func, args) => match Function::MAP.iter().position(|(name, _)| *name == func.name) {
+				_ if func.name == "round" => Instr::Round,
+				Some(index) => Instr::CallBuiltin(index as u8, args.len() as u8),
+				None => unreachable!("'{}' is not a built-in function", func.name)
+			},
+			Expr::Convert { from, to, .. } => {
+				program.conversions.push((from, to));
+				Instr::Convert(program.conversions.len() as u32 - 1)
+			}
+			Expr::Percent(_) => Instr::Percent
+		};
+		program.code.push(instr);
+	}
+}
+
+/// The slot of the variable `name`, added if it has none yet.
+fn slot(name: &str, program: &mut Program) -> u32 {
+	match program.slots.iter().position(|known| known == name) {
+		Some(slot) => slot as u32,
+		None => {
+			program.slots.push(name.to_string());
+			program.slots.len() as u32 - 1
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Formulas in `x` and `y` covering every instruction.
+	const CORPUS: [&str; 24] = [
+		"1 + 2 * 3", "x^2 - y", "-x^2", "--x", "+x", "150 + 10%", "x - 20%", "10% + 10%", "x%", "round(x)", "max(x, y) / min(x, 1)",
+		"sin(x) + cos(y)", "convert(x, km, m)", "20% of 150", "(x + y) * (x - y) / 2", "pi * x", "abs(y) + sqrt(16) + ln(e)",
+		"pctchange(x, y)", "pctof(x, y)", "1 / x", "sqrt(x)", "x ^ y ^ 2", "floor(x) + ceil(y)", "sum_over(k, 1, 4, k) * x"
+	];
+	const POINTS: [(f64, f64); 7] = [(0.0, 0.0), (1.0, 2.0), (-3.5, 4.25), (2.0, -1.0), (1e-9, 1e12), (100.0, 0.5), (-0.0, 7.0)];
+
+	fn same(expected: &Result<f64, EvalError>, actual: &Result<f64, EvalError>) -> bool {
+		match (expected, actual) {
+			(Ok(a), Ok(b)) => a == b || (a.is_nan() && b.is_nan()),
+			(Err(a), Err(b)) => a.code() == b.code(),
+			_ => false
+		}
+	}
+
+	fn check(context: &mut Context) {
+		context.set_var("x", 0.0);
+		context.set_var("y", 0.0);
+		for expression in CORPUS {
+			let mut program = context.compile(expression);
+			for (x, y) in POINTS {
+				context.set_var("x", x);
+				context.set_var("y", y);
+				let expected = context.eval(expression);
+				let actual = program.as_mut().map_err(|error| error.clone()).and_then(|program| {
+					let values: Vec<f64> = program.slots().iter().map(|name| context.get_var(name).unwrap()).collect();
+					program.eval(&values)
+				});
+				assert!(same(&expected, &actual), "{} at x = {}, y = {}: {:?} != {:?}", expression, x, y, expected, actual);
+			}
+		}
+	}
+
+	#[test]
+	fn programs_evaluate_like_the_context() {
+		check(&mut Context::new());
+	}
+
+	#[test]
+	fn programs_keep_the_angle_mode_and_rounding() {
+		let mut context = Context::new();
+		context.set_angle_mode(AngleMode::Degrees);
+		context.set_rounding(Rounding::HalfEven);
+		check(&mut context);
+	}
+
+	#[test]
+	fn programs_fail_where_the_context_does() {
+		let mut context = Context::new();
+		context.set_strict(true);
+		check(&mut context);
+		let mut program = Context::new().compile("pctchange(0, 5)").unwrap();
+		assert!(matches!(program.eval(&[]), Err(EvalError::Domain { function: "pctchange", .. })));
+	}
+
+	#[test]
+	fn variables_become_slots_in_order() {
+		let mut context = Context::new();
+		context.set_var("b", 1.0);
+		context.set_var("a", 2.0);
+		let mut program = context.compile("b * 10 + a - b").unwrap();
+		assert_eq!(program.slots(), ["b", "a"]);
+		assert_eq!(program.eval(&[3.0, 4.0]).unwrap(), 31.0);
+		assert!(program.eval(&[3.0]).unwrap().is_nan());
+		assert!(program.code().iter().filter(|instr| matches!(instr, Instr::LoadVar(_))).count() == 3);
+		let expression = Expression::parse("b * 10 + a - b", &context).unwrap();
+		assert_eq!(expression.compile(&context).unwrap().eval(&[3.0, 4.0]).unwrap(), 31.0);
+	}
+
+	#[test]
+	fn a_form_reading_a_slot_is_refused() {
+		let mut context = Context::new();
+		context.set_var("x", 10.0);
+		assert!(matches!(context.compile("sum_over(k, 1, x, k)"), Err(EvalError::Uncompilable(_))));
+		assert!(matches!(context.compile("2 * integrate(t * x, t, 0, 1)"), Err(EvalError::Uncompilable(_))));
+		// Its own variable is not a slot even when the context has one of the name
+		context.set_var("k", 3.0);
+		let mut program = context.compile("sum_over(k, 1, 10, k) + x").unwrap();
+		assert_eq!(program.eval(&[1.0]).unwrap(), 56.0);
+		// So with `let` and the functions of the program, through their bodies
+		context.register_fn("scaled", &["v"], "v * x").unwrap();
+		context.register_fn("double", &["v"], "2 * v").unwrap();
+		for formula in ["let y = x in y", "scaled(2)", "let y = 2 in scaled(y)"] {
+			assert!(matches!(context.compile(formula), Err(EvalError::Uncompilable(_))), "{}", formula);
+		}
+		let mut program = context.compile("(let y = 2 in double(y)) + x").unwrap();
+		assert_eq!(program.eval(&[1.0]).unwrap(), 5.0);
+	}
+
+	#[test]
+	fn money_mode_is_refused() {
+		let mut context = Context::new();
+		context.set_money(true);
+		assert!(matches!(context.compile("1 + 2"), Err(EvalError::Uncompilable(_))));
+	}
+
+	#[test]
+	fn a_deep_tree_compiles_without_recursing() {
+		let expression = vec!["1"; 1000].join(" + ");
+		let mut program = Context::new().compile(&expression).unwrap();
+		assert_eq!(program.eval(&[]).unwrap(), 1000.0);
+	}
+}
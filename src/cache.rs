@@ -0,0 +1,169 @@
+use alloc::collections::{BTreeMap, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::{Locale, Token};
+
+pub const DEFAULT_CAPACITY: usize = 256;
+
+/// Compiled RPN keyed by the expression text, evicting the least recently used entry when full.
+/// Entries are only valid for the locale they were parsed with, so a locale change empties the cache,
+/// and for the tokens the names they read resolved to, which the RPN holds.
+#[derive(Clone, Debug)]
+pub struct ExpressionCache {
+	capacity: usize,
+	entries: BTreeMap<String, Entry>,
+	locale: Locale,
+	clock: u64,
+	hits: u64,
+	misses: u64
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+	rpn: VecDeque<Token>,
+	tokens: usize,
+	reads: Vec<(String, Token)>,
+	used: u64
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct CacheStats {
+	pub hits: u64,
+	pub misses: u64,
+	pub entries: usize,
+	pub capacity: usize
+}
+
+impl ExpressionCache {
+	pub fn new(capacity: usize) -> Self {
+		Self { capacity, entries: BTreeMap::new(), locale: Locale::default(), clock: 0, hits: 0, misses: 0 }
+	}
+
+	/// Looks up `expression` and counts a hit or a miss. `reads` are the names it reads with the
+	/// tokens they resolve to now, an entry parsed while one of them resolved to another value is a
+	/// miss. Returns the RPN and how many tokens the expression had.
+	pub fn get(&mut self, expression: &str, locale: Locale, reads: &[(String, Token)]) -> Option<(VecDeque<Token>, usize)> {
+		if locale != self.locale {
+			self.entries.clear();
+			self.locale = locale;
+		}
+		self.clock += 1;
+		match self.entries.get_mut(expression) {
+			Some(entry) if same_reads(&entry.reads, reads) => {
+				entry.used = self.clock;
+				self.hits += 1;
+				Some((entry.rpn.clone(), entry.tokens))
+			}
+			_ => {
+				self.misses += 1;
+				None
+			}
+		}
+	}
+
+	pub fn insert(&mut self, expression: &str, rpn: &VecDeque<Token>, tokens: usize, reads: Vec<(String, Token)>) {
+		if self.capacity == 0 {
+			return;
+		}
+		if self.entries.len() >= self.capacity && !self.entries.contains_key(expression) {
+			let oldest = self.entries.iter().min_by_key(|(_, entry)| entry.used).map(|(key, _)| key.clone());
+			if let Some(oldest) = oldest {
+				self.entries.remove(&oldest);
+			}
+		}
+		self.entries.insert(expression.to_string(), Entry { rpn: rpn.clone(), tokens, reads, used: self.clock });
+	}
+
+	pub fn clear(&mut self) {
+		self.entries.clear();
+	}
+
+	pub fn stats(&self) -> CacheStats {
+		CacheStats { hits: self.hits, misses: self.misses, entries: self.entries.len(), capacity: self.capacity }
+	}
+}
+
+/// Whether the names read resolve to the same values, bit for bit, as when the entry was parsed.
+fn same_reads(then: &[(String, Token)], now: &[(String, Token)]) -> bool {
+	then.len() == now.len() && then.iter().zip(now).all(|((name, then), (other, now))| name == other && match (then, now) {
+		(Token::NumericLiteral(a), Token::NumericLiteral(b)) | (Token::Duration(a), Token::Duration(b)) => a.to_bits() == b.to_bits(),
+		(Token::Uncertain(a, sigma), Token::Uncertain(b, other)) => a.to_bits() == b.to_bits() && sigma.to_bits() == other.to_bits(),
+		(Token::Tag(a), Token::Tag(b)) => a == b,
+		_ => false
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::trace::format_tokens;
+	use crate::Context;
+
+	fn rpn(expression: &str) -> VecDeque<Token> {
+		Context::new().to_postfix(expression).unwrap()
+	}
+
+	#[test]
+	fn the_least_recently_used_entry_is_evicted() {
+		let mut cache = ExpressionCache::new(2);
+		cache.insert("1 + 2", &rpn("1 + 2"), 3, Vec::new());
+		cache.insert("3 * 4", &rpn("3 * 4"), 3, Vec::new());
+		let (mut hit, tokens) = cache.get("1 + 2", Locale::default(), &[]).unwrap();
+		assert_eq!((format_tokens(hit.make_contiguous()), tokens), ("1 2 +".to_string(), 3));
+		cache.insert("5 - 6", &rpn("5 - 6"), 3, Vec::new());
+		assert!(cache.get("3 * 4", Locale::default(), &[]).is_none());
+		assert!(cache.get("1 + 2", Locale::default(), &[]).is_some() && cache.get("5 - 6", Locale::default(), &[]).is_some());
+		assert_eq!(cache.stats(), CacheStats { hits: 3, misses: 1, entries: 2, capacity: 2 });
+		let mut disabled = ExpressionCache::new(0);
+		disabled.insert("1", &rpn("1"), 1, Vec::new());
+		assert_eq!(disabled.stats().entries, 0);
+	}
+
+	#[test]
+	fn a_locale_change_empties_the_cache() {
+		let mut cache = ExpressionCache::new(DEFAULT_CAPACITY);
+		cache.insert("1.5", &rpn("1.5"), 1, Vec::new());
+		let comma = Locale::new(',', Some('.')).unwrap();
+		assert!(cache.get("1.5", comma, &[]).is_none());
+		assert_eq!(cache.stats().entries, 0);
+	}
+
+	#[test]
+	fn cached_results_follow_the_context() {
+		let mut context = Context::new();
+		context.set_cache_capacity(4);
+		let value = |context: &mut Context, expression: &str| context.execute(expression).unwrap().value();
+		assert_eq!(value(&mut context, "2 * pi"), value(&mut context, "2 * pi"));
+		let stats = context.cache_stats().unwrap();
+		assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+		context.execute("pi = 3").unwrap();
+		assert_eq!(value(&mut context, "2 * pi"), 6.0);
+		value(&mut context, "1.5 * 2");
+		context.display_mut().locale = Locale::new(',', None).unwrap();
+		assert!(context.execute("1.5 * 2").is_err());
+		assert_eq!(value(&mut context, "1,5 * 2"), 3.0);
+		context.set_cache_capacity(0);
+		assert_eq!(context.cache_stats(), None);
+	}
+
+	#[test]
+	fn expressions_reading_variables_are_cached_for_their_values() {
+		let mut context = Context::new();
+		context.set_cache_capacity(4);
+		context.set_var("x", 2.0);
+		let counts = |context: &Context| context.cache_stats().map(|stats| (stats.hits, stats.misses)).unwrap();
+		assert_eq!(context.execute("x * 3").unwrap().value(), 6.0);
+		assert_eq!(context.execute("x * 3").unwrap().value(), 6.0);
+		assert_eq!(counts(&context), (1, 1));
+		context.set_var("x", 5.0);
+		assert_eq!(context.execute("x * 3").unwrap().value(), 15.0);
+		assert_eq!(counts(&context), (1, 2));
+		assert_eq!(context.execute("x * 3").unwrap().value(), 15.0);
+		assert_eq!(counts(&context), (2, 2));
+		// `ans` is read like a variable
+		assert_eq!(context.execute("ans + 1").unwrap().value(), 16.0);
+		assert_eq!(context.execute("ans + 1").unwrap().value(), 17.0);
+		assert_eq!(counts(&context), (2, 4));
+	}
+}
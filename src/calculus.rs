@@ -0,0 +1,270 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::budget::Budget;
+use crate::{base, binding, functions, is_identifier, split_list, Context, EvalError, Span};
+
+/// Functions that read their arguments themselves rather than getting their values: most take an
+/// expression in a variable and evaluate it for each value of the variable, the base conversions
+/// take digits in quotes. The lexer reads each call as one lexeme, which the parser evaluates as a
+/// whole.
+pub const FORMS: [&str; 6] = ["diff", "frombase", "integrate", "prod_over", "sum_over", "tobase"];
+
+/// How closely `integrate` approximates, relative to the size of the integral, unless a context
+/// says otherwise.
+pub const DEFAULT_TOLERANCE: f64 = 1e-10;
+
+/// How often `integrate` may evaluate its expression before giving up on the tolerance.
+const MAX_EVALUATIONS: usize = 100_000;
+/// How often a panel may be halved; the estimate is taken as it is there.
+const MAX_DEPTH: u32 = 50;
+
+/// How many terms `sum_over` and `prod_over` may have unless a context says otherwise.
+pub const DEFAULT_TERM_LIMIT: u64 = 1_000_000;
+
+/// The levels of [`Context::nesting_limit`] a form counts as, since evaluating its arguments as
+/// expressions of their own takes far more stack than a level of a tree.
+pub(crate) const FORM_NESTING: usize = 16;
+
+/// How many arguments the form `name` takes and, for the ones that bind a variable, which arguments
+/// are the variable and the expression it is bound in.
+pub(crate) fn layout(name: &str) -> (usize, Option<(usize, usize)>) {
+	match name {
+		"integrate" => (4, Some((1, 0))),
+		"diff" => (3, Some((1, 0))),
+		"sum_over" | "prod_over" => (4, Some((0, 3))),
+		_ => (2, None)
+	}
+}
+
+/// The name a form starts with: `sum_over` for a call of it, `let` for a `let`.
+pub(crate) fn form_name(call: &str) -> &str {
+	&call[.. call.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(call.len())]
+}
+
+/// Byte ranges of the arguments of `call`, which is `name(...)`, split at its top level.
+pub(crate) fn arguments(call: &str, separator: char) -> Vec<(usize, usize)> {
+	let open = call.find('(').unwrap_or(0);
+	let close = call.len() - 1;
+	if call[open + 1 .. close].trim().is_empty() {
+		return Vec::new();
+	}
+	split_list(&call[open + 1 .. close], separator).into_iter().map(|(start, end)| (start + open + 1, end + open + 1)).collect()
+}
+
+/// The span of an argument without the spaces around it, at least one byte long.
+pub(crate) fn span_of(call: &str, (start, end): (usize, usize)) -> Span {
+	let text = &call[start .. end];
+	let start = start + text.len() - text.trim_start().len();
+	Span::new(start, start + text.trim().len().max(1))
+}
+
+/// Evaluates a call such as `integrate(x^2, x, 0, 1)`, `diff(sin(x), x, 0)` or
+/// `sum_over(k, 1, 100, k^2)`. The variable is bound in a copy of `context`, so it neither needs a
+/// value nor loses the one it has. The other forms, a `let` and a call of a function the program
+/// defined, are evaluated here too. Every evaluation spends from `budget`. Spans of errors are
+/// relative to `call`.
+pub(crate) fn evaluate(call: &str, context: &Context, budget: &Budget) -> Result<f64, EvalError> {
+	let _nested = budget.nest(FORM_NESTING, context.nesting_limit(), Span::new(0, call.len()))?;
+	let name = match form_name(call) {
+		name if name != "let" && !context.is_visible(name) => return Err(EvalError::UnknownIdentifier(name.to_string(), Span::new(0, name.len()))),
+		"let" => return binding::evaluate(call, context, budget),
+		name if context.function(name).is_some() => return functions::call(call, context, budget),
+		"frombase" => return base::from_base(call, context, budget),
+		"tobase" => return base::to_base(call, context, budget).map(|(value, _)| value),
+		name => FORMS.into_iter().find(|form| *form == name).unwrap_or("integrate")
+	};
+	let span = Span::new(0, call.len());
+	let args = arguments(call, context.display().locale.argument_separator());
+	let (expected, Some((var, body))) = layout(name) else {
+		unreachable!()
+	};
+	if args.len() != expected {
+		return Err(EvalError::ArgumentCount { name, expected, found: args.len(), span });
+	}
+	let (var, body) = (args[var], args[body]);
+	let text = |(start, end): (usize, usize)| &call[start .. end];
+	let value = |arg: (usize, usize)| context.eval_within(text(arg), budget).map_err(|err| err.offset(arg.0));
+	let (var_span, var) = (span_of(call, var), text(var).trim());
+	if !is_identifier(var) {
+		return Err(EvalError::ExpectedVariable(var_span));
+	}
+	if context.is_protected(var) {
+		return Err(EvalError::Protected(var.to_string()));
+	}
+
+	let mut scope = context.clone();
+	let mut f = |x: f64| {
+		scope.set_var(var, x);
+		scope.eval_within(text(body), budget).map_err(|err| err.offset(body.0))
+	};
+	let finite = |at: f64, value: f64| {
+		if !value.is_finite() {
+			return Err(EvalError::NotFinite { name, var: var.to_string(), at, span });
+		}
+		Ok(value)
+	};
+	let integer = |arg: (usize, usize)| match value(arg)? {
+		bound if bound.fract() == 0.0 => Ok(bound),
+		_ => Err(EvalError::ExpectedInteger(span_of(call, arg)))
+	};
+	match name {
+		"integrate" => {
+			let a = value(args[2]).and_then(|a| finite(a, a))?;
+			let b = value(args[3]).and_then(|b| finite(b, b))?;
+			let mut evaluations = 0;
+			integrate(&mut |x| {
+				evaluations += 1;
+				if evaluations > MAX_EVALUATIONS {
+					return Err(EvalError::NoConvergence(name));
+				}
+				finite(x, f(x)?)
+			}, a, b, context.tolerance())
+		}
+		"diff" => {
+			let at = value(args[2]).and_then(|at| finite(at, at))?;
+			derivative(&mut |x| finite(x, f(x)?), at)
+		}
+		_ => {
+			let (first, last) = (integer(args[1])?, integer(args[2])?);
+			// Reversed bounds give the empty sum or product
+			let terms = (last - first + 1.0).max(0.0);
+			if terms > context.term_limit() as f64 {
+				return Err(EvalError::TooManyTerms { name, terms, limit: context.term_limit(), span });
+			}
+			let product = name == "prod_over";
+			let mut total = if product { 1.0 } else { 0.0 };
+			for i in 0 .. terms as u64 {
+				let term = f(first + i as f64)?;
+				total = if product { total * term } else { total + term };
+			}
+			Ok(total)
+		}
+	}
+}
+
+/// An interval with the values at its ends and middle, and its Simpson estimate.
+#[derive(Copy, Clone)]
+struct Panel {
+	a: f64,
+	b: f64,
+	fa: f64,
+	fm: f64,
+	fb: f64,
+	estimate: f64
+}
+impl Panel {
+	fn new(a: f64, b: f64, fa: f64, fm: f64, fb: f64) -> Self {
+		Self { a, b, fa, fm, fb, estimate: (b - a) / 6.0 * (fa + 4.0 * fm + fb) }
+	}
+}
+
+/// Adaptive Simpson: a panel is halved until its halves agree with it to within `tolerance`.
+fn integrate(f: &mut impl FnMut(f64) -> Result<f64, EvalError>, a: f64, b: f64, tolerance: f64) -> Result<f64, EvalError> {
+	let panel = Panel::new(a, b, f(a)?, f((a + b) / 2.0)?, f(b)?);
+	refine(f, panel, tolerance * panel.estimate.abs().max(1.0), MAX_DEPTH)
+}
+
+fn refine(f: &mut impl FnMut(f64) -> Result<f64, EvalError>, panel: Panel, tolerance: f64, depth: u32) -> Result<f64, EvalError> {
+	let m = (panel.a + panel.b) / 2.0;
+	let left = Panel::new(panel.a, m, panel.fa, f((panel.a + m) / 2.0)?, panel.fm);
+	let right = Panel::new(m, panel.b, panel.fm, f((m + panel.b) / 2.0)?, panel.fb);
+	let delta = left.estimate + right.estimate - panel.estimate;
+	if depth == 0 || delta.abs() <= 15.0 * tolerance {
+		return Ok(left.estimate + right.estimate + delta / 15.0);
+	}
+	Ok(refine(f, left, tolerance / 2.0, depth - 1)? + refine(f, right, tolerance / 2.0, depth - 1)?)
+}
+
+/// The five-point central difference, with a step that balances truncation against rounding.
+fn derivative(f: &mut impl FnMut(f64) -> Result<f64, EvalError>, at: f64) -> Result<f64, EvalError> {
+	let h = 1e-3 * at.abs().max(1.0);
+	Ok((f(at - 2.0 * h)? - 8.0 * f(at - h)? + 8.0 * f(at + h)? - f(at + 2.0 * h)?) / (12.0 * h))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use core::f64::consts::{E, PI};
+
+	fn assert_near(context: &Context, expression: &str, expected: f64) {
+		let value = context.eval(expression).unwrap();
+		assert!((value - expected).abs() < 1e-6, "{} gives {} instead of {}", expression, value, expected);
+	}
+
+	#[test]
+	fn known_integrals_and_derivatives_are_found() {
+		let context = Context::new();
+		assert_near(&context, "integrate(x^2, x, 0, 1)", 1.0 / 3.0);
+		assert_near(&context, "integrate(sin(t), t, 0, pi)", 2.0);
+		assert_near(&context, "integrate(exp(x), x, 0, 1)", E - 1.0);
+		assert_near(&context, "integrate(1 / x, x, 1, e)", 1.0);
+		assert_near(&context, "integrate(x, x, 1, 0)", -0.5);
+		assert_near(&context, "4 * integrate(sqrt(1 - x^2), x, 0, 1)", PI);
+		assert_near(&context, "diff(sin(x), x, 0)", 1.0);
+		assert_near(&context, "diff(x^3, x, 2)", 12.0);
+		assert_near(&context, "diff(ln(x), x, 2)", 0.5);
+		assert_near(&context, "diff(integrate(t^2, t, 0, x), x, 3)", 9.0);
+	}
+
+	#[test]
+	fn the_variable_is_bound_only_inside() {
+		let mut context = Context::new();
+		context.set_var("x", 5.0);
+		context.set_var("a", 2.0);
+		assert_near(&context, "integrate(a * x, x, 0, 1) + x", 6.0);
+		assert_eq!(context.get_var("x"), Some(5.0));
+		assert!(matches!(context.eval("integrate(x^2, 2, 0, 1)"), Err(EvalError::ExpectedVariable(_))));
+		context.protect("a");
+		assert!(matches!(context.eval("integrate(a, a, 0, 1)"), Err(EvalError::Protected(name)) if name == "a"));
+		assert!(matches!(context.eval("diff(x, x)"), Err(EvalError::ArgumentCount { name: "diff", expected: 3, found: 2, .. })));
+	}
+
+	#[test]
+	fn non_finite_values_abort() {
+		let context = Context::new();
+		let err = context.eval("integrate(1 / x, x, -1, 1)").unwrap_err();
+		assert!(matches!(&err, EvalError::NotFinite { name: "integrate", var, at, .. } if var == "x" && *at == 0.0), "{}", err);
+		assert!(matches!(context.eval("diff(sqrt(x), x, 0)"), Err(EvalError::NotFinite { name: "diff", .. })));
+		assert!(matches!(context.eval("integrate(x, x, 0, 1 / 0)"), Err(EvalError::NotFinite { .. })));
+	}
+
+	#[test]
+	fn the_tolerance_is_chosen_by_the_context() {
+		let mut context = Context::new();
+		assert_eq!(context.tolerance(), DEFAULT_TOLERANCE);
+		let exact = E - 1.0;
+		let tight = (context.eval("integrate(exp(x), x, 0, 1)").unwrap() - exact).abs();
+		context.set_tolerance(1e-2);
+		let loose = (context.eval("integrate(exp(x), x, 0, 1)").unwrap() - exact).abs();
+		assert!(tight < 1e-10 && loose > tight && loose < 1e-2, "{} {}", tight, loose);
+	}
+
+	#[test]
+	fn sums_and_products_run_over_inclusive_bounds() {
+		let context = Context::new();
+		assert_eq!(context.eval("sum_over(k, 1, 100, k^2)").unwrap(), 338350.0);
+		assert_eq!(context.eval("prod_over(k, 1, 5, k)").unwrap(), 120.0);
+		assert_eq!(context.eval("sum_over(k, 3, 3, k)").unwrap(), 3.0);
+		assert_eq!(context.eval("sum_over(k, 5, 1, k)").unwrap(), 0.0);
+		assert_eq!(context.eval("prod_over(k, 5, 1, k)").unwrap(), 1.0);
+		// 1 * (1 + 2) * (1 + 2 + 3)
+		assert_eq!(context.eval("prod_over(i, 1, 3, sum_over(j, 1, i, j))").unwrap(), 18.0);
+		assert_eq!(context.eval("sum_over(k, -2, 2, k^3)").unwrap(), 0.0);
+	}
+
+	#[test]
+	fn bounds_are_integers_within_the_term_limit() {
+		let mut context = Context::new();
+		assert!(matches!(context.eval("sum_over(k, 1.5, 3, k)"), Err(EvalError::ExpectedInteger(span)) if span == Span::new(12, 15)));
+		assert!(matches!(
+			context.eval("sum_over(k, 1, 1e12, k)"),
+			Err(EvalError::TooManyTerms { name: "sum_over", limit: DEFAULT_TERM_LIMIT, .. })
+		));
+		context.set_term_limit(10);
+		assert_eq!(context.eval("sum_over(k, 1, 10, k)").unwrap(), 55.0);
+		assert!(matches!(context.eval("prod_over(k, 1, 11, k)"), Err(EvalError::TooManyTerms { terms, limit: 10, .. }) if terms == 11.0));
+	}
+}
@@ -0,0 +1,95 @@
+use alloc::string::{String, ToString};
+
+use crate::binding::{self, Let};
+use crate::calculus::{form_name, FORM_NESTING};
+use crate::{to_postfix, Context, EvalError, Expression, Lexeme, LexemeKind, Lexer};
+
+impl Context {
+	/// Writes `expression` as it would be typed in the default locale, for storing it: numbers with a
+	/// `.` and no grouping, `,` between arguments, `of` as `*`, `+-` as `±`, and one space around
+	/// binary operators and after commas. Parsing the result in a context that differs only in
+	/// having the default locale gives the same RPN as parsing `expression` in this one. It is parsed
+	/// first and its error returned, but the names, tape references and placeholders it reads need
+	/// not be defined yet.
+	pub fn to_canonical_string(&self, expression: &str) -> Result<String, EvalError> {
+		let mut scope = self.clone();
+		scope.probing = true;
+		to_postfix(expression, &scope)?;
+		let mut out = String::with_capacity(expression.len());
+		write_canonical(expression, self, 0, &mut out)?;
+		Ok(out)
+	}
+}
+
+impl Expression {
+	/// The [`Context::to_canonical_string`] of the expression, in `context`, the one it was parsed in.
+	pub fn to_canonical_string(&self, context: &Context) -> Result<String, EvalError> {
+		context.to_canonical_string(self.as_str())
+	}
+}
+
+/// `depth` is how many forms `expression` is within.
+fn write_canonical(expression: &str, context: &Context, depth: usize, out: &mut String) -> Result<(), EvalError> {
+	// The previous lexeme and whether it was a sign rather than a binary operator
+	let mut prev: Option<(LexemeKind, bool)> = None;
+	for lexeme in context.lexer(expression) {
+		let Lexeme { kind, span } = lexeme?;
+		let text = &expression[span.start .. span.end];
+		let kind = match kind {
+			LexemeKind::Identifier if text == "of" => LexemeKind::Operator('*'),
+			_ => kind
+		};
+		let sign = matches!(kind, LexemeKind::Operator(_))
+			&& matches!(prev, None | Some((LexemeKind::Operator(_) | LexemeKind::OpenParen | LexemeKind::Separator, _)));
+		let space = match (prev, kind) {
+			(None, _) | (Some((LexemeKind::OpenParen, _)), _) => false,
+			(_, LexemeKind::CloseParen | LexemeKind::Separator | LexemeKind::Percent) => false,
+			(Some((LexemeKind::Identifier, _)), LexemeKind::OpenParen) => false,
+			// `+` right before `-` would be read as `±` in uncertainty mode
+			(Some((LexemeKind::Operator('+'), _)), LexemeKind::Operator('-')) => true,
+			(Some((LexemeKind::Operator(_), true)), _) => false,
+			_ => true
+		};
+		if space {
+			out.push(' ');
+		}
+		match kind {
+			LexemeKind::Number(value) => out.push_str(&value.to_string()),
+			LexemeKind::Duration(_) => {
+				let suffix = text.find(char::is_alphabetic).unwrap_or(text.len());
+				match Lexer::new(&text[.. suffix], context.display().locale).next() {
+					Some(Ok(Lexeme { kind: LexemeKind::Number(value), .. })) => out.push_str(&value.to_string()),
+					_ => out.push_str(&text[.. suffix])
+				}
+				out.push_str(&text[suffix ..]);
+			}
+			LexemeKind::TapeRef(index) => out.push_str(&format!("${}", index)),
+			LexemeKind::Operator(symbol) => out.push(symbol),
+			LexemeKind::OpenParen => out.push('('),
+			LexemeKind::CloseParen => out.push(')'),
+			LexemeKind::Separator => out.push(','),
+			LexemeKind::Percent => out.push('%'),
+			// The arguments of a form are expressions of their own
+			LexemeKind::Form if (depth + 1) * FORM_NESTING > context.nesting_limit() => {
+				return Err(EvalError::TooDeep { limit: context.nesting_limit(), span });
+			}
+			LexemeKind::Form if form_name(text) == "let" => {
+				let Some(Let { name, value, body }) = binding::split(text, context.display().locale.argument_separator()) else {
+					unreachable!()
+				};
+				out.push_str(&format!("let {} = ", &text[name.0 .. name.1]));
+				write_canonical(&text[value.0 .. value.1], context, depth + 1, out).map_err(|err| err.offset(span.start + value.0))?;
+				out.push_str(" in ");
+				write_canonical(&text[body.0 .. body.1], context, depth + 1, out).map_err(|err| err.offset(span.start + body.0))?;
+			}
+			LexemeKind::Form => {
+				let open = text.find('(').unwrap_or(text.len());
+				out.push_str(text[.. open].trim_end());
+				write_canonical(&text[open ..], context, depth + 1, out).map_err(|err| err.offset(span.start + open))?;
+			}
+			LexemeKind::Identifier | LexemeKind::String | LexemeKind::Placeholder | LexemeKind::Tag => out.push_str(text)
+		}
+		prev = Some((kind, sign));
+	}
+	Ok(())
+}
@@ -0,0 +1,119 @@
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::budget::Budget;
+use crate::{Context, EvalError, Expression, Violation};
+
+/// Larger whole values are not all exact, the gap between doubles there is over 1.
+const MAX_EXACT_INTEGER: f64 = 9007199254740992.0;
+
+/// What kind of number a result is, for showing results differently.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ValueClass {
+	/// A whole number small enough to be exact.
+	Integer,
+	/// A number whose shortest decimal form ends within the digits a double keeps, such as `0.125`.
+	Decimal,
+	/// Any other finite number, such as `1 / 3` or `sqrt(2)`.
+	Float,
+	/// A result of a percentage function, shown with `%` under
+	/// [`DisplaySettings::percent_helpers`](crate::DisplaySettings::percent_helpers).
+	Percentage,
+	/// NaN or an infinity.
+	Special
+}
+
+/// The class of `value` by itself. Whether it is a percentage depends on how it was computed, so
+/// this never gives [`ValueClass::Percentage`]; [`Context::eval_detailed`] does.
+pub fn classify(value: f64) -> ValueClass {
+	if !value.is_finite() {
+		return ValueClass::Special;
+	}
+	if value.fract() == 0.0 && value.abs() <= MAX_EXACT_INTEGER {
+		return ValueClass::Integer;
+	}
+	let text = value.abs().to_string().replace('.', "");
+	let digits = text.trim_start_matches('0').trim_end_matches('0').len();
+	if digits <= f64::DIGITS as usize { ValueClass::Decimal } else { ValueClass::Float }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EvalResult {
+	pub value: f64,
+	pub class: ValueClass,
+	/// What the strict rules at [`RuleLevel::Warn`](crate::RuleLevel::Warn) reported.
+	pub warnings: Vec<Violation>
+}
+
+impl Context {
+	/// Like [`Context::eval`], also classifying the value and keeping the warnings.
+	pub fn eval_detailed(&self, expression: &str) -> Result<EvalResult, EvalError> {
+		let mut warnings = Vec::new();
+		let (value, percent) = self.evaluate(expression, &Budget::new(self.budget), true, None, &mut warnings)?;
+		let class = match classify(value) {
+			ValueClass::Special => ValueClass::Special,
+			_ if percent && self.display.percent_helpers => ValueClass::Percentage,
+			class => class
+		};
+		Ok(EvalResult { value, class, warnings })
+	}
+}
+
+impl Expression {
+	/// Like [`Expression::eval`], also classifying the value, see [`Context::eval_detailed`].
+	pub fn eval_detailed(&self, context: &Context) -> Result<EvalResult, EvalError> {
+		context.eval_detailed(self.as_str())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{RuleLevel, StrictRule, Trace};
+
+	#[test]
+	fn values_are_classified_alone() {
+		assert_eq!(classify(3.0), ValueClass::Integer);
+		assert_eq!(classify(-0.0), ValueClass::Integer);
+		assert_eq!(classify(MAX_EXACT_INTEGER), ValueClass::Integer);
+		assert_eq!(classify(MAX_EXACT_INTEGER * 2.0), ValueClass::Float);
+		assert_eq!(classify(0.125), ValueClass::Decimal);
+		assert_eq!(classify(-2.5), ValueClass::Decimal);
+		assert_eq!(classify(1.0 / 3.0), ValueClass::Float);
+		assert_eq!(classify(2f64.sqrt()), ValueClass::Float);
+		assert_eq!(classify(f64::NAN), ValueClass::Special);
+		assert_eq!(classify(f64::NEG_INFINITY), ValueClass::Special);
+	}
+
+	#[test]
+	fn percentages_depend_on_how_the_value_was_computed() {
+		let mut context = Context::new();
+		assert_eq!(context.eval_detailed("pctof(1, 4)").unwrap().class, ValueClass::Integer);
+		context.display_mut().percent_helpers = true;
+		let result = context.eval_detailed("pctof(1, 4)").unwrap();
+		assert_eq!((result.value, result.class), (25.0, ValueClass::Percentage));
+		assert_eq!(context.eval_detailed("25").unwrap().class, ValueClass::Integer);
+		assert_eq!(context.eval_detailed("pctof(1, 4) + 1").unwrap().class, ValueClass::Integer);
+		assert_eq!(context.eval_detailed("pctof(1, 0)").unwrap().class, ValueClass::Special);
+	}
+
+	#[test]
+	fn every_variant_evaluates_alike() {
+		let mut context = Context::new();
+		context.set_rule(StrictRule::NonFinite, RuleLevel::Warn);
+		for expression in ["1 + 2 * 3", "-2^2", "150 + 10%", "sin(pi / 2)", "1 / 0", "pctchange(4, 5)"] {
+			let value = context.eval(expression).unwrap();
+			let result = context.eval_detailed(expression).unwrap();
+			let traced = context.eval_traced(expression, &mut Trace::default()).unwrap();
+			assert_eq!((result.value, traced), (value, value), "{}", expression);
+		}
+		let result = context.eval_detailed("1 / 0").unwrap();
+		assert_eq!(result.warnings.iter().map(|violation| violation.rule).collect::<Vec<_>>(), [StrictRule::NonFinite]);
+		assert!(context.eval_detailed("2 +").is_err());
+		let expression = Expression::parse("pctchange(4, x)", &context).unwrap();
+		context.set_var("x", 5.0);
+		assert_eq!(expression.eval_detailed(&context).unwrap().value, expression.eval(&context).unwrap());
+	}
+}
@@ -0,0 +1,419 @@
+use std::path::PathBuf;
+
+use shunting_yard::sweep::MAX_SWEEP_POINTS;
+use shunting_yard::{is_identifier, parse_grouping, split_assignment, AngleMode, DurationStyle, Locale, Notation, Precision, Radix, Rounding, SweepRange};
+
+use crate::result_format::Template;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Subcommand {
+	Repl,
+	Eval,
+	Run,
+	Serve,
+	Sweep
+}
+impl Subcommand {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Repl => "repl",
+			Self::Eval => "eval",
+			Self::Run => "run",
+			Self::Serve => "serve",
+			Self::Sweep => "sweep"
+		}
+	}
+}
+
+/// Everything the command line asked for, before the configuration file fills in the gaps.
+#[derive(Debug, Default)]
+pub struct Options {
+	/// `None` for the flag-only form, `syc -e 1+2` or `syc file.txt`.
+	pub command: Option<Subcommand>,
+	pub help: bool,
+	pub defines: Vec<String>,
+	pub expressions: Vec<String>,
+	/// The `--var NAME=RANGE` of `syc sweep`, in order.
+	pub sweeps: Vec<(String, SweepRange)>,
+	pub files: Vec<String>,
+	pub csv: Option<String>,
+	pub expr_col: Option<usize>,
+	/// The first CSV record names the columns and is written back with a `result` column.
+	pub header: bool,
+	pub out: Option<String>,
+	pub verbose: bool,
+	pub fail_fast: bool,
+	/// Report how the lines went after the results, to stderr unless `summary_file` is given.
+	pub summary: bool,
+	pub summary_file: Option<String>,
+	pub time: bool,
+	pub json: bool,
+	pub latex: bool,
+	pub serve_stdio: bool,
+	pub angle: Option<AngleMode>,
+	pub iterate: Option<usize>,
+	pub start: Option<f64>,
+	pub until_delta: Option<f64>,
+	pub cache_size: Option<usize>,
+	pub tape_size: Option<usize>,
+	pub budget: Option<u64>,
+	pub progress: Option<usize>,
+	pub jobs: Option<usize>,
+	pub no_history: bool,
+	pub dumb_terminal: bool,
+	pub history_path: Option<PathBuf>,
+	pub config: Option<String>,
+	pub startup: Vec<String>,
+	pub no_color: bool,
+	pub precision: Option<Precision>,
+	pub notation: Option<Notation>,
+	pub durations: Option<DurationStyle>,
+	pub fraction: bool,
+	pub radix: Option<Radix>,
+	pub rounding: Option<Rounding>,
+	pub uncertainty: bool,
+	pub money: bool,
+	pub strict: bool,
+	pub lint: bool,
+	pub locale: Locale,
+	pub prompt: Option<String>,
+	pub template: Option<Template>,
+	/// Set by a non-empty [`FROZEN_CLOCK_VAR`]: every time reported is 0.
+	pub frozen_clock: bool
+}
+
+/// The environment variable that makes the reported times 0, for comparing output between runs.
+pub const FROZEN_CLOCK_VAR: &str = "SYC_FROZEN_CLOCK";
+
+/// Parses the arguments after the program name. A subcommand, the first argument that is neither a
+/// flag nor the value of one, restricts the flags on either side of it to the ones that apply to
+/// it; without one every flag is accepted as before subcommands existed.
+pub fn parse_args<I: Iterator<Item = String>>(args: I) -> Result<Options, String> {
+	let mut args = args.peekable();
+	let mut options = Options::default();
+	let mut decimal = '.';
+	let mut grouping = None;
+	// The flags before the subcommand, checked once it is known
+	let mut flags: Vec<String> = Vec::new();
+	while let Some(arg) = args.next() {
+		if options.command.is_none() && options.files.is_empty() {
+			if arg == "help" {
+				options.help = true;
+				return Ok(options);
+			}
+			if let Some(command) = subcommand(&arg) {
+				for flag in &flags {
+					check_scope(flag, command)?;
+				}
+				options.command = Some(command);
+				continue;
+			}
+		}
+		match options.command {
+			Some(command) => check_scope(&arg, command)?,
+			None if flag_scope(&arg).is_some() => flags.push(arg.clone()),
+			None => {}
+		}
+		match arg.as_str() {
+			"-h" | "--help" => options.help = true,
+			"-D" | "--define" => match args.next() {
+				Some(define) if split_assignment(&define).0.is_some() => options.defines.push(define),
+				Some(define) => return Err(format!("--define expects NAME=EXPRESSION, got '{}'", define)),
+				None => return Err(format!("missing definition after '{}'", arg))
+			},
+			"-e" | "--eval" => match args.next() {
+				Some(expression) => options.expressions.push(expression),
+				None => return Err(format!("missing expression after '{}'", arg))
+			},
+			"--var" => match args.next() {
+				Some(sweep) => match sweep.split_once('=') {
+					Some((name, _)) if !is_identifier(name.trim()) => return Err(format!("'{}' is not a variable name", name.trim())),
+					Some((name, _)) if options.sweeps.iter().any(|(swept, _)| swept == name.trim()) => {
+						return Err(format!("'{}' is swept more than once", name.trim()));
+					}
+					Some((name, range)) => options.sweeps.push((name.trim().to_string(), range.parse()?)),
+					None => return Err(format!("--var expects NAME=START..END:STEP, got '{}'", sweep))
+				},
+				None => return Err(format!("missing range after '{}'", arg))
+			},
+			"-v" | "--verbose" => options.verbose = true,
+			"--fail-fast" => options.fail_fast = true,
+			"--summary" => options.summary = true,
+			"--summary-file" => match args.next() {
+				Some(path) => {
+					options.summary = true;
+					options.summary_file = Some(path);
+				}
+				None => return Err(format!("missing file after '{}'", arg))
+			},
+			"--time" => options.time = true,
+			"--json" => options.json = true,
+			"--latex" => options.latex = true,
+			"--serve-stdio" => options.serve_stdio = true,
+			"--uncertainty" => options.uncertainty = true,
+			"--money" => options.money = true,
+			"--strict" => options.strict = true,
+			"--lint" => options.lint = true,
+			"--fraction" => options.fraction = true,
+			"--degrees" => options.angle = Some(AngleMode::Degrees),
+			"--radians" => options.angle = Some(AngleMode::Radians),
+			"--config" => match args.next() {
+				Some(path) => options.config = Some(path),
+				None => return Err(format!("missing file after '{}'", arg))
+			},
+			"--iterate" => match args.next().map(|value| value.parse::<usize>()) {
+				Some(Ok(rounds)) => options.iterate = Some(rounds),
+				Some(Err(_)) => return Err("--iterate expects a number of rounds".to_string()),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--start" | "--until-delta" => match args.next().map(|value| value.parse::<f64>()) {
+				Some(Ok(value)) if arg == "--start" => options.start = Some(value),
+				Some(Ok(value)) => options.until_delta = Some(value),
+				Some(Err(_)) => return Err(format!("{} expects a number", arg)),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--decimal-comma" => decimal = ',',
+			"--grouping" => match args.next() {
+				Some(name) => grouping = parse_grouping(&name)?,
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--prompt" => match args.next() {
+				Some(prompt) => options.prompt = Some(prompt),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--template" => match args.next() {
+				Some(template) => options.template = Some(Template::parse(&template)?),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--csv" => match args.next() {
+				Some(path) => options.csv = Some(path),
+				None => return Err(format!("missing file after '{}'", arg))
+			},
+			"--expr-col" => match args.next().map(|value| value.parse::<usize>()) {
+				Some(Ok(column)) if column > 0 => options.expr_col = Some(column),
+				Some(_) => return Err("--expr-col expects a column number starting at 1".to_string()),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--header" => options.header = true,
+			"--out" => match args.next() {
+				Some(path) => options.out = Some(path),
+				None => return Err(format!("missing file after '{}'", arg))
+			},
+			"--cache-size" => match args.next().map(|value| value.parse::<usize>()) {
+				Some(Ok(size)) => options.cache_size = Some(size),
+				Some(Err(_)) => return Err("--cache-size expects a number of entries".to_string()),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--tape-size" => match args.next().map(|value| value.parse::<usize>()) {
+				Some(Ok(size)) => options.tape_size = Some(size),
+				Some(Err(_)) => return Err("--tape-size expects a number of entries".to_string()),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--budget" => match args.next().map(|value| value.parse::<u64>()) {
+				Some(Ok(budget)) => options.budget = Some(budget),
+				Some(Err(_)) => return Err("--budget expects a number of operations".to_string()),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--jobs" | "-j" => match args.next().map(|value| value.parse::<usize>()) {
+				Some(Ok(jobs)) if jobs > 0 => options.jobs = Some(jobs),
+				Some(_) => return Err("--jobs expects a number of threads".to_string()),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--progress" => match args.next().map(|value| value.parse::<usize>()) {
+				Some(Ok(every)) if every > 0 => options.progress = Some(every),
+				Some(_) => return Err("--progress expects a number of lines".to_string()),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--no-history" => options.no_history = true,
+			"--dumb-terminal" => options.dumb_terminal = true,
+			"--no-color" => options.no_color = true,
+			"--precision" => match args.next() {
+				Some(value) => options.precision = Some(value.parse()?),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--sigfig" => match args.next().map(|value| value.parse::<usize>()) {
+				Some(Ok(figures @ 1 ..= 17)) => options.precision = Some(Precision::Figures(figures)),
+				Some(_) => return Err("--sigfig expects 1 to 17 significant figures".to_string()),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--format" => match args.next() {
+				Some(value) if value == "seconds" || value == "breakdown" => {
+					options.durations = Some(if value == "seconds" { DurationStyle::Seconds } else { DurationStyle::Breakdown });
+				}
+				// `fixed 2` is two arguments on the command line
+				Some(value) if value == "fixed" && args.peek().is_some_and(|decimals| decimals.parse::<usize>().is_ok()) => {
+					let decimals = args.next().unwrap_or_default();
+					options.notation = Some(format!("fixed {}", decimals).parse()?);
+				}
+				Some(value) => options.notation = Some(value.parse()?),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"--rounding" => match args.next() {
+				Some(value) => options.rounding = Some(value.parse()?),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			"-o" | "--output" => match args.next() {
+				Some(value) => options.radix = Some(value.parse()?),
+				None => return Err(format!("missing value after '{}'", arg))
+			},
+			_ if arg.starts_with('-') && arg != "-" => return Err(format!("unknown argument '{}'", arg)),
+			_ => match options.command {
+				None | Some(Subcommand::Run) => options.files.push(arg),
+				Some(Subcommand::Eval | Subcommand::Sweep) => options.expressions.push(arg),
+				Some(command) => return Err(format!("'syc {}' takes no arguments, got '{}'", command.name(), arg))
+			}
+		}
+	}
+	if options.command == Some(Subcommand::Serve) {
+		options.serve_stdio = true;
+	}
+	if options.command == Some(Subcommand::Eval) && options.expressions.is_empty() && !options.help {
+		return Err("'syc eval' needs at least one expression".to_string());
+	}
+	if options.command == Some(Subcommand::Sweep) && !options.help {
+		if options.expressions.len() != 1 || options.sweeps.is_empty() {
+			return Err("'syc sweep' needs one expression and at least one --var".to_string());
+		}
+		let points = options.sweeps.iter().try_fold(1usize, |total, (_, range)| total.checked_mul(range.points()));
+		if points.is_none_or(|points| points > MAX_SWEEP_POINTS) {
+			return Err(format!("the ranges have more than {} points together", MAX_SWEEP_POINTS));
+		}
+	} else if !options.sweeps.is_empty() {
+		return Err("--var is only available with 'syc sweep'".to_string());
+	}
+	options.locale = Locale::new(decimal, grouping)?;
+	if options.csv.is_some() != options.expr_col.is_some() {
+		return Err("--csv and --expr-col must be used together".to_string());
+	}
+	if options.header && options.csv.is_none() {
+		return Err("--header only applies to --csv".to_string());
+	}
+	if options.serve_stdio && (!options.expressions.is_empty() || !options.files.is_empty() || options.csv.is_some()) {
+		return Err("--serve-stdio reads requests from stdin and cannot be combined with expressions, files or --csv".to_string());
+	}
+	if options.latex && (options.json || options.iterate.is_some() || !options.files.is_empty()) {
+		return Err("--latex only applies to expressions and cannot be combined with --json or --iterate".to_string());
+	}
+	if matches!(options.precision, Some(Precision::Figures(_))) && matches!(options.notation, Some(Notation::Fixed(_))) {
+		return Err("--sigfig and --format fixed cannot be combined, one counts figures and the other decimals".to_string());
+	}
+	if options.summary && options.csv.is_some() {
+		return Err("--summary applies to lines and cannot be combined with --csv".to_string());
+	}
+	if options.jobs.is_some() && (options.verbose || options.time) {
+		return Err("--jobs cannot be combined with --verbose or --time".to_string());
+	}
+	Ok(options)
+}
+
+fn subcommand(name: &str) -> Option<Subcommand> {
+	[Subcommand::Repl, Subcommand::Eval, Subcommand::Run, Subcommand::Serve, Subcommand::Sweep]
+		.into_iter()
+		.find(|command| command.name() == name)
+}
+
+fn check_scope(arg: &str, command: Subcommand) -> Result<(), String> {
+	match flag_scope(arg) {
+		Some([]) => Err(format!("'{}' is only available without a subcommand", arg)),
+		Some(scope) if !scope.contains(&command) => Err(format!("'{}' cannot be used with 'syc {}'", arg, command.name())),
+		_ => Ok(())
+	}
+}
+
+/// The subcommands a flag applies to, or `None` for arguments that are not flags.
+fn flag_scope(arg: &str) -> Option<&'static [Subcommand]> {
+	use Subcommand::*;
+	Some(match arg {
+		"-h" | "--help" | "-D" | "--define" | "--config" | "--precision" | "--sigfig" | "--format" | "--rounding" | "-o" | "--output"
+		| "--decimal-comma" | "--grouping" | "--degrees" | "--radians" | "--no-color" | "--cache-size" | "--tape-size"
+		| "--budget" | "--uncertainty" | "--money" | "--strict" | "--lint" | "--fraction" => {
+			&[Repl, Eval, Run, Serve, Sweep]
+		}
+		"-v" | "--verbose" | "--time" => &[Repl, Eval, Run],
+		"--template" => &[Repl, Eval],
+		"--no-history" | "--prompt" | "--dumb-terminal" => &[Repl],
+		"--json" => &[Eval, Run, Sweep],
+		"--fail-fast" => &[Eval, Run],
+		"-e" | "--eval" | "--latex" | "--iterate" | "--start" | "--until-delta" => &[Eval],
+		"-j" | "--jobs" | "--progress" | "--csv" | "--expr-col" | "--header" | "--out" | "--summary" | "--summary-file" => &[Run],
+		"--var" => &[Sweep],
+		"--serve-stdio" => &[],
+		_ => return None
+	})
+}
+
+pub fn usage_text() -> String {
+	let mut text = String::new();
+	text.push_str("Usage: syc [COMMAND] [OPTIONS]\n\n");
+	text.push_str("Commands:\n");
+	text.push_str("  repl                   interactive calculator, the default on a terminal\n");
+	text.push_str("  eval EXPR...           evaluate expressions and print their results\n");
+	text.push_str("  run [FILE...]          evaluate every line of the files, or of stdin ('-' or none)\n");
+	text.push_str("  serve                  answer JSON requests on stdin, one per line\n");
+	text.push_str("  sweep EXPR --var ...   tabulate EXPR over ranges of its variables\n");
+	text.push_str("  help                   show this help\n\n");
+	text.push_str("Without a command, -e EXPR evaluates expressions, FILE arguments are run and piped\n");
+	text.push_str("input is evaluated line by line; every option below is accepted, and --serve-stdio\n");
+	text.push_str("is the same as serve. Options may come before or after the command.\n\n");
+	text.push_str("Global options:\n");
+	text.push_str("  -D, --define NAME=EXPR define a variable before anything is evaluated\n");
+	text.push_str("      --config FILE      read defaults from FILE instead of ~/.config/syc/config.toml\n");
+	text.push_str("      --precision N|full show results with N significant digits\n");
+	text.push_str("      --sigfig N         show results with exactly N significant figures\n");
+	text.push_str("      --format NOTATION  auto, fixed [N], sci or eng; seconds or breakdown for durations\n");
+	text.push_str("      --rounding MODE    half-up, half-even, toward-zero or away-from-zero\n");
+	text.push_str("      --uncertainty      propagate uncertainties written as 12.3 ± 0.2 or 12.3 +- 0.2\n");
+	text.push_str("      --money            compute in whole cents and show two decimals\n");
+	text.push_str("      --fraction         show results as fractions such as 3/4 where exact\n");
+	text.push_str("      --strict           reject ans, of, percent changes, inf or NaN results, literals\n");
+	text.push_str("                         with more digits than are kept, dropped tags and rounded money\n");
+	text.push_str("      --lint             warn about redundant parentheses, double negation, division by 0\n");
+	text.push_str("                         and lets that are never read\n");
+	text.push_str("  -o, --output RADIX     show integer results as dec, hex, bin, oct or hex64\n");
+	text.push_str("      --decimal-comma    read and write numbers with a decimal comma\n");
+	text.push_str("      --grouping STYLE   group thousands with space, point, underscore or apostrophe\n");
+	text.push_str("      --degrees          measure angles in degrees\n");
+	text.push_str("      --radians          measure angles in radians (the default)\n");
+	text.push_str("      --no-color         never color the output\n");
+	text.push_str("      --cache-size N     keep N compiled expressions, 0 disables the cache\n");
+	text.push_str("      --tape-size N      keep the last N results for $1, $2, ... (default 1000)\n");
+	text.push_str("      --budget N         stop any evaluation that takes more than N operations\n");
+	text.push_str("  -h, --help             show this help\n\n");
+	text.push_str("repl options:\n");
+	text.push_str("      --prompt TEXT      use TEXT as the prompt\n");
+	text.push_str("      --template TEXT    format result lines with {input}, {result}, {index} and {time}\n");
+	text.push_str("      --no-history       do not read or save the history file\n");
+	text.push_str("      --dumb-terminal    read plain lines without colors or cursor movement, the default\n");
+	text.push_str("                         with TERM=dumb or when only the output is a terminal\n");
+	text.push_str("  -v, --verbose          print the tokens, RPN and evaluation steps\n");
+	text.push_str("      --time             report how long parsing and evaluation took\n\n");
+	text.push_str("eval options:\n");
+	text.push_str("      --json             print one JSON object per result\n");
+	text.push_str("      --template TEXT    format result lines as in the REPL\n");
+	text.push_str("      --latex            print each expression as LaTeX instead of evaluating it\n");
+	text.push_str("      --iterate N        evaluate the expression N times, feeding each result back as ans\n");
+	text.push_str("      --start X          the value of ans before the first round\n");
+	text.push_str("      --until-delta D    stop iterating once consecutive values are within D\n");
+	text.push_str("      --fail-fast        stop at the first error\n");
+	text.push_str("  -v, --verbose, --time  as for repl\n\n");
+	text.push_str("run options:\n");
+	text.push_str("      --json             print one JSON object per line\n");
+	text.push_str("      --fail-fast        stop at the first error\n");
+	text.push_str("  -j, --jobs N           evaluate independent lines on N threads\n");
+	text.push_str("      --progress N       report progress on stderr every N lines\n");
+	text.push_str("      --csv FILE         evaluate a column of FILE, with --expr-col N\n");
+	text.push_str("      --expr-col N       the 1-based column holding the expressions\n");
+	text.push_str("      --header           the first row of the CSV names the columns\n");
+	text.push_str("      --out FILE         write the CSV results to FILE\n");
+	text.push_str("      --summary          report counts, the range and mean of the results, the time\n");
+	text.push_str("                         taken and the slowest lines on stderr after each input\n");
+	text.push_str("      --summary-file F   write that report to F instead\n");
+	text.push_str("  -v, --verbose, --time  as for repl\n\n");
+	text.push_str("sweep options:\n");
+	text.push_str("      --var NAME=RANGE   sweep NAME over START..END:STEP, END included; one column\n");
+	text.push_str("                         each, and several give every combination\n");
+	text.push_str("      --json             print one JSON object per point\n\n");
+	text.push_str("Exit status is 0 on success, 1 for usage errors or unreadable input and 2 when an\n");
+	text.push_str("expression failed.\n");
+	text
+}
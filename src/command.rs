@@ -0,0 +1,594 @@
+use shunting_yard::display::DEFAULT_MAX_DENOMINATOR;
+use shunting_yard::{is_identifier, parse_grouping, AngleMode, DurationStyle, Lint, Notation, Precision, Radix, Rounding, RuleLevel};
+
+use crate::result_format::Template;
+
+/// How many evaluations `:last` lists without a count.
+const DEFAULT_LAST: usize = 10;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+	Quit,
+	Help(Option<String>),
+	Vars,
+	/// `:info NAME`, what a variable, constant or function is.
+	Info(String),
+	Delete(Vec<String>),
+	Debug(Option<bool>),
+	Time(Option<bool>),
+	Stats,
+	Tape,
+	ClearTape,
+	SaveTape(String),
+	/// `:save FILE`, the variables set this session as assignments.
+	Save(String),
+	/// `:load FILE`, the lines of a file as if typed, such as one written by `:save`.
+	Load(String),
+	/// `:template NAME = BODY`, an expression with `?name` placeholders.
+	DefineTemplate { name: String, body: String },
+	Templates,
+	/// `:use NAME p=VALUE ...`, a template with a value for each placeholder.
+	UseTemplate { name: String, values: Vec<(String, String)> },
+	/// `:last N`, the last N evaluations of the session.
+	Last(usize),
+	/// `:redo N`, evaluation N again.
+	Redo(usize),
+	Latex(String),
+	Simplify(String),
+	Explain(String),
+	/// `:tokens EXPR`, the lexemes of EXPR with their spans, without evaluating it.
+	Tokens(String),
+	/// `:rpn EXPR`, the postfix queue of EXPR, without evaluating it.
+	Rpn(String),
+	/// `:canonical EXPR`, EXPR as it would be typed in the default locale.
+	Canonical(String),
+	Dot { expression: String, path: Option<String> },
+	Precision(Option<Precision>),
+	/// `:sigfig N`, or `:sigfig off` for `None`.
+	SigFig(Option<usize>),
+	ShowSigFig,
+	Format(Option<Notation>),
+	Durations(DurationStyle),
+	/// `:format percent-helpers [on|off]`, `None` to toggle.
+	PercentHelpers(Option<bool>),
+	Output(Option<Radix>),
+	Mode(Option<AngleMode>),
+	/// `:mode money [on|off]`.
+	Money(bool),
+	Rounding(Option<Rounding>),
+	Uncertainty(Option<bool>),
+	/// `:strict on`, `:strict warn` or `:strict off`, for every rule.
+	Strict(Option<RuleLevel>),
+	/// `:lint on` or `:lint off`.
+	Lint(bool),
+	/// `:lint allow NAME`, or `:lint warn NAME` for `false`.
+	AllowLint(Lint, bool),
+	ShowLints,
+	Tolerance(Option<f64>),
+	TermLimit(Option<u64>),
+	/// `:budget N`, or `:budget off` for `None`.
+	Budget(Option<u64>),
+	ShowBudget,
+	Decimal(char),
+	Grouping(Option<char>),
+	ShowLocale,
+	/// `:fraction N`, `:fraction on` for the default denominator, or `:fraction off` for `None`.
+	Fraction(Option<u64>),
+	/// `:fraction` alone, off when on and on with the default denominator when off.
+	ToggleFraction,
+	Iterate { rounds: usize, start: Option<f64>, tolerance: Option<f64>, expression: String },
+	SetPrompt(String),
+	SetTemplate(Template),
+	Invalid(String),
+	Unknown(String)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Input<'a> {
+	Empty,
+	Command(Command),
+	Expression(&'a str)
+}
+
+pub fn parse_input(line: &str) -> Input<'_> {
+	let trimmed = line.trim();
+	if trimmed.is_empty() {
+		return Input::Empty;
+	}
+	if let Some(command) = trimmed.strip_prefix(':') {
+		return Input::Command(parse_command(command.trim()));
+	}
+	match trimmed {
+		"exit" | "quit" | "end" => Input::Command(Command::Quit),
+		_ => Input::Expression(trimmed)
+	}
+}
+
+fn parse_command(command: &str) -> Command {
+	let (name, rest) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+	let args: Vec<String> = rest.split_whitespace().map(str::to_string).collect();
+	match name {
+		"q" | "quit" | "exit" => Command::Quit,
+		"h" | "help" => match args.as_slice() {
+			[] => Command::Help(None),
+			[topic] => Command::Help(Some(topic.clone())),
+			_ => Command::Invalid("usage: :help [TOPIC]".to_string())
+		},
+		"vars" => Command::Vars,
+		"info" => match args.as_slice() {
+			[name] => Command::Info(name.clone()),
+			_ => Command::Invalid("usage: :info NAME".to_string())
+		},
+		"stats" => Command::Stats,
+		"tape" => match args.as_slice() {
+			[] => Command::Tape,
+			[action] if action == "clear" => Command::ClearTape,
+			[action, path] if action == "save" => Command::SaveTape(path.clone()),
+			_ => Command::Invalid("usage: :tape [clear | save FILE]".to_string())
+		},
+		"save" => match args.as_slice() {
+			[path] => Command::Save(path.clone()),
+			_ => Command::Invalid("usage: :save FILE".to_string())
+		},
+		"load" => match args.as_slice() {
+			[path] => Command::Load(path.clone()),
+			_ => Command::Invalid("usage: :load FILE".to_string())
+		},
+		"template" => match rest.split_once('=') {
+			Some((name, body)) if is_identifier(name.trim()) && !body.trim().is_empty() => {
+				Command::DefineTemplate { name: name.trim().to_string(), body: body.trim().to_string() }
+			}
+			_ => Command::Invalid("usage: :template NAME = EXPRESSION".to_string())
+		},
+		"templates" => Command::Templates,
+		"use" => match args.split_first() {
+			Some((name, values)) => parse_use(name, values),
+			None => Command::Invalid("usage: :use NAME [PLACEHOLDER=VALUE]...".to_string())
+		},
+		"last" => match args.as_slice() {
+			[] => Command::Last(DEFAULT_LAST),
+			[count] => count.parse().map_or_else(|_| Command::Invalid(format!("invalid count '{}'", count)), Command::Last),
+			_ => Command::Invalid("usage: :last [N]".to_string())
+		},
+		"redo" => match args.as_slice() {
+			[number] => number.trim_start_matches('#').parse().map_or_else(
+				|_| Command::Invalid(format!("invalid evaluation number '{}'", number)),
+				Command::Redo
+			),
+			_ => Command::Invalid("usage: :redo N".to_string())
+		},
+		"latex" if rest.trim().is_empty() => Command::Invalid("usage: :latex EXPRESSION".to_string()),
+		"latex" => Command::Latex(rest.trim().to_string()),
+		"simplify" if rest.trim().is_empty() => Command::Invalid("usage: :simplify EXPRESSION".to_string()),
+		"simplify" => Command::Simplify(rest.trim().to_string()),
+		"explain" if rest.trim().is_empty() => Command::Invalid("usage: :explain EXPRESSION".to_string()),
+		"explain" => Command::Explain(rest.trim().to_string()),
+		"tokens" if rest.trim().is_empty() => Command::Invalid("usage: :tokens EXPRESSION".to_string()),
+		"tokens" => Command::Tokens(rest.trim().to_string()),
+		"rpn" if rest.trim().is_empty() => Command::Invalid("usage: :rpn EXPRESSION".to_string()),
+		"rpn" => Command::Rpn(rest.trim().to_string()),
+		"canonical" if rest.trim().is_empty() => Command::Invalid("usage: :canonical EXPRESSION".to_string()),
+		"canonical" => Command::Canonical(rest.trim().to_string()),
+		"dot" => parse_dot(rest.trim()),
+		"del" if args.is_empty() => Command::Invalid("usage: :del NAME...".to_string()),
+		"del" => Command::Delete(args),
+		"debug" => match args.first().map(String::as_str) {
+			None => Command::Debug(None),
+			Some("on") if args.len() == 1 => Command::Debug(Some(true)),
+			Some("off") if args.len() == 1 => Command::Debug(Some(false)),
+			Some(_) => Command::Invalid("usage: :debug [on|off]".to_string())
+		},
+		"time" => match args.first().map(String::as_str) {
+			None => Command::Time(None),
+			Some("on") if args.len() == 1 => Command::Time(Some(true)),
+			Some("off") if args.len() == 1 => Command::Time(Some(false)),
+			Some(_) => Command::Invalid("usage: :time [on|off]".to_string())
+		},
+		"precision" => match args.as_slice() {
+			[] => Command::Precision(None),
+			[value] => value.parse().map_or_else(Command::Invalid, |precision| Command::Precision(Some(precision))),
+			_ => Command::Invalid("usage: :precision [DIGITS|full]".to_string())
+		},
+		"sigfig" => match args.as_slice() {
+			[] => Command::ShowSigFig,
+			[value] if value == "off" => Command::SigFig(None),
+			[value] => match value.parse::<usize>() {
+				Ok(figures @ 1 ..= 17) => Command::SigFig(Some(figures)),
+				_ => Command::Invalid(format!("invalid number of significant figures '{}', expected 1 to 17", value))
+			},
+			_ => Command::Invalid("usage: :sigfig [N|off]".to_string())
+		},
+		"format" if args.is_empty() => Command::Format(None),
+		"format" if args == ["seconds"] => Command::Durations(DurationStyle::Seconds),
+		"format" if args == ["breakdown"] => Command::Durations(DurationStyle::Breakdown),
+		"format" if args.first().is_some_and(|arg| arg == "percent-helpers") => match &args[1 ..] {
+			[] => Command::PercentHelpers(None),
+			[value] if value == "on" => Command::PercentHelpers(Some(true)),
+			[value] if value == "off" => Command::PercentHelpers(Some(false)),
+			_ => Command::Invalid("usage: :format percent-helpers [on|off]".to_string())
+		},
+		"format" => args.join(" ").parse().map_or_else(Command::Invalid, |notation| Command::Format(Some(notation))),
+		"output" => match args.as_slice() {
+			[] => Command::Output(None),
+			[value] => value.parse().map_or_else(Command::Invalid, |radix| Command::Output(Some(radix))),
+			_ => Command::Invalid("usage: :output [dec|hex|bin|oct|hex64]".to_string())
+		},
+		"mode" => match args.as_slice() {
+			[] => Command::Mode(None),
+			[mode] if mode == "deg" => Command::Mode(Some(AngleMode::Degrees)),
+			[mode] if mode == "rad" => Command::Mode(Some(AngleMode::Radians)),
+			[mode] if mode == "money" => Command::Money(true),
+			[mode, value] if mode == "money" && (value == "on" || value == "off") => Command::Money(value == "on"),
+			_ => Command::Invalid("usage: :mode [deg|rad|money [on|off]]".to_string())
+		},
+		"rounding" => match args.as_slice() {
+			[] => Command::Rounding(None),
+			[value] => value.parse().map_or_else(Command::Invalid, |rounding| Command::Rounding(Some(rounding))),
+			_ => Command::Invalid("usage: :rounding [half-up|half-even|toward-zero|away-from-zero]".to_string())
+		},
+		"lint" => match args.as_slice() {
+			[] => Command::ShowLints,
+			[value] if value == "on" => Command::Lint(true),
+			[value] if value == "off" => Command::Lint(false),
+			[level, name] if level == "allow" || level == "warn" => match Lint::by_name(name) {
+				Some(lint) => Command::AllowLint(lint, level == "allow"),
+				None => {
+					let names: Vec<&str> = Lint::ALL.iter().map(|lint| lint.name()).collect();
+					Command::Invalid(format!("unknown lint '{}', expected {}", name, names.join(", ")))
+				}
+			},
+			_ => Command::Invalid("usage: :lint [on|off] or :lint allow|warn NAME".to_string())
+		},
+		"uncertainty" => match args.as_slice() {
+			[] => Command::Uncertainty(None),
+			[value] if value == "on" => Command::Uncertainty(Some(true)),
+			[value] if value == "off" => Command::Uncertainty(Some(false)),
+			_ => Command::Invalid("usage: :uncertainty [on|off]".to_string())
+		},
+		"strict" => match args.as_slice() {
+			[] => Command::Strict(None),
+			[value] if value == "on" => Command::Strict(Some(RuleLevel::Error)),
+			[value] if value == "warn" => Command::Strict(Some(RuleLevel::Warn)),
+			[value] if value == "off" => Command::Strict(Some(RuleLevel::Allow)),
+			_ => Command::Invalid("usage: :strict [on|warn|off]".to_string())
+		},
+		"tolerance" => match args.as_slice() {
+			[] => Command::Tolerance(None),
+			[value] => match value.parse::<f64>() {
+				Ok(tolerance) if tolerance > 0.0 && tolerance.is_finite() => Command::Tolerance(Some(tolerance)),
+				_ => Command::Invalid(format!("invalid tolerance '{}', expected a positive number", value))
+			},
+			_ => Command::Invalid("usage: :tolerance [N]".to_string())
+		},
+		"limit" => match args.as_slice() {
+			[] => Command::TermLimit(None),
+			[value] => value.parse().map_or_else(
+				|_| Command::Invalid(format!("invalid limit '{}', expected a number of terms", value)),
+				|limit| Command::TermLimit(Some(limit))
+			),
+			_ => Command::Invalid("usage: :limit [N]".to_string())
+		},
+		"budget" => match args.as_slice() {
+			[] => Command::ShowBudget,
+			[value] if value == "off" => Command::Budget(None),
+			[value] => value.parse().map_or_else(
+				|_| Command::Invalid(format!("invalid budget '{}', expected a number of operations", value)),
+				|budget| Command::Budget(Some(budget))
+			),
+			_ => Command::Invalid("usage: :budget [N|off]".to_string())
+		},
+		"fraction" => match args.as_slice() {
+			[] => Command::ToggleFraction,
+			[value] if value == "on" => Command::Fraction(Some(DEFAULT_MAX_DENOMINATOR)),
+			[value] if value == "off" => Command::Fraction(None),
+			[value] => match value.parse::<u64>() {
+				Ok(max @ 1 ..= 1_000_000_000) => Command::Fraction(Some(max)),
+				_ => Command::Invalid(format!("invalid largest denominator '{}', expected 1 to 1000000000", value))
+			},
+			_ => Command::Invalid("usage: :fraction [on|off|MAX_DENOMINATOR]".to_string())
+		},
+		"decimal" | "grouping" if args.is_empty() => Command::ShowLocale,
+		"decimal" => match args.as_slice() {
+			[name] if name == "point" => Command::Decimal('.'),
+			[name] if name == "comma" => Command::Decimal(','),
+			_ => Command::Invalid("usage: :decimal [point|comma]".to_string())
+		},
+		"grouping" => match args.as_slice() {
+			[name] => parse_grouping(name).map_or_else(Command::Invalid, Command::Grouping),
+			_ => Command::Invalid("usage: :grouping [off|space|point|underscore|apostrophe]".to_string())
+		},
+		"set" => parse_set(rest.trim_start()),
+		"iterate" => parse_iterate(rest.trim_start()),
+		_ => Command::Unknown(name.to_string())
+	}
+}
+
+fn parse_use(name: &str, values: &[String]) -> Command {
+	let mut pairs = Vec::with_capacity(values.len());
+	for value in values {
+		match value.split_once('=') {
+			Some((placeholder, value)) if !placeholder.is_empty() && !value.is_empty() => {
+				pairs.push((placeholder.trim_start_matches('?').to_string(), value.to_string()));
+			}
+			_ => return Command::Invalid(format!("expected PLACEHOLDER=VALUE, found '{}'", value))
+		}
+	}
+	Command::UseTemplate { name: name.to_string(), values: pairs }
+}
+
+/// `:iterate N [start=X] [until=DELTA] EXPRESSION`
+fn parse_iterate(args: &str) -> Command {
+	const USAGE: &str = "usage: :iterate N [start=X] [until=DELTA] EXPRESSION";
+	let (rounds, mut rest) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+	let Ok(rounds) = rounds.parse::<usize>() else {
+		return Command::Invalid(USAGE.to_string());
+	};
+	let mut start = None;
+	let mut tolerance = None;
+	loop {
+		rest = rest.trim_start();
+		let (word, tail) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+		let (option, value) = match word.split_once('=') {
+			Some(("start", value)) => (&mut start, value),
+			Some(("until", value)) => (&mut tolerance, value),
+			_ => break
+		};
+		let Ok(value) = value.parse::<f64>() else {
+			return Command::Invalid(format!("invalid number '{}'", value));
+		};
+		*option = Some(value);
+		rest = tail;
+	}
+	if rest.is_empty() {
+		return Command::Invalid(USAGE.to_string());
+	}
+	Command::Iterate { rounds, start, tolerance, expression: rest.to_string() }
+}
+
+/// `:dot EXPRESSION [> FILE]`. Expressions never contain `>`, so the last one starts the file name.
+fn parse_dot(args: &str) -> Command {
+	let (expression, path) = match args.rsplit_once('>') {
+		Some((expression, path)) => (expression.trim(), Some(path.trim())),
+		None => (args, None)
+	};
+	if expression.is_empty() || path == Some("") {
+		return Command::Invalid("usage: :dot EXPRESSION [> FILE]".to_string());
+	}
+	Command::Dot { expression: expression.to_string(), path: path.map(str::to_string) }
+}
+
+/// `:set NAME VALUE`, where the value is the rest of the line. Surrounding double quotes are removed
+/// so that values can keep leading or trailing spaces.
+fn parse_set(args: &str) -> Command {
+	let (name, raw) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+	let raw = raw.trim();
+	let value = raw.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(raw);
+	match name {
+		"prompt" | "template" if raw.is_empty() => Command::Invalid(format!("usage: :set {} TEXT", name)),
+		"prompt" => Command::SetPrompt(value.to_string()),
+		"template" => Template::parse(value).map_or_else(Command::Invalid, Command::SetTemplate),
+		_ => Command::Invalid("usage: :set prompt TEXT | :set template TEMPLATE".to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn command(line: &str) -> Command {
+		match parse_input(line) {
+			Input::Command(command) => command,
+			input => panic!("{:?} is not a command", input)
+		}
+	}
+
+	#[test]
+	fn the_words_and_commands_for_leaving_quit() {
+		for line in [":quit", ":q", ":exit", "exit", "quit", "end", "  quit\n", ": quit"] {
+			assert_eq!(command(line), Command::Quit, "{:?}", line);
+		}
+		assert_eq!(parse_input("exit + 1"), Input::Expression("exit + 1"));
+	}
+
+	#[test]
+	fn commands_are_parsed_apart_from_expressions() {
+		assert_eq!(command(":help"), Command::Help(None));
+		assert_eq!(command(":h operators"), Command::Help(Some("operators".to_string())));
+		assert_eq!(command(":vars"), Command::Vars);
+		assert_eq!(command(":frobnicate 1"), Command::Unknown("frobnicate".to_string()));
+		assert!(matches!(command(":help a b"), Command::Invalid(_)));
+		assert_eq!(parse_input("  \r\n"), Input::Empty);
+		assert_eq!(parse_input(" 1 + 2\n"), Input::Expression("1 + 2"));
+	}
+
+	#[test]
+	fn last_and_redo_take_counts_and_numbers() {
+		assert_eq!(command(":last"), Command::Last(DEFAULT_LAST));
+		assert_eq!(command(":last 5"), Command::Last(5));
+		assert_eq!(command(":redo 3"), Command::Redo(3));
+		assert_eq!(command(":redo #3"), Command::Redo(3));
+		for line in [":last x", ":last 1 2", ":redo", ":redo three"] {
+			assert!(matches!(command(line), Command::Invalid(_)), "{}", line);
+		}
+	}
+
+	#[test]
+	fn dot_may_write_to_a_file() {
+		assert_eq!(command(":dot 5 - 3"), Command::Dot { expression: "5 - 3".to_string(), path: None });
+		assert_eq!(command(":dot 5-3 > tree.dot"), Command::Dot { expression: "5-3".to_string(), path: Some("tree.dot".to_string()) });
+		assert!(matches!(command(":dot 1 >"), Command::Invalid(_)));
+		assert!(matches!(command(":dot"), Command::Invalid(_)));
+	}
+
+	#[test]
+	fn every_command_and_its_arguments() {
+		use Command::*;
+		let s = |text: &str| text.to_string();
+		let table = [
+			(":help", Help(None)),
+			(":h operators", Help(Some(s("operators")))),
+			(":vars", Vars),
+			(":info pi", Info(s("pi"))),
+			(":stats", Stats),
+			(":tape", Tape),
+			(":tape clear", ClearTape),
+			(":tape save tape.txt", SaveTape(s("tape.txt"))),
+			(":save vars.txt", Save(s("vars.txt"))),
+			(":load vars.txt", Load(s("vars.txt"))),
+			(":template net = ?gross * 0.8", DefineTemplate { name: s("net"), body: s("?gross * 0.8") }),
+			(":templates", Templates),
+			(":use net", UseTemplate { name: s("net"), values: Vec::new() }),
+			(":use net ?gross=100 rate=2", UseTemplate { name: s("net"), values: vec![(s("gross"), s("100")), (s("rate"), s("2"))] }),
+			(":last", Last(DEFAULT_LAST)),
+			(":last 5", Last(5)),
+			(":redo #3", Redo(3)),
+			(":latex 3/(x+1)^2", Latex(s("3/(x+1)^2"))),
+			(":simplify x*1 + 0", Simplify(s("x*1 + 0"))),
+			(":explain 1 + 2", Explain(s("1 + 2"))),
+			(":tokens 1 + 2", Tokens(s("1 + 2"))),
+			(":rpn 1 + 2", Rpn(s("1 + 2"))),
+			(":canonical 1 + 2", Canonical(s("1 + 2"))),
+			(":dot a*b+c", Dot { expression: s("a*b+c"), path: None }),
+			(":del x y", Delete(vec![s("x"), s("y")])),
+			(":debug", Debug(None)),
+			(":debug on", Debug(Some(true))),
+			(":debug off", Debug(Some(false))),
+			(":time", Time(None)),
+			(":time on", Time(Some(true))),
+			(":time off", Time(Some(false))),
+			(":precision", Precision(None)),
+			(":precision 4", Precision(Some(shunting_yard::Precision::Significant(4)))),
+			(":precision full", Precision(Some(shunting_yard::Precision::Full))),
+			(":sigfig", ShowSigFig),
+			(":sigfig 3", SigFig(Some(3))),
+			(":sigfig off", SigFig(None)),
+			(":format", Format(None)),
+			(":format auto", Format(Some(Notation::Auto))),
+			(":format fixed", Format(Some(Notation::Fixed(2)))),
+			(":format fixed 4", Format(Some(Notation::Fixed(4)))),
+			(":format sci", Format(Some(Notation::Scientific))),
+			(":format eng", Format(Some(Notation::Engineering))),
+			(":format seconds", Durations(DurationStyle::Seconds)),
+			(":format breakdown", Durations(DurationStyle::Breakdown)),
+			(":format percent-helpers", PercentHelpers(None)),
+			(":format percent-helpers on", PercentHelpers(Some(true))),
+			(":format percent-helpers off", PercentHelpers(Some(false))),
+			(":output", Output(None)),
+			(":output hex", Output(Some(Radix::Hexadecimal))),
+			(":output hex64", Output(Some(Radix::Hex64))),
+			(":mode", Mode(None)),
+			(":mode deg", Mode(Some(AngleMode::Degrees))),
+			(":mode rad", Mode(Some(AngleMode::Radians))),
+			(":mode money", Money(true)),
+			(":mode money off", Money(false)),
+			(":rounding", Rounding(None)),
+			(":rounding bankers", Rounding(Some(shunting_yard::Rounding::HalfEven))),
+			(":lint", ShowLints),
+			(":lint on", Lint(true)),
+			(":lint off", Lint(false)),
+			(":lint allow double-negation", AllowLint(shunting_yard::Lint::DoubleNegation, true)),
+			(":lint warn division-by-zero", AllowLint(shunting_yard::Lint::DivisionByZero, false)),
+			(":uncertainty", Uncertainty(None)),
+			(":uncertainty on", Uncertainty(Some(true))),
+			(":strict", Strict(None)),
+			(":strict on", Strict(Some(RuleLevel::Error))),
+			(":strict warn", Strict(Some(RuleLevel::Warn))),
+			(":strict off", Strict(Some(RuleLevel::Allow))),
+			(":tolerance", Tolerance(None)),
+			(":tolerance 1e-9", Tolerance(Some(1e-9))),
+			(":limit", TermLimit(None)),
+			(":limit 500", TermLimit(Some(500))),
+			(":budget", ShowBudget),
+			(":budget 1000", Budget(Some(1000))),
+			(":budget off", Budget(None)),
+			(":fraction", ToggleFraction),
+			(":fraction on", Fraction(Some(DEFAULT_MAX_DENOMINATOR))),
+			(":fraction off", Fraction(None)),
+			(":fraction 8", Fraction(Some(8))),
+			(":decimal", ShowLocale),
+			(":grouping", ShowLocale),
+			(":decimal comma", Decimal(',')),
+			(":grouping apostrophe", Grouping(Some('\''))),
+			(":grouping off", Grouping(None)),
+			(":set prompt \"calc> \"", SetPrompt(s("calc> "))),
+			(":set template {result}", SetTemplate(Template::parse("{result}").unwrap())),
+			(":iterate 5 x / 2", Iterate { rounds: 5, start: None, tolerance: None, expression: s("x / 2") }),
+			(":iterate 20 start=1 until=1e-6 (x + 2 / x) / 2", Iterate { rounds: 20, start: Some(1.0), tolerance: Some(1e-6), expression: s("(x + 2 / x) / 2") })
+		];
+		for (line, expected) in table {
+			assert_eq!(command(line), expected, "{}", line);
+		}
+	}
+
+	#[test]
+	fn bad_arguments_are_explained() {
+		let table = [
+			(":help a b", "usage: :help [TOPIC]"),
+			(":info", "usage: :info NAME"),
+			(":info a b", "usage: :info NAME"),
+			(":tape rewind", "usage: :tape [clear | save FILE]"),
+			(":tape save", "usage: :tape [clear | save FILE]"),
+			(":save", "usage: :save FILE"),
+			(":load a b", "usage: :load FILE"),
+			(":template net", "usage: :template NAME = EXPRESSION"),
+			(":template 2x = 1", "usage: :template NAME = EXPRESSION"),
+			(":template net =", "usage: :template NAME = EXPRESSION"),
+			(":use", "usage: :use NAME [PLACEHOLDER=VALUE]..."),
+			(":use net gross", "expected PLACEHOLDER=VALUE, found 'gross'"),
+			(":use net gross=", "expected PLACEHOLDER=VALUE, found 'gross='"),
+			(":last x", "invalid count 'x'"),
+			(":last 1 2", "usage: :last [N]"),
+			(":redo", "usage: :redo N"),
+			(":redo three", "invalid evaluation number 'three'"),
+			(":latex", "usage: :latex EXPRESSION"),
+			(":simplify", "usage: :simplify EXPRESSION"),
+			(":explain", "usage: :explain EXPRESSION"),
+			(":tokens", "usage: :tokens EXPRESSION"),
+			(":rpn", "usage: :rpn EXPRESSION"),
+			(":canonical", "usage: :canonical EXPRESSION"),
+			(":dot", "usage: :dot EXPRESSION [> FILE]"),
+			(":dot 1 >", "usage: :dot EXPRESSION [> FILE]"),
+			(":del", "usage: :del NAME..."),
+			(":debug yes", "usage: :debug [on|off]"),
+			(":time on off", "usage: :time [on|off]"),
+			(":precision 18", "invalid precision '18', expected 1 to 17 digits or 'full'"),
+			(":precision 1 2", "usage: :precision [DIGITS|full]"),
+			(":sigfig 0", "invalid number of significant figures '0', expected 1 to 17"),
+			(":sigfig 3 4", "usage: :sigfig [N|off]"),
+			(":format fixed 18", "invalid number of decimals '18', expected 0 to 17"),
+			(":format roman", "invalid format 'roman', expected auto, fixed [N], sci or eng"),
+			(":format percent-helpers maybe", "usage: :format percent-helpers [on|off]"),
+			(":output ter", "invalid output 'ter', expected dec, hex, bin, oct or hex64"),
+			(":output hex bin", "usage: :output [dec|hex|bin|oct|hex64]"),
+			(":mode grad", "usage: :mode [deg|rad|money [on|off]]"),
+			(":mode money maybe", "usage: :mode [deg|rad|money [on|off]]"),
+			(":rounding up", "invalid rounding 'up', expected half-up, half-even, toward-zero or away-from-zero"),
+			(":rounding half-up half-even", "usage: :rounding [half-up|half-even|toward-zero|away-from-zero]"),
+			(":lint allow everything", "unknown lint 'everything', expected redundant-parens, double-negation, division-by-zero, unused-let"),
+			(":lint maybe", "usage: :lint [on|off] or :lint allow|warn NAME"),
+			(":uncertainty maybe", "usage: :uncertainty [on|off]"),
+			(":strict hard", "usage: :strict [on|warn|off]"),
+			(":tolerance -1", "invalid tolerance '-1', expected a positive number"),
+			(":tolerance 1 2", "usage: :tolerance [N]"),
+			(":limit many", "invalid limit 'many', expected a number of terms"),
+			(":limit 1 2", "usage: :limit [N]"),
+			(":budget lots", "invalid budget 'lots', expected a number of operations"),
+			(":budget 1 2", "usage: :budget [N|off]"),
+			(":fraction 0", "invalid largest denominator '0', expected 1 to 1000000000"),
+			(":fraction on 8", "usage: :fraction [on|off|MAX_DENOMINATOR]"),
+			(":decimal dot", "usage: :decimal [point|comma]"),
+			(":grouping comma", "invalid grouping 'comma', expected off, space, point, underscore or apostrophe"),
+			(":grouping space point", "usage: :grouping [off|space|point|underscore|apostrophe]"),
+			(":set prompt", "usage: :set prompt TEXT"),
+			(":set template", "usage: :set template TEXT"),
+			(":set color red", "usage: :set prompt TEXT | :set template TEMPLATE"),
+			(":set template {value}", "unknown placeholder '{value}', expected one of {input}, {result}, {index}, {time}"),
+			(":iterate x / 2", "usage: :iterate N [start=X] [until=DELTA] EXPRESSION"),
+			(":iterate 5 start=one x / 2", "invalid number 'one'"),
+			(":iterate 5 until=1e-6", "usage: :iterate N [start=X] [until=DELTA] EXPRESSION")
+		];
+		for (line, message) in table {
+			assert_eq!(command(line), Command::Invalid(message.to_string()), "{}", line);
+		}
+	}
+}
@@ -0,0 +1,71 @@
+use shunting_yard::{Context, Function, CONSTANTS};
+
+fn is_identifier_char(c: char) -> bool {
+	c.is_alphanumeric() || c == '_'
+}
+
+/// Completes the identifier that ends at byte offset `pos` of `line`. Returns the byte offset the
+/// identifier starts at together with the sorted candidates that may replace it; function
+/// candidates carry their opening parenthesis.
+pub fn complete(line: &str, pos: usize, context: &Context) -> (usize, Vec<String>) {
+	let before = &line[.. pos];
+	let start = before.char_indices().rev()
+		.take_while(|(_, c)| is_identifier_char(*c))
+		.last()
+		.map_or(pos, |(i, _)| i);
+	let word = &before[start ..];
+	if word.is_empty() || word.starts_with(|c: char| c.is_ascii_digit()) {
+		return (start, Vec::new());
+	}
+
+	let prefix = before[.. start].trim_start();
+	let variables_only = match prefix.strip_prefix(':') {
+		Some(command) if command.starts_with("del ") => true,
+		Some(_) => return (start, Vec::new()),
+		None => false
+	};
+
+	let mut candidates = Vec::new();
+	if !variables_only {
+		candidates.extend(Function::MAP.iter().map(|(name, _)| format!("{}(", name)));
+		candidates.extend(CONSTANTS.iter().map(|(name, _)| name.to_string()));
+		if context.answer().is_some() {
+			candidates.push("ans".to_string());
+		}
+	}
+	candidates.extend(context.variables().map(|(name, _)| name.to_string()));
+	candidates.retain(|candidate| candidate.starts_with(word));
+	candidates.sort();
+	candidates.dedup();
+	(start, candidates)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn the_identifier_at_the_cursor_is_completed() {
+		let mut context = Context::new();
+		assert_eq!(complete("sq", 2, &context), (0, vec!["sqrt(".to_string()]));
+		assert_eq!(complete("2 * sq + 1", 6, &context), (4, vec!["sqrt(".to_string()]));
+		assert_eq!(complete("p", 1, &context), (0, vec!["pctchange(".to_string(), "pctof(".to_string(), "pi".to_string(), "prod_over(".to_string()]));
+		assert_eq!(complete("2 + ", 4, &context), (4, Vec::new()));
+		assert_eq!(complete("2e", 2, &context), (0, Vec::new()));
+		context.set_var("speed", 3.0);
+		context.set_var("sqrt_two", 1.4);
+		assert_eq!(complete("sp", 2, &context), (0, vec!["speed".to_string()]));
+		assert_eq!(complete("sq", 2, &context).1, ["sqrt(", "sqrt_two"]);
+		assert!(complete("an", 2, &context).1.is_empty());
+		context.execute("1").unwrap();
+		assert_eq!(complete("an", 2, &context).1, ["ans"]);
+	}
+
+	#[test]
+	fn commands_complete_only_what_they_take() {
+		let mut context = Context::new();
+		context.set_var("sigma", 1.0);
+		assert_eq!(complete(":del s", 6, &context), (5, vec!["sigma".to_string()]));
+		assert!(complete(":vars s", 7, &context).1.is_empty());
+	}
+}
@@ -0,0 +1,261 @@
+use std::env;
+use std::path::PathBuf;
+
+use shunting_yard::{AngleMode, Notation, Precision};
+
+/// Startup defaults read from `config.toml`. Every key is optional and command line flags take
+/// precedence over all of them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigFile {
+	pub precision: Option<Precision>,
+	pub format: Option<Notation>,
+	pub angle: Option<AngleMode>,
+	pub color: Option<bool>,
+	pub prompt: Option<String>,
+	pub history: Option<PathBuf>,
+	/// Statements evaluated into the context before anything else, e.g. `g = 9.81`.
+	pub startup: Vec<String>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Value {
+	String(String),
+	Integer(i64),
+	Boolean(bool),
+	Array(Vec<Value>)
+}
+
+/// `$XDG_CONFIG_HOME/syc/config.toml`, or `~/.config/syc/config.toml` without XDG.
+pub fn default_path() -> Option<PathBuf> {
+	if let Some(config) = env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+		return Some(PathBuf::from(config).join("syc").join("config.toml"));
+	}
+	env::var_os("HOME").filter(|dir| !dir.is_empty()).map(|home| PathBuf::from(home).join(".config").join("syc").join("config.toml"))
+}
+
+/// Parses the top-level `key = value` pairs of a TOML document, supporting strings, integers,
+/// booleans and arrays. Problems are returned as warnings naming the key, and the affected keys
+/// keep their defaults.
+pub fn parse(text: &str) -> (ConfigFile, Vec<String>) {
+	let mut config = ConfigFile::default();
+	let mut warnings = Vec::new();
+	let mut lines = text.strip_prefix('\u{feff}').unwrap_or(text).lines().enumerate();
+	while let Some((index, line)) = lines.next() {
+		let line = line.trim();
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+		let Some((key, raw)) = line.split_once('=') else {
+			warnings.push(format!("line {}: expected 'key = value'", index + 1));
+			continue;
+		};
+		let key = key.trim();
+		let mut raw = raw.trim().to_string();
+		// Arrays may span several lines
+		if raw.starts_with('[') {
+			while !is_array_closed(&raw) {
+				match lines.next() {
+					Some((_, line)) => {
+						raw.push('\n');
+						raw.push_str(line);
+					}
+					None => break
+				}
+			}
+		}
+		match parse_value(&raw) {
+			Ok(value) => {
+				if let Err(message) = apply(&mut config, key, value) {
+					warnings.push(format!("key '{}': {}", key, message));
+				}
+			}
+			Err(message) => warnings.push(format!("key '{}': {}", key, message))
+		}
+	}
+	(config, warnings)
+}
+
+fn apply(config: &mut ConfigFile, key: &str, value: Value) -> Result<(), String> {
+	match (key, value) {
+		("precision", Value::Integer(digits)) => config.precision = Some(digits.to_string().parse()?),
+		("precision", Value::String(precision)) => config.precision = Some(precision.parse()?),
+		("format", Value::String(format)) => config.format = Some(format.parse()?),
+		("angle", Value::String(angle)) => config.angle = Some(match angle.as_str() {
+			"deg" => AngleMode::Degrees,
+			"rad" => AngleMode::Radians,
+			_ => return Err(format!("invalid angle mode '{}', expected \"deg\" or \"rad\"", angle))
+		}),
+		("color", Value::Boolean(color)) => config.color = Some(color),
+		("prompt", Value::String(prompt)) => config.prompt = Some(prompt),
+		("history", Value::String(path)) => config.history = Some(PathBuf::from(path)),
+		("startup", Value::Array(values)) => {
+			let mut startup = Vec::with_capacity(values.len());
+			for value in values {
+				match value {
+					Value::String(statement) => startup.push(statement),
+					_ => return Err("expected an array of strings".to_string())
+				}
+			}
+			config.startup = startup;
+		}
+		("precision" | "format" | "angle" | "color" | "prompt" | "history" | "startup", _) => {
+			return Err("unexpected type".to_string());
+		}
+		_ => return Err("unknown key".to_string())
+	}
+	Ok(())
+}
+
+fn is_array_closed(raw: &str) -> bool {
+	let mut depth = 0;
+	let mut in_string = false;
+	let mut escaped = false;
+	for c in strip_comment(raw).chars() {
+		match c {
+			_ if escaped => escaped = false,
+			'\\' if in_string => escaped = true,
+			'"' => in_string = !in_string,
+			'[' if !in_string => depth += 1,
+			']' if !in_string => depth -= 1,
+			_ => {}
+		}
+	}
+	depth <= 0
+}
+
+/// Removes a trailing `# comment` that is not inside a string, on every line.
+fn strip_comment(raw: &str) -> String {
+	let mut lines = Vec::new();
+	for line in raw.lines() {
+		let mut in_string = false;
+		let mut escaped = false;
+		let mut end = line.len();
+		for (i, c) in line.char_indices() {
+			match c {
+				_ if escaped => escaped = false,
+				'\\' if in_string => escaped = true,
+				'"' => in_string = !in_string,
+				'#' if !in_string => {
+					end = i;
+					break;
+				}
+				_ => {}
+			}
+		}
+		lines.push(&line[.. end]);
+	}
+	lines.join("\n")
+}
+
+fn parse_value(raw: &str) -> Result<Value, String> {
+	let raw = strip_comment(raw);
+	let mut chars = raw.trim().chars().peekable();
+	let value = parse_next(&mut chars)?;
+	if chars.any(|c| !c.is_whitespace()) {
+		return Err("unexpected characters after the value".to_string());
+	}
+	Ok(value)
+}
+
+fn parse_next<I: Iterator<Item = char>>(chars: &mut std::iter::Peekable<I>) -> Result<Value, String> {
+	while chars.next_if(|c| c.is_whitespace()).is_some() {}
+	match chars.peek() {
+		Some('"') => {
+			chars.next();
+			let mut text = String::new();
+			loop {
+				match chars.next() {
+					Some('"') => return Ok(Value::String(text)),
+					Some('\\') => match chars.next() {
+						Some('n') => text.push('\n'),
+						Some('t') => text.push('\t'),
+						Some('"') => text.push('"'),
+						Some('\\') => text.push('\\'),
+						Some(c) => return Err(format!("unsupported escape '\\{}'", c)),
+						None => return Err("unterminated string".to_string())
+					},
+					Some(c) => text.push(c),
+					None => return Err("unterminated string".to_string())
+				}
+			}
+		}
+		Some('[') => {
+			chars.next();
+			let mut values = Vec::new();
+			loop {
+				while chars.next_if(|c| c.is_whitespace()).is_some() {}
+				if chars.next_if_eq(&']').is_some() {
+					return Ok(Value::Array(values));
+				}
+				values.push(parse_next(chars)?);
+				while chars.next_if(|c| c.is_whitespace()).is_some() {}
+				match chars.next() {
+					Some(',') => {}
+					Some(']') => return Ok(Value::Array(values)),
+					_ => return Err("expected ',' or ']' in array".to_string())
+				}
+			}
+		}
+		Some(_) => {
+			let mut word = String::new();
+			while let Some(c) = chars.next_if(|c| !c.is_whitespace() && *c != ',' && *c != ']') {
+				word.push(c);
+			}
+			match word.as_str() {
+				"true" => Ok(Value::Boolean(true)),
+				"false" => Ok(Value::Boolean(false)),
+				_ => word.replace('_', "").parse().map(Value::Integer).map_err(|_| format!("invalid value '{}'", word))
+			}
+		}
+		None => Err("missing value".to_string())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn fixture(name: &str) -> (ConfigFile, Vec<String>) {
+		let path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/config").join(name);
+		parse(&std::fs::read_to_string(path).unwrap())
+	}
+
+	#[test]
+	fn every_key_is_read() {
+		let (config, warnings) = fixture("full.toml");
+		assert_eq!(warnings, Vec::<String>::new());
+		assert_eq!(config, ConfigFile {
+			precision: Some(Precision::Significant(6)),
+			format: Some(Notation::Scientific),
+			angle: Some(AngleMode::Degrees),
+			color: Some(false),
+			prompt: Some("calc> ".to_string()),
+			history: Some(PathBuf::from("/tmp/syc-history")),
+			startup: vec!["g = 9.81".to_string(), "c = 299_792_458".to_string()]
+		});
+	}
+
+	#[test]
+	fn invalid_keys_are_reported_and_keep_their_defaults() {
+		let (config, warnings) = fixture("partial.toml");
+		assert_eq!(config, ConfigFile {
+			prompt: Some("> ".to_string()),
+			format: Some(Notation::Fixed(2)),
+			..ConfigFile::default()
+		});
+		assert_eq!(warnings.len(), 6, "{:?}", warnings);
+		for (warning, start) in warnings.iter().zip(["key 'precision'", "key 'angle'", "key 'color'", "key 'startup'", "key 'unknown'", "line 7"]) {
+			assert!(warning.starts_with(start), "{}", warning);
+		}
+	}
+
+	#[test]
+	fn strings_keep_escapes_and_hashes() {
+		let (config, warnings) = fixture("strings.toml");
+		assert_eq!(warnings, Vec::<String>::new());
+		assert_eq!(config.precision, Some(Precision::Full));
+		assert_eq!(config.prompt.as_deref(), Some("say \"hi\"\t# "));
+		assert_eq!(config.history, Some(PathBuf::from("a#b")));
+		assert_eq!(parse(""), (ConfigFile::default(), Vec::new()));
+	}
+}
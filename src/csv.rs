@@ -0,0 +1,110 @@
+use shunting_yard::{Context, EvalError};
+
+/// Splits CSV text into records. Fields may be quoted, in which case they can contain commas, line
+/// breaks and doubled quotes (`""`).
+pub fn parse_records(text: &str) -> Result<Vec<Vec<String>>, String> {
+	let mut records = Vec::new();
+	let mut record = Vec::new();
+	let mut field = String::new();
+	let mut quoted = false;
+	let mut line = 1;
+	let mut chars = text.chars().peekable();
+	while let Some(c) = chars.next() {
+		if quoted {
+			match c {
+				'"' if chars.peek() == Some(&'"') => {
+					chars.next();
+					field.push('"');
+				}
+				'"' => quoted = false,
+				_ => {
+					if c == '\n' {
+						line += 1;
+					}
+					field.push(c);
+				}
+			}
+			continue;
+		}
+		match c {
+			'"' if field.is_empty() => quoted = true,
+			',' => record.push(std::mem::take(&mut field)),
+			'\r' if chars.peek() == Some(&'\n') => {}
+			'\n' => {
+				line += 1;
+				record.push(std::mem::take(&mut field));
+				records.push(std::mem::take(&mut record));
+			}
+			_ => field.push(c)
+		}
+	}
+	if quoted {
+		return Err(format!("unterminated quoted field on line {}", line));
+	}
+	if !field.is_empty() || !record.is_empty() {
+		record.push(field);
+		records.push(record);
+	}
+	Ok(records)
+}
+
+pub fn format_record(record: &[String]) -> String {
+	let fields: Vec<String> = record.iter().map(|field| {
+		if field.contains([',', '"', '\n', '\r']) {
+			format!("\"{}\"", field.replace('"', "\"\""))
+		} else {
+			field.clone()
+		}
+	}).collect();
+	fields.join(",")
+}
+
+/// Evaluates the expression in column `expr_col` (1-based) of `record`, with every numeric cell
+/// available as `c1`, `c2`, ... The error message is suitable for the result column.
+pub fn evaluate_record(record: &[String], expr_col: usize, context: &Context) -> Result<String, String> {
+	let Some(expression) = record.get(expr_col - 1) else {
+		return Err(format!("no column {}", expr_col));
+	};
+	let mut context = context.clone();
+	for (i, cell) in record.iter().enumerate() {
+		if let Ok(value) = cell.trim().parse::<f64>() {
+			context.set_var(&format!("c{}", i + 1), value);
+		}
+	}
+	match context.eval(expression) {
+		Ok(value) => Ok(context.format(value)),
+		Err(EvalError::UnknownIdentifier(name, _)) if is_cell_reference(&name, record.len()) => {
+			Err(format!("cell {} is not a number", name))
+		}
+		Err(err) => Err(err.to_string())
+	}
+}
+
+fn is_cell_reference(name: &str, columns: usize) -> bool {
+	name.strip_prefix('c').and_then(|index| index.parse::<usize>().ok()).is_some_and(|index| (1 ..= columns).contains(&index))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn quoted_fields_keep_commas_quotes_and_line_breaks() {
+		let records = parse_records("a,\"b, c\",\"say \"\"hi\"\"\"\r\n\"two\nlines\",2\n").unwrap();
+		assert_eq!(records, [vec!["a", "b, c", "say \"hi\""], vec!["two\nlines", "2"]]);
+		assert_eq!(format_record(&records[0]), "a,\"b, c\",\"say \"\"hi\"\"\"");
+		assert!(parse_records("1,\"open\n").unwrap_err().contains("line 2"));
+		assert_eq!(parse_records("1,2").unwrap(), [vec!["1", "2"]]);
+	}
+
+	#[test]
+	fn expressions_read_the_other_cells() {
+		let context = Context::new();
+		let record = |fields: &[&str]| fields.iter().map(|field| field.to_string()).collect::<Vec<_>>();
+		assert_eq!(evaluate_record(&record(&["2", " 3 ", "c1 * c2"]), 3, &context).unwrap(), "6");
+		assert_eq!(evaluate_record(&record(&["max(c2, c3)", "4", "9"]), 1, &context).unwrap(), "9");
+		assert_eq!(evaluate_record(&record(&["4", "x", "c1 + c2"]), 3, &context).unwrap_err(), "cell c2 is not a number");
+		assert_eq!(evaluate_record(&record(&["1", "c4"]), 2, &context).unwrap_err(), "unknown identifier 'c4'");
+		assert_eq!(evaluate_record(&record(&["1"]), 2, &context).unwrap_err(), "no column 2");
+	}
+}
@@ -0,0 +1,225 @@
+use alloc::collections::BTreeSet;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::binding::{self, Let};
+use crate::calculus::{arguments, form_name, layout};
+use crate::{constant, to_postfix, Context, EvalError, Function, Lexeme, LexemeKind, UserFunction};
+
+/// The variables `expression` reads, defined or not, for deciding what to evaluate first. Constants,
+/// functions, `ans`, unit names and the variable a form such as `sum_over(k, 1, n, k * x)` or
+/// `let k = n in k * x` binds itself are left out, so that both depend on `n` and `x`. Only lexing
+/// errors are reported; the expression is not evaluated.
+pub fn dependencies_of(expression: &str) -> Result<BTreeSet<String>, EvalError> {
+	dependencies_with(expression, &Context::new())
+}
+
+/// Like [`dependencies_of`], lexing in the locale of `context`. Its variables shadow constants and
+/// functions as they do when evaluating, so after `pi = 3` an expression using `pi` depends on it.
+/// A call of a function the program defined also depends on what its body reads besides its
+/// parameters, and so on through the functions that body calls.
+pub fn dependencies_with(expression: &str, context: &Context) -> Result<BTreeSet<String>, EvalError> {
+	let mut names = BTreeSet::new();
+	collect(expression, context, &[], &mut names, &mut BTreeSet::new())?;
+	Ok(names)
+}
+
+/// What the body of `function` reads besides its parameters, through the functions it calls too,
+/// as a call of it does in [`dependencies_with`].
+pub(crate) fn of_function(function: &UserFunction, context: &Context) -> Result<BTreeSet<String>, EvalError> {
+	let params: Vec<&str> = function.params.iter().map(String::as_str).collect();
+	let mut names = BTreeSet::new();
+	collect(&function.body, context, &params, &mut names, &mut BTreeSet::new())?;
+	Ok(names)
+}
+
+/// An expression checked to parse once and kept to be evaluated as what it reads changes, such as
+/// the formula of a spreadsheet cell.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+	text: String,
+	dependencies: BTreeSet<String>
+}
+impl Expression {
+	/// Parses `text` in `context`, where the names it reads need not be defined yet, and finds them
+	/// as [`dependencies_with`] does.
+	pub fn parse(text: &str, context: &Context) -> Result<Self, EvalError> {
+		let mut scope = context.clone();
+		scope.probing = true;
+		to_postfix(text, &scope)?;
+		Ok(Self { text: text.to_string(), dependencies: dependencies_with(text, context)? })
+	}
+	pub fn as_str(&self) -> &str {
+		&self.text
+	}
+	/// The variables the expression reads, see [`dependencies_with`].
+	pub fn dependencies(&self) -> BTreeSet<String> {
+		self.dependencies.clone()
+	}
+	pub fn eval(&self, context: &Context) -> Result<f64, EvalError> {
+		context.eval(&self.text)
+	}
+}
+
+/// Adds the names `expression` reads to `names`, except for the ones in `bound`. The functions in
+/// `expanded` have had their bodies read already, which ends the walk of recursive ones.
+fn collect<'a>(
+	expression: &str, context: &'a Context, bound: &[&str], names: &mut BTreeSet<String>, expanded: &mut BTreeSet<&'a str>
+) -> Result<(), EvalError> {
+	// For every open parenthesis, whether it is the call of `convert` and how many separators it has
+	let mut parens: Vec<(bool, usize)> = Vec::new();
+	let mut convert = false;
+	for lexeme in context.lexer(expression) {
+		let Lexeme { kind, span } = lexeme?;
+		let text = &expression[span.start .. span.end];
+		let call = core::mem::replace(&mut convert, false);
+		match kind {
+			LexemeKind::OpenParen => parens.push((call, 0)),
+			LexemeKind::CloseParen => {
+				parens.pop();
+			}
+			LexemeKind::Separator => {
+				if let Some((_, count)) = parens.last_mut() {
+					*count += 1;
+				}
+			}
+			LexemeKind::Identifier if matches!(parens.last(), Some((true, 1 ..))) => {}
+			LexemeKind::Identifier if bound.contains(&text) => {}
+			LexemeKind::Identifier if context.get_var(text).is_some() => {
+				names.insert(text.to_string());
+			}
+			LexemeKind::Identifier if text == "convert" => convert = true,
+			LexemeKind::Identifier if text == "ans" || text == "of" || context.is_visible(text) && (constant(text).is_some() || Function::by_name(text).is_some()) => {}
+			LexemeKind::Identifier => {
+				names.insert(text.to_string());
+			}
+			LexemeKind::Form => form(&expression[span.start .. span.end], context, bound, names, expanded).map_err(|err| err.offset(span.start))?,
+			_ => {}
+		}
+	}
+	Ok(())
+}
+
+/// The names read by the arguments of a form. Its expression sees its variable as bound.
+fn form<'a>(
+	call: &str, context: &'a Context, bound: &[&str], names: &mut BTreeSet<String>, expanded: &mut BTreeSet<&'a str>
+) -> Result<(), EvalError> {
+	let separator = context.display().locale.argument_separator();
+	let name = form_name(call);
+	if name == "let" {
+		let Some(Let { name, value, body }) = binding::split(call, separator) else {
+			unreachable!()
+		};
+		collect(&call[value.0 .. value.1], context, bound, names, expanded).map_err(|err| err.offset(value.0))?;
+		let inner: Vec<&str> = bound.iter().copied().chain([&call[name.0 .. name.1]]).collect();
+		return collect(&call[body.0 .. body.1], context, &inner, names, expanded).map_err(|err| err.offset(body.0));
+	}
+	if let Some((name, function)) = context.functions.get_key_value(name) {
+		for (start, end) in arguments(call, separator) {
+			collect(&call[start .. end], context, bound, names, expanded).map_err(|err| err.offset(start))?;
+		}
+		if expanded.insert(name) {
+			let inner: Vec<&str> = bound.iter().copied().chain(function.params.iter().map(String::as_str)).collect();
+			collect(&function.body, context, &inner, names, expanded)?;
+		}
+		return Ok(());
+	}
+	let open = call.find('(').unwrap_or(0);
+	let (_, binding) = layout(call[.. open].trim_end());
+	let args = arguments(call, separator);
+	let var = binding.and_then(|(var, _)| args.get(var)).map(|&(start, end)| call[start .. end].trim());
+	for (i, &(start, end)) in args.iter().enumerate() {
+		let text = &call[start .. end];
+		match binding {
+			Some((var_index, _)) if i == var_index => {}
+			Some((_, body)) if i == body => {
+				let inner: Vec<&str> = bound.iter().copied().chain(var).collect();
+				collect(text, context, &inner, names, expanded).map_err(|err| err.offset(start))?;
+			}
+			// The digits of `frombase`
+			_ if text.trim_start().starts_with('"') => {}
+			_ => collect(text, context, bound, names, expanded).map_err(|err| err.offset(start))?
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn names(expression: &str) -> Vec<String> {
+		dependencies_of(expression).unwrap().into_iter().collect()
+	}
+
+	#[test]
+	fn variables_are_read_and_everything_else_is_left_out() {
+		assert_eq!(names("a * x + b"), ["a", "b", "x"]);
+		assert_eq!(names("sin(t) * pi + e - ans + max(r, 1)"), ["r", "t"]);
+		assert_eq!(names("convert(speed, km, m)"), ["speed"]);
+		assert_eq!(names("20% of total"), ["total"]);
+		assert_eq!(names("frombase(\"ff\", 16) + n"), ["n"]);
+		assert!(names("1 + 2").is_empty());
+	}
+
+	#[test]
+	fn a_forms_variable_is_bound_only_inside_it() {
+		assert_eq!(names("sum_over(k, 1, n, k * x)"), ["n", "x"]);
+		// The outer k is a variable of its own
+		assert_eq!(names("sum_over(k, 1, 3, k + z) + k"), ["k", "z"]);
+		assert_eq!(names("integrate(x^2 * y, x, 0, b)"), ["b", "y"]);
+		assert_eq!(names("prod_over(i, 1, m, sum_over(j, 1, i, i * j * w))"), ["m", "w"]);
+		assert_eq!(names("diff(sum_over(k, 1, 3, k * x), x, at)"), ["at"]);
+	}
+
+	#[test]
+	fn a_context_shadows_constants_and_lexes_in_its_locale() {
+		let mut context = Context::new();
+		assert!(dependencies_with("pi * 2", &context).unwrap().is_empty());
+		context.set_var("pi", 3.0);
+		assert_eq!(dependencies_with("pi * 2", &context).unwrap().into_iter().collect::<Vec<_>>(), ["pi"]);
+		let err = dependencies_of("x + @").unwrap_err();
+		assert!(matches!(err, EvalError::InvalidCharacter('@', span) if span.start == 4));
+		let err = dependencies_of("sum_over(k, 1, 3, k @ y)").unwrap_err();
+		assert!(matches!(err, EvalError::InvalidCharacter('@', span) if span.start == 20));
+	}
+
+	#[test]
+	fn a_let_binds_its_name_in_its_body_only() {
+		assert_eq!(names("let x = y in x + z"), ["y", "z"]);
+		assert_eq!(names("let x = x + 1 in x * 2"), ["x"]);
+		assert_eq!(names("(let a = 1 in a) + a"), ["a"]);
+		assert_eq!(names("let a = b in let b = a in a + b + c"), ["b", "c"]);
+		assert_eq!(names("sum_over(k, 1, n, let j = k in j * w)"), ["n", "w"]);
+	}
+
+	#[test]
+	fn a_call_reads_what_the_body_of_its_function_reads() {
+		let mut context = Context::new();
+		context.register_fn("taxed", &["price"], "price * (1 + rate)").unwrap();
+		context.register_fn("total", &["n"], "taxed(n * unit) + fee").unwrap();
+		context.register_fn("countdown", &["n"], "countdown(n - 1) + step").unwrap();
+		let names = |expression: &str| dependencies_with(expression, &context).unwrap().into_iter().collect::<Vec<_>>();
+		assert_eq!(names("taxed(base)"), ["base", "rate"]);
+		assert_eq!(names("total(3) * taxed(1)"), ["fee", "rate", "unit"]);
+		assert_eq!(names("countdown(start)"), ["start", "step"]);
+		// The parameter is bound only in the body
+		assert_eq!(names("taxed(price)"), ["price", "rate"]);
+		assert!(names("let rate = 0 in taxed(1)").is_empty());
+		assert!(dependencies_of("taxed(base)").unwrap().contains("taxed"));
+	}
+
+	#[test]
+	fn an_expression_keeps_what_it_reads() {
+		let mut context = Context::new();
+		context.register_fn("area", &["w", "h"], "w * h * scale").unwrap();
+		let expression = Expression::parse("let w = width in area(w, height) + margin", &context).unwrap();
+		assert_eq!(expression.dependencies().into_iter().collect::<Vec<_>>(), ["height", "margin", "scale", "width"]);
+		for (name, value) in [("width", 2.0), ("height", 3.0), ("scale", 10.0), ("margin", 1.0)] {
+			context.set_var(name, value);
+		}
+		assert_eq!(expression.eval(&context).unwrap(), 61.0);
+		assert_eq!(expression.as_str(), "let w = width in area(w, height) + margin");
+		assert!(matches!(Expression::parse("1 + (2", &context), Err(EvalError::MismatchedParenthesis(_))));
+	}
+}
@@ -0,0 +1,720 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+
+/// The largest denominator shown by the fraction display unless another is chosen.
+pub const DEFAULT_MAX_DENOMINATOR: u64 = 10000;
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Precision {
+	/// Shortest round-trip representation, rounded to at most this many significant digits.
+	Significant(usize),
+	/// Exactly this many significant figures, keeping trailing zeros: `1.0` for `0.999` at 2. Not
+	/// combined with [`Notation::Fixed`], which counts decimals instead.
+	Figures(usize),
+	/// Rust's default `f64` formatting.
+	Full
+}
+impl Default for Precision {
+	fn default() -> Self {
+		Self::Significant(12)
+	}
+}
+
+impl FromStr for Precision {
+	type Err = String;
+
+	/// Accepts a number of significant digits (1 to 17) or `full`.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		if s == "full" {
+			return Ok(Self::Full);
+		}
+		match s.parse::<usize>() {
+			Ok(digits @ 1 ..= 17) => Ok(Self::Significant(digits)),
+			_ => Err(format!("invalid precision '{}', expected 1 to 17 digits or 'full'", s))
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Notation {
+	/// Positional for moderate magnitudes, scientific for very large or small ones.
+	#[default]
+	Auto,
+	/// Always this many digits after the decimal point.
+	Fixed(usize),
+	/// `1.2345e+07`
+	Scientific,
+	/// Like scientific, but the exponent is always a multiple of 3: `12.345e+06`.
+	Engineering
+}
+impl FromStr for Notation {
+	type Err = String;
+
+	/// Accepts `auto`, `sci`, `eng` or `fixed` followed by an optional number of decimals (2 by
+	/// default), separated by whitespace.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let words: Vec<&str> = s.split_whitespace().collect();
+		match words.as_slice() {
+			["auto"] => Ok(Self::Auto),
+			["sci"] | ["scientific"] => Ok(Self::Scientific),
+			["eng"] | ["engineering"] => Ok(Self::Engineering),
+			["fixed"] => Ok(Self::Fixed(2)),
+			["fixed", decimals] => match decimals.parse::<usize>() {
+				Ok(decimals @ 0 ..= 17) => Ok(Self::Fixed(decimals)),
+				_ => Err(format!("invalid number of decimals '{}', expected 0 to 17", decimals))
+			},
+			_ => Err(format!("invalid format '{}', expected auto, fixed [N], sci or eng", s))
+		}
+	}
+}
+
+/// How a value is rounded to a number of decimals, for display and by `round`.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Rounding {
+	/// To the nearest, ties away from zero: `2.5` to `3`, `-2.5` to `-3`.
+	#[default]
+	HalfUp,
+	/// To the nearest, ties to the even neighbour: `2.5` to `2`, `3.5` to `4`.
+	HalfEven,
+	TowardZero,
+	AwayFromZero
+}
+impl Rounding {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::HalfUp => "half-up",
+			Self::HalfEven => "half-even",
+			Self::TowardZero => "toward-zero",
+			Self::AwayFromZero => "away-from-zero"
+		}
+	}
+	pub fn round(self, value: f64) -> f64 {
+		match self {
+			Self::HalfUp => value.round(),
+			Self::HalfEven => value.round_ties_even(),
+			Self::TowardZero => value.trunc(),
+			Self::AwayFromZero => value.abs().ceil().copysign(value)
+		}
+	}
+	/// Rounds to `decimals` places, or to tens, hundreds, … when negative. Ties are judged on the
+	/// decimal digits as typed rather than on the binary value: `1.005` rounds half up to `1.01`.
+	pub fn round_to(self, value: f64, decimals: i32) -> f64 {
+		let scale = 10f64.powi(decimals);
+		let mut scaled = value * scale;
+		// Beyond 2^52 there are no fractions left to round
+		if !scaled.is_finite() || scaled.abs() >= 4503599627370496.0 {
+			return value;
+		}
+		// Scaling leaves a few ulps of error, so snap to a nearby integer or tie first
+		let tolerance = scaled.abs() * 4.0 * f64::EPSILON;
+		let half = scaled.trunc() + 0.5f64.copysign(scaled);
+		if (scaled - scaled.round()).abs() <= tolerance {
+			scaled = scaled.round();
+		} else if (scaled - half).abs() <= tolerance {
+			scaled = half;
+		}
+		self.round(scaled) / scale
+	}
+	/// Rounds to `digits` significant digits.
+	fn round_significant(self, value: f64, digits: usize) -> f64 {
+		if value == 0.0 || !value.is_finite() {
+			return value;
+		}
+		let exponent = value.abs().log10().floor() as i32;
+		self.round_to(value, digits as i32 - 1 - exponent)
+	}
+}
+impl FromStr for Rounding {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"half-up" => Ok(Self::HalfUp),
+			"half-even" | "bankers" => Ok(Self::HalfEven),
+			"toward-zero" | "truncate" => Ok(Self::TowardZero),
+			"away-from-zero" => Ok(Self::AwayFromZero),
+			_ => Err(format!("invalid rounding '{}', expected half-up, half-even, toward-zero or away-from-zero", s))
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum Radix {
+	#[default]
+	Decimal,
+	Hexadecimal,
+	Binary,
+	Octal,
+	/// Hexadecimal, showing negative values as their 64-bit two's complement.
+	Hex64
+}
+impl FromStr for Radix {
+	type Err = String;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		match s {
+			"dec" | "decimal" => Ok(Self::Decimal),
+			"hex" => Ok(Self::Hexadecimal),
+			"bin" => Ok(Self::Binary),
+			"oct" => Ok(Self::Octal),
+			"hex64" => Ok(Self::Hex64),
+			_ => Err(format!("invalid output '{}', expected dec, hex, bin, oct or hex64", s))
+		}
+	}
+}
+
+/// Separators for reading and writing numbers. With a decimal comma, function arguments are
+/// separated by `;` instead.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Locale {
+	decimal: char,
+	grouping: Option<char>
+}
+impl Locale {
+	/// The decimal separator must be `.` or `,`, and the optional thousands separator one of space,
+	/// `_`, `'` or whichever of `.` and `,` is not the decimal separator, except that a comma is never
+	/// allowed for grouping since it separates arguments.
+	pub fn new(decimal: char, grouping: Option<char>) -> Result<Self, String> {
+		if decimal != '.' && decimal != ',' {
+			return Err(format!("invalid decimal separator '{}', expected '.' or ','", decimal));
+		}
+		match grouping {
+			Some(group) if group == decimal => Err("the grouping and decimal separators must differ".to_string()),
+			Some(group) if !matches!(group, ' ' | '_' | '\'' | '.') => {
+				Err(format!("invalid grouping separator '{}'", group))
+			}
+			_ => Ok(Self { decimal, grouping })
+		}
+	}
+	pub fn decimal(&self) -> char {
+		self.decimal
+	}
+	pub fn grouping(&self) -> Option<char> {
+		self.grouping
+	}
+	pub fn argument_separator(&self) -> char {
+		if self.decimal == ',' { ';' } else { ',' }
+	}
+}
+impl Default for Locale {
+	fn default() -> Self {
+		Self { decimal: '.', grouping: None }
+	}
+}
+
+/// Parses a grouping setting: `off`, `space`, `point`, `underscore` or `apostrophe`.
+pub fn parse_grouping(name: &str) -> Result<Option<char>, String> {
+	match name {
+		"off" => Ok(None),
+		"space" => Ok(Some(' ')),
+		"point" => Ok(Some('.')),
+		"underscore" => Ok(Some('_')),
+		"apostrophe" => Ok(Some('\'')),
+		_ => Err(format!("invalid grouping '{}', expected off, space, point, underscore or apostrophe", name))
+	}
+}
+
+/// How results that are durations are shown.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum DurationStyle {
+	/// `1h 30m 45s`, to the millisecond.
+	#[default]
+	Breakdown,
+	/// `5445 s`
+	Seconds
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct DisplaySettings {
+	pub precision: Precision,
+	pub notation: Notation,
+	pub radix: Radix,
+	pub locale: Locale,
+	/// Also used by the `round` function.
+	pub rounding: Rounding,
+	pub durations: DurationStyle,
+	/// Shows results as fractions with at most this denominator, `3/4` for `0.75`.
+	pub fraction: Option<u64>,
+	/// Shows the results of `pctchange` and `pctof` with a trailing `%`, `25%`.
+	pub percent_helpers: bool
+}
+
+/// Formats `value` for display. This never changes the stored value, only how it is shown.
+pub fn format_value(value: f64, settings: &DisplaySettings) -> String {
+	if !value.is_finite() {
+		return value.to_string();
+	}
+	// Zero is shown without a sign however it came about
+	let value = if value == 0.0 { 0.0 } else { value };
+	if let Some(max) = settings.fraction {
+		return format_fraction(value, max, settings);
+	}
+	if let Some(integer) = as_integer(value).filter(|_| settings.radix != Radix::Decimal) {
+		let sign = if integer < 0 { "-" } else { "" };
+		let magnitude = integer.unsigned_abs();
+		return match settings.radix {
+			Radix::Decimal => integer.to_string(),
+			Radix::Hexadecimal => format!("{}{:#x}", sign, magnitude),
+			Radix::Binary => format!("{}{:#b}", sign, magnitude),
+			Radix::Octal => format!("{}{:#o}", sign, magnitude),
+			Radix::Hex64 => format!("{:#x}", integer)
+		};
+	}
+	let text = match (settings.notation, settings.precision) {
+		(Notation::Auto, Precision::Full) => value.to_string(),
+		(Notation::Auto, Precision::Significant(digits)) => format_significant(value, digits.max(1), settings.rounding),
+		(Notation::Auto, Precision::Figures(figures)) => format_figures(value, figures.max(1), settings.rounding),
+		// Likewise a value rounded to zero, `-0.001` is `0.00`
+		(Notation::Fixed(decimals), _) => match settings.rounding.round_to(value, decimals as i32) {
+			0.0 => format!("{:.*}", decimals, 0.0),
+			rounded => format!("{:.*}", decimals, rounded)
+		},
+		(Notation::Scientific, precision) => format_exponential(value, precision, 1, settings.rounding),
+		(Notation::Engineering, precision) => format_exponential(value, precision, 3, settings.rounding)
+	};
+	localize(&text, &settings.locale)
+}
+
+/// Shows `value` as a reduced fraction when one with a denominator of at most `max` is exactly equal
+/// to it, and otherwise as a decimal marked `≈`. Integers are shown without a denominator.
+fn format_fraction(value: f64, max: u64, settings: &DisplaySettings) -> String {
+	let decimal = DisplaySettings { fraction: None, ..*settings };
+	if value.fract() == 0.0 {
+		return format_value(value, &decimal);
+	}
+	match to_fraction(value, max) {
+		Some((numerator, denominator)) => {
+			let sign = if value < 0.0 { "-" } else { "" };
+			let numerator = localize(&numerator.to_string(), &settings.locale);
+			format!("{}{}/{}", sign, numerator, localize(&denominator.to_string(), &settings.locale))
+		}
+		None => format!("≈ {}", format_value(value, &decimal))
+	}
+}
+
+/// Finds the fraction equal to `value`, ignoring its sign, whose denominator is at most `max`. The
+/// convergents of the continued fraction are the best approximations, so the first one that is exact
+/// is the reduced fraction.
+pub fn to_fraction(value: f64, max: u64) -> Option<(u64, u64)> {
+	let target = value.abs();
+	// Beyond 2^53 the convergents are no longer exact in an f64
+	let limit = 9007199254740992.0;
+	let (mut h, mut h_prev) = (1.0, 0.0);
+	let (mut k, mut k_prev) = (0.0, 1.0);
+	let mut x = target;
+	for _ in 0 .. 64 {
+		let a = x.floor();
+		(h, h_prev) = (a * h + h_prev, h);
+		(k, k_prev) = (a * k + k_prev, k);
+		if k > max as f64 || h > limit {
+			return None;
+		}
+		if h / k == target {
+			return Some((h as u64, k as u64));
+		}
+		let rest = x - a;
+		if rest == 0.0 {
+			return None;
+		}
+		x = 1.0 / rest;
+	}
+	None
+}
+
+/// Shows a value with its uncertainty as `49.2 ± 1.5`: the uncertainty to two significant figures and
+/// the value to the same decimal place. An exact value is shown as usual.
+pub fn format_uncertain(value: f64, sigma: f64, settings: &DisplaySettings) -> String {
+	if sigma == 0.0 {
+		return format_value(value, settings);
+	}
+	if !value.is_finite() || !sigma.is_finite() {
+		return format!("{} ± {}", format_value(value, settings), format_value(sigma, settings));
+	}
+	let sigma = settings.rounding.round_significant(sigma, 2);
+	let decimals = 1 - sigma.log10().floor() as i32;
+	let shown = |x: f64| {
+		let text = format!("{:.*}", decimals.max(0) as usize, settings.rounding.round_to(x, decimals));
+		localize(&text, &settings.locale)
+	};
+	format!("{} ± {}", shown(value), shown(sigma))
+}
+
+/// Shows a number of seconds as a duration. A breakdown lists days, hours, minutes and seconds,
+/// leaving out zero parts; minutes alone are written `45min` so they do not read as metres.
+pub fn format_duration(seconds: f64, settings: &DisplaySettings) -> String {
+	if settings.durations == DurationStyle::Seconds || !seconds.is_finite() {
+		return format!("{} s", format_value(seconds, settings));
+	}
+	let sign = if seconds < 0.0 { "-" } else { "" };
+	let millis = (seconds.abs() * 1000.0).round();
+	if millis == 0.0 {
+		return "0s".to_string();
+	}
+	if millis < 1000.0 {
+		return format!("{}{}ms", sign, millis);
+	}
+	let mut rest = millis;
+	let mut parts = Vec::new();
+	for (suffix, size) in [("d", 86_400_000.0), ("h", 3_600_000.0), ("m", 60_000.0)] {
+		let count = (rest / size).floor();
+		if count > 0.0 {
+			parts.push(format!("{}{}", count, suffix));
+			rest -= count * size;
+		}
+	}
+	if rest > 0.0 {
+		parts.push(format!("{}s", localize(&(rest / 1000.0).to_string(), &settings.locale)));
+	}
+	if parts.len() == 1 && parts[0].ends_with('m') {
+		parts[0].push_str("in");
+	}
+	format!("{}{}", sign, parts.join(" "))
+}
+
+/// Replaces the decimal point and groups the integer digits of a formatted number.
+fn localize(text: &str, locale: &Locale) -> String {
+	if *locale == Locale::default() {
+		return text.to_string();
+	}
+	let (sign, unsigned) = text.split_at(if text.starts_with('-') { 1 } else { 0 });
+	let int_len = unsigned.find(|c: char| !c.is_ascii_digit()).unwrap_or(unsigned.len());
+	let (int_part, rest) = unsigned.split_at(int_len);
+	let mut localized = sign.to_string();
+	for (i, digit) in int_part.chars().enumerate() {
+		if let Some(group) = locale.grouping.filter(|_| i > 0 && (int_len - i) % 3 == 0) {
+			localized.push(group);
+		}
+		localized.push(digit);
+	}
+	localized.push_str(&rest.replacen('.', &locale.decimal.to_string(), 1));
+	localized
+}
+
+/// Explains why `value` is shown in decimal despite a different radix being selected.
+pub fn radix_note(value: f64, settings: &DisplaySettings) -> Option<String> {
+	if settings.radix == Radix::Decimal || as_integer(value).is_some() {
+		return None;
+	}
+	Some("note: only integers in the 64-bit range can be shown in another radix, showing decimal".to_string())
+}
+
+fn as_integer(value: f64) -> Option<i64> {
+	// `i64::MAX as f64` rounds up to 2^63, which is already out of range.
+	if value.fract() != 0.0 || value < i64::MIN as f64 || value >= i64::MAX as f64 {
+		return None;
+	}
+	Some(value as i64)
+}
+
+/// Formats `value` as a mantissa and an exponent that is a multiple of `step`.
+fn format_exponential(value: f64, precision: Precision, step: i32, rounding: Rounding) -> String {
+	let (negative, mut mantissa, mut exponent) = match precision {
+		Precision::Figures(figures) => {
+			let value = rounding.round_significant(value, figures.max(1));
+			decompose(&format!("{:.*e}", figures.max(1) - 1, value))
+		}
+		_ if value == 0.0 => return "0e+00".to_string(),
+		_ => decompose(&format!("{:e}", value))
+	};
+	if let Precision::Significant(digits) = precision {
+		if mantissa.len() > digits.max(1) {
+			let value = rounding.round_significant(value, digits.max(1));
+			(_, mantissa, exponent) = decompose(&format!("{:.*e}", digits.max(1) - 1, value));
+		}
+	}
+	if !matches!(precision, Precision::Figures(_)) {
+		mantissa.truncate(mantissa.trim_end_matches('0').len());
+	}
+	let scaled = exponent.div_euclid(step) * step;
+	let int_len = (exponent - scaled) as usize + 1;
+	if mantissa.len() < int_len {
+		mantissa.push_str(&"0".repeat(int_len - mantissa.len()));
+	}
+	let (head, tail) = mantissa.split_at(int_len);
+	let sign = if negative { "-" } else { "" };
+	let exp_sign = if scaled < 0 { '-' } else { '+' };
+	if tail.is_empty() {
+		format!("{}{}e{}{:02}", sign, head, exp_sign, scaled.abs())
+	} else {
+		format!("{}{}.{}e{}{:02}", sign, head, tail, exp_sign, scaled.abs())
+	}
+}
+
+fn format_significant(value: f64, digits: usize, rounding: Rounding) -> String {
+	if value == 0.0 {
+		return "0".to_string();
+	}
+	let (negative, mut mantissa, mut exponent) = decompose(&format!("{:e}", value));
+	if mantissa.len() > digits {
+		let value = rounding.round_significant(value, digits);
+		(_, mantissa, exponent) = decompose(&format!("{:.*e}", digits - 1, value));
+	}
+	let mantissa = mantissa.trim_end_matches('0');
+	let sign = if negative { "-" } else { "" };
+
+	if exponent < -6 || exponent >= digits as i32 {
+		let (head, tail) = mantissa.split_at(1);
+		if tail.is_empty() {
+			format!("{}{}e{}", sign, head, exponent)
+		} else {
+			format!("{}{}.{}e{}", sign, head, tail, exponent)
+		}
+	} else if exponent < 0 {
+		format!("{}0.{}{}", sign, "0".repeat((-exponent - 1) as usize), mantissa)
+	} else {
+		let int_len = exponent as usize + 1;
+		if mantissa.len() <= int_len {
+			format!("{}{}{}", sign, mantissa, "0".repeat(int_len - mantissa.len()))
+		} else {
+			format!("{}{}.{}", sign, &mantissa[.. int_len], &mantissa[int_len ..])
+		}
+	}
+}
+
+/// Like [`format_significant`] without dropping trailing zeros, and positional up to 15 integer
+/// digits so that `98765` at 3 figures is `98800`.
+fn format_figures(value: f64, figures: usize, rounding: Rounding) -> String {
+	// `{:.*e}` carries into the exponent, `0.999` becomes `1.0e0`
+	let value = if value == 0.0 { 0.0 } else { rounding.round_significant(value, figures) };
+	let (negative, mantissa, exponent) = decompose(&format!("{:.*e}", figures - 1, value));
+	let sign = if negative { "-" } else { "" };
+	if value != 0.0 && !(-6 .. 15).contains(&exponent) {
+		let (head, tail) = mantissa.split_at(1);
+		if tail.is_empty() {
+			format!("{}{}e{}", sign, head, exponent)
+		} else {
+			format!("{}{}.{}e{}", sign, head, tail, exponent)
+		}
+	} else if exponent < 0 {
+		format!("{}0.{}{}", sign, "0".repeat((-exponent - 1) as usize), mantissa)
+	} else {
+		let int_len = exponent as usize + 1;
+		if mantissa.len() <= int_len {
+			format!("{}{}{}", sign, mantissa, "0".repeat(int_len - mantissa.len()))
+		} else {
+			format!("{}{}.{}", sign, &mantissa[.. int_len], &mantissa[int_len ..])
+		}
+	}
+}
+
+/// Splits Rust's `{:e}` output into sign, the significant digits without the decimal point, and the
+/// decimal exponent.
+fn decompose(formatted: &str) -> (bool, String, i32) {
+	let (mantissa, exponent) = formatted.split_once('e').unwrap_or((formatted, "0"));
+	let negative = mantissa.starts_with('-');
+	let digits = mantissa.chars().filter(char::is_ascii_digit).collect();
+	(negative, digits, exponent.parse().unwrap_or(0))
+}
+
+/// `value` in whole cents with two decimals and the locale's separators, as in money mode.
+pub fn format_money(value: f64, locale: &Locale) -> String {
+	let cents = (value * 100.0).round() as i128;
+	let sign = if cents < 0 { "-" } else { "" };
+	localize(&format!("{}{}.{:02}", sign, cents.abs() / 100, cents.abs() % 100), locale)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Context;
+
+	const VALUES: [f64; 14] = [
+		1e20, -1e20, 1.234567890123e12, 1e-7, -2.5e-9, 1234.5, 0.1 + 0.2, -0.0, 0.0, -0.001, 42.0, 1e300, 5e-324, f64::MAX
+	];
+
+	fn notations() -> Vec<DisplaySettings> {
+		let comma = Locale::new(',', Some('.')).unwrap();
+		let mut settings = Vec::new();
+		for notation in [Notation::Auto, Notation::Scientific, Notation::Engineering, Notation::Fixed(3)] {
+			for locale in [Locale::default(), comma] {
+				settings.push(DisplaySettings { notation, locale, ..DisplaySettings::default() });
+			}
+		}
+		settings.push(DisplaySettings { precision: Precision::Full, ..DisplaySettings::default() });
+		settings
+	}
+
+	#[test]
+	fn shown_values_read_back_as_themselves() {
+		for settings in notations() {
+			let mut context = Context::new();
+			*context.display_mut() = settings;
+			for value in VALUES {
+				let shown = format_value(value, &settings);
+				let read = context.eval(&shown).unwrap_or_else(|err| panic!("{} from {:?}: {}", shown, settings.notation, err));
+				assert_eq!(format_value(read, &settings), shown, "{:?}", settings.notation);
+				if settings.precision == Precision::Full {
+					assert_eq!(read, value);
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn negative_zero_has_no_sign() {
+		for settings in notations() {
+			assert!(!format_value(-0.0, &settings).starts_with('-'), "{:?}", settings.notation);
+		}
+		let fixed = DisplaySettings { notation: Notation::Fixed(2), ..DisplaySettings::default() };
+		assert_eq!(format_value(-0.001, &fixed), "0.00");
+		assert_eq!(format_value(-0.005, &fixed), "-0.01");
+	}
+
+	#[test]
+	fn exponents_are_read() {
+		let context = Context::new();
+		assert_eq!(context.eval("1e20").unwrap(), 1e20);
+		assert_eq!(context.eval("1E+20").unwrap(), 1e20);
+		assert_eq!(context.eval("1.5e-7").unwrap(), 1.5e-7);
+		assert_eq!(context.eval("2 * 1e3").unwrap(), 2000.0);
+		assert_eq!(context.eval("-1e2").unwrap(), -100.0);
+		assert!(context.eval("2e").is_err());
+		assert!(context.eval("1e-").is_err());
+		let mut strict = Context::new();
+		strict.set_strict(true);
+		assert_eq!(strict.eval("1.2345678901234567e5").unwrap(), 123456.78901234567);
+		assert!(strict.eval("1.23456789012345678901e5").is_err());
+	}
+
+	#[test]
+	fn results_show_twelve_significant_digits_by_default() {
+		let settings = DisplaySettings::default();
+		assert_eq!(format_value(0.1 + 0.2, &settings), "0.3");
+		assert_eq!(format_value(1.0 / 3.0, &settings), "0.333333333333");
+		assert_eq!(format_value(1e20, &settings), "1e20");
+		assert_eq!(format_value(42.0, &settings), "42");
+		assert_eq!(format_value(-7.0, &settings), "-7");
+		assert_eq!(format_value(2.50, &settings), "2.5");
+		let short = DisplaySettings { precision: Precision::Significant(3), ..settings };
+		assert_eq!(format_value(1.0 / 3.0, &short), "0.333");
+		assert_eq!(format_value(2.0 / 3.0, &short), "0.667");
+		let full = DisplaySettings { precision: Precision::Full, ..settings };
+		assert_eq!(format_value(0.1 + 0.2, &full), "0.30000000000000004");
+	}
+
+	#[test]
+	fn precision_is_only_applied_when_shown() {
+		let mut context = Context::new();
+		context.display_mut().precision = "3".parse().unwrap();
+		context.execute("x = 1 / 3").unwrap();
+		assert_eq!(context.format(context.get_var("x").unwrap()), "0.333");
+		assert_eq!(context.eval("x * 3").unwrap(), 1.0);
+		assert_eq!(context.answer(), Some(1.0 / 3.0));
+		assert_eq!("full".parse(), Ok(Precision::Full));
+		assert!("0".parse::<Precision>().is_err() && "18".parse::<Precision>().is_err());
+	}
+
+	#[test]
+	fn integers_are_shown_in_the_chosen_radix() {
+		let shown = |value: f64, radix: &str| format_value(value, &DisplaySettings { radix: radix.parse().unwrap(), ..DisplaySettings::default() });
+		assert_eq!(shown(255.0 + 1.0, "hex"), "0x100");
+		assert_eq!(shown(5.0, "bin"), "0b101");
+		assert_eq!(shown(8.0, "oct"), "0o10");
+		assert_eq!(shown(256.0, "dec"), "256");
+		assert_eq!(shown(-16.0, "hex"), "-0x10");
+		assert_eq!(shown(-5.0, "bin"), "-0b101");
+		assert_eq!(shown(-16.0, "hex64"), "0xfffffffffffffff0");
+		assert_eq!(shown(-1.0, "hex64"), "0xffffffffffffffff");
+		assert_eq!(shown(16.0, "hex64"), "0x10");
+		assert_eq!(shown(0.0, "hex"), "0x0");
+		assert_eq!(shown(i64::MIN as f64, "hex"), "-0x8000000000000000");
+		assert!("hexadecimal".parse::<Radix>().is_err());
+	}
+
+	#[test]
+	fn other_values_fall_back_to_decimal_with_a_note() {
+		let hex = DisplaySettings { radix: Radix::Hexadecimal, ..DisplaySettings::default() };
+		assert_eq!(format_value(2.5, &hex), "2.5");
+		assert!(radix_note(2.5, &hex).is_some());
+		assert_eq!(format_value(1e19, &hex), "1e19");
+		assert!(radix_note(1e19, &hex).is_some());
+		assert!(radix_note(f64::NAN, &hex).is_some());
+		assert_eq!(radix_note(16.0, &hex), None);
+		assert_eq!(radix_note(2.5, &DisplaySettings::default()), None);
+	}
+
+	#[test]
+	fn significant_figures_keep_their_trailing_zeros() {
+		let cases: [(f64, usize, &str); 14] = [
+			(0.0123457, 3, "0.0123"),
+			(98765.0, 3, "98800"),
+			(0.999, 2, "1.0"),
+			(9.99, 2, "10"),
+			(99.5, 2, "100"),
+			(1.0, 3, "1.00"),
+			(0.0, 3, "0.00"),
+			(-0.0, 2, "0.0"),
+			(100.0, 2, "100"),
+			(0.001, 1, "0.001"),
+			(-2.345, 3, "-2.35"),
+			(1.0 / 3.0, 4, "0.3333"),
+			(6.02214076e23, 4, "6.022e23"),
+			(1.6e-19, 2, "1.6e-19")
+		];
+		for (value, figures, expected) in cases {
+			let settings = DisplaySettings { precision: Precision::Figures(figures), ..DisplaySettings::default() };
+			assert_eq!(format_value(value, &settings), expected, "{} to {} figures", value, figures);
+		}
+		let sci = DisplaySettings { precision: Precision::Figures(3), notation: Notation::Scientific, ..DisplaySettings::default() };
+		assert_eq!(format_value(98765.0, &sci), "9.88e+04");
+	}
+
+	#[test]
+	fn each_rounding_rule_is_observable() {
+		let rules = ["half-up", "half-even", "toward-zero", "away-from-zero"].map(|name| name.parse::<Rounding>().unwrap());
+		let rounded = |value: f64| rules.map(|rule| rule.round(value));
+		assert_eq!(rounded(2.5), [3.0, 2.0, 2.0, 3.0]);
+		assert_eq!(rounded(3.5), [4.0, 4.0, 3.0, 4.0]);
+		assert_eq!(rounded(-2.5), [-3.0, -2.0, -2.0, -3.0]);
+		assert_eq!(rounded(2.2), [2.0, 2.0, 2.0, 3.0]);
+		assert_eq!(rules.map(Rounding::name), ["half-up", "half-even", "toward-zero", "away-from-zero"]);
+		assert_eq!(Rounding::HalfUp.round_to(1.005, 2), 1.01);
+		assert_eq!(Rounding::HalfEven.round_to(1.005, 2), 1.0);
+		assert_eq!(Rounding::HalfEven.round_to(1250.0, -2), 1200.0);
+		assert!("nearest".parse::<Rounding>().is_err());
+	}
+
+	#[test]
+	fn fixed_decimals_and_round_follow_the_rule() {
+		let mut context = Context::new();
+		context.display_mut().notation = Notation::Fixed(2);
+		assert_eq!(context.format(0.125), "0.13");
+		assert_eq!(context.eval("round(2.5)").unwrap(), 3.0);
+		context.set_rounding(Rounding::HalfEven);
+		assert_eq!(context.rounding(), Rounding::HalfEven);
+		assert_eq!(context.format(0.125), "0.12");
+		assert_eq!(context.format(0.375), "0.38");
+		assert_eq!(context.eval("round(2.5)").unwrap(), 2.0);
+		context.set_rounding(Rounding::TowardZero);
+		assert_eq!((context.format(-0.129), context.eval("round(-2.7)").unwrap()), ("-0.12".to_string(), -2.0));
+	}
+
+	#[test]
+	fn continued_fractions_find_the_reduced_fraction() {
+		assert_eq!(to_fraction(0.75, DEFAULT_MAX_DENOMINATOR), Some((3, 4)));
+		assert_eq!(to_fraction(2.5, DEFAULT_MAX_DENOMINATOR), Some((5, 2)));
+		assert_eq!(to_fraction(0.1, DEFAULT_MAX_DENOMINATOR), Some((1, 10)));
+		assert_eq!(to_fraction(-0.75, DEFAULT_MAX_DENOMINATOR), Some((3, 4)));
+		assert_eq!(to_fraction(1.0 / 3.0, DEFAULT_MAX_DENOMINATOR), Some((1, 3)));
+		assert_eq!(to_fraction(355.0 / 113.0, DEFAULT_MAX_DENOMINATOR), Some((355, 113)));
+		assert_eq!(to_fraction(core::f64::consts::PI, DEFAULT_MAX_DENOMINATOR), None);
+		assert_eq!(to_fraction(1.0 / 10001.0, DEFAULT_MAX_DENOMINATOR), None);
+		assert_eq!(to_fraction(1.0 / 10001.0, 10001), Some((1, 10001)));
+		assert_eq!(to_fraction(0.75, 3), None);
+	}
+
+	#[test]
+	fn fractions_fall_back_to_an_approximate_decimal() {
+		let settings = DisplaySettings { fraction: Some(DEFAULT_MAX_DENOMINATOR), ..DisplaySettings::default() };
+		let cases = [(0.75, "3/4"), (2.5, "5/2"), (-0.75, "-3/4"), (4.0, "4"), (0.1, "1/10"), (core::f64::consts::PI, "≈ 3.14159265359")];
+		for (value, shown) in cases {
+			assert_eq!(format_value(value, &settings), shown, "{}", value);
+		}
+		let settings = DisplaySettings { fraction: Some(8), ..settings };
+		assert_eq!(format_value(0.1, &settings), "≈ 0.1");
+	}
+}
@@ -0,0 +1,129 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::Expr;
+
+/// Renders `expr` as a Graphviz digraph with one node per number, name, operator and function call.
+/// Operands are kept in order by `ordering=out` and numbered on their edges, so `5-3` and `3-5`
+/// differ.
+pub fn to_dot(expr: &Expr) -> String {
+	let mut out = String::from("digraph expr {\n\tordering=out;\n\tnode [shape=ellipse];\n");
+	let mut next = 0;
+	// Walked with a stack of its own rather than by recursing, a deep tree would overflow the thread's
+	let mut work = vec![Work::Node(expr, None)];
+	while let Some(item) = work.pop() {
+		match item {
+			Work::Node(expr, link) => {
+				let id = next;
+				next += 1;
+				let operands = node(expr, id, &mut out);
+				if let Some((parent, number)) = link {
+					work.push(Work::Edge(parent, id, number));
+				}
+				let numbered = operands.len() > 1;
+				for (i, operand) in operands.into_iter().enumerate().rev() {
+					work.push(Work::Node(operand, Some((id, numbered.then_some(i + 1)))));
+				}
+			}
+			Work::Edge(parent, child, Some(number)) => out.push_str(&format!("\tn{} -> n{} [label=\"{}\"];\n", parent, child, number)),
+			Work::Edge(parent, child, None) => out.push_str(&format!("\tn{} -> n{};\n", parent, child))
+		}
+	}
+	out.push_str("}\n");
+	out
+}
+
+/// What is left to write: a node with the id of its parent and the number of its edge, written
+/// before its operands, or an edge, written after the operands of its node.
+enum Work<'a> {
+	Node(&'a Expr, Option<(usize, Option<usize>)>),
+	Edge(usize, usize, Option<usize>)
+}
+
+/// Writes the node of `expr` as `id`, returning its operands.
+fn node<'a>(expr: &'a Expr, id: usize, out: &mut String) -> Vec<&'a Expr> {
+	let (label, operands): (String, Vec<&Expr>) = match expr {
+		Expr::Number(value) => (value.to_string(), Vec::new()),
+		Expr::Name(name, value) => (format!("{} = {}", name, value), Vec::new()),
+		Expr::Unary(op, value) => (format!("{} (sign)", op.symbol), vec![&**value]),
+		Expr::Binary(op, lhs, rhs) => (op.symbol.to_string(), vec![&**lhs, &**rhs]),
+		Expr::Call(func, args) => (format!("{}()", func.name), args.iter().collect()),
+		Expr::Convert { value, from, to } => (format!("convert {} → {}", from.name(), to.name()), vec![&**value]),
+		Expr::Percent(value) => ("%".to_string(), vec![&**value]),
+		Expr::Var(name) => (name.clone(), Vec::new())
+	};
+	let shape = if operands.is_empty() { ", shape=box" } else { "" };
+	out.push_str(&format!("\tn{} [label=\"{}\"{}];\n", id, escape(&label), shape));
+	operands
+}
+
+fn escape(label: &str) -> String {
+	let mut escaped = String::with_capacity(label.len());
+	for c in label.chars() {
+		match c {
+			'"' | '\\' => {
+				escaped.push('\\');
+				escaped.push(c);
+			}
+			'\n' => escaped.push_str("\\n"),
+			_ => escaped.push(c)
+		}
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Context;
+
+	fn dot(expression: &str) -> String {
+		to_dot(&Context::new().to_ast(expression).unwrap())
+	}
+
+	#[test]
+	fn operands_are_numbered_in_order() {
+		assert_eq!(dot("5-3"), concat!(
+			"digraph expr {\n\tordering=out;\n\tnode [shape=ellipse];\n",
+			"\tn0 [label=\"-\"];\n",
+			"\tn1 [label=\"5\", shape=box];\n\tn0 -> n1 [label=\"1\"];\n",
+			"\tn2 [label=\"3\", shape=box];\n\tn0 -> n2 [label=\"2\"];\n",
+			"}\n"
+		));
+		assert_ne!(dot("5-3"), dot("3-5"));
+	}
+
+	#[test]
+	fn calls_and_signs_are_nodes() {
+		assert_eq!(dot("-sqrt(max(1, pi))"), concat!(
+			"digraph expr {\n\tordering=out;\n\tnode [shape=ellipse];\n",
+			"\tn0 [label=\"- (sign)\"];\n",
+			"\tn1 [label=\"sqrt()\"];\n",
+			"\tn2 [label=\"max()\"];\n",
+			"\tn3 [label=\"1\", shape=box];\n\tn2 -> n3 [label=\"1\"];\n",
+			"\tn4 [label=\"pi = 3.141592653589793\", shape=box];\n\tn2 -> n4 [label=\"2\"];\n",
+			"\tn1 -> n2;\n",
+			"\tn0 -> n1;\n",
+			"}\n"
+		));
+	}
+
+	#[test]
+	fn labels_are_escaped() {
+		assert_eq!(escape("say \"hi\"\\\nbye"), "say \\\"hi\\\"\\\\\\nbye");
+	}
+
+	#[test]
+	fn undefined_names_are_leaves() {
+		assert_eq!(dot("a*b+c"), concat!(
+			"digraph expr {\n\tordering=out;\n\tnode [shape=ellipse];\n",
+			"\tn0 [label=\"+\"];\n",
+			"\tn1 [label=\"*\"];\n",
+			"\tn2 [label=\"a\", shape=box];\n\tn1 -> n2 [label=\"1\"];\n",
+			"\tn3 [label=\"b\", shape=box];\n\tn1 -> n3 [label=\"2\"];\n",
+			"\tn0 -> n1 [label=\"1\"];\n",
+			"\tn4 [label=\"c\", shape=box];\n\tn0 -> n4 [label=\"2\"];\n",
+			"}\n"
+		));
+	}
+}
@@ -0,0 +1,149 @@
+use alloc::collections::VecDeque;
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use crate::{BinOp, EvalError, Token};
+
+/// Literal suffixes and how many seconds they stand for. `m` is minutes here; metres are only
+/// named in `convert`.
+pub const SUFFIXES: [(&str, f64); 6] = [
+	("d", 86400.0),
+	("h", 3600.0),
+	("min", 60.0),
+	("m", 60.0),
+	("s", 1.0),
+	("ms", 1e-3)
+];
+
+pub fn suffix_seconds(suffix: &str) -> Option<f64> {
+	SUFFIXES.iter().find(|(name, _)| *name == suffix).map(|(_, seconds)| *seconds)
+}
+
+/// Checks how durations are combined in an RPN queue and returns whether its result is a duration.
+/// Durations add to and subtract from durations, scale by numbers and divide into a plain ratio.
+pub(crate) fn check(rpn: &VecDeque<Token>) -> Result<bool, EvalError> {
+	if !rpn.iter().any(|tok| matches!(tok, Token::Duration(_))) {
+		return Ok(false);
+	}
+	let mismatch = |what: &str| Err(EvalError::DurationMismatch(what.to_string()));
+	// Whether each operand on the stack is a duration
+	let mut stack: Vec<bool> = Vec::with_capacity(rpn.len());
+	for tok in rpn {
+		let argc = match tok {
+			Token::NumericLiteral(_) | Token::Uncertain(..) => {
+				stack.push(false);
+				continue;
+			}
+			Token::Duration(_) => {
+				stack.push(true);
+				continue;
+			}
+			Token::Operator(op) => op.argc,
+			Token::Function(func) => func.argc,
+			Token::Convert(..) | Token::Percent | Token::Tag(_) => 1,
+			Token::PercentChange(_) => 2,
+			Token::OpenParen | Token::CloseParen | Token::Comma | Token::Unit(_) => 0
+		};
+		if stack.len() < argc {
+			return Err(EvalError::NotEnoughArguments);
+		}
+		let base = stack.len() - argc;
+		let args = &stack[base ..];
+		let duration = match *tok {
+			Token::Operator(_) if argc == 1 => args[0],
+			Token::Operator(op) => match (op.op, args[0], args[1]) {
+				(_, false, false) => false,
+				(BinOp::Add | BinOp::PlusMinus, lhs, rhs) if lhs != rhs => return mismatch("add a duration and a number"),
+				(BinOp::Sub, lhs, rhs) if lhs != rhs => return mismatch("subtract a duration and a number"),
+				(BinOp::Add | BinOp::Sub | BinOp::PlusMinus, ..) => true,
+				(BinOp::Mul, true, true) => return mismatch("multiply two durations"),
+				(BinOp::Mul, ..) => true,
+				(BinOp::Div, true, true) => false,
+				(BinOp::Div, true, false) => true,
+				(BinOp::Div, ..) => return mismatch("divide a number by a duration"),
+				(BinOp::Pow, ..) => return mismatch("raise to a power with durations")
+			},
+			// The percentage itself is a number, `90min + 10%` is 99min
+			Token::PercentChange(_) if args[1] => return mismatch("add a percentage given as a duration"),
+			Token::PercentChange(_) => args[0],
+			Token::Tag(_) => args[0],
+			Token::Percent if args[0] => return mismatch("take a duration as a percentage"),
+			Token::Function(_) if !args.contains(&true) => false,
+			Token::Function(func) if matches!(func.name, "abs" | "max" | "min" | "round" | "floor" | "ceil") => {
+				if args.contains(&false) {
+					return mismatch(&format!("pass a duration and a number to '{}'", func.name));
+				}
+				true
+			}
+			Token::Function(func) => return mismatch(&format!("pass a duration to '{}'", func.name)),
+			Token::Convert(..) if args[0] => return mismatch("convert a duration, it is already in seconds"),
+			_ => false
+		};
+		stack.truncate(base);
+		stack.push(duration);
+	}
+	Ok(stack.last().copied().unwrap_or(false))
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::string::String;
+	use super::*;
+	use crate::{Context, DurationStyle, EvalOutcome};
+
+	fn shown(context: &mut Context, expression: &str) -> String {
+		match context.execute(expression) {
+			Ok(EvalOutcome::Value(value)) => context.format_result(value),
+			Ok(outcome) => panic!("{}: {:?}", expression, outcome),
+			Err(err) => err.to_string()
+		}
+	}
+
+	#[test]
+	fn suffixes_are_seconds() {
+		assert_eq!(suffix_seconds("h"), Some(3600.0));
+		assert_eq!(suffix_seconds("m"), suffix_seconds("min"));
+		assert_eq!(suffix_seconds("ms"), Some(0.001));
+		assert_eq!(suffix_seconds("km"), None);
+	}
+
+	#[test]
+	fn each_operator_keeps_or_drops_the_duration() {
+		let mut context = Context::new();
+		let cases = [
+			("1h + 30min + 45s", "1h 30m 45s"),
+			("90min / 2", "45min"),
+			("2h - 30min", "1h 30m"),
+			("3 * 20s", "1min"),
+			("20s * 3", "1min"),
+			("1h / 30min", "2"),
+			("-1h", "-1h"),
+			("1d + 1500ms", "1d 1.5s"),
+			("90min + 10%", "1h 39m"),
+			("max(1h, 50min)", "1h")
+		];
+		for (expression, expected) in cases {
+			assert_eq!(shown(&mut context, expression), expected, "{}", expression);
+		}
+		context.display_mut().durations = DurationStyle::Seconds;
+		assert_eq!(shown(&mut context, "1h + 30min + 45s"), "5445 s");
+	}
+
+	#[test]
+	fn durations_and_numbers_do_not_mix_under_every_operator() {
+		let mut context = Context::new();
+		let cases = [
+			("1h + 5", "add a duration and a number"),
+			("5 - 1h", "subtract a duration and a number"),
+			("1h * 1h", "multiply two durations"),
+			("10 / 1h", "divide a number by a duration"),
+			("1h ^ 2", "raise to a power with durations"),
+			("sqrt(1h)", "pass a duration to 'sqrt'"),
+			("max(1h, 5)", "pass a duration and a number to 'max'")
+		];
+		for (expression, what) in cases {
+			let err = context.execute(expression).unwrap_err();
+			assert!(matches!(&err, EvalError::DurationMismatch(message) if message == what), "{}: {}", expression, err);
+		}
+	}
+}
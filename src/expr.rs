@@ -0,0 +1,263 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::budget::Budget;
+use crate::calculus::form_name;
+use crate::{is_identifier, resolve_identifier, shunting_yard, BinOp, Context, EvalError, Function, Operator, Span, Token, Unit};
+
+/// A parsed expression as a tree. It is built from the RPN, so it groups exactly like evaluation.
+#[derive(Clone, Debug)]
+pub enum Expr {
+	Number(f64),
+	/// A variable, constant, `ans` or duration literal, with the value it had when parsed.
+	Name(String, f64),
+	/// A sign, `-x` or `+x`.
+	Unary(Operator, Box<Expr>),
+	Binary(Operator, Box<Expr>, Box<Expr>),
+	Call(Function, Vec<Expr>),
+	Convert { value: Box<Expr>, from: &'static Unit, to: &'static Unit },
+	/// `x%`. Added to or subtracted from a value that is not a percentage, it is that percent of it.
+	Percent(Box<Expr>),
+	/// A name the context did not define when parsed, such as a variable not set yet. It takes the
+	/// value of the variable in the context the tree is evaluated in, or NaN.
+	Var(String)
+}
+impl Expr {
+	/// Builds the tree for an RPN queue. `leaves` holds the source text of every number in the queue,
+	/// in order, so that names survive; a number without one is shown as its value.
+	pub fn from_postfix(rpn: VecDeque<Token>, leaves: &[&str]) -> Result<Expr, EvalError> {
+		let mut leaves = leaves.iter();
+		let mut stack: Vec<Expr> = Vec::with_capacity(rpn.len());
+		for tok in rpn {
+			let argc = match tok {
+				Token::NumericLiteral(value) | Token::Uncertain(value, _) | Token::Duration(value) => {
+					stack.push(match leaves.next() {
+						Some(text) if matches!(tok, Token::Duration(_)) || text.starts_with(|c: char| c.is_alphabetic() || c == '_' || c == '$') => {
+							Expr::Name(text.to_string(), value)
+						}
+						_ => Expr::Number(value)
+					});
+					continue;
+				}
+				Token::Operator(op) => op.argc,
+				Token::Function(func) => func.argc,
+				// A display label, the value is the same
+				Token::Tag(_) => continue,
+				Token::Convert(..) | Token::Percent => 1,
+				Token::PercentChange(_) => 2,
+				Token::OpenParen | Token::CloseParen | Token::Comma | Token::Unit(_) => {
+					return Err(EvalError::UnexpectedToken(tok));
+				}
+			};
+			if stack.len() < argc {
+				return Err(EvalError::NotEnoughArguments);
+			}
+			let mut args = stack.split_off(stack.len() - argc).into_iter();
+			let node = match tok {
+				Token::Operator(op) if argc == 1 => Expr::Unary(op, Box::new(args.next().unwrap())),
+				Token::Operator(op) | Token::PercentChange(op) => {
+					Expr::Binary(op, Box::new(args.next().unwrap()), Box::new(args.next().unwrap()))
+				}
+				Token::Function(func) => Expr::Call(func, args.collect()),
+				Token::Convert(from, to) => Expr::Convert { value: Box::new(args.next().unwrap()), from, to },
+				Token::Percent => Expr::Percent(Box::new(args.next().unwrap())),
+				_ => unreachable!()
+			};
+			stack.push(node);
+		}
+		stack.pop().ok_or(EvalError::NoResult)
+	}
+
+	/// Evaluates the tree with the default settings, giving the same value as the RPN it was built
+	/// from.
+	pub fn eval(&self) -> f64 {
+		self.eval_in(&Context::new())
+	}
+	/// Evaluates the tree with the angle mode and rounding of `context`.
+	pub fn eval_in(&self, context: &Context) -> f64 {
+		match self {
+			Expr::Number(value) | Expr::Name(_, value) => *value,
+			Expr::Unary(op, value) if op.op == BinOp::Sub => -value.eval_in(context),
+			Expr::Unary(_, value) => value.eval_in(context),
+			Expr::Binary(op, lhs, rhs) if is_percent_change(op, lhs, rhs) => {
+				let base = lhs.eval_in(context);
+				op.op.apply(base, base * rhs.eval_in(context))
+			}
+			Expr::Binary(op, lhs, rhs) => op.op.apply(lhs.eval_in(context), rhs.eval_in(context)),
+			Expr::Call(func, args) if func.name == "round" => context.rounding().round(args[0].eval_in(context)),
+			Expr::Call(func, args) => {
+				let args: Vec<f64> = args.iter().map(|arg| arg.eval_in(context)).collect();
+				func.resolve_in(&args, context.angle_mode())
+			}
+			Expr::Convert { value, from, to } => from.convert(value.eval_in(context), to),
+			Expr::Percent(value) => value.eval_in(context) / 100.0,
+			Expr::Var(name) => context.get_var(name).unwrap_or(f64::NAN)
+		}
+	}
+}
+
+/// `150 + 10%`, but not `10% + 10%`.
+pub(crate) fn is_percent_change(op: &Operator, lhs: &Expr, rhs: &Expr) -> bool {
+	matches!(op.op, BinOp::Add | BinOp::Sub) && matches!(rhs, Expr::Percent(_)) && !matches!(lhs, Expr::Percent(_))
+}
+
+/// Parses `expression` into a tree. Variables keep their names, with the values they have in
+/// `context`, and the names it does not define become [`Expr::Var`], so `3/(x+1)^2` has a tree
+/// before `x` is set.
+pub fn to_ast(expression: &str, context: &Context) -> Result<Expr, EvalError> {
+	let err = match parse(expression, context) {
+		Err(err @ (EvalError::UnknownIdentifier(..) | EvalError::MissingAnswer(_) | EvalError::MissingTapeEntry(..))) => err,
+		parsed => return parsed.map(|(_, ast)| ast)
+	};
+	// Read as 1 while parsing, the undefined names are told apart afterwards. An unknown function
+	// is still an error, the one it was without this.
+	let mut scope = context.clone();
+	scope.probing = true;
+	let (_, mut ast) = parse(expression, &scope).map_err(|_| err)?;
+	free(&mut ast, context);
+	Ok(ast)
+}
+
+/// Turns the names in `expr` that `context` does not define into [`Expr::Var`].
+fn free(expr: &mut Expr, context: &Context) {
+	match expr {
+		Expr::Name(name, _) if undefined(name, context) => {
+			let name = core::mem::take(name);
+			*expr = Expr::Var(name);
+		}
+		Expr::Number(_) | Expr::Name(..) | Expr::Var(_) => {}
+		Expr::Unary(_, value) | Expr::Percent(value) | Expr::Convert { value, .. } => free(value, context),
+		Expr::Binary(_, lhs, rhs) => {
+			free(lhs, context);
+			free(rhs, context);
+		}
+		Expr::Call(_, args) => args.iter_mut().for_each(|arg| free(arg, context))
+	}
+}
+
+fn undefined(name: &str, context: &Context) -> bool {
+	match name.strip_prefix('$') {
+		Some(index) => index.parse().map_or(true, |index| context.tape.get(index).is_none()),
+		// Not a form, whose text is kept as its name too
+		None => is_identifier(name) && resolve_identifier(name, context, Span::new(0, 0)).is_err()
+	}
+}
+
+/// The RPN of `expression` and its tree.
+pub(crate) fn parse(expression: &str, context: &Context) -> Result<(VecDeque<Token>, Expr), EvalError> {
+	let mut tokens = Vec::new();
+	let rpn = shunting_yard(expression, context, Some(&mut tokens), None, &Budget::new(context.budget()))?;
+	// Every lexeme is recorded as one token, so numbers pair up with the text they were read from
+	let leaves: Vec<&str> = context.lexer(expression).zip(&tokens)
+		.filter_map(|(lexeme, tok)| match (lexeme, tok) {
+			(Ok(lexeme), Token::NumericLiteral(_) | Token::Uncertain(..) | Token::Duration(_)) => Some(&expression[lexeme.span.start .. lexeme.span.end]),
+			_ => None
+		})
+		.collect();
+	if depth(&rpn) > context.nesting_limit() {
+		return Err(EvalError::RecursionLimit { limit: context.nesting_limit() });
+	}
+	let ast = Expr::from_postfix(rpn.clone(), &leaves)?;
+	Ok((rpn, ast))
+}
+
+/// How many levels deep the tree of an RPN queue is, found without building it, so that the
+/// walks of the tree can recurse.
+fn depth(rpn: &VecDeque<Token>) -> usize {
+	let mut stack: Vec<usize> = Vec::with_capacity(rpn.len());
+	for tok in rpn {
+		let argc = match tok {
+			Token::Operator(op) => op.argc,
+			Token::Function(func) => func.argc,
+			Token::Convert(..) | Token::Percent => 1,
+			Token::PercentChange(_) => 2,
+			Token::Tag(_) => continue,
+			_ => 0
+		};
+		let mut below = 0;
+		for _ in 0 .. argc {
+			below = below.max(stack.pop().unwrap_or(0));
+		}
+		stack.push(below + 1);
+	}
+	stack.into_iter().max().unwrap_or(0)
+}
+
+/// Writes `expr` back as an expression that parses to the same tree in the default locale, with
+/// parentheses only where the parser's precedence and associativity require them.
+pub fn to_infix(expr: &Expr) -> String {
+	let mut out = String::new();
+	write_infix(expr, &mut out);
+	out
+}
+
+fn write_infix(expr: &Expr, out: &mut String) {
+	match expr {
+		Expr::Number(value) => out.push_str(&value.to_string()),
+		// A `let` reaches as far as it can, so it is put back in the parentheses it came in
+		Expr::Name(name, _) if name != "let" && form_name(name) == "let" => {
+			out.push('(');
+			out.push_str(name);
+			out.push(')');
+		}
+		Expr::Name(name, _) | Expr::Var(name) => out.push_str(name),
+		Expr::Unary(op, value) => {
+			out.push(op.symbol);
+			// A sign applies to the operand right after it, except that `^` binds tighter
+			let bare = match &**value {
+				Expr::Binary(inner, ..) => inner.op == BinOp::Pow,
+				Expr::Unary(..) => false,
+				_ => true
+			};
+			write_operand(value, bare, out);
+		}
+		Expr::Binary(op, lhs, rhs) => {
+			let right = op.op == BinOp::Pow;
+			let bare_lhs = match &**lhs {
+				Expr::Binary(inner, ..) => inner.precedence > op.precedence || (inner.precedence == op.precedence && !right),
+				Expr::Unary(..) => !right,
+				_ => true
+			};
+			let bare_rhs = match &**rhs {
+				Expr::Binary(inner, ..) => inner.precedence > op.precedence || (inner.precedence == op.precedence && right),
+				_ => true
+			};
+			write_operand(lhs, bare_lhs, out);
+			out.push(op.symbol);
+			write_operand(rhs, bare_rhs, out);
+		}
+		Expr::Call(func, args) => {
+			out.push_str(func.name);
+			out.push('(');
+			for (i, arg) in args.iter().enumerate() {
+				if i > 0 {
+					out.push_str(", ");
+				}
+				write_infix(arg, out);
+			}
+			out.push(')');
+		}
+		Expr::Convert { value, from, to } => {
+			out.push_str("convert(");
+			write_infix(value, out);
+			out.push_str(&format!(", {}, {})", from.name(), to.name()));
+		}
+		Expr::Percent(value) => {
+			let bare = !matches!(**value, Expr::Unary(..) | Expr::Binary(..));
+			write_operand(value, bare, out);
+			out.push('%');
+		}
+	}
+}
+
+fn write_operand(expr: &Expr, bare: bool, out: &mut String) {
+	if bare {
+		write_infix(expr, out);
+	} else {
+		out.push('(');
+		write_infix(expr, out);
+		out.push(')');
+	}
+}
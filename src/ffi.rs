@@ -0,0 +1,193 @@
+//! C interface, built with the `ffi` feature. Every function that can fail returns [`SYC_OK`] or a
+//! negative error code, and the message for the last failure on the calling thread is available
+//! from [`syc_last_error_message`]. The library is built with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib`, or `staticlib`, and a header
+//! can be generated with `cbindgen --config cbindgen.toml --output syc.h`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::{is_identifier, Context, EvalError};
+
+pub const SYC_OK: i32 = 0;
+// Evaluation errors have the numbers of their codes in `error_catalog`, negated
+pub const SYC_ERR_INVALID_CHARACTER: i32 = -1;
+pub const SYC_ERR_UNEXPECTED_TOKEN: i32 = -2;
+pub const SYC_ERR_DUPLICATE_DECIMAL: i32 = -3;
+pub const SYC_ERR_NUMBER_PARSE: i32 = -4;
+pub const SYC_ERR_MISMATCHED_PARENTHESIS: i32 = -5;
+pub const SYC_ERR_NOT_ENOUGH_ARGUMENTS: i32 = -6;
+pub const SYC_ERR_INCOMPLETE_EXPRESSION: i32 = -7;
+pub const SYC_ERR_UNKNOWN_IDENTIFIER: i32 = -8;
+pub const SYC_ERR_EXPECTED_CALL: i32 = -9;
+pub const SYC_ERR_ARGUMENT_COUNT: i32 = -10;
+pub const SYC_ERR_NO_RESULT: i32 = -11;
+pub const SYC_ERR_MISSING_ANSWER: i32 = -12;
+pub const SYC_ERR_UNKNOWN_UNIT: i32 = -13;
+pub const SYC_ERR_EXPECTED_UNIT: i32 = -14;
+pub const SYC_ERR_INCOMPATIBLE_UNITS: i32 = -15;
+pub const SYC_ERR_MISSING_TAPE_ENTRY: i32 = -16;
+pub const SYC_ERR_UNCERTAINTY_OFF: i32 = -17;
+pub const SYC_ERR_NO_PROPAGATION_RULE: i32 = -18;
+pub const SYC_ERR_DURATION_MISMATCH: i32 = -19;
+pub const SYC_ERR_EXPECTED_EQUATION: i32 = -20;
+pub const SYC_ERR_NONLINEAR: i32 = -21;
+pub const SYC_ERR_MULTIPLE_UNKNOWNS: i32 = -22;
+pub const SYC_ERR_NO_SOLUTION: i32 = -23;
+pub const SYC_ERR_INFINITE_SOLUTIONS: i32 = -24;
+pub const SYC_ERR_EXPECTED_VARIABLE: i32 = -25;
+pub const SYC_ERR_NOT_FINITE: i32 = -26;
+pub const SYC_ERR_NO_CONVERGENCE: i32 = -27;
+pub const SYC_ERR_EXPECTED_INTEGER: i32 = -28;
+pub const SYC_ERR_TOO_MANY_TERMS: i32 = -29;
+pub const SYC_ERR_INVALID_BASE: i32 = -30;
+pub const SYC_ERR_INVALID_DIGIT: i32 = -31;
+pub const SYC_ERR_EXPECTED_DIGITS: i32 = -32;
+pub const SYC_ERR_DIGITS_IN_EXPRESSION: i32 = -33;
+pub const SYC_ERR_EXPECTED_OPERAND: i32 = -34;
+pub const SYC_ERR_EXPECTED_OPERATOR: i32 = -35;
+pub const SYC_ERR_BUDGET_EXCEEDED: i32 = -36;
+pub const SYC_ERR_STRICT: i32 = -37;
+pub const SYC_ERR_TARGET_COUNT: i32 = -38;
+pub const SYC_ERR_DOMAIN: i32 = -39;
+pub const SYC_ERR_CONTAINED_PANIC: i32 = -40;
+pub const SYC_ERR_UNFILLED_PLACEHOLDERS: i32 = -41;
+pub const SYC_ERR_UNKNOWN_TEMPLATE: i32 = -42;
+pub const SYC_ERR_NOT_MONEY: i32 = -43;
+pub const SYC_ERR_TOKEN_TOO_LONG: i32 = -44;
+pub const SYC_ERR_TOO_DEEP: i32 = -45;
+pub const SYC_ERR_RECURSION_LIMIT: i32 = -46;
+pub const SYC_ERR_PROTECTED: i32 = -47;
+pub const SYC_ERR_UNCOMPILABLE: i32 = -48;
+pub const SYC_ERR_UNEXPECTED_ASSIGNMENT: i32 = -49;
+pub const SYC_ERR_INVALID_BINDING: i32 = -50;
+/// A required pointer argument was null.
+pub const SYC_ERR_NULL_POINTER: i32 = -100;
+/// A string argument was not valid UTF-8.
+pub const SYC_ERR_INVALID_UTF8: i32 = -101;
+/// A variable name is not an identifier.
+pub const SYC_ERR_INVALID_NAME: i32 = -102;
+/// The calculator panicked; this is a bug.
+pub const SYC_ERR_INTERNAL: i32 = -103;
+
+/// Opaque handle to a [`Context`], so variables and `ans` persist across calls.
+pub struct SycContext {
+	context: Context
+}
+
+type Failure = (i32, String);
+
+thread_local! {
+	static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+/// The number of the error's code negated, so `E008` is -8, [`SYC_ERR_UNKNOWN_IDENTIFIER`].
+pub fn error_code(err: &EvalError) -> i32 {
+	-(err.number() as i32)
+}
+
+/// The message includes the names an unknown identifier may be a misspelling of in `context`.
+fn eval_failure(err: EvalError, context: &Context) -> Failure {
+	let message = match err.did_you_mean(context) {
+		Some(question) => format!("{}, {}", err, question),
+		None => err.to_string()
+	};
+	(error_code(&err), message)
+}
+
+/// Runs `body` without letting a panic cross the C boundary, recording the message of any failure.
+fn guard(body: impl FnOnce() -> Result<(), Failure>) -> i32 {
+	let (code, message) = match panic::catch_unwind(AssertUnwindSafe(body)) {
+		Ok(Ok(())) => return SYC_OK,
+		Ok(Err(failure)) => failure,
+		Err(_) => (SYC_ERR_INTERNAL, "internal error".to_string())
+	};
+	let message = CString::new(message).unwrap_or_default();
+	LAST_ERROR.with(|last| *last.borrow_mut() = message);
+	code
+}
+
+/// # Safety
+/// `ptr` must be null or point to a NUL-terminated string that outlives `'a`.
+unsafe fn read_str<'a>(ptr: *const c_char) -> Result<&'a str, Failure> {
+	if ptr.is_null() {
+		return Err((SYC_ERR_NULL_POINTER, "null pointer".to_string()));
+	}
+	CStr::from_ptr(ptr).to_str().map_err(|e| (SYC_ERR_INVALID_UTF8, format!("invalid UTF-8: {}", e)))
+}
+
+/// Evaluates `expr` in a fresh context and stores the value in `*out`.
+///
+/// # Safety
+/// `expr` must be a NUL-terminated string and `out` must point to writable memory for an `f64`.
+#[no_mangle]
+pub unsafe extern "C" fn syc_eval(expr: *const c_char, out: *mut f64) -> i32 {
+	guard(|| {
+		let expr = read_str(expr)?;
+		if out.is_null() {
+			return Err((SYC_ERR_NULL_POINTER, "null pointer".to_string()));
+		}
+		let context = Context::new();
+		*out = context.eval(expr).map_err(|err| eval_failure(err, &context))?;
+		Ok(())
+	})
+}
+
+/// The message of the last failure on this thread, or an empty string. The pointer stays valid
+/// until the next failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn syc_last_error_message() -> *const c_char {
+	LAST_ERROR.with(|last| last.borrow().as_ptr())
+}
+
+/// Creates a context, to be released with [`syc_context_free`].
+#[no_mangle]
+pub extern "C" fn syc_context_new() -> *mut SycContext {
+	Box::into_raw(Box::new(SycContext { context: Context::new() }))
+}
+
+/// # Safety
+/// `ctx` must be null or come from [`syc_context_new`], and must not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn syc_context_free(ctx: *mut SycContext) {
+	if !ctx.is_null() {
+		drop(Box::from_raw(ctx));
+	}
+}
+
+/// # Safety
+/// `ctx` must come from [`syc_context_new`] and `name` must be a NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn syc_context_set_var(ctx: *mut SycContext, name: *const c_char, value: f64) -> i32 {
+	guard(|| {
+		let name = read_str(name)?;
+		let Some(ctx) = ctx.as_mut() else {
+			return Err((SYC_ERR_NULL_POINTER, "null pointer".to_string()));
+		};
+		if !is_identifier(name) {
+			return Err((SYC_ERR_INVALID_NAME, format!("'{}' is not a valid variable name", name)));
+		}
+		ctx.context.set_var(name, value);
+		Ok(())
+	})
+}
+
+/// Executes an expression or an assignment `name = expression` in `ctx` and stores the value in
+/// `*out`.
+///
+/// # Safety
+/// `ctx` must come from [`syc_context_new`], `expr` must be a NUL-terminated string and `out` must
+/// point to writable memory for an `f64`.
+#[no_mangle]
+pub unsafe extern "C" fn syc_context_eval(ctx: *mut SycContext, expr: *const c_char, out: *mut f64) -> i32 {
+	guard(|| {
+		let expr = read_str(expr)?;
+		let (Some(ctx), false) = (ctx.as_mut(), out.is_null()) else {
+			return Err((SYC_ERR_NULL_POINTER, "null pointer".to_string()));
+		};
+		let outcome = ctx.context.execute(expr);
+		*out = outcome.map_err(|err| eval_failure(err, &ctx.context))?.value();
+		Ok(())
+	})
+}
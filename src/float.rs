@@ -0,0 +1,367 @@
+//! The float functions core leaves out, for building without `std`. With `std` the inherent
+//! methods of `f64` are used and this module is empty.
+//!
+//! Rounding and the like are exact. The others are accurate to a few units in the last place,
+//! enough that results shown with the default precision match those of a `std` build, but not bit
+//! for bit.
+
+use core::f64::consts::{FRAC_PI_2, FRAC_PI_4, LN_10, LN_2};
+
+/// Brings the methods into scope where `f64` lacks them.
+pub(crate) trait Float {
+	fn trunc(self) -> Self;
+	fn floor(self) -> Self;
+	fn ceil(self) -> Self;
+	/// Half away from zero.
+	fn round(self) -> Self;
+	fn round_ties_even(self) -> Self;
+	fn fract(self) -> Self;
+	fn sqrt(self) -> Self;
+	fn hypot(self, other: Self) -> Self;
+	fn exp(self) -> Self;
+	fn ln(self) -> Self;
+	fn log10(self) -> Self;
+	fn powi(self, n: i32) -> Self;
+	fn powf(self, n: Self) -> Self;
+	fn sin(self) -> Self;
+	fn cos(self) -> Self;
+	fn tan(self) -> Self;
+	fn asin(self) -> Self;
+	fn acos(self) -> Self;
+	fn atan(self) -> Self;
+}
+
+/// From here on every `f64` is an integer.
+const EXACT: f64 = 4503599627370496.0;
+
+impl Float for f64 {
+	fn trunc(self) -> f64 {
+		if self.is_nan() || self.abs() >= EXACT {
+			return self;
+		}
+		// The cast rounds toward zero, and the sign keeps -0.5 at -0
+		(self as i64 as f64).copysign(self)
+	}
+	fn floor(self) -> f64 {
+		let whole = self.trunc();
+		if whole > self { whole - 1.0 } else { whole }
+	}
+	fn ceil(self) -> f64 {
+		let whole = self.trunc();
+		if whole < self { whole + 1.0 } else { whole }
+	}
+	fn round(self) -> f64 {
+		let whole = self.trunc();
+		// Exact, unlike adding one half first
+		if (self - whole).abs() >= 0.5 { whole + 1.0_f64.copysign(self) } else { whole }
+	}
+	fn round_ties_even(self) -> f64 {
+		let whole = self.trunc();
+		let rest = (self - whole).abs();
+		if rest > 0.5 || (rest == 0.5 && whole % 2.0 != 0.0) { whole + 1.0_f64.copysign(self) } else { whole }
+	}
+	fn fract(self) -> f64 {
+		self - self.trunc()
+	}
+
+	fn sqrt(self) -> f64 {
+		if self.is_nan() || self < 0.0 {
+			return f64::NAN;
+		}
+		if self == 0.0 || self.is_infinite() {
+			return self;
+		}
+		if self < f64::MIN_POSITIVE {
+			return (self * EXACT * EXACT).sqrt() / EXACT;
+		}
+		// Halving the exponent is within a few percent, and every Newton step doubles the digits
+		let mut root = f64::from_bits((self.to_bits() >> 1) + (1023 << 51));
+		for _ in 0 .. 6 {
+			root = 0.5 * (root + self / root);
+		}
+		root
+	}
+	fn hypot(self, other: f64) -> f64 {
+		let (a, b) = (self.abs(), other.abs());
+		let (big, small) = if a >= b { (a, b) } else { (b, a) };
+		if big.is_infinite() {
+			return f64::INFINITY;
+		}
+		if big == 0.0 || small.is_nan() {
+			return big + small;
+		}
+		// Scaled so that squaring overflows for neither
+		let ratio = small / big;
+		big * (1.0 + ratio * ratio).sqrt()
+	}
+
+	fn exp(self) -> f64 {
+		if self.is_nan() {
+			return self;
+		}
+		if self > 709.8 {
+			return f64::INFINITY;
+		}
+		if self < -745.2 {
+			return 0.0;
+		}
+		// e^x = 2^k e^r with |r| at most ln 2 / 2, where the series is short. ln 2 is subtracted in
+		// two parts as in `quarter_turns`, the first short enough that its product with k is exact.
+		const LN_2_HIGH: f64 = 0.6931471803691238;
+		const LN_2_LOW: f64 = 1.9082149292705877e-10;
+		let k = (self / LN_2).round();
+		let r = (self - k * LN_2_HIGH) - k * LN_2_LOW;
+		let (mut term, mut sum) = (1.0, 1.0);
+		for n in 1 .. 20 {
+			term *= r / n as f64;
+			sum += term;
+		}
+		scale(sum, k as i32)
+	}
+	fn ln(self) -> f64 {
+		if self.is_nan() || self < 0.0 {
+			return f64::NAN;
+		}
+		if self == 0.0 {
+			return f64::NEG_INFINITY;
+		}
+		if self.is_infinite() {
+			return self;
+		}
+		// x = m 2^e with m near 1, and ln m = 2 atanh((m - 1) / (m + 1))
+		let (mut m, mut e) = (self, 0);
+		if m < f64::MIN_POSITIVE {
+			m *= EXACT;
+			e -= 52;
+		}
+		e += ((m.to_bits() >> 52) & 0x7ff) as i32 - 1023;
+		m = f64::from_bits((m.to_bits() & !(0x7ff << 52)) | (1023 << 52));
+		if m > core::f64::consts::SQRT_2 {
+			m /= 2.0;
+			e += 1;
+		}
+		let s = (m - 1.0) / (m + 1.0);
+		let (s2, mut power, mut sum) = (s * s, s, 0.0);
+		for n in (1 .. 40).step_by(2) {
+			sum += power / n as f64;
+			power *= s2;
+		}
+		2.0 * sum + e as f64 * LN_2
+	}
+	fn log10(self) -> f64 {
+		self.ln() / LN_10
+	}
+	fn powi(self, n: i32) -> f64 {
+		let (mut base, mut exponent, mut result) = (self, n.unsigned_abs(), 1.0);
+		while exponent > 0 {
+			if exponent & 1 == 1 {
+				result *= base;
+			}
+			base *= base;
+			exponent >>= 1;
+		}
+		if n < 0 { 1.0 / result } else { result }
+	}
+	fn powf(self, n: f64) -> f64 {
+		if n == 0.0 || self == 1.0 {
+			return 1.0;
+		}
+		if self.is_nan() || n.is_nan() {
+			return f64::NAN;
+		}
+		if n.is_infinite() {
+			let magnitude = self.abs();
+			return if magnitude == 1.0 { 1.0 } else if (magnitude > 1.0) == (n > 0.0) { f64::INFINITY } else { 0.0 };
+		}
+		// Each squaring doubles the error of the one before, so only short chains of them are exact
+		// enough
+		let integer = n.fract() == 0.0;
+		if integer && n.abs() <= 64.0 {
+			return self.powi(n as i32);
+		}
+		let odd = integer && (n / 2.0).fract() != 0.0;
+		if self == 0.0 {
+			let zero = if n > 0.0 { 0.0 } else { f64::INFINITY };
+			return if odd { zero.copysign(self) } else { zero };
+		}
+		if self < 0.0 {
+			// Only an integer power of a negative number is real
+			return if !integer { f64::NAN } else if odd { -(-self).powf(n) } else { (-self).powf(n) };
+		}
+		(n * self.ln()).exp()
+	}
+
+	fn sin(self) -> f64 {
+		let (quadrant, r) = quarter_turns(self);
+		match quadrant {
+			0 => sin_series(r),
+			1 => cos_series(r),
+			2 => -sin_series(r),
+			_ => -cos_series(r)
+		}
+	}
+	fn cos(self) -> f64 {
+		let (quadrant, r) = quarter_turns(self);
+		match quadrant {
+			0 => cos_series(r),
+			1 => -sin_series(r),
+			2 => -cos_series(r),
+			_ => sin_series(r)
+		}
+	}
+	fn tan(self) -> f64 {
+		self.sin() / self.cos()
+	}
+	fn asin(self) -> f64 {
+		if self.is_nan() || self.abs() > 1.0 {
+			return f64::NAN;
+		}
+		(self / ((1.0 - self) * (1.0 + self)).sqrt()).atan()
+	}
+	fn acos(self) -> f64 {
+		if self.is_nan() || self.abs() > 1.0 {
+			return f64::NAN;
+		}
+		// Accurate near 1, unlike pi/2 - asin x
+		2.0 * ((1.0 - self) / (1.0 + self)).sqrt().atan()
+	}
+	fn atan(self) -> f64 {
+		if self.is_nan() {
+			return self;
+		}
+		let x = self.abs();
+		// atan x = pi/2 - atan(1/x), and around 1, pi/4 + atan((x - 1) / (x + 1))
+		let (offset, reduced, sign) = if x > 2.414213562373095 {
+			(FRAC_PI_2, 1.0 / x, -1.0)
+		} else if x > 0.41421356237309503 {
+			(FRAC_PI_4, (x - 1.0) / (x + 1.0), 1.0)
+		} else {
+			(0.0, x, 1.0)
+		};
+		let (r2, mut power, mut sum) = (reduced * reduced, reduced, 0.0);
+		for n in 0 .. 30 {
+			let term = power / (2 * n + 1) as f64;
+			sum += if n % 2 == 0 { term } else { -term };
+			power *= r2;
+		}
+		(offset + sign * sum).copysign(self)
+	}
+}
+
+/// `value` times 2^k, in two steps so that neither overflows early.
+fn scale(value: f64, k: i32) -> f64 {
+	let half = k / 2;
+	let power = |k: i32| f64::from_bits(((k + 1023).clamp(1, 2046) as u64) << 52);
+	value * power(half) * power(k - half)
+}
+
+/// `x` as a number of quarter turns, modulo 4, and what remains, at most an eighth of a turn. The
+/// quarter is subtracted in two parts, the second holding the digits the first cannot.
+fn quarter_turns(x: f64) -> (u8, f64) {
+	const PI_2_HIGH: f64 = 1.5707963267341256;
+	const PI_2_LOW: f64 = 6.077100506506192e-11;
+	let k = (x / FRAC_PI_2).round();
+	let r = (x - k * PI_2_HIGH) - k * PI_2_LOW;
+	(((k % 4.0 + 4.0) % 4.0) as u8, r)
+}
+
+fn sin_series(r: f64) -> f64 {
+	let (r2, mut term, mut sum) = (r * r, r, r);
+	for n in 1 .. 12 {
+		term *= -r2 / ((2 * n) * (2 * n + 1)) as f64;
+		sum += term;
+	}
+	sum
+}
+
+fn cos_series(r: f64) -> f64 {
+	let (r2, mut term, mut sum) = (r * r, 1.0, 1.0);
+	for n in 1 .. 12 {
+		term *= -r2 / ((2 * n - 1) * (2 * n)) as f64;
+		sum += term;
+	}
+	sum
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Whether `a` is `b` to within `ulps` units in the last place of `b`, or both are NaN.
+	fn close(a: f64, b: f64, ulps: f64) -> bool {
+		(a.is_nan() && b.is_nan()) || a == b || (a - b).abs() <= ulps * f64::EPSILON * b.abs().max(f64::MIN_POSITIVE)
+	}
+
+	const SPECIAL: [f64; 6] = [0.0, -0.0, f64::INFINITY, f64::NEG_INFINITY, f64::NAN, f64::MIN_POSITIVE / 4.0];
+
+	#[test]
+	fn rounding_is_exact() {
+		let values = [0.5, -0.5, 1.5, 2.5, -2.5, 0.49999999999999994, 3.7, -3.7, 1e300, 4503599627370495.5, 123.0];
+		for x in values.into_iter().chain(SPECIAL) {
+			let pairs = [
+				(Float::trunc(x), x.trunc()),
+				(Float::floor(x), x.floor()),
+				(Float::ceil(x), x.ceil()),
+				(Float::round(x), x.round()),
+				(Float::round_ties_even(x), x.round_ties_even()),
+				(Float::fract(x), x.fract())
+			];
+			for (i, (ours, std)) in pairs.into_iter().enumerate() {
+				assert!(ours.to_bits() == std.to_bits() || (ours.is_nan() && std.is_nan()), "{} of {}: {} and {}", i, x, ours, std);
+			}
+		}
+	}
+
+	#[test]
+	fn roots_exponentials_and_logarithms_are_within_a_few_ulps() {
+		let values = [1e-300, 1e-10, 0.001, 0.3, 0.5, 1.0, 1.5, 2.0, core::f64::consts::E, 10.0, 123.456, 1e10, 1e300, 5e-324];
+		for x in values.into_iter().chain(SPECIAL) {
+			assert!(close(Float::sqrt(x), x.sqrt(), 1.0), "sqrt {}", x);
+			assert!(close(Float::ln(x), x.ln(), 2.0), "ln {}: {} and {}", x, Float::ln(x), x.ln());
+			assert!(close(Float::log10(x), x.log10(), 4.0), "log10 {}", x);
+			assert!(close(Float::hypot(x, 3.0), x.hypot(3.0), 2.0), "hypot {}", x);
+			for sign in [1.0, -1.0] {
+				let y = (sign * x).min(700.0);
+				assert!(close(Float::exp(y), y.exp(), 4.0), "exp {}: {} and {}", y, Float::exp(y), y.exp());
+				assert!(close(Float::sqrt(sign * x), (sign * x).sqrt(), 1.0), "sqrt {}", sign * x);
+			}
+		}
+		assert_eq!(Float::exp(710.0), f64::INFINITY);
+		assert_eq!(Float::exp(-746.0), 0.0);
+	}
+
+	#[test]
+	fn powers_are_exact_for_integers_and_close_otherwise() {
+		for (x, n) in [(2.0, 10.0), (-3.0, 3.0), (10.0, -2.0), (1.5, 2.0), (0.0, -1.0), (-0.0, 3.0), (-8.0, 1.0 / 3.0), (7.0, 0.0), (f64::NAN, 0.0), (-1000.0, f64::INFINITY), (-1.0, f64::NEG_INFINITY), (0.5, f64::INFINITY), (-0.0, 65.0), (-0.0, -65.0), (-0.0, 0.5), (-2.0, 1e300)] {
+			let (ours, std) = (Float::powf(x, n), x.powf(n));
+			assert!(ours == std || (ours.is_nan() && std.is_nan()), "{}^{}: {} and {}", x, n, ours, std);
+		}
+		for (x, n) in [(2.0, 0.5), (10.0, 2.5), (0.3, -1.7), (1.0001, 1e4), (-1.0001, 10001.0), (2.0, 1e6), (7.0, -300.0)] {
+			// The error of n ln x grows with it, and e^y carries it over
+			let ulps = 8.0 * (n * x.ln()).abs().max(1.0);
+			assert!(close(Float::powf(x, n), x.powf(n), ulps), "{}^{}: {} and {}", x, n, Float::powf(x, n), x.powf(n));
+		}
+		assert_eq!(Float::powi(2.0, -3), 0.125);
+	}
+
+	#[test]
+	fn trigonometry_is_within_a_few_ulps() {
+		let values: [f64; 13] = [0.0, 1e-8, 0.1, 0.5, core::f64::consts::FRAC_PI_4, 1.0, core::f64::consts::FRAC_PI_2, 2.0, core::f64::consts::PI, 4.0, 10.0, 100.0, 12345.678];
+		for x in values.into_iter().flat_map(|x| [x, -x]) {
+			// Near a zero of the function only the absolute error is small
+			let near = |a: f64, b: f64| close(a, b, 4.0) || (a - b).abs() < 1e-15 * x.abs().max(1.0);
+			assert!(near(Float::sin(x), x.sin()), "sin {}: {} and {}", x, Float::sin(x), x.sin());
+			assert!(near(Float::cos(x), x.cos()), "cos {}: {} and {}", x, Float::cos(x), x.cos());
+			assert!(close(Float::atan(x), x.atan(), 2.0), "atan {}: {} and {}", x, Float::atan(x), x.atan());
+			if x.abs() < 1.5 {
+				assert!(near(Float::tan(x), x.tan()), "tan {}", x);
+			}
+		}
+		for x in [-1.0, -0.99, -0.5, -1e-9, 0.0, 0.3, core::f64::consts::FRAC_1_SQRT_2, 0.999999, 1.0] {
+			assert!(close(Float::asin(x), x.asin(), 4.0), "asin {}: {} and {}", x, Float::asin(x), x.asin());
+			assert!(close(Float::acos(x), x.acos(), 4.0), "acos {}: {} and {}", x, Float::acos(x), x.acos());
+		}
+		assert!(Float::asin(1.5).is_nan() && Float::acos(f64::NAN).is_nan() && Float::sin(f64::INFINITY).is_nan());
+		assert_eq!(Float::atan(f64::INFINITY), core::f64::consts::FRAC_PI_2);
+	}
+}
@@ -0,0 +1,119 @@
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::budget::Budget;
+use crate::calculus::{arguments, form_name};
+use crate::{is_identifier, tag, Context, EvalError, Span};
+
+/// A function defined with [`Context::register_fn`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserFunction {
+	pub params: Vec<String>,
+	pub body: String
+}
+
+impl Context {
+	/// Defines `name`, so that `name(a, b)` evaluates `body` with `params` set to the values of `a`
+	/// and `b`. The body also reads the variables of the context it is called in, and is only
+	/// checked to lex here. A function of that name is replaced, and one of a built-in function or
+	/// calculus form takes its place. A name or parameter that is not an identifier, or is `let`, is
+	/// [`EvalError::InvalidBinding`].
+	pub fn register_fn(&mut self, name: &str, params: &[&str], body: &str) -> Result<(), EvalError> {
+		for (i, name) in core::iter::once(&name).chain(params).enumerate() {
+			if !is_identifier(name) || *name == "let" || params[.. i.saturating_sub(1)].contains(name) {
+				return Err(EvalError::InvalidBinding(name.to_string()));
+			}
+		}
+		self.tokenize(body)?;
+		let function = UserFunction { params: params.iter().map(|param| param.to_string()).collect(), body: body.trim().to_string() };
+		Arc::make_mut(&mut self.functions).insert(name.to_string(), function);
+		Ok(())
+	}
+	/// Defines `name` like [`Context::register_fn`] and [protects](Context::protect) it, so
+	/// expressions can call it but never shadow it.
+	pub fn register_fn_protected(&mut self, name: &str, params: &[&str], body: &str) -> Result<(), EvalError> {
+		self.register_fn(name, params, body)?;
+		self.protect(name);
+		Ok(())
+	}
+	pub fn remove_fn(&mut self, name: &str) -> Option<UserFunction> {
+		if !self.functions.contains_key(name) {
+			return None;
+		}
+		Arc::make_mut(&mut self.functions).remove(name)
+	}
+	pub fn function(&self, name: &str) -> Option<&UserFunction> {
+		self.functions.get(name)
+	}
+	/// Every function the program defined, by name.
+	pub fn functions(&self) -> impl Iterator<Item = (&str, &UserFunction)> {
+		self.functions.iter().map(|(name, function)| (name.as_str(), function))
+	}
+}
+
+/// Evaluates `call`, a call of a function of `context` as the lexer found it, within `budget`. The
+/// arguments are evaluated in `context`, and the body in a copy of it with the parameters set.
+pub(crate) fn call(call: &str, context: &Context, budget: &Budget) -> Result<f64, EvalError> {
+	let name = form_name(call);
+	let function = &context.functions[name];
+	let args = arguments(call, context.display().locale.argument_separator());
+	if args.len() != function.params.len() {
+		let (expected, found, span) = (function.params.len(), args.len(), Span::new(0, call.len()));
+		return Err(EvalError::ArgumentCount { name: tag::intern(name), expected, found, span });
+	}
+	let mut scope = context.clone();
+	for (param, &(start, end)) in function.params.iter().zip(&args) {
+		let value = context.eval_within(&call[start .. end], budget).map_err(|err| err.offset(start))?;
+		scope.set_var(param, value);
+	}
+	scope.eval_within(&function.body, budget)
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::vec::Vec;
+	use super::*;
+
+	#[test]
+	fn a_function_is_called_with_its_arguments() {
+		let mut context = Context::new();
+		context.register_fn("area", &["w", "h"], "w * h").unwrap();
+		context.register_fn("half", &["x"], "x / 2").unwrap();
+		context.register_fn("two", &[], "2").unwrap();
+		assert_eq!(context.eval("area(3, 4) + 1").unwrap(), 13.0);
+		assert_eq!(context.eval("half(area(two(), 5))").unwrap(), 5.0);
+		assert_eq!(context.functions().map(|(name, _)| name).collect::<Vec<_>>(), ["area", "half", "two"]);
+		// Parameters shadow variables only inside the body
+		context.set_var("w", 100.0);
+		assert_eq!(context.eval("area(w, 2) + w").unwrap(), 300.0);
+		assert_eq!(context.remove_fn("two").map(|function| function.body), Some("2".to_string()));
+		assert!(matches!(context.eval("two()"), Err(EvalError::UnknownIdentifier(name, _)) if name == "two"));
+	}
+
+	#[test]
+	fn the_body_reads_the_variables_of_the_caller() {
+		let mut context = Context::new();
+		context.register_fn("taxed", &["price"], "price * (1 + rate)").unwrap();
+		assert!(matches!(context.eval("taxed(10)"), Err(EvalError::UnknownIdentifier(name, _)) if name == "rate"));
+		context.set_var("rate", 0.5);
+		assert_eq!(context.eval("taxed(10)").unwrap(), 15.0);
+		assert_eq!(context.eval("let rate = 1 in taxed(10)").unwrap(), 20.0);
+		assert_eq!(context.eval("sum_over(k, 1, 2, taxed(k))").unwrap(), 4.5);
+	}
+
+	#[test]
+	fn bad_definitions_and_calls_are_errors() {
+		let mut context = Context::new();
+		for (name, params, bad) in [("2f", &["x"][..], "2f"), ("f", &["x", "x"], "x"), ("let", &[], "let"), ("f", &["of"], "of")] {
+			assert!(matches!(context.register_fn(name, params, "1"), Err(EvalError::InvalidBinding(found)) if found == bad), "{} {:?}", name, params);
+		}
+		assert!(matches!(context.register_fn("f", &[], "1 @ 2"), Err(EvalError::InvalidCharacter('@', _))));
+		context.register_fn("f", &["x"], "x").unwrap();
+		let err = context.eval("1 + f(1, 2)").unwrap_err();
+		assert!(matches!(err, EvalError::ArgumentCount { name: "f", expected: 1, found: 2, span } if span == Span::new(4, 11)), "{:?}", err);
+		// Each call nests, so recursion ends at the nesting limit
+		context.register_fn("forever", &["x"], "forever(x + 1)").unwrap();
+		assert!(matches!(context.eval("forever(1)"), Err(EvalError::TooDeep { .. })));
+	}
+}
@@ -0,0 +1,157 @@
+use shunting_yard::{Dimension, Function, Operator, UNITS};
+
+pub fn help_text() -> String {
+	let mut text = String::new();
+	text.push_str("Enter an expression to evaluate it, or assign it with `name = expression`. The last result\n");
+	text.push_str("is available as `ans`. `solve 2*x + 3 = 7` solves a linear equation in one unknown.\n\n");
+	text.push_str("Commands:\n");
+	text.push_str("  :help [TOPIC]       show this help, or help on operators, functions, units or one\n");
+	text.push_str("  :vars               list the defined variables\n");
+	text.push_str("  :info NAME          show a variable and where the tape sets and reads it, or a function\n");
+	text.push_str("  :del NAME...        delete variables\n");
+	text.push_str("  :save FILE          write the variables and templates set this session to FILE\n");
+	text.push_str("  :load FILE          run the lines of FILE, such as one written by :save\n");
+	text.push_str("  :template N = EXPR  define template N, an expression with ?name placeholders\n");
+	text.push_str("  :templates          list the templates\n");
+	text.push_str("  :use NAME p=VALUE   evaluate a template with a value for each placeholder\n");
+	text.push_str("  :debug [on|off]     print the tokens, RPN and evaluation steps\n");
+	text.push_str("  :time [on|off]      report how long parsing and evaluation took\n");
+	text.push_str("  :stats              show how often the expression cache was used\n");
+	text.push_str("  :tape               list every result so far; $N in an expression is result N\n");
+	text.push_str("  :tape clear         empty the tape\n");
+	text.push_str("  :tape save FILE     write the tape to FILE\n");
+	text.push_str("  :last [N]           list the last N evaluations (10), errors included\n");
+	text.push_str("  :redo N             evaluate #N of :last again with the current variables\n");
+	text.push_str("  :latex EXPR         print EXPR as LaTeX\n");
+	text.push_str("  :simplify EXPR      fold constants and drop identities, then print EXPR\n");
+	text.push_str("  :explain EXPR       show step by step how EXPR is parsed and evaluated\n");
+	text.push_str("  :tokens EXPR        list the lexemes of EXPR with their spans, without evaluating it\n");
+	text.push_str("  :rpn EXPR           list the postfix queue of EXPR, without evaluating it\n");
+	text.push_str("  :canonical EXPR     write EXPR as typed in the default locale, for storing it\n");
+	text.push_str("  :dot EXPR [> FILE]  print the tree of EXPR as a Graphviz graph, or write it to FILE\n");
+	text.push_str("  :precision [N|full] show results with N significant digits\n");
+	text.push_str("  :sigfig [N|off]     show results with exactly N significant figures\n");
+	text.push_str("  :format [NOTATION]  show results as auto, fixed [N], sci or eng\n");
+	text.push_str("  :format seconds     show durations such as 1h + 30min as seconds, or again as a\n");
+	text.push_str("                      breakdown with :format breakdown\n");
+	text.push_str("  :format percent-helpers [on|off]\n");
+	text.push_str("                      show the results of pctchange and pctof with a %\n");
+	text.push_str("  :fraction [on|off|N] show results as fractions with denominators up to N (10000)\n");
+	text.push_str("  :output [RADIX]     show integer results as dec, hex, bin, oct or hex64\n");
+	text.push_str("  :mode [deg|rad]     measure angles in degrees or radians\n");
+	text.push_str("  :mode money [on|off] compute in whole cents, shown with two decimals; 100 / 3 is\n");
+	text.push_str("                      33.33 with a warning about the 0.01 left\n");
+	text.push_str("  :rounding [MODE]    round results and round() half-up, half-even, toward-zero or\n");
+	text.push_str("                      away-from-zero\n");
+	text.push_str("  :uncertainty [on|off]\n");
+	text.push_str("                      propagate uncertainties written as 12.3 ± 0.2 or 12.3 +- 0.2\n");
+	text.push_str("  :strict [on|warn|off]\n");
+	text.push_str("                      reject or warn about ans, of, percent changes and inf or NaN;\n");
+	text.push_str("                      literals that lose digits always warn unless rejected\n");
+	text.push_str("  :lint [on|off]      warn about redundant parentheses, double negation, division by 0\n");
+	text.push_str("                      and lets that are never read\n");
+	text.push_str("  :lint allow NAME    stop warning about one lint, or :lint warn NAME to start again\n");
+	text.push_str("  :tolerance [N]      integrate to within N of the integral's size (1e-10)\n");
+	text.push_str("  :limit [N]          let sum_over and prod_over have up to N terms (1000000)\n");
+	text.push_str("  :budget [N|off]     stop any evaluation that takes more than N operations\n");
+	text.push_str("  :decimal [MODE]     read and show numbers with a decimal point or comma\n");
+	text.push_str("  :grouping [STYLE]   group thousands with off, space, point, underscore or apostrophe\n");
+	text.push_str("  :iterate N [start=X] [until=DELTA] EXPR\n");
+	text.push_str("                      evaluate EXPR N times, feeding each result back in as ans\n");
+	text.push_str("  :set prompt TEXT    change the prompt\n");
+	text.push_str("  :set template TEXT  change the result line, using {input}, {result}, {index} and {time}\n");
+	text.push_str("  :quit, :q, exit     leave the calculator (Ctrl-D works too)\n\n");
+	text.push_str("Operators: ");
+	let symbols: Vec<String> = Operator::MAP.iter().map(|(symbol, _)| symbol.to_string()).collect();
+	text.push_str(&symbols.join(" "));
+	text.push_str("  (see :help operators and :help functions)\n");
+	text
+}
+
+pub fn operators_text() -> String {
+	let mut text = format!("  {:<8}{:<12}{:<15}{}\n", "symbol", "precedence", "associativity", "example");
+	for (symbol, op) in Operator::MAP.iter() {
+		text.push_str(&format!("  {:<8}{:<12}{:<15}{}\n", symbol, op.precedence, op.associativity(), op.example));
+	}
+	text.push_str("Higher precedence binds tighter.\n");
+	text.push_str("A trailing % divides by 100 and `of` multiplies, so 20% of 150 is 30. Adding or subtracting\n");
+	text.push_str("a percentage changes the value by that percent of it: 150 + 10% is 165, 10% + 10% is 0.2.\n");
+	text.push_str("A trailing [tag] labels a value for display, 19.99 [usd] * 3 is 59.97 [usd]. Scaling keeps\n");
+	text.push_str("the tag, as does adding the same tag; anything else drops it with a warning.\n");
+	text
+}
+
+pub fn functions_text() -> String {
+	let mut text = format!("  {:<10}{:<7}{}\n", "function", "arity", "description");
+	for (name, func) in Function::MAP.iter() {
+		text.push_str(&format!("  {:<10}{:<7}{}\n", name, func.argc, func.description));
+	}
+	text
+}
+
+pub fn units_text() -> String {
+	let mut text = String::from("Units for convert(value, from, to), by dimension:\n");
+	let dimensions = [Dimension::Length, Dimension::Mass, Dimension::Temperature, Dimension::Time, Dimension::Data];
+	for dimension in dimensions {
+		let names: Vec<String> = UNITS.iter().filter(|unit| unit.dimension == dimension).map(|unit| unit.names.join("/")).collect();
+		text.push_str(&format!("  {:<13}{}\n", dimension.to_string(), names.join(" ")));
+	}
+	text.push_str("Names are case-sensitive and may be quoted, as in convert(32, \"F\", \"C\").\n");
+	text
+}
+
+/// Help on `topic`: `operators`, `functions`, `units`, or the name of one operator or function.
+pub fn topic_text(topic: &str) -> String {
+	match topic {
+		"operators" => return operators_text(),
+		"functions" => return functions_text(),
+		"units" => return units_text(),
+		_ => {}
+	}
+	if let Some(op) = topic.chars().next().filter(|_| topic.chars().count() == 1).and_then(Operator::by_char) {
+		return format!(
+			"{}  {}, precedence {}, {} associative\n  example: {}\n",
+			op.symbol, op.description, op.precedence, op.associativity(), op.example
+		);
+	}
+	if let Some(func) = Function::by_name(topic) {
+		return format!("{}  {}, {} argument(s)\n  example: {}\n", func.name, func.description, func.argc, func.example);
+	}
+	let topics = ["operators", "functions", "units"].into_iter().chain(Function::MAP.iter().map(|(name, _)| *name));
+	let matches: Vec<&str> = topics.filter(|name| name.starts_with(topic)).collect();
+	if matches.is_empty() {
+		format!("No help on '{}', try :help operators or :help functions\n", topic)
+	} else {
+		format!("No help on '{}', did you mean {}?\n", topic, matches.join(", "))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn tables_list_every_operator_and_function() {
+		let operators = operators_text();
+		for (symbol, op) in Operator::MAP.iter() {
+			assert!(operators.lines().any(|line| line.trim_start().starts_with(*symbol) && line.ends_with(op.example)), "{}", symbol);
+		}
+		assert!(operators.contains("  ^       3           right          2 ^ 10\n"));
+		let functions = functions_text();
+		for (name, func) in Function::MAP.iter() {
+			assert!(functions.contains(&format!("  {:<10}{:<7}{}\n", name, func.argc, func.description)), "{}", name);
+		}
+		assert!(help_text().contains("  :help [TOPIC]  ") && help_text().ends_with("(see :help operators and :help functions)\n"));
+	}
+
+	#[test]
+	fn topics_describe_one_entry_or_suggest_others() {
+		assert_eq!(topic_text("sqrt"), "sqrt  square root, 1 argument(s)\n  example: sqrt(16)\n");
+		assert!(topic_text("^").starts_with("^  "));
+		assert!(topic_text("^").contains("right associative\n  example: 2 ^ 10"));
+		assert_eq!(topic_text("operators"), operators_text());
+		assert_eq!(topic_text("sq"), "No help on 'sq', did you mean sqrt?\n");
+		assert!(topic_text("fun").contains("did you mean functions"));
+		assert_eq!(topic_text("zzz"), "No help on 'zzz', try :help operators or :help functions\n");
+	}
+}
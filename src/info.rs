@@ -0,0 +1,231 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::dependencies::{self, dependencies_with};
+use crate::{constant, split_assignment, to_ast, to_infix, Context, EvalError, Function, UserFunction, CONSTANTS};
+
+/// The most close matches suggested for an unknown name.
+const MAX_SUGGESTIONS: usize = 5;
+/// The most names an error suggests, fewer than `:info` lists.
+const MAX_ERROR_SUGGESTIONS: usize = 3;
+
+/// What a name is in a context, for `:info`.
+#[derive(Clone, Debug)]
+pub enum Info {
+	Variable(VariableInfo),
+	/// `ans` and the last result.
+	Answer(f64),
+	Constant(f64),
+	Function(Function),
+	/// A function defined with [`Context::register_fn`].
+	UserFunction(UserFunctionInfo),
+	/// The name has no meaning; the known names within an edit distance of 2, closest first and
+	/// then alphabetically.
+	Unknown(Vec<String>)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VariableInfo {
+	pub value: f64,
+	/// Its uncertainty, when it was assigned one in uncertainty mode.
+	pub sigma: Option<f64>,
+	pub duration: bool,
+	/// The tape index of the last assignment to it still on the tape.
+	pub assigned: Option<usize>,
+	/// The names that assignment read.
+	pub reads: Vec<String>,
+	/// The tape indexes of the expressions that read it.
+	pub used_by: Vec<usize>,
+	/// The functions defined with [`Context::register_fn`] whose bodies read it.
+	pub functions: Vec<String>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserFunctionInfo {
+	pub params: Vec<String>,
+	/// The body as [`to_infix`] writes it.
+	pub body: String,
+	/// The variables the body reads besides the parameters, through the functions it calls too.
+	pub reads: Vec<String>
+}
+
+impl Context {
+	/// What `name` is, looked up in the order expressions resolve names: variables shadow constants
+	/// and functions, and the functions of the program shadow the built-in ones.
+	pub fn info(&self, name: &str) -> Info {
+		if let Some(value) = self.get_var(name) {
+			return Info::Variable(self.variable_info(name, value));
+		}
+		if let Some(value) = self.answer().filter(|_| name == "ans") {
+			return Info::Answer(value);
+		}
+		if let Some(function) = self.function(name) {
+			return Info::UserFunction(self.function_info(function));
+		}
+		if let Some(value) = constant(name) {
+			return Info::Constant(value);
+		}
+		if let Some(func) = Function::by_name(name) {
+			return Info::Function(func);
+		}
+		Info::Unknown(self.close_names(name, MAX_SUGGESTIONS))
+	}
+
+	fn variable_info(&self, name: &str, value: f64) -> VariableInfo {
+		let mut assigned = None;
+		let mut reads = Vec::new();
+		let mut used_by = Vec::new();
+		for entry in self.tape.entries() {
+			let (target, expression) = split_assignment(&entry.input);
+			let names = dependencies_with(expression, self).unwrap_or_default();
+			if names.contains(name) {
+				used_by.push(entry.index);
+			}
+			if target == Some(name) {
+				assigned = Some(entry.index);
+				reads = names.into_iter().collect();
+			}
+		}
+		let functions = self.functions()
+			.filter(|(_, function)| dependencies::of_function(function, self).is_ok_and(|reads| reads.contains(name)))
+			.map(|(name, _)| name.to_string())
+			.collect();
+		VariableInfo { value, sigma: self.var_uncertainty(name), duration: self.is_duration(name), assigned, reads, used_by, functions }
+	}
+
+	fn function_info(&self, function: &UserFunction) -> UserFunctionInfo {
+		let body = to_ast(&function.body, self).map_or_else(|_| function.body.clone(), |ast| to_infix(&ast));
+		let reads = dependencies::of_function(function, self).unwrap_or_default().into_iter().collect();
+		UserFunctionInfo { params: function.params.clone(), body, reads }
+	}
+
+	fn close_names(&self, name: &str, limit: usize) -> Vec<String> {
+		let known = self.variables().map(|(name, _)| name)
+			.chain(self.functions().map(|(name, _)| name))
+			.chain(CONSTANTS.iter().map(|(name, _)| *name))
+			.chain(Function::MAP.iter().map(|(name, _)| *name))
+			.chain(["ans"]);
+		let mut close: Vec<(usize, &str)> = known
+			.map(|known| (edit_distance(name, known), known))
+			.filter(|(distance, _)| *distance <= 2)
+			.collect();
+		close.sort();
+		close.dedup();
+		close.into_iter().take(limit).map(|(_, known)| known.to_string()).collect()
+	}
+}
+
+impl EvalError {
+	/// For an unknown identifier, up to three known names of `context` it may be a misspelling of,
+	/// closest first and then alphabetically. Names of one or two characters get none, nearly
+	/// everything is close to them.
+	pub fn suggestions(&self, context: &Context) -> Vec<String> {
+		match self {
+			Self::UnknownIdentifier(name, _) if name.chars().count() > 2 => context.close_names(name, MAX_ERROR_SUGGESTIONS),
+			_ => Vec::new()
+		}
+	}
+	/// The [`EvalError::suggestions`] as a question, "did you mean `sqrt`?", if there are any.
+	pub fn did_you_mean(&self, context: &Context) -> Option<String> {
+		let names: Vec<String> = self.suggestions(context).iter().map(|name| format!("`{}`", name)).collect();
+		match names.as_slice() {
+			[] => None,
+			[name] => Some(format!("did you mean {}?", name)),
+			[names @ .., last] => Some(format!("did you mean {} or {}?", names.join(", "), last))
+		}
+	}
+}
+
+/// The number of characters to insert, delete or replace, or of neighbours to swap, to turn `a`
+/// into `b`; `sqart` is 1 from `sqrt` and `sqtr` is too.
+fn edit_distance(a: &str, b: &str) -> usize {
+	let (a, b): (Vec<char>, Vec<char>) = (a.chars().collect(), b.chars().collect());
+	// Three rows of the distances between prefixes: two back, the last and the one being filled
+	let mut before: Vec<usize> = vec![0; b.len() + 1];
+	let mut last: Vec<usize> = (0 ..= b.len()).collect();
+	let mut row: Vec<usize> = vec![0; b.len() + 1];
+	for i in 1 ..= a.len() {
+		row[0] = i;
+		for j in 1 ..= b.len() {
+			let cost = usize::from(a[i - 1] != b[j - 1]);
+			row[j] = (last[j - 1] + cost).min(last[j] + 1).min(row[j - 1] + 1);
+			if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+				row[j] = row[j].min(before[j - 2] + 1);
+			}
+		}
+		core::mem::swap(&mut before, &mut last);
+		core::mem::swap(&mut last, &mut row);
+	}
+	last[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Span;
+
+	fn suggested(context: &Context, expression: &str) -> Vec<String> {
+		context.eval(expression).unwrap_err().suggestions(context)
+	}
+
+	#[test]
+	fn a_variable_lists_the_functions_that_read_it() {
+		let mut context = Context::new();
+		context.execute("scale = 2").unwrap();
+		context.register_fn("area", &["w", "h"], "w * h * scale").unwrap();
+		context.register_fn("perimeter", &["w", "h"], "2 * (w + h) * scale").unwrap();
+		context.register_fn("square", &["w"], "area(w, w)").unwrap();
+		// The parameter shadows the variable
+		context.register_fn("grow", &["scale"], "scale + 1").unwrap();
+		let Info::Variable(info) = context.info("scale") else { panic!("scale is a variable") };
+		assert_eq!(info.assigned, Some(1));
+		assert_eq!(info.functions, ["area", "perimeter", "square"]);
+		let Info::UserFunction(info) = context.info("square") else { panic!("square is a function") };
+		assert_eq!((info.params, info.body, info.reads), (vec!["w".to_string()], "area(w, w)".to_string(), vec!["scale".to_string()]));
+		assert!(matches!(context.info("grow"), Info::UserFunction(info) if info.reads.is_empty()));
+	}
+
+	#[test]
+	fn unknown_names_suggest_close_ones() {
+		let mut context = Context::new();
+		context.set_var("rate", 0.5);
+		context.register_fn("area", &["w", "h"], "w * h").unwrap();
+		assert!(matches!(context.info("aria"), Info::Unknown(close) if close.first().map(String::as_str) == Some("area")));
+		assert!(matches!(context.info("rat"), Info::Unknown(close) if close.first().map(String::as_str) == Some("rate")));
+		assert!(matches!(context.info("qwertyuiop"), Info::Unknown(close) if close.is_empty()));
+	}
+
+	#[test]
+	fn edits_and_swaps_count_one_each() {
+		assert_eq!(edit_distance("sqart", "sqrt"), 1);
+		assert_eq!(edit_distance("sqtr", "sqrt"), 1);
+		assert_eq!(edit_distance("sqr", "sqrt"), 1);
+		assert_eq!(edit_distance("tsqr", "sqrt"), 2);
+		assert_eq!(edit_distance("", "pi"), 2);
+		assert_eq!(edit_distance("sqrt", "sqrt"), 0);
+	}
+
+	#[test]
+	fn misspellings_suggest_the_closest_names_first() {
+		let mut context = Context::new();
+		assert_eq!(suggested(&context, "sqart(2)"), ["sqrt"]);
+		// `min` and `sin` are both 2 away, alphabetically after `pi` at 1
+		assert_eq!(suggested(&context, "pii"), ["pi", "min", "sin"]);
+		context.set_var("rate", 0.5);
+		assert_eq!(suggested(&context, "2 * rtae"), ["rate", "atan", "tan"]);
+		let err = context.eval("sqart(2)").unwrap_err();
+		assert_eq!(err.did_you_mean(&context).as_deref(), Some("did you mean `sqrt`?"));
+		assert_eq!(context.eval("pii").unwrap_err().did_you_mean(&context).as_deref(), Some("did you mean `pi`, `min` or `sin`?"));
+	}
+
+	#[test]
+	fn nonsense_short_names_and_other_errors_suggest_nothing() {
+		let context = Context::new();
+		assert!(suggested(&context, "qwertyuiop").is_empty());
+		// `si` is close to `sin` and much else
+		assert!(suggested(&context, "si").is_empty());
+		assert!(suggested(&context, "1 +").is_empty());
+		assert_eq!(EvalError::UnknownIdentifier("qqqq".into(), Span::new(0, 4)).did_you_mean(&context), None);
+		assert!(matches!(context.info("si"), Info::Unknown(close) if close.len() == MAX_SUGGESTIONS));
+	}
+}
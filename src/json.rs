@@ -0,0 +1,310 @@
+use shunting_yard::{Context, EvalError, EvalOutcome};
+
+/// Renders the outcome of evaluating `input` as a single-line JSON object. Non-finite values have no
+/// JSON representation and are written as the strings `"inf"`, `"-inf"` and `"nan"`.
+pub fn render_result(input: &str, result: &Result<EvalOutcome, EvalError>, context: &Context) -> String {
+	let input = quote(input);
+	match result {
+		Ok(EvalOutcome::Value(value)) => format!("{{\"input\":{},\"ok\":true,\"value\":{}}}", input, number(*value)),
+		Ok(EvalOutcome::Assigned(name, value) | EvalOutcome::Solved(name, value)) => format!(
+			"{{\"input\":{},\"ok\":true,\"name\":{},\"value\":{}}}", input, quote(name), number(*value)
+		),
+		Ok(EvalOutcome::Multiple(values)) => format!(
+			"{{\"input\":{},\"ok\":true,\"values\":{}}}", input, array(values.iter().map(|value| number(*value)))
+		),
+		Ok(EvalOutcome::AssignedMultiple(assigned)) => format!(
+			"{{\"input\":{},\"ok\":true,\"names\":{},\"values\":{}}}", input,
+			array(assigned.iter().map(|(name, _)| quote(name))), array(assigned.iter().map(|(_, value)| number(*value)))
+		),
+		Err(err) => format!("{{\"input\":{},\"ok\":false,\"error\":{}}}", input, render_error(err, context))
+	}
+}
+
+/// Renders a point of a sweep, `{"vars":{"x":0.5},"ok":true,"value":1.25}`, the values of `vars`
+/// in their order.
+pub fn render_point(vars: &[&str], values: &[f64], result: &Result<f64, EvalError>, context: &Context) -> String {
+	let members: Vec<String> = vars.iter().zip(values).map(|(name, value)| format!("{}:{}", quote(name), number(*value))).collect();
+	let vars = format!("{{{}}}", members.join(","));
+	match result {
+		Ok(value) => format!("{{\"vars\":{},\"ok\":true,\"value\":{}}}", vars, number(*value)),
+		Err(err) => format!("{{\"vars\":{},\"ok\":false,\"error\":{}}}", vars, render_error(err, context))
+	}
+}
+
+/// The `error` member of a failed result, `{"kind":...,"pos":...,"message":...}`, with
+/// `"suggestions"` when the error suggests names in `context`.
+pub fn render_error(err: &EvalError, context: &Context) -> String {
+	let pos = err.span().map_or("null".to_string(), |span| span.start.to_string());
+	let suggestions = err.suggestions(context);
+	let suggestions = if suggestions.is_empty() {
+		String::new()
+	} else {
+		format!(",\"suggestions\":{}", array(suggestions.iter().map(|name| quote(name))))
+	};
+	format!(
+		"{{\"kind\":{},\"code\":{},\"pos\":{},\"message\":{}{}}}",
+		quote(err.kind()), quote(err.code()), pos, quote(&err.to_string()), suggestions
+	)
+}
+
+/// `[a,b]` from members already rendered.
+pub fn array(members: impl Iterator<Item = String>) -> String {
+	format!("[{}]", members.collect::<Vec<_>>().join(","))
+}
+
+/// A number, or for a non-finite value a string JSON readers can tell from `null`.
+pub fn number(value: f64) -> String {
+	match value {
+		_ if value.is_finite() => value.to_string(),
+		_ if value.is_nan() => "\"nan\"".to_string(),
+		f64::INFINITY => "\"inf\"".to_string(),
+		_ => "\"-inf\"".to_string()
+	}
+}
+
+pub fn quote(text: &str) -> String {
+	let mut quoted = String::with_capacity(text.len() + 2);
+	quoted.push('"');
+	for c in text.chars() {
+		match c {
+			'"' => quoted.push_str("\\\""),
+			'\\' => quoted.push_str("\\\\"),
+			'\n' => quoted.push_str("\\n"),
+			'\r' => quoted.push_str("\\r"),
+			'\t' => quoted.push_str("\\t"),
+			c if (c as u32) < 0x20 => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+			c => quoted.push(c)
+		}
+	}
+	quoted.push('"');
+	quoted
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+	Null,
+	Boolean(bool),
+	Number(f64),
+	String(String),
+	Array(Vec<Value>),
+	/// Members in document order.
+	Object(Vec<(String, Value)>)
+}
+impl Value {
+	pub fn get(&self, key: &str) -> Option<&Value> {
+		match self {
+			Self::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+			_ => None
+		}
+	}
+
+	/// Compact JSON text for the value.
+	pub fn render(&self) -> String {
+		match self {
+			Self::Null => "null".to_string(),
+			Self::Boolean(value) => value.to_string(),
+			Self::Number(value) => number(*value),
+			Self::String(text) => quote(text),
+			Self::Array(values) => {
+				let values: Vec<String> = values.iter().map(Self::render).collect();
+				format!("[{}]", values.join(","))
+			}
+			Self::Object(members) => {
+				let members: Vec<String> = members.iter().map(|(name, value)| format!("{}:{}", quote(name), value.render())).collect();
+				format!("{{{}}}", members.join(","))
+			}
+		}
+	}
+}
+
+/// Parses a complete JSON document.
+pub fn parse(text: &str) -> Result<Value, String> {
+	let mut chars = text.chars().peekable();
+	let value = parse_value(&mut chars, 0)?;
+	skip_whitespace(&mut chars);
+	match chars.next() {
+		None => Ok(value),
+		Some(c) => Err(format!("unexpected '{}' after the value", c))
+	}
+}
+
+/// Recovers the value of member `key` from a document that does not parse as a whole, when the
+/// member itself is intact.
+pub fn find_member(text: &str, key: &str) -> Option<Value> {
+	let needle = quote(key);
+	text.match_indices(&needle).find_map(|(i, _)| {
+		let mut chars = text[i + needle.len() ..].chars().peekable();
+		skip_whitespace(&mut chars);
+		chars.next_if_eq(&':')?;
+		parse_value(&mut chars, 0).ok()
+	})
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::Chars<'a>>;
+
+fn skip_whitespace(chars: &mut Chars) {
+	while chars.next_if(|c| c.is_whitespace()).is_some() {}
+}
+
+/// How deeply arrays and objects may nest in a document, a request needs far less.
+const MAX_DEPTH: usize = 128;
+
+/// `depth` is how many arrays and objects the value is within.
+fn parse_value(chars: &mut Chars, depth: usize) -> Result<Value, String> {
+	skip_whitespace(chars);
+	if depth > MAX_DEPTH && matches!(chars.peek(), Some('{' | '[')) {
+		return Err(format!("arrays and objects nested more than {} deep", MAX_DEPTH));
+	}
+	match chars.peek() {
+		Some('{') => {
+			chars.next();
+			let mut members = Vec::new();
+			skip_whitespace(chars);
+			if chars.next_if_eq(&'}').is_some() {
+				return Ok(Value::Object(members));
+			}
+			loop {
+				skip_whitespace(chars);
+				if chars.next() != Some('"') {
+					return Err("expected a member name".to_string());
+				}
+				let name = parse_string(chars)?;
+				skip_whitespace(chars);
+				if chars.next() != Some(':') {
+					return Err(format!("expected ':' after \"{}\"", name));
+				}
+				members.push((name, parse_value(chars, depth + 1)?));
+				skip_whitespace(chars);
+				match chars.next() {
+					Some(',') => {}
+					Some('}') => return Ok(Value::Object(members)),
+					_ => return Err("expected ',' or '}' in object".to_string())
+				}
+			}
+		}
+		Some('[') => {
+			chars.next();
+			let mut values = Vec::new();
+			skip_whitespace(chars);
+			if chars.next_if_eq(&']').is_some() {
+				return Ok(Value::Array(values));
+			}
+			loop {
+				values.push(parse_value(chars, depth + 1)?);
+				skip_whitespace(chars);
+				match chars.next() {
+					Some(',') => {}
+					Some(']') => return Ok(Value::Array(values)),
+					_ => return Err("expected ',' or ']' in array".to_string())
+				}
+			}
+		}
+		Some('"') => {
+			chars.next();
+			parse_string(chars).map(Value::String)
+		}
+		Some(_) => {
+			let mut word = String::new();
+			while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '+' | '.')) {
+				word.push(c);
+			}
+			match word.as_str() {
+				"null" => Ok(Value::Null),
+				"true" => Ok(Value::Boolean(true)),
+				"false" => Ok(Value::Boolean(false)),
+				_ if word.starts_with(|c: char| c == '-' || c.is_ascii_digit()) => {
+					word.parse().map(Value::Number).map_err(|_| format!("invalid number '{}'", word))
+				}
+				"" => Err(format!("unexpected '{}'", chars.peek().unwrap())),
+				_ => Err(format!("unexpected '{}'", word))
+			}
+		}
+		None => Err("unexpected end of input".to_string())
+	}
+}
+
+/// Reads the rest of a string whose opening quote has been consumed.
+fn parse_string(chars: &mut Chars) -> Result<String, String> {
+	let mut text = String::new();
+	loop {
+		match chars.next() {
+			Some('"') => return Ok(text),
+			Some('\\') => match chars.next() {
+				Some('"') => text.push('"'),
+				Some('\\') => text.push('\\'),
+				Some('/') => text.push('/'),
+				Some('b') => text.push('\u{8}'),
+				Some('f') => text.push('\u{c}'),
+				Some('n') => text.push('\n'),
+				Some('r') => text.push('\r'),
+				Some('t') => text.push('\t'),
+				Some('u') => {
+					let hex: String = chars.by_ref().take(4).collect();
+					let code = u32::from_str_radix(&hex, 16).map_err(|_| format!("invalid escape '\\u{}'", hex))?;
+					text.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+				}
+				Some(c) => return Err(format!("invalid escape '\\{}'", c)),
+				None => return Err("unterminated string".to_string())
+			},
+			Some(c) => text.push(c),
+			None => return Err("unterminated string".to_string())
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn results_render_as_one_object() {
+		let mut context = Context::new();
+		let result = context.execute("2*(3+4)");
+		assert_eq!(render_result("2*(3+4)", &result, &context), r#"{"input":"2*(3+4)","ok":true,"value":14}"#);
+		let result = context.execute("2*(");
+		let rendered = parse(&render_result("2*(", &result, &context)).unwrap();
+		assert_eq!(rendered.get("ok"), Some(&Value::Boolean(false)));
+		let error = rendered.get("error").unwrap();
+		assert_eq!(error.get("kind"), Some(&Value::String("MismatchedParenthesis".to_string())));
+		assert_eq!(error.get("pos"), Some(&Value::Number(2.0)));
+	}
+
+	#[test]
+	fn non_finite_results_are_strings() {
+		let mut context = Context::new();
+		for (input, value) in [("1/0", "inf"), ("-1/0", "-inf"), ("sqrt(-1)", "nan")] {
+			let rendered = parse(&render_result(input, &context.execute(input), &context)).unwrap();
+			assert_eq!(rendered.get("ok"), Some(&Value::Boolean(true)), "{}", input);
+			assert_eq!(rendered.get("value"), Some(&Value::String(value.to_string())), "{}", input);
+		}
+	}
+
+	#[test]
+	fn text_and_numbers_are_escaped_and_read_back() {
+		assert_eq!(quote("a\"b\\c\n\u{1}"), r#""a\"b\\c\n\u0001""#);
+		assert_eq!([number(f64::NAN), number(f64::INFINITY), number(f64::NEG_INFINITY)], ["\"nan\"", "\"inf\"", "\"-inf\""]);
+		let value = Value::Object(vec![
+			("list".to_string(), Value::Array(vec![Value::Number(1.5), Value::Null, Value::Boolean(true)])),
+			("name".to_string(), Value::String("é \"x\"".to_string()))
+		]);
+		assert_eq!(parse(&value.render()), Ok(value));
+		assert_eq!(parse(" [1, -2.5e1, \"\\u00e9\"] "), Ok(Value::Array(vec![Value::Number(1.0), Value::Number(-25.0), Value::String("é".to_string())])));
+		assert!(parse("[1,]").is_err() && parse("{\"a\" 1}").is_err() && parse("1 2").is_err());
+		assert_eq!(find_member("{\"a\": 3, \"b\": [oops", "a"), Some(Value::Number(3.0)));
+	}
+
+	#[test]
+	fn documents_nested_too_deep_are_refused() {
+		let nested = |depth: usize| format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+		assert!(parse(&nested(MAX_DEPTH)).is_ok());
+		assert_eq!(parse(&nested(100_000)), Err("arrays and objects nested more than 128 deep".to_string()));
+		assert!(parse(&"{\"a\":".repeat(100_000)).is_err());
+	}
+
+	#[test]
+	fn a_sweep_point_writes_its_variables_and_a_non_finite_value_as_strings() {
+		let context = Context::new();
+		assert_eq!(render_point(&["x", "y"], &[0.0, f64::NEG_INFINITY], &Ok(f64::NAN), &context), r#"{"vars":{"x":0,"y":"-inf"},"ok":true,"value":"nan"}"#);
+		assert_eq!(render_point(&["x"], &[2.5], &Ok(1.0), &context), r#"{"vars":{"x":2.5},"ok":true,"value":1}"#);
+	}
+}
@@ -0,0 +1,191 @@
+use alloc::string::{String, ToString};
+
+use crate::{BinOp, Expr};
+
+/// Renders `expr` as LaTeX math, such as `\frac{3}{(x+1)^{2}}` for `3/(x+1)^2`. The output is read
+/// by people, so parentheses follow the usual conventions rather than the parser's precedence table:
+/// they appear only where the conventional reading would group differently from the tree.
+pub fn to_latex(expr: &Expr) -> String {
+	let mut out = String::new();
+	render(expr, &mut out);
+	out
+}
+
+/// How tightly a node binds in conventional notation. Calls and leaves are atoms, and a fraction is
+/// only grouped as the base of a power.
+fn binding(expr: &Expr) -> u8 {
+	match expr {
+		Expr::Unary(..) => 1,
+		Expr::Binary(op, ..) => match op.op {
+			BinOp::Add | BinOp::Sub | BinOp::PlusMinus => 1,
+			BinOp::Mul => 2,
+			BinOp::Pow => 3,
+			BinOp::Div => 4
+		},
+		_ => 5
+	}
+}
+
+/// Renders `expr`, parenthesized unless it binds at least as tightly as `min`.
+fn operand(expr: &Expr, min: u8, out: &mut String) {
+	if binding(expr) < min {
+		out.push('(');
+		render(expr, out);
+		out.push(')');
+	} else {
+		render(expr, out);
+	}
+}
+
+fn render(expr: &Expr, out: &mut String) {
+	match expr {
+		Expr::Number(value) => out.push_str(&value.to_string()),
+		Expr::Name(name, _) | Expr::Var(name) => out.push_str(&name_text(name)),
+		Expr::Unary(op, value) => {
+			out.push(op.symbol);
+			operand(value, 2, out);
+		}
+		Expr::Binary(op, lhs, rhs) => match op.op {
+			BinOp::Div => {
+				out.push_str("\\frac{");
+				render(lhs, out);
+				out.push_str("}{");
+				render(rhs, out);
+				out.push('}');
+			}
+			BinOp::Pow => {
+				operand(lhs, 5, out);
+				out.push_str("^{");
+				render(rhs, out);
+				out.push('}');
+			}
+			BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::PlusMinus => {
+				let level = binding(expr);
+				// A leading sign reads the same either way, `-a \cdot b`
+				operand(lhs, if matches!(**lhs, Expr::Unary(..)) { 1 } else { level }, out);
+				out.push_str(match op.op {
+					BinOp::Mul => " \\cdot ",
+					BinOp::Add => "+",
+					BinOp::PlusMinus => " \\pm ",
+					_ => "-"
+				});
+				// Only an addition may leave a right operand of the same level bare, and not one with a sign
+				let min = if op.op == BinOp::Add && !matches!(**rhs, Expr::Unary(..)) { level } else { level + 1 };
+				operand(rhs, min, out);
+			}
+		},
+		Expr::Call(func, args) => {
+			let (open, close) = match func.name {
+				"sqrt" => ("\\sqrt{", "}"),
+				"abs" => ("\\left|", "\\right|"),
+				"floor" => ("\\lfloor ", " \\rfloor"),
+				"ceil" => ("\\lceil ", " \\rceil"),
+				_ => {
+					out.push_str(&function_name(func.name));
+					("(", ")")
+				}
+			};
+			out.push_str(open);
+			for (i, arg) in args.iter().enumerate() {
+				if i > 0 {
+					out.push_str(", ");
+				}
+				render(arg, out);
+			}
+			out.push_str(close);
+		}
+		Expr::Convert { value, from, to } => {
+			out.push_str("\\operatorname{convert}(");
+			render(value, out);
+			out.push_str(&format!(", \\mathrm{{{}}}, \\mathrm{{{}}})", from.name(), to.name()));
+		}
+		Expr::Percent(value) => {
+			operand(value, 5, out);
+			out.push_str("\\%");
+		}
+	}
+}
+
+fn function_name(name: &str) -> String {
+	match name {
+		"sin" | "cos" | "tan" | "exp" | "ln" | "log" | "max" | "min" => format!("\\{}", name),
+		"asin" | "acos" | "atan" => format!("\\arc{}", &name[1 ..]),
+		_ => format!("\\operatorname{{{}}}", name.replace('_', "\\_"))
+	}
+}
+
+/// Greek constants become their letters, `x1` becomes `x_{1}`, tape references keep their `$` and
+/// longer names are set upright.
+fn name_text(name: &str) -> String {
+	let digits = name.trim_start_matches(|c: char| !c.is_ascii_digit());
+	let head = &name[.. name.len() - digits.len()];
+	match name {
+		"pi" | "tau" => format!("\\{}", name),
+		_ if name.starts_with('$') => format!("\\{}", name),
+		_ if head.chars().count() == 1 && head != "_" && !digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()) => {
+			format!("{}_{{{}}}", head, digits)
+		}
+		_ if name.chars().count() == 1 && name != "_" => name.to_string(),
+		_ => format!("\\mathrm{{{}}}", name.replace('_', "\\_"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Context;
+
+	fn latex(expression: &str) -> String {
+		let mut context = Context::new();
+		for name in ["x", "y", "x1", "rate"] {
+			context.set_var(name, 2.0);
+		}
+		to_latex(&context.to_ast(expression).unwrap())
+	}
+
+	#[test]
+	fn fractions_and_powers_take_braces() {
+		assert_eq!(latex("3/(x+1)^2"), "\\frac{3}{(x+1)^{2}}");
+		assert_eq!(latex("(1/2)/(3/4)"), "\\frac{\\frac{1}{2}}{\\frac{3}{4}}");
+		assert_eq!(latex("(1/2)^2"), "(\\frac{1}{2})^{2}");
+		assert_eq!(latex("2^3^2"), "2^{3^{2}}");
+		assert_eq!(latex("(2^3)^2"), "(2^{3})^{2}");
+		assert_eq!(latex("x^(y+1)"), "x^{y+1}");
+	}
+
+	#[test]
+	fn parentheses_follow_the_conventional_reading() {
+		assert_eq!(latex("1 + 2 * 3"), "1+2 \\cdot 3");
+		assert_eq!(latex("(1 + 2) * 3"), "(1+2) \\cdot 3");
+		assert_eq!(latex("1 - (2 - 3)"), "1-(2-3)");
+		assert_eq!(latex("1 - 2 + 3"), "1-2+3");
+		assert_eq!(latex("-x * 2"), "-x \\cdot 2");
+		assert_eq!(latex("2 * -x"), "2 \\cdot (-x)");
+		assert_eq!(latex("-(x + 1)"), "-(x+1)");
+	}
+
+	#[test]
+	fn functions_and_names_are_typeset() {
+		assert_eq!(latex("sin(x) + sqrt(2)"), "\\sin(x)+\\sqrt{2}");
+		assert_eq!(latex("abs(floor(x) - ceil(y))"), "\\left|\\lfloor x \\rfloor-\\lceil y \\rceil\\right|");
+		assert_eq!(latex("atan(1) * max(x1, pi)"), "\\arctan(1) \\cdot \\max(x_{1}, \\pi)");
+		assert_eq!(latex("sqrt(1/x) * rate"), "\\sqrt{\\frac{1}{x}} \\cdot \\mathrm{rate}");
+		assert_eq!(latex("50%"), "50\\%");
+	}
+
+	#[test]
+	fn undefined_names_are_typeset_too() {
+		let context = Context::new();
+		assert_eq!(to_latex(&context.to_ast("3/(x+1)^2").unwrap()), "\\frac{3}{(x+1)^{2}}");
+		assert_eq!(to_latex(&context.to_ast("ans * rate + $2").unwrap()), "\\mathrm{ans} \\cdot \\mathrm{rate}+\\$2");
+		assert!(matches!(context.to_ast("f(2)"), Err(crate::EvalError::UnknownIdentifier(name, _)) if name == "f"));
+	}
+
+	#[test]
+	fn a_form_keeps_its_value_beside_undefined_names() {
+		let ast = Context::new().to_ast("sum_over(k, 1, 3, k) * x").unwrap();
+		let mut context = Context::new();
+		context.set_var("x", 2.0);
+		assert_eq!(ast.eval_in(&context), 12.0);
+	}
+}
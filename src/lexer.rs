@@ -0,0 +1,417 @@
+use alloc::collections::BTreeMap;
+use alloc::string::String;
+use alloc::sync::Arc;
+use core::fmt;
+
+use crate::functions::UserFunction;
+use crate::{binding, EvalError, Locale, Span};
+
+/// How many characters one lexeme may have unless a context says otherwise.
+pub const DEFAULT_TOKEN_LIMIT: usize = 4096;
+
+/// Reads a number at the start of the rest of the input, returning its value and how many bytes it
+/// took, or `None` to leave it to the built-in number syntax.
+pub type LiteralParser = dyn Fn(&str) -> Option<(f64, usize)> + Send + Sync;
+
+/// A [`LiteralParser`] kept by a context, see [`crate::Context::set_literal_parser`].
+#[derive(Clone)]
+pub(crate) struct LiteralHook(pub(crate) Arc<LiteralParser>);
+impl fmt::Debug for LiteralHook {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str("LiteralHook")
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum LexemeKind {
+	Number(f64),
+	/// A number with a duration suffix, `90min`, in seconds.
+	Duration(f64),
+	/// `$3`, the third result on the tape.
+	TapeRef(usize),
+	/// A variable, constant or function name; the text is the lexeme's span of the input.
+	Identifier,
+	Operator(char),
+	OpenParen,
+	CloseParen,
+	/// `,` between function arguments, `;` with a decimal comma.
+	Separator,
+	/// Text in double quotes; the span includes the quotes.
+	String,
+	/// `%` after a value.
+	Percent,
+	/// A whole call of one of [`crate::calculus::FORMS`] or of a function the program defined, from
+	/// its name to its closing parenthesis, or a `let` to the end of its body.
+	Form,
+	/// `?w`, a value filled in when a template is used; the span includes the `?`.
+	Placeholder,
+	/// `[usd]` after a value, a label shown with the result; the span includes the brackets.
+	Tag
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Lexeme {
+	pub kind: LexemeKind,
+	pub span: Span
+}
+
+/// Splits an expression into lexemes by position, without copying any of its text. Numbers are
+/// parsed straight from the input unless the locale needs separators removed first. After an error
+/// the iterator ends, as it does at the first character past the token limit of a lexeme, so a
+/// runaway literal is never read to its end.
+pub struct Lexer<'a> {
+	input: &'a str,
+	pos: usize,
+	locale: Locale,
+	plus_minus: bool,
+	token_limit: usize,
+	literal: Option<Arc<LiteralParser>>,
+	functions: Option<Arc<BTreeMap<String, UserFunction>>>,
+	/// Whether the last lexeme leaves a value to come, as at the start and after an operator.
+	operand: bool
+}
+impl<'a> Lexer<'a> {
+	pub fn new(input: &'a str, locale: Locale) -> Self {
+		Self { input, pos: 0, locale, plus_minus: false, token_limit: DEFAULT_TOKEN_LIMIT, literal: None, functions: None, operand: true }
+	}
+	/// Reads `+-` written together as the operator `±`, for uncertainty mode.
+	pub fn plus_minus(mut self, on: bool) -> Self {
+		self.plus_minus = on;
+		self
+	}
+	/// Fails with [`EvalError::TokenTooLong`] on a lexeme of more than `limit` characters.
+	pub fn token_limit(mut self, limit: usize) -> Self {
+		self.token_limit = limit;
+		self
+	}
+	/// Offers the input to `parser` wherever a value may start, before the built-in number syntax.
+	pub fn literal_parser(mut self, parser: Option<Arc<LiteralParser>>) -> Self {
+		self.literal = parser;
+		self
+	}
+	/// Reads a call of one of `functions` as one [`LexemeKind::Form`], as it does the calculus forms.
+	pub fn functions(mut self, functions: Option<Arc<BTreeMap<String, UserFunction>>>) -> Self {
+		self.functions = functions;
+		self
+	}
+
+	fn peek(&self) -> Option<char> {
+		self.input[self.pos ..].chars().next()
+	}
+
+	/// The error for the lexeme from `start` once `pos` is past the token limit.
+	fn too_long(&self, start: usize, pos: usize) -> EvalError {
+		EvalError::TokenTooLong { span: Span::new(start, pos), len: self.token_limit + 1, limit: self.token_limit }
+	}
+	/// Where the run of characters matching `pred` from `from` ends, for a lexeme that began at
+	/// `start`. Reading stops at the first character past the token limit.
+	fn run(&self, start: usize, from: usize, pred: impl Fn(char) -> bool) -> Result<usize, EvalError> {
+		let mut length = self.input[start .. from].chars().count();
+		let mut end = from;
+		for c in self.input[from ..].chars().take_while(|c| pred(*c)) {
+			length += 1;
+			end += c.len_utf8();
+			if length > self.token_limit {
+				return Err(self.too_long(start, end));
+			}
+		}
+		Ok(end)
+	}
+
+	/// The literal `parser` claims at the current position, if it claims any characters. A claim
+	/// past the end of the input or with a value that is not finite is an error at its span.
+	fn claimed(&mut self, parser: &LiteralParser) -> Result<Option<Lexeme>, EvalError> {
+		let start = self.pos;
+		let rest = &self.input[start ..];
+		let Some((value, length)) = parser(rest).filter(|(_, length)| *length > 0) else {
+			return Ok(None);
+		};
+		let mut end = (start + length).min(self.input.len());
+		while !self.input.is_char_boundary(end) {
+			end += 1;
+		}
+		let span = Span::new(start, end);
+		if length > rest.len() || !self.input.is_char_boundary(start + length) || !value.is_finite() {
+			return Err(EvalError::NumberParseError(span));
+		}
+		if self.input[start .. end].chars().count() > self.token_limit {
+			return Err(self.too_long(start, end));
+		}
+		self.pos = end;
+		Ok(Some(Lexeme { kind: LexemeKind::Number(value), span }))
+	}
+
+	fn number(&mut self) -> Result<Lexeme, EvalError> {
+		let start = self.pos;
+		let mut seen_decimal = false;
+		let mut grouped = false;
+		let mut length = 0;
+		while let Some(c) = self.peek() {
+			if length == self.token_limit {
+				return Err(self.too_long(start, self.pos + c.len_utf8()));
+			}
+			length += 1;
+			if c.is_ascii_digit() {
+				self.pos += 1;
+			} else if c == self.locale.decimal() {
+				if seen_decimal {
+					return Err(EvalError::DuplicateDecimal(Span::at(self.pos, c)));
+				}
+				seen_decimal = true;
+				self.pos += 1;
+			} else if Some(c) == self.locale.grouping() && self.input[self.pos + c.len_utf8() ..].starts_with(|next: char| next.is_ascii_digit()) {
+				grouped = true;
+				self.pos += c.len_utf8();
+			} else {
+				break;
+			}
+		}
+		// An exponent, as results are shown: `1e20`, `1e+20`, `1.5e-7`
+		if self.pos > start && self.peek().is_some_and(|c| c == 'e' || c == 'E') {
+			let rest = &self.input[self.pos + 1 ..];
+			let sign = usize::from(rest.starts_with(['+', '-']));
+			if rest[sign ..].starts_with(|c: char| c.is_ascii_digit()) {
+				self.pos = self.run(start, self.pos + 1 + sign, |c| c.is_ascii_digit())?;
+			}
+		}
+		let span = Span::new(start, self.pos);
+		let text = &self.input[start .. self.pos];
+		// Only separators that `f64::from_str` does not understand need a copy without them
+		let parsed = if grouped || (seen_decimal && self.locale.decimal() != '.') {
+			let canonical: String = text.chars()
+				.filter(|c| Some(*c) != self.locale.grouping())
+				.map(|c| if c == self.locale.decimal() { '.' } else { c })
+				.collect();
+			canonical.parse::<f64>()
+		} else {
+			text.parse::<f64>()
+		};
+		parsed.map(|value| Lexeme { kind: LexemeKind::Number(value), span }).map_err(|_| EvalError::NumberParseError(span))
+	}
+}
+impl Lexer<'_> {
+	/// Turns a number directly followed by a duration suffix into a duration.
+	fn duration(&mut self, lexeme: Lexeme) -> Result<Lexeme, EvalError> {
+		let LexemeKind::Number(value) = lexeme.kind else {
+			return Ok(lexeme);
+		};
+		let end = self.run(lexeme.span.start, self.pos, |c| c.is_alphabetic() || c == '_' || c.is_ascii_digit())?;
+		Ok(match crate::duration::suffix_seconds(&self.input[self.pos .. end]) {
+			Some(seconds) => {
+				self.pos = end;
+				Lexeme { kind: LexemeKind::Duration(value * seconds), span: Span::new(lexeme.span.start, self.pos) }
+			}
+			None => lexeme
+		})
+	}
+}
+impl Lexer<'_> {
+	/// Where the call whose name was just read ends, after its closing parenthesis.
+	fn call_end(&self) -> Option<usize> {
+		let rest = &self.input[self.pos ..];
+		let open = rest.len() - rest.trim_start().len();
+		if !rest[open ..].starts_with('(') {
+			return None;
+		}
+		let (mut depth, mut quoted) = (0, false);
+		for (i, c) in rest[open ..].char_indices() {
+			match c {
+				'"' => quoted = !quoted,
+				_ if quoted => {}
+				'(' => depth += 1,
+				')' if depth == 1 => return Some(self.pos + open + i + 1),
+				')' => depth -= 1,
+				_ => {}
+			}
+		}
+		None
+	}
+}
+/// Spaces between lexemes. Of the control characters only tab and line endings count, others such
+/// as NUL are invalid.
+fn is_space(c: char) -> bool {
+	matches!(c, '\t' | '\n' | '\r') || (c.is_whitespace() && !c.is_control())
+}
+
+impl Lexer<'_> {
+	fn lexeme(&mut self) -> Result<Option<Lexeme>, EvalError> {
+		while let Some(c) = self.peek().filter(|c| is_space(*c)) {
+			self.pos += c.len_utf8();
+		}
+		let Some(c) = self.peek() else {
+			return Ok(None);
+		};
+		if let Some(parser) = self.literal.clone().filter(|_| self.operand) {
+			if let Some(lexeme) = self.claimed(&*parser)? {
+				return Ok(Some(lexeme));
+			}
+		}
+		let start = self.pos;
+		let kind = if c.is_ascii_digit() || c == self.locale.decimal() {
+			let lexeme = self.number()?;
+			return self.duration(lexeme).map(Some);
+		} else if c.is_alphabetic() || c == '_' {
+			self.pos = self.run(start, start, |c| c.is_alphabetic() || c == '_' || c.is_ascii_digit())?;
+			let word = &self.input[start .. self.pos];
+			if word == "let" && self.operand {
+				if let Some(binding) = binding::split(&self.input[start ..], self.locale.argument_separator()) {
+					self.pos = start + binding.body.1;
+					return Ok(Some(Lexeme { kind: LexemeKind::Form, span: Span::new(start, self.pos) }));
+				}
+			}
+			if crate::calculus::FORMS.contains(&word) || self.functions.as_ref().is_some_and(|functions| functions.contains_key(word)) {
+				if let Some(end) = self.call_end() {
+					self.pos = end;
+					return Ok(Some(Lexeme { kind: LexemeKind::Form, span: Span::new(start, end) }));
+				}
+			}
+			return Ok(Some(Lexeme { kind: LexemeKind::Identifier, span: Span::new(start, self.pos) }));
+		} else if c == '$' {
+			let end = self.run(start, start + 1, |c| c.is_ascii_digit())?;
+			if end == start + 1 {
+				return Err(EvalError::InvalidCharacter(c, Span::at(start, c)));
+			}
+			self.pos = end;
+			let span = Span::new(start, self.pos);
+			let index = self.input[start + 1 .. self.pos].parse().map_err(|_| EvalError::NumberParseError(span))?;
+			return Ok(Some(Lexeme { kind: LexemeKind::TapeRef(index), span }));
+		} else if c == '?' {
+			let end = self.run(start, start + 1, |c| c.is_alphanumeric() || c == '_')?;
+			if end == start + 1 || self.input[start + 1 ..].starts_with(|c: char| c.is_ascii_digit()) {
+				return Err(EvalError::InvalidCharacter(c, Span::at(start, c)));
+			}
+			self.pos = end;
+			return Ok(Some(Lexeme { kind: LexemeKind::Placeholder, span: Span::new(start, self.pos) }));
+		} else if c == '[' {
+			let end = self.run(start, start + 1, |c| c != ']' && c != '[')?;
+			if !self.input[end ..].starts_with(']') || self.input[start + 1 .. end].trim().is_empty() {
+				return Err(EvalError::InvalidCharacter(c, Span::at(start, c)));
+			}
+			self.pos = end + 1;
+			return Ok(Some(Lexeme { kind: LexemeKind::Tag, span: Span::new(start, self.pos) }));
+		} else if c == '"' {
+			let end = self.run(start, start + 1, |c| c != '"')?;
+			if !self.input[end ..].starts_with('"') {
+				return Err(EvalError::InvalidCharacter(c, Span::at(start, c)));
+			}
+			self.pos = end + 1;
+			return Ok(Some(Lexeme { kind: LexemeKind::String, span: Span::new(start, self.pos) }));
+		} else if c == '+' && self.plus_minus && self.input[start + 1 ..].starts_with('-') {
+			self.pos = start + 2;
+			return Ok(Some(Lexeme { kind: LexemeKind::Operator('±'), span: Span::new(start, self.pos) }));
+		} else if c == '%' {
+			LexemeKind::Percent
+		} else if c == '(' {
+			LexemeKind::OpenParen
+		} else if c == ')' {
+			LexemeKind::CloseParen
+		} else if c == self.locale.argument_separator() {
+			LexemeKind::Separator
+		} else if crate::Operator::by_char(c).is_some() {
+			LexemeKind::Operator(c)
+		} else {
+			return Err(EvalError::InvalidCharacter(c, Span::at(start, c)));
+		};
+		self.pos += c.len_utf8();
+		Ok(Some(Lexeme { kind, span: Span::at(start, c) }))
+	}
+}
+
+impl Iterator for Lexer<'_> {
+	type Item = Result<Lexeme, EvalError>;
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let lexeme = self.lexeme();
+		match &lexeme {
+			Ok(Some(Lexeme { kind, span })) => {
+				self.operand = matches!(kind, LexemeKind::Operator(_) | LexemeKind::OpenParen | LexemeKind::Separator)
+					|| (*kind == LexemeKind::Identifier && &self.input[span.start .. span.end] == "of");
+			}
+			Ok(None) => {}
+			Err(_) => self.pos = self.input.len()
+		}
+		lexeme.transpose()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::string::String;
+	use alloc::vec::Vec;
+
+	use super::*;
+
+	/// The lexer this one replaced, which gathered the characters of a number into a `String`
+	/// before parsing it. Only knows numbers, operators and parentheses.
+	fn char_pushing(input: &str) -> Vec<LexemeKind> {
+		let mut lexemes = Vec::new();
+		let mut temp = String::new();
+		for c in input.chars().chain([' ']) {
+			if c.is_ascii_digit() || c == '.' {
+				temp.push(c);
+				continue;
+			}
+			if !temp.is_empty() {
+				lexemes.push(LexemeKind::Number(temp.parse().unwrap()));
+				temp.clear();
+			}
+			match c {
+				'(' => lexemes.push(LexemeKind::OpenParen),
+				')' => lexemes.push(LexemeKind::CloseParen),
+				'+' | '-' | '*' | '/' | '^' => lexemes.push(LexemeKind::Operator(c)),
+				_ => {}
+			}
+		}
+		lexemes
+	}
+
+	fn lex(input: &str) -> Result<Vec<Lexeme>, EvalError> {
+		Lexer::new(input, Locale::default()).collect()
+	}
+
+	#[test]
+	fn token_streams_match_the_char_pushing_lexer() {
+		let mut long = String::from("1");
+		for i in 0 .. 10_000 {
+			long.push_str(&alloc::format!(" {} {}.25", ['+', '-', '*', '/', '^'][i % 5], i));
+		}
+		for input in ["1 + 2", "(3.5*4)-2^10", "  12.75 /(0.5) ", "((1))", "007 + .5", &long] {
+			let kinds: Vec<LexemeKind> = lex(input).unwrap().iter().map(|lexeme| lexeme.kind).collect();
+			assert_eq!(kinds, char_pushing(input), "{}", input);
+		}
+	}
+
+	#[test]
+	fn lexemes_carry_their_spans() {
+		let input = "12.5 + sqrt(x) * $2";
+		let lexemes = lex(input).unwrap();
+		let texts: Vec<&str> = lexemes.iter().map(|lexeme| &input[lexeme.span.start .. lexeme.span.end]).collect();
+		assert_eq!(texts, ["12.5", "+", "sqrt", "(", "x", ")", "*", "$2"]);
+		assert_eq!(lexemes[7].kind, LexemeKind::TapeRef(2));
+		assert!(matches!(lex("1 + 1.2.3"), Err(EvalError::DuplicateDecimal(span)) if span == Span::new(7, 8)));
+		let tokens: Vec<_> = Lexer::new("1 + 1.2.3 + 4", Locale::default()).collect();
+		assert!(tokens.len() == 3 && tokens[2].is_err());
+	}
+
+	#[test]
+	fn lexemes_past_the_limit_are_refused_without_reading_on() {
+		let input = alloc::format!("1 + {}", "9".repeat(100));
+		assert!(lex(&input).is_ok());
+		let lexemes: Vec<_> = Lexer::new(&input, Locale::default()).token_limit(10).collect();
+		assert!(matches!(lexemes.as_slice(), [Ok(_), Ok(_), Err(_)]));
+	}
+
+	#[test]
+	fn literals_end_exactly_where_they_stop() {
+		let spans = |input: &str| -> Vec<(usize, usize)> { lex(input).unwrap().iter().map(|lexeme| (lexeme.span.start, lexeme.span.end)).collect() };
+		assert_eq!(spans("1 + 23.5"), [(0, 1), (2, 3), (4, 8)]);
+		assert_eq!(spans("(2.5)"), [(0, 1), (1, 4), (4, 5)]);
+		assert_eq!(spans("1.5*2-3/4.25"), [(0, 3), (3, 4), (4, 5), (5, 6), (6, 7), (7, 8), (8, 12)]);
+		assert_eq!(lex("7.").unwrap()[0].kind, LexemeKind::Number(7.0));
+		assert_eq!(lex(".5)").unwrap()[0].kind, LexemeKind::Number(0.5));
+		assert!(matches!(lex("1 + 2.5.").unwrap_err(), EvalError::DuplicateDecimal(span) if span == Span::new(7, 8)));
+		assert!(matches!(lex("1..2)").unwrap_err(), EvalError::DuplicateDecimal(span) if span == Span::new(2, 3)));
+		assert!(matches!(lex("2 * .").unwrap_err(), EvalError::NumberParseError(span) if span == Span::new(4, 5)));
+		assert!(matches!(lex(".)").unwrap_err(), EvalError::NumberParseError(span) if span == Span::new(0, 1)));
+	}
+}
@@ -0,0 +1,2413 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[macro_use]
+extern crate alloc;
+#[cfg(all(test, not(feature = "std")))]
+extern crate std;
+
+use alloc::collections::{BTreeMap, BTreeSet, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::f64::consts;
+use core::fmt::{self, Display};
+#[cfg(feature = "std")]
+use std::panic::{self, AssertUnwindSafe};
+
+#[cfg(not(any(feature = "std", test)))]
+use float::Float;
+use budget::Budget;
+use trace::Stopwatch;
+
+pub mod base;
+mod binding;
+#[cfg(feature = "std")]
+pub mod batch;
+mod budget;
+pub mod bytecode;
+pub mod cache;
+pub mod calculus;
+pub mod canonical;
+pub mod classify;
+pub mod dependencies;
+pub mod display;
+pub mod dot;
+pub mod duration;
+pub mod expr;
+pub mod functions;
+#[cfg(not(feature = "std"))]
+mod float;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod info;
+pub mod latex;
+pub mod lexer;
+pub mod lint;
+mod money;
+pub mod partial;
+pub mod sandbox;
+pub mod simplify;
+pub mod snapshot;
+pub mod solve;
+pub mod strict;
+pub mod sweep;
+pub mod tag;
+pub mod tape;
+pub mod templates;
+pub mod trace;
+pub mod uncertain;
+pub mod units;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+pub use base::to_digits;
+pub use bytecode::{Instr, Program};
+#[cfg(feature = "std")]
+pub use batch::{clean_line, eval_batch_jobs, eval_batch_parallel, eval_lines, eval_lines_with, execute_batch_jobs, BatchOptions, BatchStats, LineTime};
+pub use cache::{CacheStats, ExpressionCache};
+pub use classify::{classify, EvalResult, ValueClass};
+pub use display::{format_duration, format_money, format_uncertain, format_value, parse_grouping, radix_note, to_fraction, DisplaySettings, DurationStyle, Locale, Notation, Precision, Radix, Rounding};
+pub use dependencies::{dependencies_of, dependencies_with, Expression};
+pub use dot::to_dot;
+pub use expr::{to_ast, to_infix, Expr};
+pub use functions::UserFunction;
+pub use info::{Info, UserFunctionInfo, VariableInfo};
+pub use latex::to_latex;
+pub use lexer::{Lexeme, LexemeKind, Lexer};
+pub use lint::{lint, lint_with, Lint, LintWarning};
+pub use partial::{try_eval_partial, Expectation, PartialResult};
+pub use simplify::{simplify, simplify_in};
+pub use snapshot::{ContextDiff, Snapshot};
+pub use strict::{RuleLevel, StrictRule, Violation};
+pub use sweep::SweepRange;
+pub use tape::{Tape, TapeEntry};
+pub use trace::{Event, Rule, Step, Timing, Trace};
+pub use uncertain::Uncertain;
+pub use units::{find_unit, Dimension, Unit, UNITS};
+
+/// The arithmetic behind a built-in [`Operator`], matched directly while evaluating.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum BinOp {
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Pow,
+	/// `12.3 ± 0.2`, a value with its uncertainty. Without uncertainties only the value remains.
+	PlusMinus
+}
+impl BinOp {
+	#[inline]
+	pub fn apply(self, lhs: f64, rhs: f64) -> f64 {
+		match self {
+			Self::Add => lhs + rhs,
+			Self::Sub => lhs - rhs,
+			Self::Mul => lhs * rhs,
+			Self::Div => lhs / rhs,
+			Self::Pow => lhs.powf(rhs),
+			Self::PlusMinus => lhs
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Operator {
+	pub symbol: char,
+	pub argc: usize,
+	pub precedence: usize,
+	pub description: &'static str,
+	pub example: &'static str,
+	pub op: BinOp
+}
+impl Operator {
+	/// Sorted by symbol so it can be binary searched.
+	pub const MAP: [(char, Self); 6] = [
+		('*', Self { symbol: '*', argc: 2, precedence: 2, description: "multiplication", example: "6 * 7", op: BinOp::Mul }),
+		('+', Self { symbol: '+', argc: 2, precedence: 1, description: "addition", example: "2 + 3", op: BinOp::Add }),
+		('-', Self { symbol: '-', argc: 2, precedence: 1, description: "subtraction, or negation", example: "10 - 4", op: BinOp::Sub }),
+		('/', Self { symbol: '/', argc: 2, precedence: 2, description: "division", example: "7 / 2", op: BinOp::Div }),
+		('^', Self { symbol: '^', argc: 2, precedence: 3, description: "exponentiation", example: "2 ^ 10", op: BinOp::Pow }),
+		('±', Self { symbol: '±', argc: 2, precedence: 4, description: "uncertainty, see :uncertainty", example: "12.3 ± 0.2", op: BinOp::PlusMinus })
+	];
+
+	pub fn by_char(c: char) -> Option<Self> {
+		Self::MAP.binary_search_by(|(k, _)| k.cmp(&c)).map(|x| Self::MAP[x].1).ok()
+	}
+	/// Operands are passed in source order, `[6, 7]` for `6 * 7`.
+	pub fn resolve(&self, args: &[f64]) -> f64 {
+		self.op.apply(args[0], args[1])
+	}
+	/// Operators of equal precedence are grouped from the left, `8 / 2 / 2` is `(8 / 2) / 2`, except
+	/// for `^`: `2 ^ 3 ^ 2` is `2 ^ (3 ^ 2)`.
+	pub fn associativity(&self) -> &'static str {
+		match self.op {
+			BinOp::Pow => "right",
+			_ => "left"
+		}
+	}
+}
+
+/// Whether trigonometric functions take and return angles in radians or degrees.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum AngleMode {
+	#[default]
+	Radians,
+	Degrees
+}
+
+/// How a function relates to angles, so it can be adapted to the [`AngleMode`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Angle {
+	None,
+	Argument,
+	Result
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Function {
+	pub name: &'static str,
+	pub argc: usize,
+	angle: Angle,
+	pub description: &'static str,
+	pub example: &'static str,
+	resolver: fn(args: &[f64]) -> f64
+}
+impl Function {
+	/// `convert` is compiled into [`Token::Convert`] by the parser, which reads its last two arguments
+	/// as unit names, so its resolver only passes the value through. `round` rounds half up here and
+	/// follows the context's [`Rounding`] when evaluated. The [`calculus::FORMS`] are evaluated whole
+	/// by the parser, so their resolvers are never called.
+	pub const MAP: [(&'static str, Self); 25] = [
+		("abs", Self { name: "abs", argc: 1, angle: Angle::None, description: "absolute value", example: "abs(-3)",
+			resolver: |args| args[0].abs() }),
+		("acos", Self { name: "acos", argc: 1, angle: Angle::Result, description: "inverse cosine", example: "acos(0.5)",
+			resolver: |args| args[0].acos() }),
+		("asin", Self { name: "asin", argc: 1, angle: Angle::Result, description: "inverse sine", example: "asin(1)",
+			resolver: |args| args[0].asin() }),
+		("atan", Self { name: "atan", argc: 1, angle: Angle::Result, description: "inverse tangent", example: "atan(1)",
+			resolver: |args| args[0].atan() }),
+		("ceil", Self { name: "ceil", argc: 1, angle: Angle::None, description: "round up to the next integer", example: "ceil(2.1)",
+			resolver: |args| args[0].ceil() }),
+		("convert", Self { name: "convert", argc: 3, angle: Angle::None, description: "convert between units, see :help units", example: "convert(100, km, mi)",
+			resolver: |args| args[0] }),
+		("cos", Self { name: "cos", argc: 1, angle: Angle::Argument, description: "cosine", example: "cos(pi)",
+			resolver: |args| args[0].cos() }),
+		("diff", Self { name: "diff", argc: 3, angle: Angle::None, description: "derivative of an expression in a variable at a point", example: "diff(sin(x), x, 0)",
+			resolver: |args| args[0] }),
+		("exp", Self { name: "exp", argc: 1, angle: Angle::None, description: "e raised to the given power", example: "exp(1)",
+			resolver: |args| args[0].exp() }),
+		("floor", Self { name: "floor", argc: 1, angle: Angle::None, description: "round down to the previous integer", example: "floor(2.9)",
+			resolver: |args| args[0].floor() }),
+		("frombase", Self { name: "frombase", argc: 2, angle: Angle::None, description: "value of digits in quotes in a base from 2 to 36", example: "frombase(\"ff\", 16)",
+			resolver: |args| args[0] }),
+		("integrate", Self { name: "integrate", argc: 4, angle: Angle::None, description: "integral of an expression in a variable between two bounds, see :tolerance", example: "integrate(x^2, x, 0, 1)",
+			resolver: |args| args[0] }),
+		("ln", Self { name: "ln", argc: 1, angle: Angle::None, description: "natural logarithm", example: "ln(e)",
+			resolver: |args| args[0].ln() }),
+		("log", Self { name: "log", argc: 1, angle: Angle::None, description: "base 10 logarithm", example: "log(1000)",
+			resolver: |args| args[0].log10() }),
+		("max", Self { name: "max", argc: 2, angle: Angle::None, description: "larger of two values", example: "max(3, 7)",
+			resolver: |args| args[0].max(args[1]) }),
+		("min", Self { name: "min", argc: 2, angle: Angle::None, description: "smaller of two values", example: "min(3, 7)",
+			resolver: |args| args[0].min(args[1]) }),
+		("pctchange", Self { name: "pctchange", argc: 2, angle: Angle::None, description: "change from old to new in percent", example: "pctchange(80, 100)",
+			resolver: |args| (args[1] - args[0]) / args[0] * 100.0 }),
+		("pctof", Self { name: "pctof", argc: 2, angle: Angle::None, description: "part as a percentage of whole", example: "pctof(30, 120)",
+			resolver: |args| args[0] / args[1] * 100.0 }),
+		("prod_over", Self { name: "prod_over", argc: 4, angle: Angle::None, description: "product of an expression over an integer variable, see :limit", example: "prod_over(k, 1, 5, k)",
+			resolver: |args| args[0] }),
+		("round", Self { name: "round", argc: 1, angle: Angle::None, description: "round to an integer, see :rounding", example: "round(2.5)",
+			resolver: |args| args[0].round() }),
+		("sin", Self { name: "sin", argc: 1, angle: Angle::Argument, description: "sine", example: "sin(pi/2)",
+			resolver: |args| args[0].sin() }),
+		("sqrt", Self { name: "sqrt", argc: 1, angle: Angle::None, description: "square root", example: "sqrt(16)",
+			resolver: |args| args[0].sqrt() }),
+		("sum_over", Self { name: "sum_over", argc: 4, angle: Angle::None, description: "sum of an expression over an integer variable, see :limit", example: "sum_over(k, 1, 100, k^2)",
+			resolver: |args| args[0] }),
+		("tan", Self { name: "tan", argc: 1, angle: Angle::Argument, description: "tangent", example: "tan(pi/4)",
+			resolver: |args| args[0].tan() }),
+		("tobase", Self { name: "tobase", argc: 2, angle: Angle::None, description: "digits of an integer in a base from 2 to 36, as a whole expression", example: "tobase(255, 16)",
+			resolver: |args| args[0] })
+	];
+
+	pub fn by_name(name: &str) -> Option<Self> {
+		Self::MAP.binary_search_by(|(k, _)| k.cmp(&name)).map(|x| Self::MAP[x].1).ok()
+	}
+	/// Fails for arguments the function is not defined for, which would otherwise give inf or NaN.
+	pub(crate) fn check_domain(&self, args: &[f64]) -> Result<(), EvalError> {
+		match self.name {
+			"pctchange" if args[0] == 0.0 => Err(EvalError::Domain { function: self.name, requirement: "an old value other than 0" }),
+			_ => Ok(())
+		}
+	}
+	/// Whether the result is a percentage, shown with `%` under [`DisplaySettings::percent_helpers`].
+	pub(crate) fn gives_percent(&self) -> bool {
+		matches!(self.name, "pctchange" | "pctof")
+	}
+	/// Arguments are passed in call order. Angles are in radians.
+	pub fn resolve(&self, args: &[f64]) -> f64 {
+		(self.resolver)(args)
+	}
+	pub fn resolve_in(&self, args: &[f64], mode: AngleMode) -> f64 {
+		match (mode, self.angle) {
+			// Converted on the stack for the usual arities, so evaluation does not allocate
+			(AngleMode::Degrees, Angle::Argument) if args.len() <= 4 => {
+				let mut radians = [0.0; 4];
+				for (radian, arg) in radians.iter_mut().zip(args) {
+					*radian = arg.to_radians();
+				}
+				self.resolve(&radians[.. args.len()])
+			}
+			(AngleMode::Degrees, Angle::Argument) => {
+				let args: Vec<f64> = args.iter().map(|arg| arg.to_radians()).collect();
+				self.resolve(&args)
+			}
+			(AngleMode::Degrees, Angle::Result) => self.resolve(args).to_degrees(),
+			_ => self.resolve(args)
+		}
+	}
+}
+
+/// How deeply an expression may nest unless a context says otherwise, see
+/// [`Context::nesting_limit`].
+pub const DEFAULT_NESTING_LIMIT: usize = 1024;
+
+pub const CONSTANTS: [(&str, f64); 3] = [
+	("e", consts::E),
+	("pi", consts::PI),
+	("tau", consts::TAU)
+];
+
+pub fn constant(name: &str) -> Option<f64> {
+	CONSTANTS.iter().find(|(k, _)| *k == name).map(|(_, value)| *value)
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Token {
+	NumericLiteral(f64),
+	Operator(Operator),
+	Function(Function),
+	OpenParen,
+	CloseParen,
+	Comma,
+	/// A unit name in a `convert` call. Recorded with the tokens but never part of the RPN.
+	Unit(&'static Unit),
+	/// `convert(x, from, to)` once both units are known, a unary operation on `x`.
+	Convert(&'static Unit, &'static Unit),
+	/// A variable or `ans` holding a value with its uncertainty, in uncertainty mode.
+	Uncertain(f64, f64),
+	/// A duration in seconds, from a literal such as `90min` or a variable holding one.
+	Duration(f64),
+	/// `x%`, applied to the value before it: `x / 100`.
+	Percent,
+	/// `a + b%` or `a - b%`, which add or subtract b percent of `a`.
+	PercentChange(Operator),
+	/// `[usd]` after a value, which labels it for display and leaves it as it is.
+	Tag(&'static str)
+}
+impl Display for Token {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Token::NumericLiteral(num) => write!(f, "{}", num),
+			Token::Operator(op) => write!(f, "{}", op.symbol),
+			Token::Function(func) => write!(f, "{}", func.name),
+			Token::OpenParen => write!(f, "("),
+			Token::CloseParen => write!(f, ")"),
+			Token::Comma => write!(f, ","),
+			Token::Unit(unit) => write!(f, "{}", unit.name()),
+			Token::Convert(from, to) => write!(f, "convert[{}→{}]", from.name(), to.name()),
+			Token::Uncertain(value, sigma) => write!(f, "{}±{}", value, sigma),
+			Token::Duration(seconds) => write!(f, "{}s", seconds),
+			Token::Percent => write!(f, "%"),
+			Token::PercentChange(op) => write!(f, "{}%", op.symbol),
+			Token::Tag(tag) => write!(f, "[{}]", tag)
+		}
+	}
+}
+
+/// Byte range of the input an error refers to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Span {
+	pub start: usize,
+	pub end: usize
+}
+impl Span {
+	pub fn new(start: usize, end: usize) -> Self {
+		Self { start, end }
+	}
+	pub fn at(pos: usize, c: char) -> Self {
+		Self::new(pos, pos + c.len_utf8())
+	}
+	pub fn len(&self) -> usize {
+		self.end - self.start
+	}
+	pub fn is_empty(&self) -> bool {
+		self.start == self.end
+	}
+	fn shift(&mut self, offset: usize) {
+		self.start += offset;
+		self.end += offset;
+	}
+}
+
+#[derive(Clone, Debug)]
+pub enum EvalError {
+	InvalidCharacter(char, Span),
+	UnexpectedToken(Token),
+	DuplicateDecimal(Span),
+	NumberParseError(Span),
+	MismatchedParenthesis(Span),
+	NotEnoughArguments,
+	IncompleteExpression(char, Span),
+	UnknownIdentifier(String, Span),
+	ExpectedCall(&'static str, Span),
+	ArgumentCount { name: &'static str, expected: usize, found: usize, span: Span },
+	NoResult,
+	/// `ans` was used before anything was evaluated.
+	MissingAnswer(Span),
+	UnknownUnit(String, Span),
+	/// A `convert` argument that should be a unit name is something else or missing.
+	ExpectedUnit(Span),
+	IncompatibleUnits { from: String, to: String, dimensions: (Dimension, Dimension), span: Span },
+	/// `$n` for a result that was never recorded or has been dropped from the tape.
+	MissingTapeEntry(usize, Span),
+	/// `±` outside uncertainty mode.
+	UncertaintyOff(Span),
+	/// A function without a rule for propagating uncertainty was given an uncertain argument.
+	NoPropagationRule(&'static str),
+	/// Durations combined in a way that has no meaning, described as what cannot be done.
+	DurationMismatch(String),
+	/// `solve` without exactly one `=`.
+	ExpectedEquation,
+	/// The unknown of `solve` appears other than linearly.
+	Nonlinear(String),
+	MultipleUnknowns(Vec<String>),
+	/// `solve` of an equation that never holds, such as `x = x + 1`.
+	NoSolution,
+	/// `solve` of an equation that always holds, such as `0 = 0`.
+	InfiniteSolutions,
+	/// The variable argument of `integrate` or `diff` is not a name.
+	ExpectedVariable(Span),
+	/// The expression of `integrate` or `diff`, or a bound, was infinite or NaN at a value of the variable.
+	NotFinite { name: &'static str, var: String, at: f64, span: Span },
+	/// `integrate` was still short of its tolerance after evaluating its expression many times.
+	NoConvergence(&'static str),
+	/// A bound of `sum_over` or `prod_over` that is not an integer.
+	ExpectedInteger(Span),
+	/// `sum_over` or `prod_over` with more terms than the context allows.
+	TooManyTerms { name: &'static str, terms: f64, limit: u64, span: Span },
+	/// A base for `tobase` or `frombase` that is not an integer from 2 to 36.
+	InvalidBase(Span),
+	InvalidDigit { digit: char, base: u32, span: Span },
+	/// The first argument of `frombase` is not digits in quotes.
+	ExpectedDigits(Span),
+	/// `tobase` inside a larger expression, which would need its digits as a number.
+	DigitsInExpression(Span),
+	/// Something other than a value where one belongs, such as the `)` of `(1 + )`.
+	ExpectedOperand { found: String, span: Span },
+	/// A value right after another, such as the `3` of `2 3`.
+	ExpectedOperator { found: String, span: Span },
+	/// An evaluation spent more operations than [`Context::set_budget`] allows.
+	BudgetExceeded { spent: u64, limit: u64 },
+	/// A convenience whose rule is at [`RuleLevel::Error`], at its span if it is about what was written.
+	Strict { rule: StrictRule, span: Option<Span> },
+	/// `a, b = 1, 2, 3`, a different number of names and values.
+	TargetCount { targets: usize, values: usize, span: Span },
+	/// A function given an argument it is not defined for, such as `pctchange(0, 5)`.
+	Domain { function: &'static str, requirement: &'static str },
+	/// An evaluation panicked, with the panic's message. This is a bug, contained by [`contain_panic`].
+	Internal(String),
+	/// A template used without a value for each of these placeholders, or a placeholder outside one.
+	UnfilledPlaceholders(Vec<String>),
+	/// `:use` of a template that was never defined.
+	UnknownTemplate(String),
+	/// Something with no meaning for amounts of money, such as `sqrt`, in money mode.
+	NotMoney(String),
+	/// A lexeme longer than [`Context::token_limit`], such as a literal of thousands of digits,
+	/// noticed after `len` characters.
+	TokenTooLong { span: Span, len: usize, limit: usize },
+	/// Operators, calls and parentheses waiting for their operands, or calculus forms within each
+	/// other, more than [`Context::nesting_limit`] deep.
+	TooDeep { limit: usize, span: Span },
+	/// The tree of an expression is deeper than [`Context::nesting_limit`], as for a sum of
+	/// thousands of terms, too deep to print or transform.
+	RecursionLimit { limit: usize },
+	/// An assignment, or a calculus form binding its variable, to a name that
+	/// [`Context::protect`] keeps from expressions.
+	Protected(String),
+	/// Something [`Context::compile`] cannot turn into a [`Program`] that evaluates like the
+	/// expression, such as a calculus form reading one of its variables.
+	Uncompilable(String),
+	/// An assignment where only expressions are evaluated, as by [`eval_batch_parallel`], which
+	/// shares the context between threads.
+	UnexpectedAssignment(String),
+	/// A binding for [`eval_with_vars`], or a name or parameter for [`Context::register_fn`], that
+	/// is not an identifier.
+	InvalidBinding(String)
+}
+impl EvalError {
+	pub fn is_incomplete(&self) -> bool {
+		matches!(self, Self::IncompleteExpression(..))
+	}
+	/// The stable code of this kind of error, such as `E001`, see [`error_catalog`].
+	pub fn code(&self) -> &'static str {
+		ERROR_CATALOG[self.number() - 1].0
+	}
+	/// The number in [`EvalError::code`], also the negated ffi error code.
+	pub(crate) fn number(&self) -> usize {
+		match self {
+			Self::InvalidCharacter(..) => 1,
+			Self::UnexpectedToken(_) => 2,
+			Self::DuplicateDecimal(_) => 3,
+			Self::NumberParseError(_) => 4,
+			Self::MismatchedParenthesis(_) => 5,
+			Self::NotEnoughArguments => 6,
+			Self::IncompleteExpression(..) => 7,
+			Self::UnknownIdentifier(..) => 8,
+			Self::ExpectedCall(..) => 9,
+			Self::ArgumentCount { .. } => 10,
+			Self::NoResult => 11,
+			Self::MissingAnswer(_) => 12,
+			Self::UnknownUnit(..) => 13,
+			Self::ExpectedUnit(_) => 14,
+			Self::IncompatibleUnits { .. } => 15,
+			Self::MissingTapeEntry(..) => 16,
+			Self::UncertaintyOff(_) => 17,
+			Self::NoPropagationRule(_) => 18,
+			Self::DurationMismatch(_) => 19,
+			Self::ExpectedEquation => 20,
+			Self::Nonlinear(_) => 21,
+			Self::MultipleUnknowns(_) => 22,
+			Self::NoSolution => 23,
+			Self::InfiniteSolutions => 24,
+			Self::ExpectedVariable(_) => 25,
+			Self::NotFinite { .. } => 26,
+			Self::NoConvergence(_) => 27,
+			Self::ExpectedInteger(_) => 28,
+			Self::TooManyTerms { .. } => 29,
+			Self::InvalidBase(_) => 30,
+			Self::InvalidDigit { .. } => 31,
+			Self::ExpectedDigits(_) => 32,
+			Self::DigitsInExpression(_) => 33,
+			Self::ExpectedOperand { .. } => 34,
+			Self::ExpectedOperator { .. } => 35,
+			Self::BudgetExceeded { .. } => 36,
+			Self::Strict { .. } => 37,
+			Self::TargetCount { .. } => 38,
+			Self::Domain { .. } => 39,
+			Self::Internal(_) => 40,
+			Self::UnfilledPlaceholders(_) => 41,
+			Self::UnknownTemplate(_) => 42,
+			Self::NotMoney(_) => 43,
+			Self::TokenTooLong { .. } => 44,
+			Self::TooDeep { .. } => 45,
+			Self::RecursionLimit { .. } => 46,
+			Self::Protected(_) => 47,
+			Self::Uncompilable(_) => 48,
+			Self::UnexpectedAssignment(_) => 49,
+			Self::InvalidBinding(_) => 50
+		}
+	}
+	/// A stable name for the kind of error, for machine-readable output. It matches the variant name
+	/// and will not change when messages are reworded.
+	pub fn kind(&self) -> &'static str {
+		match self {
+			Self::InvalidCharacter(..) => "InvalidCharacter",
+			Self::UnexpectedToken(_) => "UnexpectedToken",
+			Self::DuplicateDecimal(_) => "DuplicateDecimal",
+			Self::NumberParseError(_) => "NumberParseError",
+			Self::MismatchedParenthesis(_) => "MismatchedParenthesis",
+			Self::NotEnoughArguments => "NotEnoughArguments",
+			Self::IncompleteExpression(..) => "IncompleteExpression",
+			Self::UnknownIdentifier(..) => "UnknownIdentifier",
+			Self::ExpectedCall(..) => "ExpectedCall",
+			Self::ArgumentCount { .. } => "ArgumentCount",
+			Self::NoResult => "NoResult",
+			Self::MissingAnswer(_) => "MissingAnswer",
+			Self::UnknownUnit(..) => "UnknownUnit",
+			Self::ExpectedUnit(_) => "ExpectedUnit",
+			Self::IncompatibleUnits { .. } => "IncompatibleUnits",
+			Self::MissingTapeEntry(..) => "MissingTapeEntry",
+			Self::UncertaintyOff(_) => "UncertaintyOff",
+			Self::NoPropagationRule(_) => "NoPropagationRule",
+			Self::DurationMismatch(_) => "DurationMismatch",
+			Self::ExpectedEquation => "ExpectedEquation",
+			Self::Nonlinear(_) => "Nonlinear",
+			Self::MultipleUnknowns(_) => "MultipleUnknowns",
+			Self::NoSolution => "NoSolution",
+			Self::InfiniteSolutions => "InfiniteSolutions",
+			Self::ExpectedVariable(_) => "ExpectedVariable",
+			Self::NotFinite { .. } => "NotFinite",
+			Self::NoConvergence(_) => "NoConvergence",
+			Self::ExpectedInteger(_) => "ExpectedInteger",
+			Self::TooManyTerms { .. } => "TooManyTerms",
+			Self::InvalidBase(_) => "InvalidBase",
+			Self::InvalidDigit { .. } => "InvalidDigit",
+			Self::ExpectedDigits(_) => "ExpectedDigits",
+			Self::DigitsInExpression(_) => "DigitsInExpression",
+			Self::ExpectedOperand { .. } => "ExpectedOperand",
+			Self::ExpectedOperator { .. } => "ExpectedOperator",
+			Self::BudgetExceeded { .. } => "BudgetExceeded",
+			Self::Strict { .. } => "Strict",
+			Self::TargetCount { .. } => "TargetCount",
+			Self::Domain { .. } => "Domain",
+			Self::Internal(_) => "Internal",
+			Self::UnfilledPlaceholders(_) => "UnfilledPlaceholders",
+			Self::UnknownTemplate(_) => "UnknownTemplate",
+			Self::NotMoney(_) => "NotMoney",
+			Self::TokenTooLong { .. } => "TokenTooLong",
+			Self::TooDeep { .. } => "TooDeep",
+			Self::RecursionLimit { .. } => "RecursionLimit",
+			Self::Protected(_) => "Protected",
+			Self::Uncompilable(_) => "Uncompilable",
+			Self::UnexpectedAssignment(_) => "UnexpectedAssignment",
+			Self::InvalidBinding(_) => "InvalidBinding"
+		}
+	}
+	pub fn span(&self) -> Option<Span> {
+		match self {
+			Self::InvalidCharacter(_, span)
+			| Self::DuplicateDecimal(span)
+			| Self::NumberParseError(span)
+			| Self::MismatchedParenthesis(span)
+			| Self::IncompleteExpression(_, span)
+			| Self::UnknownIdentifier(_, span)
+			| Self::ExpectedCall(_, span)
+			| Self::ArgumentCount { span, .. }
+			| Self::MissingAnswer(span)
+			| Self::UnknownUnit(_, span)
+			| Self::ExpectedUnit(span)
+			| Self::IncompatibleUnits { span, .. }
+			| Self::MissingTapeEntry(_, span)
+			| Self::UncertaintyOff(span)
+			| Self::ExpectedVariable(span)
+			| Self::NotFinite { span, .. }
+			| Self::ExpectedInteger(span)
+			| Self::TooManyTerms { span, .. }
+			| Self::TokenTooLong { span, .. }
+			| Self::TooDeep { span, .. }
+			| Self::InvalidBase(span)
+			| Self::InvalidDigit { span, .. }
+			| Self::ExpectedDigits(span)
+			| Self::DigitsInExpression(span)
+			| Self::ExpectedOperand { span, .. }
+			| Self::ExpectedOperator { span, .. }
+			| Self::TargetCount { span, .. } => Some(*span),
+			Self::Strict { span, .. } => *span,
+			Self::UnexpectedToken(_) | Self::NotEnoughArguments | Self::NoResult | Self::NoPropagationRule(_)
+			| Self::DurationMismatch(_) | Self::ExpectedEquation | Self::Nonlinear(_) | Self::MultipleUnknowns(_)
+			| Self::NoSolution | Self::InfiniteSolutions | Self::NoConvergence(_) | Self::BudgetExceeded { .. }
+			| Self::Domain { .. } | Self::Internal(_) | Self::UnfilledPlaceholders(_) | Self::UnknownTemplate(_)
+			| Self::NotMoney(_) | Self::RecursionLimit { .. } | Self::Protected(_) | Self::Uncompilable(_)
+			| Self::UnexpectedAssignment(_) | Self::InvalidBinding(_) => None
+		}
+	}
+	fn span_mut(&mut self) -> Option<&mut Span> {
+		match self {
+			Self::InvalidCharacter(_, span)
+			| Self::DuplicateDecimal(span)
+			| Self::NumberParseError(span)
+			| Self::MismatchedParenthesis(span)
+			| Self::IncompleteExpression(_, span)
+			| Self::UnknownIdentifier(_, span)
+			| Self::ExpectedCall(_, span)
+			| Self::ArgumentCount { span, .. }
+			| Self::MissingAnswer(span)
+			| Self::UnknownUnit(_, span)
+			| Self::ExpectedUnit(span)
+			| Self::IncompatibleUnits { span, .. }
+			| Self::MissingTapeEntry(_, span)
+			| Self::UncertaintyOff(span)
+			| Self::ExpectedVariable(span)
+			| Self::NotFinite { span, .. }
+			| Self::ExpectedInteger(span)
+			| Self::TooManyTerms { span, .. }
+			| Self::TokenTooLong { span, .. }
+			| Self::TooDeep { span, .. }
+			| Self::InvalidBase(span)
+			| Self::InvalidDigit { span, .. }
+			| Self::ExpectedDigits(span)
+			| Self::DigitsInExpression(span)
+			| Self::ExpectedOperand { span, .. }
+			| Self::ExpectedOperator { span, .. }
+			| Self::TargetCount { span, .. } => Some(span),
+			Self::Strict { span, .. } => span.as_mut(),
+			Self::UnexpectedToken(_) | Self::NotEnoughArguments | Self::NoResult | Self::NoPropagationRule(_)
+			| Self::DurationMismatch(_) | Self::ExpectedEquation | Self::Nonlinear(_) | Self::MultipleUnknowns(_)
+			| Self::NoSolution | Self::InfiniteSolutions | Self::NoConvergence(_) | Self::BudgetExceeded { .. }
+			| Self::Domain { .. } | Self::Internal(_) | Self::UnfilledPlaceholders(_) | Self::UnknownTemplate(_)
+			| Self::NotMoney(_) | Self::RecursionLimit { .. } | Self::Protected(_) | Self::Uncompilable(_)
+			| Self::UnexpectedAssignment(_) | Self::InvalidBinding(_) => None
+		}
+	}
+	/// Moves the span of an error raised on a substring so it is relative to the enclosing input.
+	fn offset(mut self, offset: usize) -> Self {
+		if let Some(span) = self.span_mut() {
+			span.shift(offset);
+		}
+		self
+	}
+}
+/// Every error code with what it means, in order. A code keeps its meaning for good: a new kind of
+/// error gets the next code, and the code of one that is removed is not given out again.
+const ERROR_CATALOG: [(&str, &str); 50] = [
+	("E001", "a character that cannot start a token"),
+	("E002", "a token where it cannot be used"),
+	("E003", "a second decimal point in a number"),
+	("E004", "a number that cannot be read"),
+	("E005", "a parenthesis without its partner"),
+	("E006", "an operator or function without enough operands"),
+	("E007", "an expression that ends after an operator"),
+	("E008", "a name that is not a variable, constant or function"),
+	("E009", "a function name without its parenthesis"),
+	("E010", "a call with the wrong number of arguments"),
+	("E011", "an expression without a value"),
+	("E012", "ans before anything was evaluated"),
+	("E013", "a unit name that is not known"),
+	("E014", "something other than a unit name where one belongs"),
+	("E015", "a conversion between units of different dimensions"),
+	("E016", "a tape reference to a result that is not on the tape"),
+	("E017", "an uncertainty outside uncertainty mode"),
+	("E018", "an uncertain argument to a function that cannot propagate it"),
+	("E019", "durations combined in a way that has no meaning"),
+	("E020", "solve without exactly one equation"),
+	("E021", "an equation whose unknown does not appear linearly"),
+	("E022", "an equation with more than one unknown"),
+	("E023", "an equation that never holds"),
+	("E024", "an equation that always holds"),
+	("E025", "something other than a name where a variable belongs"),
+	("E026", "an expression or bound that is infinite or NaN"),
+	("E027", "an integral that does not reach its tolerance"),
+	("E028", "a non-integer where an integer belongs"),
+	("E029", "a sum or product with more terms than the limit"),
+	("E030", "a base that is not an integer from 2 to 36"),
+	("E031", "a digit that does not belong to the base"),
+	("E032", "something other than digits in quotes where they belong"),
+	("E033", "tobase inside a larger expression"),
+	("E034", "something other than a value where one belongs"),
+	("E035", "a value right after another"),
+	("E036", "an evaluation over its operation budget"),
+	("E037", "a convenience that strict mode rejects"),
+	("E038", "an assignment with more or fewer names than values"),
+	("E039", "a function argument outside the values it is defined for"),
+	("E040", "a panic during evaluation, which is a bug"),
+	("E041", "placeholders without a value"),
+	("E042", "a template that is not defined"),
+	("E043", "something that money mode cannot compute"),
+	("E044", "a token longer than the limit"),
+	("E045", "an expression nested too deep"),
+	("E046", "an expression whose tree is too deep to walk"),
+	("E047", "an assignment to a protected name"),
+	("E048", "an expression that cannot be compiled"),
+	("E049", "an assignment where only expressions are evaluated"),
+	("E050", "a variable name that is not an identifier")
+];
+
+/// Every error code with a description, in order, for tools to list them.
+pub fn error_catalog() -> &'static [(&'static str, &'static str)] {
+	&ERROR_CATALOG
+}
+
+impl Display for EvalError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			// Control characters and the like are shown escaped, they would not be visible
+			Self::InvalidCharacter(c, _) => write!(f, "unexpected character '{}'", c.escape_debug()),
+			Self::UnexpectedToken(Token::NumericLiteral(num)) => write!(f, "unexpected number {}", num),
+			Self::UnexpectedToken(Token::Operator(op)) => write!(f, "unexpected operator '{}'", op.symbol),
+			Self::UnexpectedToken(Token::Function(func)) => write!(f, "unexpected function '{}'", func.name),
+			Self::UnexpectedToken(tok) => write!(f, "unexpected '{}'", tok),
+			Self::DuplicateDecimal(_) => write!(f, "duplicate decimal point in number"),
+			Self::NumberParseError(_) => write!(f, "failed to parse number"),
+			Self::MismatchedParenthesis(_) => write!(f, "mismatched parenthesis"),
+			Self::NotEnoughArguments => write!(f, "not enough arguments"),
+			Self::IncompleteExpression(symbol, _) => {
+				write!(f, "incomplete expression: expected a value after '{}'", symbol)
+			}
+			Self::UnknownIdentifier(name, _) => write!(f, "unknown identifier '{}'", name),
+			Self::ExpectedCall(name, _) => write!(f, "expected '(' after function '{}'", name),
+			Self::ArgumentCount { name, expected, found, .. } => {
+				write!(f, "function '{}' expects {} argument(s), got {}", name, expected, found)
+			}
+			Self::NoResult => write!(f, "no result"),
+			Self::MissingAnswer(_) => write!(f, "'ans' has no value yet, evaluate something first"),
+			Self::UnknownUnit(name, _) => write!(f, "unknown unit '{}', see :help units", name),
+			Self::ExpectedUnit(_) => write!(f, "expected a unit name"),
+			Self::IncompatibleUnits { from, to, dimensions, .. } => {
+				write!(f, "cannot convert {} ({}) to {} ({})", from, dimensions.0, to, dimensions.1)
+			}
+			Self::MissingTapeEntry(index, _) => write!(f, "there is no result ${} on the tape", index),
+			Self::UncertaintyOff(_) => write!(f, "'±' needs uncertainty mode, see :uncertainty"),
+			Self::NoPropagationRule(name) => write!(f, "function '{}' cannot propagate an uncertainty", name),
+			Self::DurationMismatch(what) => write!(f, "cannot {}", what),
+			Self::ExpectedEquation => write!(f, "solve needs one equation, such as solve 2*x + 3 = 7"),
+			Self::Nonlinear(name) => write!(f, "cannot solve for '{}', it does not appear linearly", name),
+			Self::MultipleUnknowns(names) => write!(f, "cannot solve for more than one unknown: {}", names.join(", ")),
+			Self::NoSolution => write!(f, "the equation has no solution"),
+			Self::InfiniteSolutions => write!(f, "the equation always holds, it has infinite solutions"),
+			Self::ExpectedVariable(_) => write!(f, "expected the name of a variable"),
+			Self::NotFinite { name, var, at, .. } => write!(f, "'{}' met a value that is not finite at {} = {}", name, var, at),
+			Self::NoConvergence(name) => write!(f, "'{}' did not reach its tolerance, see :tolerance", name),
+			Self::ExpectedInteger(_) => write!(f, "expected an integer"),
+			Self::TooManyTerms { name, terms, limit, .. } => {
+				write!(f, "'{}' has {} terms, more than the limit of {}, see :limit", name, terms, limit)
+			}
+			Self::InvalidBase(_) => write!(f, "a base is an integer from 2 to 36"),
+			Self::InvalidDigit { digit, base, .. } => write!(f, "'{}' is not a digit in base {}", digit, base),
+			Self::ExpectedDigits(_) => write!(f, "expected digits in quotes, such as \"ff\""),
+			Self::DigitsInExpression(_) => write!(f, "'tobase' gives digits rather than a number, so it must be the whole expression"),
+			Self::ExpectedOperand { found, .. } => write!(f, "expected a value, found '{}'", found),
+			Self::ExpectedOperator { found, .. } => write!(f, "expected an operator, found '{}'", found),
+			Self::BudgetExceeded { spent, limit } => {
+				write!(f, "evaluation stopped after {} operations, more than the budget of {}", spent, limit)
+			}
+			Self::Strict { rule, .. } => write!(f, "{} is not allowed in strict mode", rule.description()),
+			Self::TargetCount { targets, values, .. } => {
+				let plural = |count: usize, noun: &str| format!("{} {}{}", count, noun, if count == 1 { "" } else { "s" });
+				write!(f, "{} but {}", plural(*targets, "name"), plural(*values, "value"))
+			}
+			Self::Domain { function, requirement } => write!(f, "{} needs {}", function, requirement),
+			Self::Internal(message) => write!(f, "internal error: {}", message),
+			Self::UnfilledPlaceholders(names) => {
+				let names: Vec<String> = names.iter().map(|name| format!("?{}", name)).collect();
+				write!(f, "no value for {}", names.join(", "))
+			}
+			Self::UnknownTemplate(name) => write!(f, "unknown template '{}', :templates lists them", name),
+			Self::NotMoney(what) => write!(f, "{} cannot be used in money mode", what),
+			Self::TokenTooLong { limit, .. } => write!(f, "a token longer than {} characters", limit),
+			Self::TooDeep { limit, .. } => write!(f, "the expression is nested more than {} deep", limit),
+			Self::RecursionLimit { limit } => write!(f, "the expression's tree is more than {} levels deep", limit),
+			Self::Protected(name) => write!(f, "'{}' is protected and cannot be assigned", name),
+			Self::Uncompilable(what) => write!(f, "{} cannot be compiled", what),
+			Self::UnexpectedAssignment(name) => write!(f, "cannot assign '{}' here, only expressions are evaluated", name),
+			Self::InvalidBinding(name) => write!(f, "'{}' is not a valid variable name", name)
+		}
+	}
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EvalOutcome {
+	Value(f64),
+	Assigned(String, f64),
+	/// `solve` found the value of the unknown, which is not assigned.
+	Solved(String, f64),
+	/// `3 + 4, 3 * 4`, the values in order.
+	Multiple(Vec<f64>),
+	/// `a, b = 3 + 4, 3 * 4`.
+	AssignedMultiple(Vec<(String, f64)>)
+}
+impl EvalOutcome {
+	/// The value, or the last of several, which becomes `ans`.
+	pub fn value(&self) -> f64 {
+		match self {
+			Self::Value(value) | Self::Assigned(_, value) | Self::Solved(_, value) => *value,
+			Self::Multiple(values) => values.last().copied().unwrap_or(f64::NAN),
+			Self::AssignedMultiple(assigned) => assigned.last().map_or(f64::NAN, |(_, value)| *value)
+		}
+	}
+}
+
+/// Why [`Context::iterate`] stopped.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum IterationStop {
+	/// All rounds were evaluated.
+	Completed,
+	/// Two consecutive values were within the tolerance.
+	Converged,
+	/// The last value is infinite or NaN.
+	NonFinite
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Iteration {
+	pub values: Vec<f64>,
+	pub stop: IterationStop
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Context {
+	variables: Arc<BTreeMap<String, f64>>,
+	display: DisplaySettings,
+	angle_mode: AngleMode,
+	answer: Option<f64>,
+	cache: Option<ExpressionCache>,
+	tape: Arc<Tape>,
+	uncertainty: bool,
+	/// Uncertainties of variables assigned in uncertainty mode; a missing entry means exact.
+	sigmas: Arc<BTreeMap<String, f64>>,
+	answer_sigma: Option<f64>,
+	/// Variables holding durations.
+	durations: Arc<BTreeSet<String>>,
+	answer_duration: bool,
+	/// The display tags of variables assigned a tagged value, see [`Token::Tag`].
+	tags: Arc<BTreeMap<String, &'static str>>,
+	answer_tag: Option<&'static str>,
+	/// The result is a percentage from `pctchange` or `pctof`.
+	answer_percent: bool,
+	/// The base of a `tobase` result, which is shown as its digits.
+	answer_base: Option<u32>,
+	/// Values are whole cents, see [`Context::set_money`].
+	money: bool,
+	/// For `integrate`, [`calculus::DEFAULT_TOLERANCE`] when not set.
+	tolerance: Option<f64>,
+	/// For `sum_over` and `prod_over`, [`calculus::DEFAULT_TERM_LIMIT`] when not set.
+	term_limit: Option<u64>,
+	/// For each lexeme, [`lexer::DEFAULT_TOKEN_LIMIT`] when not set.
+	token_limit: Option<usize>,
+	/// [`DEFAULT_NESTING_LIMIT`] when not set.
+	nesting_limit: Option<usize>,
+	/// Tried before the built-in number syntax, see [`Context::set_literal_parser`].
+	literal_parser: Option<lexer::LiteralHook>,
+	/// Operations one evaluation may spend, unlimited when not set.
+	budget: Option<u64>,
+	/// The level of every [`StrictRule`], by its position in [`StrictRule::ALL`], `None` for its
+	/// [`StrictRule::default_level`].
+	rules: [Option<RuleLevel>; 7],
+	/// Conveniences used by the last [`Context::execute`] whose rules only warn.
+	warnings: Vec<Violation>,
+	/// Whether [`Context::lint`] reports anything.
+	lint: bool,
+	/// Whether every [`Lint`] is allowed, by its position in [`Lint::ALL`].
+	allowed_lints: [bool; 4],
+	/// Template bodies by name, see [`Context::define_template`].
+	templates: Arc<BTreeMap<String, String>>,
+	/// The values of the placeholders while a template is used.
+	placeholders: BTreeMap<String, f64>,
+	/// Names expressions cannot assign, see [`Context::protect`].
+	protected: Arc<BTreeSet<String>>,
+	/// Functions defined by the program, see [`Context::register_fn`].
+	functions: Arc<BTreeMap<String, UserFunction>>,
+	/// The constants, functions and calculus forms expressions may name, every one if `None`, see
+	/// [`Context::sandbox`].
+	visible: Option<Arc<BTreeSet<String>>>,
+	/// Unknown names, `ans`, tape references and placeholders are read as 1, for checking how an
+	/// expression parses before anything it reads is defined.
+	probing: bool
+}
+impl Context {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn get_var(&self, name: &str) -> Option<f64> {
+		self.variables.get(name).copied()
+	}
+	pub fn set_var(&mut self, name: &str, value: f64) {
+		self.forget_kind(name);
+		Arc::make_mut(&mut self.variables).insert(name.to_string(), value);
+	}
+	/// Sets `name` and [protects](Context::protect) it, so expressions can read it but only the
+	/// program can change it.
+	pub fn set_var_protected(&mut self, name: &str, value: f64) {
+		self.set_var(name, value);
+		self.protect(name);
+	}
+	/// Keeps expressions from assigning `name`, a variable, constant or function, so they can neither
+	/// shadow nor replace it. [`Context::set_var`] and [`Context::remove_var`] still can. `ans` is
+	/// always protected, a variable of that name would hide the last result.
+	pub fn protect(&mut self, name: &str) {
+		if !self.protected.contains(name) {
+			Arc::make_mut(&mut self.protected).insert(name.to_string());
+		}
+	}
+	pub fn is_protected(&self, name: &str) -> bool {
+		name == "ans" || self.protected.contains(name)
+	}
+	/// Whether expressions may name the constant, function or form `name`.
+	pub(crate) fn is_visible(&self, name: &str) -> bool {
+		self.visible.as_ref().is_none_or(|visible| visible.contains(name))
+	}
+	pub fn remove_var(&mut self, name: &str) -> Option<f64> {
+		self.forget_kind(name);
+		if !self.variables.contains_key(name) {
+			return None;
+		}
+		Arc::make_mut(&mut self.variables).remove(name)
+	}
+	/// Drops the uncertainty, duration and tag of `name`. The maps are shared with snapshots, so they
+	/// are only copied when there is something to drop.
+	fn forget_kind(&mut self, name: &str) {
+		self.invalidate_shadowed(name);
+		if self.sigmas.contains_key(name) {
+			Arc::make_mut(&mut self.sigmas).remove(name);
+		}
+		if self.durations.contains(name) {
+			Arc::make_mut(&mut self.durations).remove(name);
+		}
+		if self.tags.contains_key(name) {
+			Arc::make_mut(&mut self.tags).remove(name);
+		}
+	}
+	/// The uncertainty a variable was assigned with in uncertainty mode, if it has one.
+	pub fn var_uncertainty(&self, name: &str) -> Option<f64> {
+		self.sigmas.get(name).copied()
+	}
+	/// Whether a variable was assigned a duration, which it holds in seconds.
+	pub fn is_duration(&self, name: &str) -> bool {
+		self.durations.contains(name)
+	}
+	/// The display tag of a variable, `usd` after `price = 19.99 [usd]`.
+	pub fn var_tag(&self, name: &str) -> Option<&'static str> {
+		self.tags.get(name).copied()
+	}
+	/// The tag a name carries into an expression, a variable's or for `ans` the last result's.
+	pub(crate) fn name_tag(&self, name: &str) -> Option<&'static str> {
+		match self.var_tag(name) {
+			None if name == "ans" && self.get_var(name).is_none() => self.answer_tag,
+			tag => tag
+		}
+	}
+	/// Cached expressions may have resolved `name` as a constant or function.
+	fn invalidate_shadowed(&mut self, name: &str) {
+		if let Some(cache) = self.cache.as_mut() {
+			if constant(name).is_some() || Function::by_name(name).is_some() {
+				cache.clear();
+			}
+		}
+	}
+	pub fn variables(&self) -> impl Iterator<Item = (&str, f64)> {
+		self.variables.iter().map(|(name, value)| (name.as_str(), *value))
+	}
+
+	/// The result of the last successful [`Context::execute`], available as `ans` in expressions.
+	pub fn answer(&self) -> Option<f64> {
+		self.answer
+	}
+	pub fn tape(&self) -> &Tape {
+		&self.tape
+	}
+	pub fn tape_mut(&mut self) -> &mut Tape {
+		Arc::make_mut(&mut self.tape)
+	}
+	pub fn set_answer(&mut self, value: f64) {
+		self.answer = Some(value);
+	}
+
+	pub fn display(&self) -> &DisplaySettings {
+		&self.display
+	}
+	pub fn display_mut(&mut self) -> &mut DisplaySettings {
+		&mut self.display
+	}
+	pub fn format(&self, value: f64) -> String {
+		if self.money && value.is_finite() {
+			return format_money(value, &self.display.locale);
+		}
+		format_value(value, &self.display)
+	}
+
+	/// How closely `integrate` approximates, relative to the size of the integral.
+	pub fn tolerance(&self) -> f64 {
+		self.tolerance.unwrap_or(calculus::DEFAULT_TOLERANCE)
+	}
+	pub fn set_tolerance(&mut self, tolerance: f64) {
+		self.tolerance = Some(tolerance);
+	}
+	/// How many terms `sum_over` and `prod_over` may have; more is an error.
+	pub fn term_limit(&self) -> u64 {
+		self.term_limit.unwrap_or(calculus::DEFAULT_TERM_LIMIT)
+	}
+	pub fn set_term_limit(&mut self, limit: u64) {
+		self.term_limit = Some(limit);
+	}
+	/// How many characters a single number, name, string or tag may have; more is an error.
+	pub fn token_limit(&self) -> usize {
+		self.token_limit.unwrap_or(lexer::DEFAULT_TOKEN_LIMIT)
+	}
+	pub fn set_token_limit(&mut self, limit: usize) {
+		self.token_limit = Some(limit);
+	}
+	/// How deeply an expression may nest, the one limit that keeps parsing and every walk of an
+	/// expression's tree from running out of stack. It bounds the operators, calls and parentheses
+	/// waiting on the parser's holding stack, the depth of the tree from [`Context::to_ast`], and
+	/// calculus forms within each other, each of which counts as 16 levels.
+	pub fn nesting_limit(&self) -> usize {
+		self.nesting_limit.unwrap_or(DEFAULT_NESTING_LIMIT)
+	}
+	pub fn set_nesting_limit(&mut self, limit: usize) {
+		self.nesting_limit = Some(limit);
+	}
+	/// Lets `parser` read numbers in formats of its own, such as `1'234.56`. Wherever a value may
+	/// start it is given the rest of the input and returns the value and how many bytes it read, or
+	/// `None` to fall back to the built-in syntax; a claim of no bytes counts as `None`. A claim past
+	/// the end of the input or with a value that is not finite fails with
+	/// [`EvalError::NumberParseError`] at the claimed span.
+	pub fn set_literal_parser(&mut self, parser: impl Fn(&str) -> Option<(f64, usize)> + Send + Sync + 'static) {
+		self.literal_parser = Some(lexer::LiteralHook(Arc::new(parser)));
+		// Compiled expressions were lexed without it
+		if let Some(cache) = self.cache.as_mut() {
+			cache.clear();
+		}
+	}
+	pub fn remove_literal_parser(&mut self) {
+		if let (Some(_), Some(cache)) = (self.literal_parser.take(), self.cache.as_mut()) {
+			cache.clear();
+		}
+	}
+	/// How many operations one evaluation may spend, counting every token of every expression it
+	/// reduces, including each term of a sum and each point of an integral.
+	pub fn budget(&self) -> Option<u64> {
+		self.budget
+	}
+	/// Limits evaluations to `budget` operations, beyond which they fail with
+	/// [`EvalError::BudgetExceeded`], or lifts the limit with `None`.
+	pub fn set_budget(&mut self, budget: Option<u64>) {
+		self.budget = budget;
+	}
+
+	pub fn rule(&self, rule: StrictRule) -> RuleLevel {
+		self.rules[rule as usize].unwrap_or(rule.default_level())
+	}
+	pub fn set_rule(&mut self, rule: StrictRule, level: RuleLevel) {
+		self.rules[rule as usize] = Some(level);
+	}
+	/// Sets every rule to [`RuleLevel::Error`], or back to its default level.
+	pub fn set_strict(&mut self, on: bool) {
+		self.rules = [on.then_some(RuleLevel::Error); 7];
+	}
+	/// Whether any rule fails evaluations, as after `--strict`.
+	pub fn is_strict(&self) -> bool {
+		StrictRule::ALL.iter().any(|rule| self.rule(*rule) == RuleLevel::Error)
+	}
+	/// What the last [`Context::execute`] used of the conveniences whose rules warn.
+	pub fn warnings(&self) -> &[Violation] {
+		&self.warnings
+	}
+
+	/// Keeps up to `capacity` compiled expressions for [`Context::execute`], or none with 0.
+	pub fn set_cache_capacity(&mut self, capacity: usize) {
+		self.cache = (capacity > 0).then(|| ExpressionCache::new(capacity));
+	}
+	pub fn cache_stats(&self) -> Option<CacheStats> {
+		self.cache.as_ref().map(ExpressionCache::stats)
+	}
+
+	pub fn angle_mode(&self) -> AngleMode {
+		self.angle_mode
+	}
+	pub fn set_angle_mode(&mut self, mode: AngleMode) {
+		self.angle_mode = mode;
+	}
+	pub fn rounding(&self) -> Rounding {
+		self.display.rounding
+	}
+	/// Sets how results are rounded for display and by `round`.
+	pub fn set_rounding(&mut self, rounding: Rounding) {
+		self.display.rounding = rounding;
+	}
+
+	pub fn uncertainty(&self) -> bool {
+		self.uncertainty
+	}
+	pub fn money(&self) -> bool {
+		self.money
+	}
+	/// In money mode every value is a whole number of cents and is shown with two decimals. Only
+	/// `+`, `-`, `*`, `/`, percentages, `abs`, `min` and `max` can be used; what does not come out
+	/// in whole cents is rounded in the rounding mode and reported by [`StrictRule::MoneyRounding`].
+	pub fn set_money(&mut self, on: bool) {
+		self.money = on;
+	}
+	/// In uncertainty mode `±`, or `+-` written together, gives a value an uncertainty, and results
+	/// carry the uncertainty propagated from their operands. Evaluation is not traced in this mode.
+	pub fn set_uncertainty(&mut self, on: bool) {
+		if on != self.uncertainty {
+			// `+-` lexes differently, so compiled expressions no longer apply
+			if let Some(cache) = self.cache.as_mut() {
+				cache.clear();
+			}
+		}
+		self.uncertainty = on;
+	}
+	/// The propagated uncertainty of the last result, if it was evaluated in uncertainty mode.
+	pub fn answer_uncertainty(&self) -> Option<f64> {
+		self.answer_sigma
+	}
+	pub fn format_uncertain(&self, value: f64, sigma: f64) -> String {
+		format_uncertain(value, sigma, &self.display)
+	}
+	/// Formats the values of [`EvalOutcome::Multiple`] or [`EvalOutcome::AssignedMultiple`] on one
+	/// line, separated as arguments are.
+	pub fn format_list(&self, values: impl IntoIterator<Item = f64>) -> String {
+		let separator = format!("{} ", self.display.locale.argument_separator());
+		values.into_iter().map(|value| self.format(value)).collect::<Vec<_>>().join(&separator)
+	}
+	/// Formats a value just returned by [`Context::execute`], with its uncertainty in uncertainty mode,
+	/// as digits after `tobase` and with `%` after `pctchange` or `pctof` when the display asks for it.
+	pub fn format_result(&self, value: f64) -> String {
+		let text = self.format_untagged(value);
+		match self.answer_tag {
+			Some(tag) => format!("{} [{}]", text, tag),
+			None => text
+		}
+	}
+	fn format_untagged(&self, value: f64) -> String {
+		match (self.answer_base, self.answer_sigma) {
+			(Some(base), _) => to_digits(value, base),
+			_ if self.answer_duration => format_duration(value, &self.display),
+			_ if self.answer_percent && self.display.percent_helpers => format!("{}%", self.format(value)),
+			(_, Some(sigma)) => self.format_uncertain(value, sigma),
+			(_, None) => self.format(value)
+		}
+	}
+	/// Splits `input` into lexemes the way this context parses it.
+	pub(crate) fn lexer<'a>(&self, input: &'a str) -> Lexer<'a> {
+		Lexer::new(input, self.display.locale)
+			.plus_minus(self.uncertainty)
+			.token_limit(self.token_limit())
+			.literal_parser(self.literal_parser.as_ref().map(|hook| Arc::clone(&hook.0)))
+			.functions((!self.functions.is_empty()).then(|| Arc::clone(&self.functions)))
+	}
+
+	/// The lexemes of `expression` as this context splits it, names unresolved and nothing evaluated.
+	pub fn tokenize(&self, expression: &str) -> Result<Vec<Lexeme>, EvalError> {
+		self.lexer(expression).collect()
+	}
+	pub fn to_postfix(&self, expression: &str) -> Result<VecDeque<Token>, EvalError> {
+		to_postfix(expression, self)
+	}
+	pub fn to_ast(&self, expression: &str) -> Result<Expr, EvalError> {
+		to_ast(expression, self)
+	}
+	/// Evaluates `expression` without changing the context. Money mode applies as in
+	/// [`Context::execute`], and in uncertainty mode this is the central value.
+	pub fn eval(&self, expression: &str) -> Result<f64, EvalError> {
+		self.evaluate(expression, &Budget::new(self.budget), true, None, &mut Vec::new()).map(|(value, _)| value)
+	}
+	/// Evaluates part of a larger evaluation, spending from its budget. A part is reduced as plain
+	/// numbers whatever the mode, the mode applies to the whole.
+	pub(crate) fn eval_within(&self, expression: &str, budget: &Budget) -> Result<f64, EvalError> {
+		self.evaluate(expression, budget, false, None, &mut Vec::new()).map(|(value, _)| value)
+	}
+	/// Like [`Context::eval`], recording the tokens, what the parser did with each of them, the RPN
+	/// and every reduction step into `trace`, replacing what it held.
+	pub fn eval_traced(&self, expression: &str, trace: &mut Trace) -> Result<f64, EvalError> {
+		self.evaluate(expression, &Budget::new(self.budget), true, Some(trace), &mut Vec::new()).map(|(value, _)| value)
+	}
+	/// What [`Context::eval`] and its variants share: parses, checks and reduces `expression`,
+	/// recording into `trace` when given and collecting the warnings into `warnings`. Also tells
+	/// whether the value is a percentage. Only a `whole` expression is reduced in the mode.
+	pub(crate) fn evaluate(
+		&self, expression: &str, budget: &Budget, whole: bool, mut trace: Option<&mut Trace>, warnings: &mut Vec<Violation>
+	) -> Result<(f64, bool), EvalError> {
+		if let Some(trace) = trace.as_deref_mut() {
+			*trace = Trace::default();
+		}
+		let (tokens, events) = match trace.as_deref_mut() {
+			Some(trace) => (Some(&mut trace.tokens), Some(&mut trace.events)),
+			None => (None, None)
+		};
+		let rpn = shunting_yard(expression, self, tokens, events, budget)?;
+		if let Some(trace) = trace.as_deref_mut() {
+			trace.rpn = rpn.iter().cloned().collect();
+		}
+		duration::check(&rpn)?;
+		let percent = matches!(rpn.back(), Some(Token::Function(func)) if func.gives_percent());
+		strict::check_input(expression, 0, &rpn, self, warnings)?;
+		let steps = trace.map(|trace| &mut trace.steps);
+		let value = match whole {
+			true => self.reduce_in_mode(rpn, steps, budget, warnings)?.0,
+			false => reduce(rpn, steps, self.angle_mode, self.display.rounding, budget)?
+		};
+		strict::check_value(value, self, warnings)?;
+		Ok((value, percent))
+	}
+	/// Reduces `rpn` in the mode of the context: in cents in money mode, with the standard deviation
+	/// in uncertainty mode, and otherwise recording `steps` when given.
+	fn reduce_in_mode(
+		&self, rpn: VecDeque<Token>, steps: Option<&mut Vec<Step>>, budget: &Budget, violations: &mut Vec<Violation>
+	) -> Result<(f64, Option<f64>), EvalError> {
+		if self.money {
+			money::reduce(rpn, self, budget, violations).map(|value| (value, None))
+		} else if self.uncertainty {
+			uncertain::reduce(rpn, self.angle_mode, self.display.rounding, budget).map(|result| (result.value, Some(result.sigma)))
+		} else {
+			reduce(rpn, steps, self.angle_mode, self.display.rounding, budget).map(|value| (value, None))
+		}
+	}
+	/// Evaluates a statement, which is either an expression or an assignment `name = expression`.
+	/// Error spans are relative to the whole statement.
+	pub fn execute(&mut self, statement: &str) -> Result<EvalOutcome, EvalError> {
+		self.execute_instrumented(statement, None, None)
+	}
+	/// Executes `statement` up to `rounds` times, each round seeing the previous result as `ans`.
+	/// Stops early when a value is not finite or, given a `tolerance`, when a value differs from the
+	/// previous one by at most that much.
+	pub fn iterate(&mut self, statement: &str, rounds: usize, tolerance: Option<f64>) -> Result<Iteration, EvalError> {
+		let mut values = Vec::with_capacity(rounds);
+		for _ in 0 .. rounds {
+			let previous = self.answer;
+			let value = self.execute(statement)?.value();
+			values.push(value);
+			if !value.is_finite() {
+				return Ok(Iteration { values, stop: IterationStop::NonFinite });
+			}
+			if let (Some(tolerance), Some(previous)) = (tolerance, previous) {
+				if (value - previous).abs() <= tolerance {
+					return Ok(Iteration { values, stop: IterationStop::Converged });
+				}
+			}
+		}
+		Ok(Iteration { values, stop: IterationStop::Completed })
+	}
+	/// Like [`Context::execute`], additionally recording the tokens, the RPN and every reduction
+	/// step into `trace`, replacing what it held. Whatever was recorded before an error is kept.
+	pub fn execute_traced(&mut self, statement: &str, trace: &mut Trace) -> Result<EvalOutcome, EvalError> {
+		self.execute_instrumented(statement, Some(trace), None)
+	}
+	/// Like [`Context::execute`], additionally measuring the parse and evaluation phases into `timing`.
+	pub fn execute_timed(&mut self, statement: &str, timing: &mut Timing) -> Result<EvalOutcome, EvalError> {
+		self.execute_instrumented(statement, None, Some(timing))
+	}
+	/// Combines [`Context::execute_traced`] and [`Context::execute_timed`]; either may be left out.
+	pub fn execute_instrumented(
+		&mut self, statement: &str, mut trace: Option<&mut Trace>, mut timing: Option<&mut Timing>
+	) -> Result<EvalOutcome, EvalError> {
+		self.warnings.clear();
+		if let Some(trace) = trace.as_deref_mut() {
+			*trace = Trace::default();
+		}
+		if let Some((equation, offset)) = solve::equation(statement) {
+			return self.solve(equation).map_err(|err| err.offset(offset));
+		}
+		if let Some(outcome) = self.execute_list(statement) {
+			return outcome;
+		}
+		let (name, expression) = split_assignment(statement);
+		if let Some(name) = name.filter(|name| self.is_protected(name)) {
+			return Err(EvalError::Protected(name.to_string()));
+		}
+		let offset = statement.len() - expression.len();
+		let budget = Budget::new(self.budget);
+		let start = Stopwatch::start(timing.is_some());
+		let reads = self.cache.as_ref().and_then(|_| self.cache_reads(expression));
+		// A trace needs the tokens, so it always parses
+		let cached = match (self.cache.as_mut(), &reads) {
+			(Some(cache), Some(reads)) if trace.is_none() => cache.get(expression, self.display.locale, reads),
+			_ => None
+		};
+		let rpn = match cached {
+			Some((rpn, count)) => {
+				if let Some(timing) = timing.as_deref_mut() {
+					timing.parse = start.elapsed();
+					timing.tokens = count;
+				}
+				rpn
+			}
+			None => {
+				let cacheable = reads.is_some();
+				let mut scratch = Vec::new();
+				let (tokens, events) = match trace.as_deref_mut() {
+					Some(trace) => (Some(&mut trace.tokens), Some(&mut trace.events)),
+					None if timing.is_some() || cacheable => (Some(&mut scratch), None),
+					None => (None, None)
+				};
+				let parsed = shunting_yard(expression, self, tokens, events, &budget);
+				let count = trace.as_deref().map_or(scratch.len(), |trace| trace.tokens.len());
+				if let Some(timing) = timing.as_deref_mut() {
+					timing.parse = start.elapsed();
+					timing.tokens = count;
+				}
+				let rpn = parsed.map_err(|err| err.offset(offset))?;
+				if let (Some(cache), Some(reads)) = (self.cache.as_mut(), reads) {
+					cache.insert(expression, &rpn, count, reads);
+				}
+				rpn
+			}
+		};
+		if let Some(trace) = trace.as_deref_mut() {
+			trace.rpn = rpn.iter().cloned().collect();
+		}
+		if let Some(timing) = timing.as_deref_mut() {
+			timing.rpn_len = rpn.len();
+		}
+		let duration = duration::check(&rpn)?;
+		let percent = matches!(rpn.back(), Some(Token::Function(func)) if func.gives_percent());
+		let mut violations = Vec::new();
+		strict::check_input(expression, offset, &rpn, self, &mut violations)?;
+		let tag = tag::check(&rpn, self, &mut violations)?;
+		let start = Stopwatch::start(timing.is_some());
+		let reduced = self.reduce_in_mode(rpn, trace.map(|trace| &mut trace.steps), &budget, &mut violations);
+		if let Some(timing) = timing {
+			timing.eval = start.elapsed();
+		}
+		let (value, sigma) = reduced?;
+		strict::check_value(value, self, &mut violations)?;
+		self.warnings = violations;
+		let base = base::result_base(expression, self, &budget).map_err(|err| err.offset(offset))?;
+		self.answer = Some(value);
+		self.answer_sigma = sigma;
+		self.answer_duration = duration;
+		self.answer_tag = tag;
+		self.answer_percent = percent;
+		self.answer_base = base;
+		self.tape_mut().push(statement.trim(), value);
+		match name {
+			Some(name) => {
+				self.assign(name, value, sigma, duration, tag);
+				Ok(EvalOutcome::Assigned(name.to_string(), value))
+			}
+			None => Ok(EvalOutcome::Value(value))
+		}
+	}
+	fn assign(&mut self, name: &str, value: f64, sigma: Option<f64>, duration: bool, tag: Option<&'static str>) {
+		self.set_var(name, value);
+		if let Some(sigma) = sigma.filter(|sigma| *sigma != 0.0) {
+			Arc::make_mut(&mut self.sigmas).insert(name.to_string(), sigma);
+		}
+		if duration {
+			Arc::make_mut(&mut self.durations).insert(name.to_string());
+		}
+		if let Some(tag) = tag {
+			Arc::make_mut(&mut self.tags).insert(name.to_string(), tag);
+		}
+	}
+
+	/// `a, b = 3 + 4, 3 * 4` and `3 + 4, 3 * 4`, or `None` for a statement with one target and one
+	/// value. Every value is evaluated before any is assigned, so `a, b = b, a` swaps.
+	fn execute_list(&mut self, statement: &str) -> Option<Result<EvalOutcome, EvalError>> {
+		let separator = self.display.locale.argument_separator();
+		let targets: Option<Vec<&str>> = statement.split_once('=').and_then(|(targets, _)| {
+			let targets: Vec<&str> = targets.split(separator).map(str::trim).collect();
+			targets.iter().all(|target| is_identifier(target)).then_some(targets)
+		});
+		let offset = match targets {
+			Some(_) => statement.find('=').unwrap_or(0) + 1,
+			None => 0
+		};
+		let parts = split_list(&statement[offset ..], separator);
+		if parts.len() == 1 && targets.as_ref().is_none_or(|targets| targets.len() == 1) {
+			return None;
+		}
+		Some(self.evaluate_list(statement, targets, parts.iter().map(|(start, end)| (start + offset, end + offset)).collect()))
+	}
+	fn evaluate_list(&mut self, statement: &str, targets: Option<Vec<&str>>, parts: Vec<(usize, usize)>) -> Result<EvalOutcome, EvalError> {
+		if let Some(name) = targets.iter().flatten().find(|name| self.is_protected(name)) {
+			return Err(EvalError::Protected(name.to_string()));
+		}
+		if let Some(targets) = targets.as_ref().filter(|targets| targets.len() != parts.len()) {
+			let span = Span::new(0, statement.len());
+			return Err(EvalError::TargetCount { targets: targets.len(), values: parts.len(), span });
+		}
+		let mut results = Vec::with_capacity(parts.len());
+		let mut warnings = Vec::new();
+		for (start, end) in parts {
+			let part = &statement[start .. end];
+			if let Some(i) = part.find('=') {
+				return Err(EvalError::InvalidCharacter('=', Span::at(start + i, '=')));
+			}
+			let value = self.execute(part).map_err(|err| err.offset(start))?.value();
+			results.push((value, self.answer_sigma, self.answer_duration, self.answer_tag));
+			warnings.append(&mut self.warnings);
+		}
+		self.warnings = warnings;
+		let Some(targets) = targets else {
+			return Ok(EvalOutcome::Multiple(results.into_iter().map(|(value, ..)| value).collect()));
+		};
+		let mut assigned = Vec::with_capacity(targets.len());
+		for (name, (value, sigma, duration, tag)) in targets.into_iter().zip(results) {
+			self.assign(name, value, sigma, duration, tag);
+			assigned.push((name.to_string(), value));
+		}
+		Ok(EvalOutcome::AssignedMultiple(assigned))
+	}
+
+	/// The variables and `ans` that `expression` reads, with the tokens they resolve to now, for
+	/// keying its cached RPN, or `None` if it uses the tape, a form or a placeholder, which are not
+	/// cached.
+	fn cache_reads(&self, expression: &str) -> Option<Vec<(String, Token)>> {
+		let mut reads = Vec::new();
+		for lexeme in self.lexer(expression) {
+			match lexeme {
+				Ok(Lexeme { kind: LexemeKind::TapeRef(_) | LexemeKind::Form | LexemeKind::Placeholder, .. }) => return None,
+				Ok(Lexeme { kind: LexemeKind::Identifier, span }) => {
+					let name = &expression[span.start .. span.end];
+					if name != "ans" && !self.variables.contains_key(name) {
+						continue;
+					}
+					if let Ok(tok) = resolve_identifier(name, self, span) {
+						reads.push((name.to_string(), tok));
+					}
+					if let Some(tag) = self.name_tag(name) {
+						reads.push((name.to_string(), Token::Tag(tag)));
+					}
+				}
+				_ => {}
+			}
+		}
+		Some(reads)
+	}
+}
+
+fn is_identifier_char(c: char) -> bool {
+	c.is_alphanumeric() || c == '_'
+}
+
+/// Whether `name` can name a variable or template.
+pub fn is_identifier(name: &str) -> bool {
+	let mut chars = name.chars();
+	match chars.next() {
+		Some(c) if c.is_alphabetic() || c == '_' => chars.all(is_identifier_char) && name != "of",
+		_ => false
+	}
+}
+
+/// Whether `input` looks like the start of a longer expression: a parenthesis is still open, or the
+/// last character is an operator, a comma or the `=` of an assignment that needs something after it.
+pub fn is_incomplete(input: &str) -> bool {
+	let mut depth = 0isize;
+	for c in input.chars() {
+		match c {
+			'(' => depth += 1,
+			')' => depth -= 1,
+			_ => {}
+		}
+	}
+	match input.trim_end().chars().last() {
+		_ if depth > 0 => true,
+		Some(c) => c == ',' || c == '=' || Operator::by_char(c).is_some(),
+		None => false
+	}
+}
+
+/// Byte ranges of the parts of `text` between separators outside parentheses and quotes, always at
+/// least one.
+pub(crate) fn split_list(text: &str, separator: char) -> Vec<(usize, usize)> {
+	let mut parts = Vec::new();
+	let (mut depth, mut quoted, mut start) = (0, false, 0);
+	for (i, c) in text.char_indices() {
+		match c {
+			'"' => quoted = !quoted,
+			_ if quoted => {}
+			'(' => depth += 1,
+			')' => depth -= 1,
+			_ if c == separator && depth == 0 => {
+				parts.push((start, i));
+				start = i + c.len_utf8();
+			}
+			_ => {}
+		}
+	}
+	parts.push((start, text.len()));
+	parts
+}
+
+pub fn split_assignment(statement: &str) -> (Option<&str>, &str) {
+	if let Some((target, expression)) = statement.split_once('=') {
+		let target = target.trim();
+		if is_identifier(target) {
+			return (Some(target), expression);
+		}
+	}
+	(None, statement)
+}
+
+/// The name `statement` assigns, the first one of a list assignment.
+pub fn assigned_name<'a>(statement: &'a str, context: &Context) -> Option<&'a str> {
+	if let (Some(name), _) = split_assignment(statement) {
+		return Some(name);
+	}
+	let (targets, _) = statement.split_once('=')?;
+	let mut targets = targets.split(context.display().locale.argument_separator()).map(str::trim);
+	let first = targets.next()?;
+	(is_identifier(first) && targets.all(is_identifier)).then_some(first)
+}
+
+fn resolve_identifier(name: &str, context: &Context, span: Span) -> Result<Token, EvalError> {
+	let sigma = |sigma: Option<f64>| sigma.filter(|_| context.uncertainty).filter(|sigma| *sigma != 0.0);
+	if let Some(value) = context.get_var(name) {
+		return Ok(match sigma(context.var_uncertainty(name)) {
+			_ if context.is_duration(name) => Token::Duration(value),
+			Some(sigma) => Token::Uncertain(value, sigma),
+			None => Token::NumericLiteral(value)
+		});
+	}
+	if name == "ans" {
+		let value = context.answer.or(context.probing.then_some(1.0)).ok_or(EvalError::MissingAnswer(span))?;
+		return Ok(match sigma(context.answer_sigma) {
+			_ if context.answer_duration => Token::Duration(value),
+			Some(sigma) => Token::Uncertain(value, sigma),
+			None => Token::NumericLiteral(value)
+		});
+	}
+	if let Some(value) = constant(name).filter(|_| context.is_visible(name)) {
+		return Ok(Token::NumericLiteral(value));
+	}
+	match Function::by_name(name).filter(|_| context.is_visible(name)) {
+		Some(function) => Ok(Token::Function(function)),
+		None if context.probing => Ok(Token::NumericLiteral(1.0)),
+		None => Err(EvalError::UnknownIdentifier(name.to_string(), span))
+	}
+}
+
+pub fn to_postfix(expression: &str, context: &Context) -> Result<VecDeque<Token>, EvalError> {
+	shunting_yard(expression, context, None, None, &Budget::new(context.budget))
+}
+
+/// An open parenthesis and, for calls, where the function name starts and how many arguments have
+/// been seen so far. `convert` calls also collect their unit names.
+struct Paren<'a> {
+	pos: usize,
+	call: Option<(usize, usize)>,
+	units: Option<Vec<(&'static Unit, &'a str)>>
+}
+
+/// What the parser accepts next. Signs, `%` and values out of place are all decided from it.
+#[derive(Copy, Clone)]
+enum Expect {
+	/// A value, a sign, a function or `(`: at the start and after an operator, `(` or separator.
+	/// Holds the operator just read, which is incomplete if the input ends here.
+	Operand(Option<char>),
+	/// An operator, `%`, a separator or `)`, after a value.
+	Operator,
+	/// The `(` of a call, after a function name.
+	Call(Function)
+}
+
+fn shunting_yard(
+	expression: &str, context: &Context, mut tokens: Option<&mut Vec<Token>>, mut events: Option<&mut Vec<Event>>,
+	budget: &Budget
+) -> Result<VecDeque<Token>, EvalError> {
+	let mut record = |tok: Token| {
+		if let Some(tokens) = tokens.as_deref_mut() {
+			tokens.push(tok);
+		}
+	};
+	let mut event = |event: Event| {
+		if let Some(events) = events.as_deref_mut() {
+			events.push(event);
+		}
+	};
+	let mut holding = VecDeque::new();
+	// Only so much is reserved up front, a long input may well be refused by a limit before long
+	let mut output = VecDeque::with_capacity((expression.len() / 2 + 1).min(256));
+	let mut expect = Expect::Operand(None);
+	let mut last_span = Span::new(0, 0);
+	let mut parens: Vec<Paren> = Vec::new();
+	// Whether the last lexeme opened a call, which may then close right away as in `sin()`
+	let mut opened_call = false;
+
+	let mut percent = false;
+	for lexeme in context.lexer(expression) {
+		let Lexeme { kind, span } = lexeme?;
+		// Every lexeme pushes at most one token, so this bounds `(((` and `-(-(` alike
+		if holding.len() >= context.nesting_limit() {
+			return Err(EvalError::TooDeep { limit: context.nesting_limit(), span });
+		}
+		// `20% of 150` is `20% * 150`
+		let kind = match kind {
+			LexemeKind::Identifier if &expression[span.start .. span.end] == "of" => LexemeKind::Operator('*'),
+			_ => kind
+		};
+		let i = span.start;
+		if let Expect::Call(func) = expect {
+			if kind != LexemeKind::OpenParen {
+				return Err(EvalError::ExpectedCall(func.name, last_span));
+			}
+		}
+		let empty_call = core::mem::replace(&mut opened_call, false);
+		// The second and third arguments of `convert` are unit names
+		if let Some(Paren { call: Some((_, count)), units: Some(units), .. }) = parens.last_mut() {
+			if *count >= 2 {
+				if matches!(kind, LexemeKind::Separator | LexemeKind::CloseParen) {
+					if units.len() + 1 != *count {
+						return Err(EvalError::ExpectedUnit(span));
+					}
+				} else {
+					let name = match kind {
+						LexemeKind::Identifier => &expression[span.start .. span.end],
+						LexemeKind::String => &expression[span.start + 1 .. span.end - 1],
+						_ => return Err(EvalError::ExpectedUnit(span))
+					};
+					let unit = find_unit(name).ok_or_else(|| EvalError::UnknownUnit(name.to_string(), span))?;
+					if units.len() + 2 != *count {
+						return Err(EvalError::UnexpectedToken(Token::Unit(unit)));
+					}
+					units.push((unit, name));
+					record(Token::Unit(unit));
+					expect = Expect::Operator;
+					continue;
+				}
+			}
+		}
+
+		let operand = matches!(expect, Expect::Operand(_));
+		let found = || expression[span.start .. span.end].to_string();
+		match kind {
+			LexemeKind::String => return Err(EvalError::InvalidCharacter('"', Span::at(span.start, '"'))),
+			// A value right after another, `2 3` or `2 (3)`
+			LexemeKind::Number(_) | LexemeKind::Duration(_) | LexemeKind::Form | LexemeKind::TapeRef(_)
+			| LexemeKind::Identifier | LexemeKind::Placeholder | LexemeKind::OpenParen if matches!(expect, Expect::Operator) => {
+				return Err(EvalError::ExpectedOperator { found: found(), span });
+			}
+			LexemeKind::Percent | LexemeKind::Tag | LexemeKind::Separator if operand => return Err(EvalError::ExpectedOperand { found: found(), span }),
+			LexemeKind::CloseParen if operand && !empty_call => return Err(EvalError::ExpectedOperand { found: found(), span }),
+			LexemeKind::Number(value) => {
+				last_span = span;
+				output.push_back(Token::NumericLiteral(value));
+				record(Token::NumericLiteral(value));
+				event(Event::Output(Token::NumericLiteral(value)));
+				expect = Expect::Operator;
+			}
+			LexemeKind::Percent => {
+				// Binds tighter than anything, so it applies to the operand just completed
+				percent = true;
+				output.push_back(Token::Percent);
+				record(Token::Percent);
+				event(Event::Output(Token::Percent));
+				last_span = span;
+			}
+			LexemeKind::Tag => {
+				// Like `%`, it labels the operand just completed
+				let tok = Token::Tag(tag::intern(expression[span.start + 1 .. span.end - 1].trim()));
+				output.push_back(tok);
+				record(tok);
+				event(Event::Output(tok));
+				last_span = span;
+			}
+			LexemeKind::Duration(seconds) => {
+				last_span = span;
+				output.push_back(Token::Duration(seconds));
+				record(Token::Duration(seconds));
+				event(Event::Output(Token::Duration(seconds)));
+				expect = Expect::Operator;
+			}
+			LexemeKind::Form => {
+				if context.money {
+					let name = &expression[span.start .. span.end];
+					return Err(EvalError::NotMoney(format!("'{}'", calculus::form_name(name))));
+				}
+				if expression[span.start ..].starts_with("tobase") && span.len() != expression.trim().len() {
+					return Err(EvalError::DigitsInExpression(span));
+				}
+				last_span = span;
+				let call = &expression[span.start .. span.end];
+				let value = calculus::evaluate(call, context, budget).map_err(|err| err.offset(span.start))?;
+				output.push_back(Token::NumericLiteral(value));
+				record(Token::NumericLiteral(value));
+				event(Event::Output(Token::NumericLiteral(value)));
+				expect = Expect::Operator;
+			}
+			LexemeKind::TapeRef(index) => {
+				last_span = span;
+				let value = context.tape.get(index).or(context.probing.then_some(1.0)).ok_or(EvalError::MissingTapeEntry(index, span))?;
+				output.push_back(Token::NumericLiteral(value));
+				record(Token::NumericLiteral(value));
+				event(Event::Output(Token::NumericLiteral(value)));
+				expect = Expect::Operator;
+			}
+			LexemeKind::Placeholder => {
+				last_span = span;
+				let name = &expression[span.start + 1 .. span.end];
+				let value = context.placeholders.get(name).copied().or(context.probing.then_some(1.0))
+					.ok_or_else(|| EvalError::UnfilledPlaceholders(vec![name.to_string()]))?;
+				output.push_back(Token::NumericLiteral(value));
+				record(Token::NumericLiteral(value));
+				event(Event::Output(Token::NumericLiteral(value)));
+				expect = Expect::Operator;
+			}
+			LexemeKind::Identifier => {
+				last_span = span;
+				let tok = resolve_identifier(&expression[span.start .. span.end], context, span)?;
+				match tok {
+					Token::Function(func) => {
+						holding.push_front(tok);
+						event(Event::Push(tok));
+						expect = Expect::Call(func);
+					}
+					_ => {
+						output.push_back(tok);
+						event(Event::Output(tok));
+						if let Some(tag) = context.name_tag(&expression[span.start .. span.end]) {
+							output.push_back(Token::Tag(tag));
+						}
+						expect = Expect::Operator;
+					}
+				}
+				record(tok);
+			}
+			LexemeKind::OpenParen => {
+				match expect {
+					Expect::Call(func) => {
+						parens.push(Paren { pos: i, call: Some((last_span.start, 1)), units: (func.name == "convert").then(Vec::new) });
+						opened_call = true;
+					}
+					_ => parens.push(Paren { pos: i, call: None, units: None })
+				}
+				holding.push_front(Token::OpenParen);
+				record(Token::OpenParen);
+				event(Event::Push(Token::OpenParen));
+				expect = Expect::Operand(None);
+			}
+			LexemeKind::Separator => {
+				match parens.last_mut() {
+					Some(Paren { call: Some((_, count)), .. }) => *count += 1,
+					_ => {
+						let c = context.display().locale.argument_separator();
+						return Err(EvalError::InvalidCharacter(c, span));
+					}
+				}
+				while let Some(Token::Operator(_)) = holding.front() {
+					let tok = holding.pop_front().unwrap();
+					event(Event::Pop(tok, Rule::Separator));
+					output.push_back(tok);
+				}
+				record(Token::Comma);
+				expect = Expect::Operand(None);
+			}
+			LexemeKind::CloseParen => {
+				while !holding.is_empty() {
+					if let Some(Token::OpenParen) = holding.front() {
+						break;
+					}
+					let tok = holding.pop_front().unwrap();
+					event(Event::Pop(tok, Rule::CloseParen));
+					output.push_back(tok);
+				}
+				if holding.is_empty() {
+					return Err(EvalError::MismatchedParenthesis(span));
+				}
+				record(Token::CloseParen);
+				expect = Expect::Operator;
+				if let Some(Token::OpenParen) = holding.front().cloned() {
+					holding.pop_front();
+					event(Event::Pop(Token::OpenParen, Rule::CloseParen));
+				}
+				if let Some(Paren { call: Some((start, count)), units, .. }) = parens.pop() {
+					if let Some(Token::Function(func)) = holding.pop_front() {
+						let found = if empty_call { 0 } else { count };
+						let span = Span::new(start, span.end);
+						if found != func.argc {
+							return Err(EvalError::ArgumentCount { name: func.name, expected: func.argc, found, span });
+						}
+						let tok = match units.as_deref() {
+							Some(&[(from, from_name), (to, to_name)]) => {
+								if from.dimension != to.dimension {
+									return Err(EvalError::IncompatibleUnits {
+										from: from_name.to_string(), to: to_name.to_string(), dimensions: (from.dimension, to.dimension), span
+									});
+								}
+								Token::Convert(from, to)
+							}
+							_ => Token::Function(func)
+						};
+						event(Event::Pop(tok, Rule::CloseParen));
+						output.push_back(tok);
+					}
+				}
+			}
+			LexemeKind::Operator(c) => {
+				let Some(mut op) = Operator::by_char(c) else {
+					return Err(EvalError::InvalidCharacter(c, span));
+				};
+				if op.op == BinOp::PlusMinus && !context.uncertainty {
+					return Err(EvalError::UncertaintyOff(span));
+				}
+				// Where a value belongs, only a sign can stand
+				if operand {
+					if op.symbol != '+' && op.symbol != '-' {
+						return Err(EvalError::ExpectedOperand { found: found(), span });
+					}
+					op.argc = 1;
+					op.precedence = 255;
+				}
+				while let Some(Token::Operator(op_prev)) = holding.front() {
+					// `^` also binds tighter than a sign before its base, `-2 ^ 2` is `-(2 ^ 2)`
+					let pops = match op.op {
+						// A sign is a prefix, it completes nothing before it: `--3` is `-(-3)`
+						_ if op.argc == 1 => false,
+						BinOp::Pow => op_prev.argc == 2 && op_prev.precedence > op.precedence,
+						_ => op_prev.precedence >= op.precedence
+					};
+					if pops {
+						let tok = holding.pop_front().unwrap();
+						event(Event::Pop(tok, Rule::Precedence(op)));
+						output.push_back(tok);
+					} else {
+						break;
+					}
+				}
+				holding.push_front(Token::Operator(op));
+				record(Token::Operator(op));
+				event(Event::Push(Token::Operator(op)));
+				expect = Expect::Operand(Some(op.symbol));
+				last_span = span;
+			}
+		}
+	}
+
+	match expect {
+		Expect::Operand(Some(symbol)) => return Err(EvalError::IncompleteExpression(symbol, last_span)),
+		Expect::Call(func) => return Err(EvalError::ExpectedCall(func.name, last_span)),
+		_ => {}
+	}
+	if let Some(paren) = parens.last() {
+		return Err(EvalError::MismatchedParenthesis(Span::new(paren.pos, paren.pos + 1)));
+	}
+
+	while !holding.is_empty() {
+		if let Some(tok) = holding.pop_front() {
+			event(Event::Pop(tok, Rule::End));
+			output.push_back(tok);
+		}
+	}
+	if percent {
+		mark_percent_changes(&mut output);
+	}
+
+	Ok(output)
+}
+
+/// Turns `a + b%` and `a - b%` into [`Token::PercentChange`], the retail reading of 150 + 10% as
+/// 165. When `a` is a percentage too the sum stays plain, `10% + 10%` is 0.2.
+fn mark_percent_changes(output: &mut VecDeque<Token>) {
+	// Whether each operand on the stack ends in `%`
+	let mut percent: Vec<bool> = Vec::with_capacity(output.len());
+	for tok in output.iter_mut() {
+		let argc = match *tok {
+			Token::Percent => {
+				percent.pop();
+				percent.push(true);
+				continue;
+			}
+			Token::Tag(_) => continue,
+			Token::Operator(op) => op.argc,
+			Token::Function(func) => func.argc,
+			Token::Convert(..) => 1,
+			_ => 0
+		};
+		let base = percent.len().saturating_sub(argc);
+		if let Token::Operator(op) = *tok {
+			if argc == 2 && matches!(op.op, BinOp::Add | BinOp::Sub) && percent[base ..] == [false, true] {
+				*tok = Token::PercentChange(op);
+			}
+		}
+		percent.truncate(base);
+		percent.push(false);
+	}
+}
+
+/// Evaluates an RPN queue with angles in radians.
+pub fn eval_postfix(output: VecDeque<Token>) -> Result<f64, EvalError> {
+	reduce(output, None, AngleMode::Radians, Rounding::default(), &Budget::new(None))
+}
+
+fn reduce(
+	output: VecDeque<Token>, mut steps: Option<&mut Vec<Step>>, mode: AngleMode, rounding: Rounding, budget: &Budget
+) -> Result<f64, EvalError> {
+	budget.spend(output.len())?;
+	let mut record = |tok: Token, args: &[f64], result: f64| {
+		if let Some(steps) = steps.as_deref_mut() {
+			steps.push(Step { token: tok, args: args.to_vec(), result });
+		}
+	};
+	// Operands are read in place off the top of the stack, which is the end of the vector
+	let mut solve: Vec<f64> = Vec::with_capacity(output.len());
+	for tok in output {
+		let argc = match tok {
+			Token::NumericLiteral(num) | Token::Uncertain(num, _) | Token::Duration(num) => {
+				solve.push(num);
+				continue;
+			}
+			Token::Operator(op) => op.argc,
+			Token::Function(func) => func.argc,
+			Token::Convert(..) | Token::Percent | Token::Tag(_) => 1,
+			Token::PercentChange(_) => 2,
+			Token::OpenParen | Token::CloseParen | Token::Comma | Token::Unit(_) => {
+				return Err(EvalError::UnexpectedToken(tok));
+			}
+		};
+		if solve.len() < argc {
+			return Err(EvalError::NotEnoughArguments);
+		}
+		let base = solve.len() - argc;
+		let args = &solve[base ..];
+		let result = match tok {
+			Token::Tag(_) => continue,
+			Token::Operator(op) if op.symbol == '+' && argc == 1 => args[0],
+			Token::Operator(op) if op.symbol == '-' && argc == 1 => -args[0],
+			Token::Operator(op) => op.op.apply(args[0], args[1]),
+			Token::Function(func) if func.name == "round" => rounding.round(args[0]),
+			Token::Function(func) => {
+				func.check_domain(args)?;
+				func.resolve_in(args, mode)
+			}
+			Token::Convert(from, to) => from.convert(args[0], to),
+			Token::Percent => args[0] / 100.0,
+			Token::PercentChange(op) => op.op.apply(args[0], args[0] * args[1]),
+			_ => unreachable!()
+		};
+		record(tok, args, result);
+		solve.truncate(base);
+		solve.push(result);
+	}
+
+	solve.last().copied().ok_or(EvalError::NoResult)
+}
+
+/// Runs `evaluate`, turning a panic into [`EvalError::Internal`] so that a bug in one evaluation
+/// does not end a session. The panic hook still reports it on stderr. A context the evaluation
+/// was changing keeps whatever it had finished changing.
+#[cfg(feature = "std")]
+pub fn contain_panic<T>(evaluate: impl FnOnce() -> Result<T, EvalError>) -> Result<T, EvalError> {
+	panic::catch_unwind(AssertUnwindSafe(evaluate)).unwrap_or_else(|payload| {
+		let message = payload.downcast_ref::<&str>().map(|message| message.to_string())
+			.or_else(|| payload.downcast_ref::<String>().cloned())
+			.unwrap_or_else(|| "panic without a message".to_string());
+		Err(EvalError::Internal(message))
+	})
+}
+
+pub fn eval(expression: &str) -> Result<f64, EvalError> {
+	Context::new().eval(expression)
+}
+
+/// Evaluates `expression` in a fresh context holding `vars`, for a single parameterized call. The
+/// bindings are variables as if assigned, so one named after a constant or function shadows it;
+/// the context is dropped afterwards, so an assignment in `expression` is refused with
+/// [`EvalError::UnexpectedAssignment`]. A name that is not an identifier is
+/// [`EvalError::InvalidBinding`].
+pub fn eval_with_vars(expression: &str, vars: &[(&str, f64)]) -> Result<f64, EvalError> {
+	eval_with_bindings(expression, vars.iter().copied())
+}
+
+/// Like [`eval_with_vars`], with the bindings in a map.
+#[cfg(feature = "std")]
+pub fn eval_with_var_map(expression: &str, vars: &std::collections::HashMap<String, f64>) -> Result<f64, EvalError> {
+	eval_with_bindings(expression, vars.iter().map(|(name, value)| (name.as_str(), *value)))
+}
+
+fn eval_with_bindings<'a>(expression: &str, vars: impl Iterator<Item = (&'a str, f64)>) -> Result<f64, EvalError> {
+	let mut context = Context::new();
+	for (name, value) in vars {
+		if !is_identifier(name) {
+			return Err(EvalError::InvalidBinding(name.to_string()));
+		}
+		context.set_var(name, value);
+	}
+	if let Some(name) = assigned_name(expression, &context) {
+		return Err(EvalError::UnexpectedAssignment(name.to_string()));
+	}
+	context.eval(expression)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn eval(expression: &str) -> Result<f64, EvalError> {
+		Context::new().eval(expression)
+	}
+
+	#[test]
+	fn operators_of_one_level_group_from_the_left() {
+		assert_eq!(eval("8/2*4").unwrap(), 16.0);
+		assert_eq!(eval("1-2+3").unwrap(), 2.0);
+		assert_eq!(eval("2*6/3*2").unwrap(), 8.0);
+		assert_eq!(eval("10-2-3+1").unwrap(), 6.0);
+		assert_eq!(eval("2^3^2").unwrap(), 512.0);
+	}
+
+	#[test]
+	fn a_sign_stands_where_an_operand_is_expected() {
+		assert_eq!(eval("-3").unwrap(), -3.0);
+		assert_eq!(eval("+3").unwrap(), 3.0);
+		assert_eq!(eval("2 * -3").unwrap(), -6.0);
+		assert_eq!(eval("(-3)").unwrap(), -3.0);
+		assert_eq!(eval("max(-1, -2)").unwrap(), -1.0);
+	}
+
+	#[test]
+	fn chained_signs_nest() {
+		assert_eq!(eval("--1").unwrap(), 1.0);
+		assert_eq!(eval("- -1").unwrap(), 1.0);
+		assert_eq!(eval("+-1").unwrap(), -1.0);
+		assert_eq!(eval("-+-1").unwrap(), 1.0);
+		assert_eq!(eval("1+--3").unwrap(), 4.0);
+		assert_eq!(eval("1 - -1").unwrap(), 2.0);
+		assert_eq!(eval("2*--3").unwrap(), 6.0);
+		assert_eq!(eval("---2").unwrap(), -2.0);
+	}
+
+	#[test]
+	fn a_sign_binds_looser_than_a_power() {
+		assert_eq!(eval("-2^2").unwrap(), -4.0);
+		assert_eq!(eval("2^-2").unwrap(), 0.25);
+		assert_eq!(eval("-2*3").unwrap(), -6.0);
+	}
+
+	#[test]
+	fn an_operator_where_an_operand_is_expected_is_reported_at_it() {
+		assert!(matches!(eval("* 2"), Err(EvalError::ExpectedOperand { ref found, span }) if found == "*" && span == Span::new(0, 1)));
+		assert!(matches!(eval("(,)"), Err(EvalError::ExpectedOperand { ref found, span }) if found == "," && span == Span::new(1, 2)));
+		assert!(matches!(eval("2 * )"), Err(EvalError::ExpectedOperand { ref found, span }) if found == ")" && span == Span::new(4, 5)));
+		assert!(matches!(eval("%"), Err(EvalError::ExpectedOperand { ref found, .. }) if found == "%"));
+		assert!(matches!(eval("max(1,)"), Err(EvalError::ExpectedOperand { span, .. }) if span == Span::new(6, 7)));
+	}
+
+	#[test]
+	fn a_value_where_an_operator_is_expected_is_reported_at_it() {
+		assert!(matches!(eval("2 3"), Err(EvalError::ExpectedOperator { ref found, span }) if found == "3" && span == Span::new(2, 3)));
+		assert!(matches!(eval("2 (3)"), Err(EvalError::ExpectedOperator { ref found, .. }) if found == "("));
+		assert!(matches!(eval("pi pi"), Err(EvalError::ExpectedOperator { span, .. }) if span == Span::new(3, 5)));
+		assert!(matches!(eval("2 [usd] 3"), Err(EvalError::ExpectedOperator { span, .. }) if span == Span::new(8, 9)));
+	}
+
+	#[test]
+	fn a_function_name_expects_its_call() {
+		assert!(matches!(eval("sin 3"), Err(EvalError::ExpectedCall("sin", span)) if span == Span::new(0, 3)));
+		assert!(matches!(eval("sin"), Err(EvalError::ExpectedCall("sin", _))));
+		assert!(matches!(eval("sin()"), Err(EvalError::ArgumentCount { expected: 1, found: 0, .. })));
+	}
+
+	#[test]
+	fn input_ending_after_an_operator_is_incomplete() {
+		assert!(matches!(eval("2 +"), Err(EvalError::IncompleteExpression('+', span)) if span == Span::new(2, 3)));
+		assert!(matches!(eval("-"), Err(EvalError::IncompleteExpression('-', _))));
+		assert!(matches!(eval("(2"), Err(EvalError::MismatchedParenthesis(span)) if span == Span::new(0, 1)));
+		assert!(matches!(eval("2)"), Err(EvalError::MismatchedParenthesis(span)) if span == Span::new(1, 2)));
+	}
+
+	#[test]
+	fn postfix_percent_follows_an_operand() {
+		assert_eq!(eval("50%").unwrap(), 0.5);
+		assert_eq!(eval("150 + 10%").unwrap(), 165.0);
+		assert!((eval("2 %%").unwrap() - 0.0002).abs() < 1e-15);
+	}
+
+	#[test]
+	fn percent_phrases_take_the_retail_reading() {
+		let cases = [
+			("20% of 150", 30.0),
+			("150 + 10%", 165.0),
+			("150 - 10%", 135.0),
+			("10% + 10%", 0.2),
+			("(150 + 10%) * 2", 330.0),
+			("150 + (10%)", 165.0),
+			("150 * 10%", 15.0),
+			("50% of 50% of 80", 20.0)
+		];
+		for (expression, value) in cases {
+			assert_eq!(eval(expression).unwrap(), value, "{}", expression);
+		}
+		// A variable holds the number, not the phrase
+		let mut context = Context::new();
+		context.execute("p = 10%").unwrap();
+		assert_eq!(context.eval("150 + p").unwrap(), 150.1);
+	}
+
+	#[test]
+	fn a_trailing_operator_is_incomplete_rather_than_an_arity_error() {
+		for (expression, symbol) in [("2 +", '+'), ("2 * 3 -", '-'), ("2^", '^'), ("(1 + 2) /", '/')] {
+			let err = eval(expression).unwrap_err();
+			assert!(matches!(err, EvalError::IncompleteExpression(found, _) if found == symbol), "{}", expression);
+			assert!(err.is_incomplete());
+		}
+		assert_eq!(eval("2 +").unwrap_err().to_string(), "incomplete expression: expected a value after '+'");
+		assert!(!eval("max(1)").unwrap_err().is_incomplete());
+		assert!(!eval("2 3").unwrap_err().is_incomplete());
+		assert!(is_incomplete("2 +") && is_incomplete("max(1,") && is_incomplete("(1 + 2"));
+		assert!(!is_incomplete("2 + 3") && !is_incomplete("") && !is_incomplete("2)"));
+	}
+
+	#[test]
+	fn input_is_incomplete_while_brackets_are_open_or_an_operator_trails() {
+		for input in ["1 *", "2 ^  ", "((1) + 2", "max(1,", "x =", "1 +\n(2"] {
+			assert!(is_incomplete(input), "{:?}", input);
+		}
+		for input in ["1 * 2", "((1) + 2)", "x = 1", "x == 1", "50%", "(1))", "   "] {
+			assert!(!is_incomplete(input), "{:?}", input);
+		}
+		assert_eq!(eval("1 +\n2").unwrap(), 3.0);
+	}
+
+	#[test]
+	fn the_angle_mode_only_changes_the_trigonometric_functions() {
+		let mut context = Context::new();
+		assert_eq!(context.angle_mode(), AngleMode::Radians);
+		assert!((context.eval("sin(90)").unwrap() - 0.893996663600558).abs() < 1e-12);
+		context.set_angle_mode(AngleMode::Degrees);
+		assert_eq!(context.eval("sin(90)").unwrap(), 1.0);
+		assert!((context.eval("acos(0.5)").unwrap() - 60.0).abs() < 1e-12);
+		assert_eq!(context.eval("sqrt(16) + exp(0) + ln(1)").unwrap(), 5.0);
+		assert_eq!(context.clone().eval("cos(180)").unwrap(), -1.0);
+		context.set_angle_mode(AngleMode::Radians);
+		assert_eq!(context.eval("cos(0)").unwrap(), 1.0);
+	}
+
+	#[test]
+	fn ans_cannot_be_assigned() {
+		let mut context = Context::new();
+		assert!(matches!(context.execute("ans = 1"), Err(EvalError::Protected(name)) if name == "ans"));
+		assert!(matches!(context.execute("ans, x = 1, 2"), Err(EvalError::Protected(name)) if name == "ans"));
+		context.execute("2 + 2").unwrap();
+		assert_eq!(context.execute("ans").unwrap().value(), 4.0);
+	}
+
+	#[test]
+	fn iteration_converges_to_a_fixed_point() {
+		let mut context = Context::new();
+		assert!(matches!(context.iterate("(ans + 2/ans)/2", 20, None), Err(EvalError::MissingAnswer(_))));
+		context.set_answer(1.0);
+		let iteration = context.iterate("(ans + 2/ans)/2", 20, Some(1e-12)).unwrap();
+		assert_eq!(iteration.stop, IterationStop::Converged);
+		assert!(iteration.values.len() < 20);
+		assert!((iteration.values.last().unwrap() - 2f64.sqrt()).abs() < 1e-12);
+		context.set_answer(1.0);
+		let iteration = context.iterate("ans * 10^300", 5, None).unwrap();
+		assert_eq!((iteration.stop, iteration.values.len()), (IterationStop::NonFinite, 2));
+		context.set_answer(1.0);
+		assert_eq!(context.iterate("ans + 1", 3, None).unwrap().values, [2.0, 3.0, 4.0]);
+	}
+
+	#[test]
+	fn error_codes_follow_the_catalog() {
+		for (index, (code, _)) in error_catalog().iter().enumerate() {
+			assert_eq!(*code, format!("E{:03}", index + 1));
+		}
+		let err = eval("2 +").unwrap_err();
+		assert_eq!((err.code(), err.kind()), ("E007", "IncompleteExpression"));
+		assert_eq!(EvalError::InvalidBinding("2x".to_string()).code(), "E050");
+	}
+
+	#[test]
+	fn bindings_are_variables_of_a_fresh_context() {
+		assert_eq!(eval_with_vars("x * y + 1", &[("x", 2.0), ("y", 3.0)]).unwrap(), 7.0);
+		assert_eq!(eval_with_vars("pi", &[("pi", 3.0)]).unwrap(), 3.0);
+		assert_eq!(eval_with_vars("x", &[("x", 1.0), ("x", 2.0)]).unwrap(), 2.0);
+		assert!(matches!(eval_with_vars("x", &[]), Err(EvalError::UnknownIdentifier(..))));
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn bindings_in_a_map_are_checked_alike() {
+		let vars = std::collections::HashMap::from([("rate".to_string(), 0.5), ("n".to_string(), 4.0)]);
+		assert_eq!(eval_with_var_map("rate * n", &vars).unwrap(), 2.0);
+		let vars = std::collections::HashMap::from([("a b".to_string(), 1.0)]);
+		assert!(matches!(eval_with_var_map("1", &vars), Err(EvalError::InvalidBinding(_))));
+	}
+
+	#[test]
+	fn bindings_refuse_assignments_and_invalid_names() {
+		assert!(matches!(eval_with_vars("x = 2", &[("x", 1.0)]), Err(EvalError::UnexpectedAssignment(name)) if name == "x"));
+		assert!(matches!(eval_with_vars("a, b = 1, 2", &[]), Err(EvalError::UnexpectedAssignment(_))));
+		assert!(matches!(eval_with_vars("1", &[("2x", 1.0)]), Err(EvalError::InvalidBinding(name)) if name == "2x"));
+		assert!(matches!(eval_with_vars("1", &[("", 1.0)]), Err(EvalError::InvalidBinding(_))));
+	}
+
+	#[test]
+	fn concurrent_calls_share_no_state() {
+		fn shareable<T: Send + Sync>() {}
+		shareable::<Context>();
+		shareable::<EvalError>();
+		let threads: Vec<_> = (0 .. 8).map(|i| std::thread::spawn(move || {
+			(0 .. 200).map(|j| eval_with_vars("x * 1000 + y", &[("x", i as f64), ("y", j as f64)]).unwrap()).collect::<Vec<_>>()
+		})).collect();
+		for (i, thread) in threads.into_iter().enumerate() {
+			let values = thread.join().unwrap();
+			assert!(values.iter().enumerate().all(|(j, value)| *value == (i * 1000 + j) as f64));
+		}
+		assert!(eval_with_vars("x", &[]).is_err());
+	}
+
+	#[test]
+	fn long_expressions_evaluate_alike_on_every_path() {
+		let terms = vec!["1"; 1000].join(" + ");
+		assert_eq!(eval(&terms).unwrap(), 1000.0);
+		let mut expression = String::from("1");
+		for i in 0 .. 1000 {
+			expression.push_str(&format!(" {} {}.5", ['+', '-', '*', '/'][i % 4], i % 97 + 1));
+		}
+		let context = Context::new();
+		let value = context.eval(&expression).unwrap();
+		assert!(value.is_finite());
+		assert_eq!(eval_postfix(context.to_postfix(&expression).unwrap()).unwrap(), value);
+		assert_eq!(context.to_ast(&expression).unwrap().eval(), value);
+		assert_eq!(eval("1 + max(2, 3) * min(4, 5) - max(1, 2) ^ 2").unwrap(), 9.0);
+	}
+
+	/// Evaluates RPN of numbers and operators the way the calculator first did: operands popped
+	/// into a `Vec`, most recent first, passed to a function pointer.
+	fn reduce_with_pointers(rpn: VecDeque<Token>) -> f64 {
+		let resolver = |symbol: char| -> fn(&Vec<f64>) -> f64 {
+			match symbol {
+				'+' => |args| args[1] + args[0],
+				'-' => |args| args[1] - args[0],
+				'*' => |args| args[1] * args[0],
+				'/' => |args| args[1] / args[0],
+				'^' => |args| args[1].powf(args[0]),
+				_ => unreachable!()
+			}
+		};
+		let mut stack = Vec::new();
+		for token in rpn {
+			match token {
+				Token::NumericLiteral(value) => stack.push(value),
+				Token::Operator(op) => {
+					let mut args = Vec::with_capacity(op.argc);
+					for _ in 0 .. op.argc {
+						args.push(stack.pop().unwrap());
+					}
+					stack.push(resolver(op.symbol)(&args));
+				}
+				_ => unreachable!()
+			}
+		}
+		stack.pop().unwrap()
+	}
+
+	#[test]
+	fn operators_give_the_same_bits_as_function_pointers() {
+		let operands = [0.0, -0.0, 1.0, -2.5, 3.0, 1e308, 1e-308, f64::INFINITY, f64::NEG_INFINITY, f64::NAN, 0.1, 7.0];
+		for (symbol, op) in Operator::MAP.iter().filter(|(symbol, _)| *symbol != '±') {
+			for lhs in operands {
+				for rhs in operands {
+					let rpn = VecDeque::from([Token::NumericLiteral(lhs), Token::NumericLiteral(rhs), Token::Operator(*op)]);
+					let expected = reduce_with_pointers(rpn.clone());
+					let value = eval_postfix(rpn).unwrap();
+					assert_eq!(value.to_bits(), expected.to_bits(), "{} {} {}", lhs, symbol, rhs);
+				}
+			}
+		}
+		let context = Context::new();
+		for expression in ["10 - 4 - 3", "8 / 2 / 2", "2 ^ 3 ^ 2", "0.1 + 0.2 * 3 - 1 / 7", "(1 - 2) * (3 - 4) / (5 - 6)", "1 / 3 * 3 - 1"] {
+			let expected = reduce_with_pointers(context.to_postfix(expression).unwrap());
+			assert_eq!(context.eval(expression).unwrap().to_bits(), expected.to_bits(), "{}", expression);
+		}
+	}
+
+	#[test]
+	fn arguments_keep_their_order_off_the_stack() {
+		assert_eq!(eval("10 - 4 - 3").unwrap(), 3.0);
+		assert_eq!(eval("8 / 2 / 2").unwrap(), 2.0);
+		assert_eq!(eval("pctof(1, 4)").unwrap(), 25.0);
+		assert_eq!(eval("pctchange(4, 5)").unwrap(), 25.0);
+		assert_eq!(eval("1 + pctof(3, max(2, 12)) - 4").unwrap(), 22.0);
+		let weighted = Function { name: "weighted", argc: 6, angle: Angle::Argument, description: "", example: "", resolver: |args| {
+			args.iter().enumerate().map(|(i, arg)| arg * (i + 1) as f64).sum()
+		} };
+		let degrees = [180.0, 0.0, 0.0, 0.0, 0.0, 90.0];
+		let expected = consts::PI + 6.0 * consts::FRAC_PI_2;
+		assert!((weighted.resolve_in(&degrees, AngleMode::Degrees) - expected).abs() < 1e-12);
+		assert!((weighted.resolve_in(&degrees[.. 4], AngleMode::Degrees) - consts::PI).abs() < 1e-12);
+		assert_eq!(weighted.resolve_in(&[1.0, 2.0], AngleMode::Radians), 5.0);
+	}
+
+	fn assigned(context: &mut Context, statement: &str) -> Vec<(String, f64)> {
+		match context.execute(statement) {
+			Ok(EvalOutcome::AssignedMultiple(pairs)) => pairs,
+			outcome => panic!("{}: {:?}", statement, outcome)
+		}
+	}
+
+	#[test]
+	fn several_targets_are_assigned_after_every_value_is_evaluated() {
+		let mut context = Context::new();
+		assert_eq!(assigned(&mut context, "a, b = 3+4, 3*4"), [("a".to_string(), 7.0), ("b".to_string(), 12.0)]);
+		assert_eq!(assigned(&mut context, "a, b = b, a"), [("a".to_string(), 12.0), ("b".to_string(), 7.0)]);
+		assert_eq!((context.get_var("a"), context.get_var("b")), (Some(12.0), Some(7.0)));
+		assert_eq!(assigned(&mut context, "x, y = max(1, 2), (3 * (4 + 1))"), [("x".to_string(), 2.0), ("y".to_string(), 15.0)]);
+		assert!(matches!(context.execute("3+4, 3*4"), Ok(EvalOutcome::Multiple(values)) if values == [7.0, 12.0]));
+		assert_eq!(context.answer(), Some(12.0));
+		assert!(matches!(context.execute("max(1, 2)"), Ok(EvalOutcome::Value(value)) if value == 2.0));
+	}
+
+	#[test]
+	fn targets_and_values_must_match_in_number() {
+		let mut context = Context::new();
+		assert!(matches!(context.execute("a, b = 1"), Err(EvalError::TargetCount { targets: 2, values: 1, .. })));
+		assert!(matches!(context.execute("a, b, c = 1, 2"), Err(EvalError::TargetCount { targets: 3, values: 2, .. })));
+		assert!(matches!(context.execute("x = 1, 2"), Err(EvalError::TargetCount { targets: 1, values: 2, .. })));
+		assert_eq!(context.execute("a, b = 1").unwrap_err().to_string(), "2 names but 1 value");
+		// Nothing is assigned when one value fails
+		assert!(context.execute("a, b = 1, c").is_err());
+		assert!(matches!(context.execute("1, x = 2"), Err(EvalError::InvalidCharacter('=', span)) if span == Span::new(5, 6)));
+		assert_eq!((context.get_var("a"), context.get_var("x")), (None, None));
+	}
+
+	#[test]
+	fn percentage_functions_take_their_operands_in_reading_order() {
+		assert_eq!(eval("pctchange(80, 100)").unwrap(), 25.0);
+		assert_eq!(eval("pctchange(100, 80)").unwrap(), -20.0);
+		assert_eq!(eval("pctof(30, 120)").unwrap(), 25.0);
+		assert!(matches!(eval("pctchange(0, 5)"), Err(EvalError::Domain { function: "pctchange", .. })));
+		assert!(matches!(eval("pctchange(0, 0)"), Err(EvalError::Domain { function: "pctchange", .. })));
+	}
+
+	#[test]
+	fn percentage_results_are_shown_with_a_percent_sign_when_asked() {
+		let mut context = Context::new();
+		let shown = |context: &mut Context, statement: &str| {
+			let value = context.execute(statement).unwrap().value();
+			context.format_result(value)
+		};
+		assert_eq!(shown(&mut context, "pctchange(80, 100)"), "25");
+		context.display_mut().percent_helpers = true;
+		assert_eq!(shown(&mut context, "pctchange(80, 100)"), "25%");
+		assert_eq!(shown(&mut context, "pctof(30, 120)"), "25%");
+		// Only the function's own result is a percentage
+		assert_eq!(shown(&mut context, "pctof(30, 120) * 2"), "50");
+		assert_eq!(shown(&mut context, "25"), "25");
+		assert_eq!(shown(&mut context, "p = pctof(1, 8)"), "12.5%");
+	}
+
+	#[cfg(feature = "std")]
+	#[test]
+	fn a_panicking_hook_fails_one_evaluation_and_keeps_the_session() {
+		let mut context = Context::new();
+		context.execute("rate = 0.5").unwrap();
+		context.set_literal_parser(|rest| {
+			if rest.starts_with("boom") {
+				panic!("the hook broke on {}", rest);
+			}
+			None
+		});
+		let hook = std::panic::take_hook();
+		std::panic::set_hook(Box::new(|_| {}));
+		let err = contain_panic(|| context.execute("x = boom")).unwrap_err();
+		let payload = contain_panic::<f64>(|| std::panic::panic_any(7));
+		std::panic::set_hook(hook);
+		assert!(matches!(&err, EvalError::Internal(message) if message == "the hook broke on boom"), "{}", err);
+		assert_eq!(contain_panic(|| context.execute("rate * 4")).unwrap().value(), 2.0);
+		assert_eq!((context.get_var("rate"), context.get_var("x")), (Some(0.5), None));
+		assert!(matches!(payload, Err(EvalError::Internal(message)) if message == "panic without a message"));
+	}
+
+	/// Reads `1'234.56`, a number grouped with apostrophes, and nothing without one.
+	fn swiss(rest: &str) -> Option<(f64, usize)> {
+		let length = rest.find(|c: char| !(c.is_ascii_digit() || c == '\'' || c == '.')).unwrap_or(rest.len());
+		let text = rest[.. length].trim_end_matches('\'');
+		if !text.contains('\'') || text.starts_with('\'') {
+			return None;
+		}
+		text.replace('\'', "").parse().ok().map(|value| (value, text.len()))
+	}
+
+	#[test]
+	fn a_literal_parser_reads_where_a_value_may_start() {
+		let mut context = Context::new();
+		context.set_literal_parser(swiss);
+		assert_eq!(context.eval("1'234.56 + 1").unwrap(), 1235.56);
+		assert_eq!(context.eval("2 * (1'000 - 1.5)").unwrap(), 1997.0);
+		assert_eq!(context.eval("max(1'000, 2'000'000)").unwrap(), 2_000_000.0);
+		assert_eq!(context.eval("1234 + 0.5").unwrap(), 1234.5);
+		assert_eq!(context.execute("price = 1'500").unwrap().value(), 1500.0);
+		assert_eq!(context.eval("price / 1'000").unwrap(), 1.5);
+		let spans: Vec<(usize, usize)> = context.tokenize("3 * 1'000").unwrap().iter().map(|lexeme| (lexeme.span.start, lexeme.span.end)).collect();
+		assert_eq!(spans, [(0, 1), (2, 3), (4, 9)]);
+		context.remove_literal_parser();
+		assert!(context.eval("1'000").is_err());
+	}
+
+	#[test]
+	fn a_literal_parser_cannot_claim_nothing_or_too_much() {
+		let mut context = Context::new();
+		context.set_literal_parser(|_| Some((1.0, 0)));
+		assert_eq!(context.eval("2 + 3").unwrap(), 5.0);
+		context.set_literal_parser(|rest| rest.starts_with('#').then_some((1.0, rest.len() + 1)));
+		assert!(matches!(context.eval("2 + #1"), Err(EvalError::NumberParseError(span)) if span == Span::new(4, 6)));
+		context.set_literal_parser(|rest| rest.starts_with('#').then_some((f64::INFINITY, 2)));
+		assert!(matches!(context.eval("2 + #1 + 3"), Err(EvalError::NumberParseError(span)) if span == Span::new(4, 6)));
+		assert_eq!(context.eval("2 + 3").unwrap(), 5.0);
+	}
+}
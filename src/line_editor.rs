@@ -0,0 +1,474 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufRead, Read, Write};
+use std::path::PathBuf;
+
+const MAX_HISTORY: usize = 1000;
+
+/// Given the current line and the cursor's byte offset, returns the byte offset where the word being
+/// completed starts and the candidates that may replace it.
+pub type Completer<'a> = &'a dyn Fn(&str, usize) -> (usize, Vec<String>);
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReadResult {
+	Line(String),
+	Interrupted,
+	Eof
+}
+
+/// `$XDG_STATE_HOME/syc/history` when XDG is configured, `~/.syc_history` otherwise.
+pub fn default_history_path() -> Option<PathBuf> {
+	if let Some(state) = env::var_os("XDG_STATE_HOME").filter(|dir| !dir.is_empty()) {
+		return Some(PathBuf::from(state).join("syc").join("history"));
+	}
+	env::var_os("HOME").filter(|dir| !dir.is_empty()).map(|home| PathBuf::from(home).join(".syc_history"))
+}
+
+pub struct LineEditor {
+	history: Vec<String>,
+	history_path: Option<PathBuf>,
+	dumb: bool
+}
+impl LineEditor {
+	pub fn new(history_path: Option<PathBuf>) -> Self {
+		let mut history = Vec::new();
+		if let Some(contents) = history_path.as_ref().and_then(|path| fs::read_to_string(path).ok()) {
+			history.extend(contents.lines().filter(|line| !line.is_empty()).map(str::to_string));
+			if history.len() > MAX_HISTORY {
+				history.drain(.. history.len() - MAX_HISTORY);
+			}
+		}
+		Self { history, history_path, dumb: false }
+	}
+	/// Reads whole lines as the terminal sends them, without raw mode or escape sequences, for
+	/// terminals that cannot show them.
+	pub fn dumb(mut self, on: bool) -> Self {
+		self.dumb = on;
+		self
+	}
+
+	pub fn add_history(&mut self, line: &str) {
+		if line.is_empty() || self.history.last().map(String::as_str) == Some(line) {
+			return;
+		}
+		self.history.push(line.to_string());
+		if self.history.len() > MAX_HISTORY {
+			self.history.remove(0);
+		}
+	}
+
+	pub fn save_history(&self) -> io::Result<()> {
+		let Some(path) = &self.history_path else {
+			return Ok(());
+		};
+		if let Some(dir) = path.parent() {
+			fs::create_dir_all(dir)?;
+		}
+		let mut contents = self.history.join("\n");
+		contents.push('\n');
+		fs::write(path, contents)
+	}
+
+	pub fn read_line(&mut self, prompt: &str, completer: Completer) -> io::Result<ReadResult> {
+		if self.dumb {
+			let _guard = raw::RawMode::line_mode();
+			return read_line_plain(prompt, &mut io::stdin().lock());
+		}
+		match raw::RawMode::enable() {
+			Some(_guard) => self.read_line_raw(prompt, completer),
+			None => read_line_plain(prompt, &mut io::stdin().lock())
+		}
+	}
+
+	fn read_line_raw(&mut self, prompt: &str, completer: Completer) -> io::Result<ReadResult> {
+		let stdin = io::stdin();
+		let mut input = stdin.lock().bytes();
+		let mut next_byte = move || input.next().transpose();
+		let mut out = io::stdout().lock();
+
+		let mut buffer: Vec<char> = Vec::new();
+		let mut cursor = 0;
+		let mut history_index = self.history.len();
+		let mut pending = String::new();
+
+		render(&mut out, prompt, &buffer, cursor)?;
+		loop {
+			let Some(byte) = next_byte()? else {
+				writeln!(out)?;
+				return Ok(ReadResult::Eof);
+			};
+			match byte {
+				b'\r' | b'\n' => {
+					writeln!(out)?;
+					return Ok(ReadResult::Line(buffer.into_iter().collect()));
+				}
+				0x03 => {
+					writeln!(out, "^C")?;
+					return Ok(ReadResult::Interrupted);
+				}
+				0x04 if buffer.is_empty() => {
+					writeln!(out)?;
+					return Ok(ReadResult::Eof);
+				}
+				0x04 if cursor < buffer.len() => {
+					buffer.remove(cursor);
+				}
+				0x7f | 0x08 if cursor > 0 => {
+					cursor -= 1;
+					buffer.remove(cursor);
+				}
+				b'\t' => {
+					let line: String = buffer.iter().collect();
+					let pos = buffer[.. cursor].iter().map(|c| c.len_utf8()).sum();
+					let (start, candidates) = completer(&line, pos);
+					let start = line[.. start].chars().count();
+					let word: String = buffer[start .. cursor].iter().collect();
+					let replacement = match candidates.as_slice() {
+						[] => None,
+						[single] => Some(single.clone()),
+						[first, rest @ ..] => {
+							let common = rest.iter().fold(first.as_str(), |common, candidate| {
+								let len = common.chars().zip(candidate.chars())
+									.take_while(|(a, b)| a == b)
+									.map(|(a, _)| a.len_utf8())
+									.sum();
+								&common[.. len]
+							});
+							if common.chars().count() > word.chars().count() {
+								Some(common.to_string())
+							} else {
+								writeln!(out)?;
+								writeln!(out, "{}", candidates.join("  "))?;
+								None
+							}
+						}
+					};
+					if let Some(replacement) = replacement {
+						buffer.splice(start .. cursor, replacement.chars());
+						cursor = start + replacement.chars().count();
+					}
+				}
+				0x01 => cursor = 0,
+				0x05 => cursor = buffer.len(),
+				0x02 => cursor = cursor.saturating_sub(1),
+				0x06 => cursor = (cursor + 1).min(buffer.len()),
+				0x0b => buffer.truncate(cursor),
+				0x15 => {
+					buffer.drain(.. cursor);
+					cursor = 0;
+				}
+				0x17 => {
+					let mut start = cursor;
+					while start > 0 && buffer[start - 1].is_whitespace() {
+						start -= 1;
+					}
+					while start > 0 && !buffer[start - 1].is_whitespace() {
+						start -= 1;
+					}
+					buffer.drain(start .. cursor);
+					cursor = start;
+				}
+				0x1b => {
+					let key = read_escape(&mut next_byte)?;
+					match key {
+						Key::Left => cursor = cursor.saturating_sub(1),
+						Key::Right => cursor = (cursor + 1).min(buffer.len()),
+						Key::Home => cursor = 0,
+						Key::End => cursor = buffer.len(),
+						Key::Delete => {
+							if cursor < buffer.len() {
+								buffer.remove(cursor);
+							}
+						}
+						Key::Up | Key::Down => {
+							if history_index == self.history.len() {
+								pending = buffer.iter().collect();
+							}
+							if key == Key::Up && history_index > 0 {
+								history_index -= 1;
+							} else if key == Key::Down && history_index < self.history.len() {
+								history_index += 1;
+							}
+							let entry = self.history.get(history_index).unwrap_or(&pending);
+							buffer = entry.chars().collect();
+							cursor = buffer.len();
+						}
+						Key::Unknown => {}
+					}
+				}
+				_ if byte >= 0x20 => {
+					if let Some(c) = read_utf8(byte, &mut next_byte)? {
+						buffer.insert(cursor, c);
+						cursor += 1;
+					}
+				}
+				_ => {}
+			}
+			render(&mut out, prompt, &buffer, cursor)?;
+		}
+	}
+}
+
+/// Reads up to a line break, or up to a Ctrl-C, which [`raw::RawMode::line_mode`] makes end a line
+/// instead of killing the process.
+fn read_line_plain<R: BufRead>(prompt: &str, input: &mut R) -> io::Result<ReadResult> {
+	print!("{}", prompt);
+	io::stdout().flush()?;
+	let text = |line: &[u8]| ReadResult::Line(String::from_utf8_lossy(line).trim_end_matches('\r').to_string());
+	let mut line = Vec::new();
+	loop {
+		let available = input.fill_buf()?;
+		if available.is_empty() {
+			break;
+		}
+		match available.iter().position(|byte| matches!(byte, b'\n' | 0x03)) {
+			Some(end) => {
+				let interrupted = available[end] == 0x03;
+				line.extend_from_slice(&available[.. end]);
+				input.consume(end + 1);
+				if interrupted {
+					println!();
+					return Ok(ReadResult::Interrupted);
+				}
+				return Ok(text(&line));
+			}
+			None => {
+				let len = available.len();
+				line.extend_from_slice(available);
+				input.consume(len);
+			}
+		}
+	}
+	if line.is_empty() {
+		return Ok(ReadResult::Eof);
+	}
+	Ok(text(&line))
+}
+
+fn render<W: Write>(out: &mut W, prompt: &str, buffer: &[char], cursor: usize) -> io::Result<()> {
+	let line: String = buffer.iter().collect();
+	write!(out, "\r{}{}\x1b[K", prompt, line)?;
+	if cursor < buffer.len() {
+		write!(out, "\x1b[{}D", buffer.len() - cursor)?;
+	}
+	out.flush()
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Key {
+	Left,
+	Right,
+	Up,
+	Down,
+	Home,
+	End,
+	Delete,
+	Unknown
+}
+
+fn read_escape<F: FnMut() -> io::Result<Option<u8>>>(next_byte: &mut F) -> io::Result<Key> {
+	match next_byte()? {
+		Some(b'[') | Some(b'O') => {}
+		_ => return Ok(Key::Unknown)
+	}
+	let mut param = Vec::new();
+	loop {
+		let Some(byte) = next_byte()? else {
+			return Ok(Key::Unknown);
+		};
+		return Ok(match byte {
+			b'0' ..= b'9' | b';' => {
+				param.push(byte);
+				continue;
+			}
+			b'A' => Key::Up,
+			b'B' => Key::Down,
+			b'C' => Key::Right,
+			b'D' => Key::Left,
+			b'H' => Key::Home,
+			b'F' => Key::End,
+			b'~' => match param.as_slice() {
+				b"1" | b"7" => Key::Home,
+				b"4" | b"8" => Key::End,
+				b"3" => Key::Delete,
+				_ => Key::Unknown
+			},
+			_ => Key::Unknown
+		});
+	}
+}
+
+fn read_utf8<F: FnMut() -> io::Result<Option<u8>>>(first: u8, next_byte: &mut F) -> io::Result<Option<char>> {
+	let len = match first {
+		0x00 ..= 0x7f => 1,
+		0xc0 ..= 0xdf => 2,
+		0xe0 ..= 0xef => 3,
+		0xf0 ..= 0xf7 => 4,
+		_ => return Ok(None)
+	};
+	let mut bytes = vec![first];
+	for _ in 1 .. len {
+		match next_byte()? {
+			Some(byte) => bytes.push(byte),
+			None => return Ok(None)
+		}
+	}
+	Ok(std::str::from_utf8(&bytes).ok().and_then(|s| s.chars().next()))
+}
+
+// The layout of `Termios` and the values of its flags are those of glibc on these targets. Anywhere
+// else the terminal is left as it is and lines are read plainly.
+#[cfg(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"), target_env = "gnu"))]
+mod raw {
+	use std::io::{self, IsTerminal};
+	use std::os::raw::c_int;
+
+	#[repr(C)]
+	#[derive(Copy, Clone)]
+	struct Termios {
+		c_iflag: u32,
+		c_oflag: u32,
+		c_cflag: u32,
+		c_lflag: u32,
+		c_line: u8,
+		c_cc: [u8; 32],
+		c_ispeed: u32,
+		c_ospeed: u32
+	}
+
+	const ISIG: u32 = 0o000001;
+	const ICANON: u32 = 0o000002;
+	const ECHO: u32 = 0o000010;
+	const IEXTEN: u32 = 0o100000;
+	const ICRNL: u32 = 0o000400;
+	const IXON: u32 = 0o002000;
+	const VTIME: usize = 5;
+	const VMIN: usize = 6;
+	const VEOL: usize = 11;
+	const TCSANOW: c_int = 0;
+	const STDIN_FILENO: c_int = 0;
+
+	extern "C" {
+		fn tcgetattr(fd: c_int, termios: *mut Termios) -> c_int;
+		fn tcsetattr(fd: c_int, optional_actions: c_int, termios: *const Termios) -> c_int;
+	}
+
+	/// Restores the original terminal attributes when dropped.
+	pub struct RawMode {
+		original: Termios
+	}
+	impl RawMode {
+		pub fn enable() -> Option<Self> {
+			if !io::stdout().is_terminal() {
+				return None;
+			}
+			Self::change(|raw| {
+				raw.c_lflag &= !(ECHO | ICANON | ISIG | IEXTEN);
+				raw.c_iflag &= !(ICRNL | IXON);
+				raw.c_cc[VMIN] = 1;
+				raw.c_cc[VTIME] = 0;
+			})
+		}
+		/// Keeps the terminal reading whole lines with echo, for dumb terminals, but makes Ctrl-C end
+		/// the line as a `0x03` byte rather than send SIGINT.
+		pub fn line_mode() -> Option<Self> {
+			Self::change(|line| {
+				line.c_lflag &= !ISIG;
+				line.c_cc[VEOL] = 0x03;
+			})
+		}
+		fn change(change: impl FnOnce(&mut Termios)) -> Option<Self> {
+			if !io::stdin().is_terminal() {
+				return None;
+			}
+			let mut original = Termios {
+				c_iflag: 0, c_oflag: 0, c_cflag: 0, c_lflag: 0, c_line: 0, c_cc: [0; 32], c_ispeed: 0, c_ospeed: 0
+			};
+			// SAFETY: `original` is a valid, writable termios structure matching the glibc/musl layout.
+			if unsafe { tcgetattr(STDIN_FILENO, &mut original) } != 0 {
+				return None;
+			}
+			let mut changed = original;
+			change(&mut changed);
+			// SAFETY: `changed` is a fully initialised copy of the attributes returned by tcgetattr.
+			if unsafe { tcsetattr(STDIN_FILENO, TCSANOW, &changed) } != 0 {
+				return None;
+			}
+			Some(Self { original })
+		}
+	}
+	impl Drop for RawMode {
+		fn drop(&mut self) {
+			// SAFETY: restores the attributes previously read by tcgetattr.
+			unsafe {
+				tcsetattr(STDIN_FILENO, TCSANOW, &self.original);
+			}
+		}
+	}
+}
+
+#[cfg(not(all(target_os = "linux", any(target_arch = "x86_64", target_arch = "aarch64"), target_env = "gnu")))]
+mod raw {
+	pub struct RawMode;
+	impl RawMode {
+		pub fn enable() -> Option<Self> {
+			None
+		}
+		pub fn line_mode() -> Option<Self> {
+			None
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn plain_lines_end_at_a_line_break_or_ctrl_c() {
+		let mut input = io::Cursor::new(&b"2 + 2\nab\x03cd\r\n\xffe\ntail"[..]);
+		let mut read = || read_line_plain("", &mut input).unwrap();
+		assert_eq!(read(), ReadResult::Line("2 + 2".to_string()));
+		assert_eq!(read(), ReadResult::Interrupted);
+		assert_eq!(read(), ReadResult::Line("cd".to_string()));
+		assert_eq!(read(), ReadResult::Line("\u{fffd}e".to_string()));
+		assert_eq!(read(), ReadResult::Line("tail".to_string()));
+		assert_eq!(read(), ReadResult::Eof);
+	}
+
+	#[test]
+	fn history_is_kept_between_sessions_without_repeats() {
+		let path = env::temp_dir().join(format!("syc-history-test-{}", std::process::id()));
+		let mut editor = LineEditor::new(Some(path.clone()));
+		for line in ["1 + 1", "1 + 1", "", "x = 2", "1 + 1"] {
+			editor.add_history(line);
+		}
+		assert_eq!(editor.history, ["1 + 1", "x = 2", "1 + 1"]);
+		editor.save_history().unwrap();
+		assert_eq!(LineEditor::new(Some(path.clone())).history, editor.history);
+		for i in 0 .. MAX_HISTORY + 5 {
+			editor.add_history(&i.to_string());
+		}
+		assert_eq!((editor.history.len(), editor.history[0].as_str()), (MAX_HISTORY, "5"));
+		fs::remove_file(&path).unwrap();
+		assert!(LineEditor::new(None).save_history().is_ok());
+	}
+
+	#[test]
+	fn escape_sequences_and_utf8_are_decoded() {
+		let decode = |bytes: &[u8]| {
+			let mut bytes = bytes.iter().copied();
+			read_escape(&mut || Ok(bytes.next())).unwrap()
+		};
+		assert_eq!(decode(b"[A"), Key::Up);
+		assert_eq!(decode(b"[D"), Key::Left);
+		assert_eq!(decode(b"OH"), Key::Home);
+		assert_eq!(decode(b"[3~"), Key::Delete);
+		assert_eq!(decode(b"[1;5C"), Key::Right);
+		assert_eq!(decode(b"[9~"), Key::Unknown);
+		assert_eq!(decode(b"x"), Key::Unknown);
+		let mut rest = "µ".bytes().skip(1);
+		assert_eq!(read_utf8(0xc2, &mut || Ok(rest.next())).unwrap(), Some('µ'));
+		assert_eq!(read_utf8(0xff, &mut || Ok(None)).unwrap(), None);
+	}
+}
@@ -0,0 +1,228 @@
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+use crate::binding::{self, Let};
+use crate::calculus::form_name;
+use crate::{dependencies_with, split_assignment, Context, Lexeme, LexemeKind, Span};
+
+/// A construct that is valid but probably not what was meant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Lint {
+	/// Parentheses around a single value, or around all of an expression or argument: `2 * (3)`.
+	RedundantParens,
+	/// A negation of a negation, `--x` or `-(-x)`.
+	DoubleNegation,
+	/// Division by a literal 0, `x / 0`.
+	DivisionByZero,
+	/// A `let` whose body never reads the name it binds, `let x = 2 in y`.
+	UnusedLet
+}
+impl Lint {
+	pub const ALL: [Lint; 4] = [Lint::RedundantParens, Lint::DoubleNegation, Lint::DivisionByZero, Lint::UnusedLet];
+
+	/// The stable name for suppressing it, as in `:lint allow double-negation`.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::RedundantParens => "redundant-parens",
+			Self::DoubleNegation => "double-negation",
+			Self::DivisionByZero => "division-by-zero",
+			Self::UnusedLet => "unused-let"
+		}
+	}
+	pub fn by_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|lint| lint.name() == name)
+	}
+	pub fn description(self) -> &'static str {
+		match self {
+			Self::RedundantParens => "parentheses that change nothing",
+			Self::DoubleNegation => "a negation of a negation",
+			Self::DivisionByZero => "division by zero",
+			Self::UnusedLet => "a let binding that is never read"
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LintWarning {
+	pub lint: Lint,
+	pub span: Span
+}
+impl Display for LintWarning {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} at position {} [{}]", self.lint.description(), self.span.start, self.lint.name())
+	}
+}
+
+/// Every lint in `expression`, in the order they start. An expression that does not lex has none
+/// after the error, which evaluating it reports.
+pub fn lint(expression: &str) -> Vec<LintWarning> {
+	lint_with(expression, &Context::new())
+}
+
+/// Like [`lint`], lexing in the locale of `context`. Allowed lints are reported too, see
+/// [`Context::lint`].
+pub fn lint_with(expression: &str, context: &Context) -> Vec<LintWarning> {
+	let lexemes: Vec<Lexeme> = context.lexer(expression).map_while(Result::ok).collect();
+	let mut warnings = Vec::new();
+	// For every open parenthesis, its index and whether it is a call
+	let mut open: Vec<(usize, bool)> = Vec::new();
+	let is_let = |lexeme: &Lexeme| lexeme.kind == LexemeKind::Form && form_name(&expression[lexeme.span.start .. lexeme.span.end]) == "let";
+	for (i, lexeme) in lexemes.iter().enumerate() {
+		let previous = i.checked_sub(1).map(|i| lexemes[i].kind);
+		match lexeme.kind {
+			LexemeKind::OpenParen => open.push((i, matches!(previous, Some(LexemeKind::Identifier)))),
+			LexemeKind::CloseParen => {
+				if let Some((start, false)) = open.pop() {
+					// A `let` reaches as far as it can, so its parentheses end it
+					let single = i == start + 2 && is_operand(lexemes[start + 1].kind) && !is_let(&lexemes[start + 1]);
+					let before = start.checked_sub(1).map(|i| lexemes[i].kind);
+					let after = lexemes.get(i + 1).map(|lexeme| lexeme.kind);
+					let whole = matches!(before, None | Some(LexemeKind::OpenParen | LexemeKind::Separator))
+						&& matches!(after, None | Some(LexemeKind::CloseParen | LexemeKind::Separator));
+					if single || whole {
+						let span = Span::new(lexemes[start].span.start, lexeme.span.end);
+						warnings.push(LintWarning { lint: Lint::RedundantParens, span });
+					}
+				}
+			}
+			LexemeKind::Operator('-') if is_unary(previous) => {
+				let next = match lexemes.get(i + 1) {
+					Some(Lexeme { kind: LexemeKind::OpenParen, .. }) => lexemes.get(i + 2),
+					next => next
+				};
+				if let Some(next) = next.filter(|next| next.kind == LexemeKind::Operator('-')) {
+					let span = Span::new(lexeme.span.start, next.span.end);
+					warnings.push(LintWarning { lint: Lint::DoubleNegation, span });
+				}
+			}
+			LexemeKind::Operator('/') => {
+				let divisor = lexemes.get(i + 1)
+					.filter(|next| matches!(next.kind, LexemeKind::Number(value) | LexemeKind::Duration(value) if value == 0.0));
+				// 0 ^ 0 is 1
+				let power = matches!(lexemes.get(i + 2), Some(Lexeme { kind: LexemeKind::Operator('^'), .. }));
+				if let Some(divisor) = divisor.filter(|_| !power) {
+					let span = Span::new(lexeme.span.start, divisor.span.end);
+					warnings.push(LintWarning { lint: Lint::DivisionByZero, span });
+				}
+			}
+			LexemeKind::Form if is_let(lexeme) => {
+				lint_let(&expression[lexeme.span.start .. lexeme.span.end], lexeme.span.start, context, &mut warnings);
+			}
+			_ => {}
+		}
+	}
+	warnings.sort_by_key(|warning| warning.span.start);
+	warnings
+}
+
+/// The lints of `text`, a `let` starting at `offset`, and of its value and body, which are read
+/// as one lexeme.
+fn lint_let(text: &str, offset: usize, context: &Context, warnings: &mut Vec<LintWarning>) {
+	let Some(Let { name, value, body }) = binding::split(text, context.display().locale.argument_separator()) else {
+		return;
+	};
+	// Bound in the scope, the name shadows a constant or function as it does when evaluating
+	let mut scope = context.clone();
+	scope.set_var(&text[name.0 .. name.1], 0.0);
+	if dependencies_with(&text[body.0 .. body.1], &scope).is_ok_and(|names| !names.contains(&text[name.0 .. name.1])) {
+		warnings.push(LintWarning { lint: Lint::UnusedLet, span: Span::new(offset + name.0, offset + name.1) });
+	}
+	for (start, end) in [value, body] {
+		let inner = lint_with(&text[start .. end], context).into_iter()
+			.map(|warning| LintWarning { span: Span::new(warning.span.start + offset + start, warning.span.end + offset + start), ..warning });
+		warnings.extend(inner);
+	}
+}
+
+fn is_operand(kind: LexemeKind) -> bool {
+	matches!(
+		kind,
+		LexemeKind::Number(_) | LexemeKind::Duration(_) | LexemeKind::TapeRef(_) | LexemeKind::Identifier | LexemeKind::Form
+			| LexemeKind::Placeholder
+	)
+}
+
+/// Whether a `-` after `previous` negates rather than subtracts.
+fn is_unary(previous: Option<LexemeKind>) -> bool {
+	matches!(previous, None | Some(LexemeKind::Operator(_) | LexemeKind::OpenParen | LexemeKind::Separator))
+}
+
+impl Context {
+	/// The lints of `statement` that are not allowed, nothing when linting is off. The target of an
+	/// assignment is left out and the spans are in the whole statement.
+	pub fn lint(&self, statement: &str) -> Vec<LintWarning> {
+		if !self.lint {
+			return Vec::new();
+		}
+		let (_, expression) = split_assignment(statement);
+		let offset = statement.len() - expression.len();
+		lint_with(expression, self)
+			.into_iter()
+			.filter(|warning| !self.allowed_lints[warning.lint as usize])
+			.map(|warning| LintWarning { span: Span::new(warning.span.start + offset, warning.span.end + offset), ..warning })
+			.collect()
+	}
+	pub fn lint_enabled(&self) -> bool {
+		self.lint
+	}
+	pub fn set_lint(&mut self, on: bool) {
+		self.lint = on;
+	}
+	pub fn is_lint_allowed(&self, lint: Lint) -> bool {
+		self.allowed_lints[lint as usize]
+	}
+	/// Stops [`Context::lint`] reporting `lint`, or starts again.
+	pub fn allow_lint(&mut self, lint: Lint, allowed: bool) {
+		self.allowed_lints[lint as usize] = allowed;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::string::{String, ToString};
+	use super::*;
+
+	fn lints(expression: &str) -> Vec<(&'static str, usize, usize)> {
+		lint(expression).into_iter().map(|warning| (warning.lint.name(), warning.span.start, warning.span.end)).collect()
+	}
+
+	#[test]
+	fn each_lint_is_found_with_its_span() {
+		assert_eq!(lints("2 * (3)"), [("redundant-parens", 4, 7)]);
+		assert_eq!(lints("(1 + 2)"), [("redundant-parens", 0, 7)]);
+		assert_eq!(lints("max((1 + 2), 3)"), [("redundant-parens", 4, 11)]);
+		assert_eq!(lints("--x"), [("double-negation", 0, 2)]);
+		assert_eq!(lints("1 + -(-x)"), [("double-negation", 4, 7)]);
+		assert_eq!(lints("x / 0"), [("division-by-zero", 2, 5)]);
+		assert_eq!(lints("x / 0h + (2) / 0.0"), [("division-by-zero", 2, 6), ("redundant-parens", 9, 12), ("division-by-zero", 13, 18)]);
+		assert_eq!(lints("let x = 2 in y"), [("unused-let", 4, 5)]);
+		assert_eq!(lints("1 + (let r = 2 in let s = r / 0 in 3)"), [("unused-let", 22, 23), ("division-by-zero", 28, 31)]);
+	}
+
+	#[test]
+	fn valid_uses_are_not_lints() {
+		for expression in [
+			"(1 + 2) * 3", "max(1, 2)", "sqrt(2)", "1 - -x", "x - (-1)", "x / 0 ^ 0", "x / 0.5", "2 * (3 + 4)",
+			"let x = 2 in x * x", "let pi = 3 in pi", "(let x = 2 in x) + 1", "let x = 2 in sum_over(k, 1, 3, k * x)"
+		] {
+			assert!(lint(expression).is_empty(), "{}: {:?}", expression, lints(expression));
+		}
+		assert!(lint("2 + @ (3)").is_empty());
+		assert_eq!(Lint::ALL.map(|lint| Lint::by_name(lint.name())), Lint::ALL.map(Some));
+		assert_eq!(Lint::by_name("float-eq"), None);
+	}
+
+	#[test]
+	fn a_context_lints_when_enabled_and_suppresses_what_it_allows() {
+		let mut context = Context::new();
+		assert!(context.lint("x = --2 / 0").is_empty());
+		context.set_lint(true);
+		let warnings: Vec<String> = context.lint("x = --2 / 0").iter().map(LintWarning::to_string).collect();
+		assert_eq!(warnings, ["a negation of a negation at position 4 [double-negation]", "division by zero at position 8 [division-by-zero]"]);
+		context.allow_lint(Lint::DoubleNegation, true);
+		assert!(context.is_lint_allowed(Lint::DoubleNegation));
+		assert_eq!(context.lint("x = --2 / 0").iter().map(|warning| warning.lint).collect::<Vec<_>>(), [Lint::DivisionByZero]);
+		// Lints never change what is evaluated
+		assert_eq!(context.execute("x = --2 / 4").unwrap().value(), 0.5);
+	}
+}
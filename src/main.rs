@@ -1,231 +1,702 @@
-use std::clone::Clone;
-use std::collections::VecDeque;
-use std::fmt::Display;
-use std::io;
-use std::iter::Iterator;
-
-macro_rules! map {
-	() => {
-		HashMap::new()
-	};
-	($($x:expr),+ $(,)?) => {
-		[
-			$($x),+
-		].iter().collect();
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, IsTerminal, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
+
+use shunting_yard::cache::DEFAULT_CAPACITY;
+use shunting_yard::display::DEFAULT_MAX_DENOMINATOR;
+use shunting_yard::{assigned_name, clean_line, contain_panic, eval_lines_with, execute_batch_jobs, split_assignment, radix_note, to_latex, BatchOptions, BatchStats, Context, IterationStop, EvalError, EvalOutcome, SweepRange, Timing, Trace};
+
+use cli::{parse_args, usage_text, Options, Subcommand, FROZEN_CLOCK_VAR};
+use render::{render_error, render_error_in, render_trace, Style, Styles};
+use config::ConfigFile;
+use result_format::Fields;
+
+mod cli;
+mod command;
+mod completion;
+mod config;
+mod csv;
+mod help;
+mod json;
+mod line_editor;
+mod render;
+mod repl;
+mod result_format;
+mod serve;
+
+/// Executes `statement`, writing the evaluation trace to `err` first when running verbosely. The
+/// timing is only measured with `--time`; the caller reports it once the result has been written.
+fn execute<E: Write>(
+	context: &mut Context, statement: &str, options: &Options, err: &mut E
+) -> io::Result<(Result<EvalOutcome, EvalError>, Timing)> {
+	let mut trace = Trace::default();
+	let mut timing = Timing::default();
+	let result = contain_panic(|| context.execute_instrumented(
+		statement, options.verbose.then_some(&mut trace), options.time.then_some(&mut timing)
+	));
+	if options.verbose {
+		writeln!(err, "{}", render_trace(&trace))?;
+	}
+	if options.frozen_clock {
+		timing = timing.without_durations();
+	}
+	Ok((result, timing))
+}
+
+fn write_timing<E: Write>(timing: &Timing, options: &Options, err: &mut E) -> io::Result<()> {
+	if options.time {
+		writeln!(err, "{}", timing)?;
+	}
+	Ok(())
+}
+
+/// The note goes to `err` so piped results stay parseable.
+fn write_radix_note<E: Write>(context: &Context, value: f64, styles: Styles, err: &mut E) -> io::Result<()> {
+	match radix_note(value, context.display()) {
+		Some(note) => writeln!(err, "{}", styles.err.warning(&note)),
+		None => Ok(())
+	}
+}
+
+/// Writes the lints of `statement` and what its evaluation warned about, such as a literal that lost
+/// digits.
+fn write_warnings<E: Write>(context: &Context, statement: &str, styles: Styles, err: &mut E) -> io::Result<()> {
+	for warning in context.lint(statement) {
+		writeln!(err, "{}", styles.err.warning(&format!("lint: {}", warning)))?;
+	}
+	for violation in context.warnings() {
+		writeln!(err, "{}", styles.err.warning(&format!("warning: {}", violation)))?;
+	}
+	Ok(())
+}
+
+/// Writes the value of every round of `--iterate`, and why it stopped early if it did.
+fn run_iteration<W: Write, E: Write>(
+	context: &mut Context, expression: &str, rounds: usize, options: &Options, styles: Styles, out: &mut W, err: &mut E
+) -> io::Result<bool> {
+	let iteration = match context.iterate(expression, rounds, options.until_delta) {
+		Ok(iteration) => iteration,
+		Err(e) => {
+			writeln!(err, "{}", render_error(expression, &e, styles.err))?;
+			return Ok(false);
+		}
 	};
+	for value in &iteration.values {
+		writeln!(out, "{}", styles.out.result(&context.format(*value)))?;
+	}
+	match iteration.stop {
+		IterationStop::Completed => {}
+		IterationStop::Converged => writeln!(err, "converged after {} rounds", iteration.values.len())?,
+		IterationStop::NonFinite => writeln!(err, "{}", styles.err.warning("stopped, the value is no longer finite"))?
+	}
+	Ok(true)
 }
 
-#[derive(Copy, Clone, Debug)]
-pub struct Operator {
-	pub symbol: char,
-	pub argc: usize,
-	pub precedence: usize,
-	resolver: fn(args: &Vec<f64>) -> f64
-}
-impl Operator {
-	pub const MAP: [(char, Self); 4] = [
-		('/', Self { symbol: '/', argc: 2, precedence: 4, resolver: |args| {
-			args.get(1).unwrap() / args.get(0).unwrap()
-		}}),
-		('*', Self { symbol: '*', argc: 2, precedence: 3, resolver: |args| {
-			args.get(1).unwrap() * args.get(0).unwrap()
-		} }),
-		('+', Self { symbol: '+', argc: 2, precedence: 2, resolver: |args| {
-			args.get(1).unwrap() + args.get(0).unwrap()
-		} }),
-		('-', Self { symbol: '-', argc: 2, precedence: 1, resolver: |args| {
-			args.get(1).unwrap() - args.get(0).unwrap()
-		} })
-	];
-
-	pub fn by_char(c: char) -> Option<Self> {
-		if let Ok(result) = 
-			Self::MAP.binary_search_by(|(k, _)| k.cmp(&c)).map(|x| Self::MAP[x].1) {
-			return Some(result);
-		}
-		return None;
-	}
-	pub fn resolve(&self, args: &Vec<f64>) -> f64 {
-		(self.resolver)(args)
-	}
-}
-
-#[derive(Copy, Clone, Debug)]
-enum Token {
-	NumericLiteral(f64),
-	Operator(Operator),
-	OpenParen
-}
-
-#[derive(Copy, Clone, Debug)]
-enum EvalError {
-	InvalidCharacter,
-	UnexpectedToken(Token),
-	DuplicateDecimal,
-	NumberParseError,
-	MismatchedParenthesis,
-	NotEnoughArguments,
-	NoResult
-}
-
-fn eval(expression: &str) -> Result<f64, EvalError> {
-	let mut holding = VecDeque::new();
-	let mut output = VecDeque::new();
-	let mut temp = String::new();
-	let mut last_token = None;
-
-	for c in expression.chars() {
-		if c.is_digit(10) {
-			temp.push(c);
-		} else if c == '.' {
-			if temp.contains('.') {
-				return Err(EvalError::DuplicateDecimal);
+/// `--latex`: writes the rendering of an expression, or of the right-hand side of an assignment,
+/// which is still executed so that later expressions can use the variable.
+fn run_latex<W: Write, E: Write>(
+	context: &mut Context, statement: &str, styles: Styles, out: &mut W, err: &mut E
+) -> io::Result<bool> {
+	let (name, expression) = split_assignment(statement);
+	let latex = match context.to_ast(expression) {
+		Ok(ast) => to_latex(&ast),
+		Err(e) => {
+			writeln!(err, "{}", render_error(expression, &e, styles.err))?;
+			return Ok(false);
+		}
+	};
+	match name {
+		Some(name) => {
+			if let Err(e) = context.execute(statement) {
+				writeln!(err, "{}", render_error(statement, &e, styles.err))?;
+				return Ok(false);
 			}
-			temp.push(c);
+			writeln!(out, "{} = {}", name, latex)?;
+		}
+		None => writeln!(out, "{}", latex)?
+	}
+	Ok(true)
+}
+
+/// Evaluates every `-e` in order, reporting each that fails. Returns whether all succeeded; with
+/// `--fail-fast` the first that fails is the last evaluated.
+fn run_expressions<W: Write, E: Write>(
+	context: &mut Context, options: &Options, styles: Styles, out: &mut W, err: &mut E
+) -> io::Result<bool> {
+	if let Some(start) = options.start {
+		context.set_answer(start);
+	}
+	let mut all = true;
+	for (index, expression) in options.expressions.iter().enumerate() {
+		let success = if options.latex {
+			run_latex(context, expression, styles, out, err)?
+		} else if let Some(rounds) = options.iterate {
+			run_iteration(context, expression, rounds, options, styles, out, err)?
 		} else {
-			if !temp.is_empty() {
-				let val = temp.parse::<f64>();
-				if val.is_err() {
-					return Err(EvalError::NumberParseError);
-				}
-				output.push_back(Token::NumericLiteral(val.unwrap()));
-				last_token = output.back().cloned();
-				temp.clear();
+			run_expression(context, (index, expression), options, styles, out, err)?
+		};
+		all &= success;
+		if !success && options.fail_fast {
+			break;
+		}
+	}
+	Ok(all)
+}
+
+/// One `-e`, the `index`th. Returns whether it succeeded.
+fn run_expression<W: Write, E: Write>(
+	context: &mut Context, (index, expression): (usize, &str), options: &Options, styles: Styles, out: &mut W, err: &mut E
+) -> io::Result<bool> {
+	let (result, timing) = execute(context, expression, options, err)?;
+	let time = if options.time { format!("{}µs", (timing.parse + timing.eval).as_micros()) } else { String::new() };
+	let render = |name: Option<&str>, value: f64| match (&options.template, name) {
+		(Some(template), _) => template.render(&Fields {
+			input: &styles.out.expression(name.unwrap_or(expression)),
+			result: &styles.out.result(&context.format_result(value)),
+			index: index + 1,
+			time: &time
+		}),
+		(None, Some(name)) => format!("{} = {}", name, styles.out.result(&context.format_result(value))),
+		(None, None) => styles.out.result(&context.format_result(value))
+	};
+	let success = match result {
+		_ if options.json => {
+			writeln!(out, "{}", json::render_result(expression, &result, context))?;
+			result.is_ok()
+		}
+		Ok(EvalOutcome::Value(value)) => {
+			writeln!(out, "{}", render(None, value))?;
+			write_radix_note(context, value, styles, err)?;
+			true
+		}
+		Ok(EvalOutcome::Solved(name, value)) => {
+			writeln!(out, "{}", render(Some(&name), value))?;
+			true
+		}
+		Ok(EvalOutcome::Assigned(name, value)) => {
+			if options.verbose {
+				writeln!(out, "{}", render(Some(&name), value))?;
 			}
-			if c == '(' {
-				holding.push_front(Token::OpenParen);
-				last_token = holding.front().cloned();
-			} else if c == ')' {
-				while !holding.is_empty() {
-					if let Some(Token::OpenParen) = holding.front() {
-						break;
-					}
-					output.push_back(holding.pop_front().unwrap());
-				}
-				if holding.is_empty() {
-					return Err(EvalError::MismatchedParenthesis);
-				}
-				last_token = holding.front().cloned();
-				if let Some(Token::OpenParen) = holding.front().cloned() {
-					holding.pop_front();
-				}
-			} else if !c.is_whitespace() {
-				if let Some(mut op) = Operator::by_char(c) {
-					if op.symbol == '+' || op.symbol == '-' {
-						match last_token {
-							Some(Token::Operator(_)) | None => {
-								op.argc = 1;
-								op.precedence = 255;
-							}
-							_ => { /* Do nothing */ }
-						}
-					}
-					while !holding.is_empty() {
-						if let Some(Token::OpenParen) = holding.front() {
-							break;
-						}
-						if let Some(Token::Operator(op_prev)) = holding.front() {
-							if op_prev.precedence >= op.precedence {
-								output.push_back(holding.pop_front().unwrap());
-							} else {
-								break;
-							}
-						}
-					}
-					holding.push_front(Token::Operator(op));
-					last_token = holding.front().cloned();
-				} else {
-					return Err(EvalError::InvalidCharacter);
-				}
+			true
+		}
+		Ok(EvalOutcome::Multiple(values)) => {
+			writeln!(out, "{}", styles.out.result(&context.format_list(values)))?;
+			true
+		}
+		Ok(EvalOutcome::AssignedMultiple(assigned)) => {
+			if options.verbose {
+				let (names, values): (Vec<String>, Vec<f64>) = assigned.into_iter().unzip();
+				writeln!(out, "{} = {}", names.join(", "), styles.out.result(&context.format_list(values)))?;
 			}
+			true
+		}
+		Err(e) => {
+			writeln!(err, "{}", render_error_in(expression, &e, styles.err, context))?;
+			false
 		}
+	};
+	write_warnings(context, expression, styles, err)?;
+	write_timing(&timing, options, err)?;
+	Ok(success)
+}
+
+/// `syc sweep`: the expression at every combination of the `--var` ranges, as a table with a column
+/// per variable and one for the result, or one JSON object per point. A point that fails shows its
+/// error in the table and the others are still evaluated.
+fn run_sweep<W: Write>(context: &Context, options: &Options, styles: Styles, out: &mut W) -> io::Result<bool> {
+	let expression = &options.expressions[0];
+	let axes: Vec<(&str, SweepRange)> = options.sweeps.iter().map(|(name, range)| (name.as_str(), *range)).collect();
+	let names: Vec<&str> = axes.iter().map(|(name, _)| *name).collect();
+	let points = context.sweep_grid(expression, &axes);
+	let success = points.iter().all(|(_, result)| result.is_ok());
+	if options.json {
+		for (values, result) in &points {
+			writeln!(out, "{}", json::render_point(&names, values, result, context))?;
+		}
+		return Ok(success);
 	}
+	let rows: Vec<(Vec<String>, Result<String, String>)> = points.iter().map(|(values, result)| {
+		let values = values.iter().map(|value| context.format(*value)).collect();
+		let result = match result {
+			Ok(value) => Ok(context.format(*value)),
+			Err(e) => Err(match e.did_you_mean(context) {
+				Some(question) => format!("error: {}, {}", e, question),
+				None => format!("error: {}", e)
+			})
+		};
+		(values, result)
+	}).collect();
+	// Padded before styling, escape codes have no width
+	let widths: Vec<usize> = names.iter().enumerate()
+		.map(|(column, name)| rows.iter().map(|(values, _)| values[column].chars().count()).fold(name.chars().count(), usize::max))
+		.collect();
+	let pad = |text: &str, width: usize| format!("{:<width$}", text, width = width);
+	let header: Vec<String> = names.iter().zip(&widths).map(|(name, width)| pad(name, *width)).collect();
+	writeln!(out, "{}  {}", header.join("  "), styles.out.expression(expression.trim()))?;
+	for (values, result) in &rows {
+		let values: Vec<String> = values.iter().zip(&widths).map(|(value, width)| pad(value, *width)).collect();
+		let result = match result {
+			Ok(text) => styles.out.result(text),
+			Err(text) => styles.out.error(text)
+		};
+		writeln!(out, "{}  {}", values.join("  "), result)?;
+	}
+	Ok(success)
+}
 
-	if !temp.is_empty() {
-		let val = temp.parse::<f64>();
-		if val.is_err() {
-			return Err(EvalError::NumberParseError);
+/// `--summary`: how the lines of one input went, after its results. Each line is prefixed with the
+/// name of the input when it has one.
+fn write_summary<S: Write>(source: Option<&str>, stats: &BatchStats, context: &Context, summary: &mut S) -> io::Result<()> {
+	let prefix = source.map_or(String::new(), |name| format!("{}: ", name));
+	writeln!(summary, "{}{} lines, {} ok, {} failed", prefix, stats.lines, stats.ok, stats.errors)?;
+	if let (Some(min), Some(max), Some(mean)) = (stats.min, stats.max, stats.mean()) {
+		writeln!(summary, "{}min {}, max {}, mean {}", prefix, context.format(min), context.format(max), context.format(mean))?;
+	}
+	if let Some(elapsed) = stats.elapsed {
+		writeln!(summary, "{}total time {}µs", prefix, elapsed.as_micros())?;
+	}
+	for slow in &stats.slowest {
+		writeln!(summary, "{}slow: line {} took {}µs: {}", prefix, slow.number, slow.time.as_micros(), slow.line)?;
+	}
+	Ok(())
+}
+
+/// Where batch lines come from and how their results are written.
+#[derive(Copy, Clone)]
+struct Batch<'a> {
+	source: Option<&'a str>,
+	options: &'a Options,
+	styles: Styles
+}
+
+/// Evaluates every non-empty line of `reader`. Lines read from a named file are echoed alongside
+/// their result and errors are reported as `name:line:`; without a name (piped stdin) only the
+/// bare results are written so the output stays pipeline-friendly.
+fn run_lines<R: BufRead, W: Write, E: Write>(
+	source: Option<&str>, reader: R, context: &mut Context, options: &Options, styles: Styles, out: &mut W, err: &mut E
+) -> io::Result<BatchStats> {
+	let batch = Batch { source, options, styles };
+	if let Some(jobs) = options.jobs {
+		return run_parallel(batch, reader, context, jobs, out, err);
+	}
+	let batch_options = BatchOptions {
+		fail_fast: options.fail_fast,
+		progress: options.progress,
+		timed: options.summary,
+		frozen_clock: options.frozen_clock
+	};
+	eval_lines_with(reader, out, context, &batch_options, |out, context, number, line| {
+		// Keep diagnostics on stderr next to the results they belong to
+		if options.verbose {
+			out.flush()?;
+		}
+		let (result, timing) = execute(context, line, options, err)?;
+		let success = write_line_result(batch, (number, line), result, context, out, err)?;
+		if options.time {
+			out.flush()?;
 		}
-		output.push_back(Token::NumericLiteral(val.unwrap()));
-		temp.clear();
+		write_timing(&timing, options, err)?;
+		Ok(success)
+	})
+}
+
+/// `--jobs`: evaluates the lines of `reader` on several threads and writes the results in input
+/// order, as [`run_lines`] would. Lines must not depend on each other, so assignments are refused
+/// before evaluating any.
+fn run_parallel<R: BufRead, W: Write, E: Write>(
+	batch: Batch, reader: R, context: &Context, jobs: usize, out: &mut W, err: &mut E
+) -> io::Result<BatchStats> {
+	let start = batch.options.summary.then(Instant::now);
+	let lines = reader.lines().collect::<io::Result<Vec<String>>>()?;
+	let mut stats = BatchStats { lines: lines.len(), ..BatchStats::default() };
+	let numbered: Vec<(usize, &str)> = lines.iter().enumerate()
+		.map(|(index, line)| (index + 1, clean_line(line, index == 0)))
+		.filter(|(_, line)| !line.trim().is_empty())
+		.collect();
+	if let Some(&(number, line)) = numbered.iter().find(|(_, line)| assigned_name(line, context).is_some()) {
+		let message = batch.styles.err.error("assignments cannot be evaluated in parallel, run without --jobs");
+		write_line_error(batch, number, &message, err)?;
+		stats.record(number, line.trim(), None, None);
+		return Ok(stats);
 	}
+	let expressions: Vec<&str> = numbered.iter().map(|(_, line)| *line).collect();
+	// Each line writes into its own buffers, which are copied out in order
+	let results = execute_batch_jobs(&expressions, context, jobs, |context, index, line| {
+		let (mut line_out, mut line_err) = (Vec::new(), Vec::new());
+		let written = execute(context, line, batch.options, &mut line_err).and_then(|(result, timing)| {
+			let success = write_line_result(batch, (numbered[index].0, line), result, context, &mut line_out, &mut line_err)?;
+			write_timing(&timing, batch.options, &mut line_err)?;
+			Ok(success.then(|| context.answer().unwrap_or(f64::NAN)))
+		});
+		(line_out, line_err, written)
+	});
+	let mut out = io::BufWriter::new(out);
+	for ((number, line), (line_out, line_err, written)) in numbered.iter().zip(results) {
+		out.write_all(&line_out)?;
+		if !line_err.is_empty() {
+			out.flush()?;
+			err.write_all(&line_err)?;
+		}
+		let value = written?;
+		stats.record(*number, line.trim(), value, None);
+		if value.is_none() && batch.options.fail_fast {
+			break;
+		}
+	}
+	out.flush()?;
+	stats.elapsed = start.map(|start| if batch.options.frozen_clock { Duration::ZERO } else { start.elapsed() });
+	Ok(stats)
+}
 
-	while !holding.is_empty() {
-		if let Some(tok) = holding.pop_front() {
-			output.push_back(tok);
+/// Writes the outcome of a numbered batch line. Returns whether it succeeded.
+fn write_line_result<W: Write, E: Write>(
+	batch: Batch, (number, line): (usize, &str), result: Result<EvalOutcome, EvalError>, context: &Context,
+	out: &mut W, err: &mut E
+) -> io::Result<bool> {
+	let styles = batch.styles;
+	if batch.options.json {
+		writeln!(out, "{}", json::render_result(line, &result, context))?;
+		return Ok(result.is_ok());
+	}
+	match (result, batch.source) {
+		(Ok(EvalOutcome::Value(value)), Some(_)) => {
+			writeln!(out, "{} = {}", styles.out.expression(line.trim()), styles.out.result(&context.format_result(value)))?;
+			write_radix_note(context, value, styles, err)?;
+		}
+		(Ok(EvalOutcome::Assigned(name, value) | EvalOutcome::Solved(name, value)), Some(_))
+		| (Ok(EvalOutcome::Solved(name, value)), None) => {
+			writeln!(out, "{} = {}", name, styles.out.result(&context.format_result(value)))?;
+		}
+		(Ok(EvalOutcome::Value(value)), None) => {
+			writeln!(out, "{}", styles.out.result(&context.format_result(value)))?;
+			write_radix_note(context, value, styles, err)?;
+		}
+		(Ok(EvalOutcome::Multiple(values)), Some(_)) => {
+			writeln!(out, "{} = {}", styles.out.expression(line.trim()), styles.out.result(&context.format_list(values)))?;
+		}
+		(Ok(EvalOutcome::AssignedMultiple(assigned)), Some(_)) => {
+			let (names, values): (Vec<String>, Vec<f64>) = assigned.into_iter().unzip();
+			writeln!(out, "{} = {}", names.join(", "), styles.out.result(&context.format_list(values)))?;
+		}
+		(Ok(EvalOutcome::Multiple(values)), None) => {
+			writeln!(out, "{}", styles.out.result(&context.format_list(values)))?;
+		}
+		(Ok(EvalOutcome::Assigned(..) | EvalOutcome::AssignedMultiple(_)), None) => {}
+		(Err(e), _) => {
+			out.flush()?;
+			let message = match e.did_you_mean(context) {
+				Some(question) => format!("{}, {}", e, question),
+				None => e.to_string()
+			};
+			write_line_error(batch, number, &styles.err.error(&message), err)?;
+			return Ok(false);
 		}
 	}
+	write_warnings(context, line, styles, err)?;
+	Ok(true)
+}
 
-	for tok in &output {
-		match tok {
-			Token::NumericLiteral(num) => {
-				print!("{} ", *num)
-			}
-			Token::Operator(op) => {
-				print!("{} ", op.symbol);
+fn write_line_error<E: Write>(batch: Batch, number: usize, message: &str, err: &mut E) -> io::Result<()> {
+	match batch.source {
+		Some(name) => writeln!(err, "{}:{}: {}", name, number, message),
+		None => writeln!(err, "{}: {}", number, message)
+	}
+}
+
+/// Evaluates the expression column of every CSV record and writes the records back with the result
+/// appended. Failed rows get `error: ...` as their result unless failing fast.
+fn run_csv<W: Write, E: Write>(
+	source: &str, text: &str, context: &Context, options: &Options, out: &mut W, err: &mut E
+) -> io::Result<bool> {
+	let records = match csv::parse_records(text) {
+		Ok(records) => records,
+		Err(message) => {
+			writeln!(err, "{}: {}", source, message)?;
+			return Ok(false);
+		}
+	};
+	let expr_col = options.expr_col.unwrap_or(1);
+	let mut success = true;
+	for (index, mut record) in records.into_iter().enumerate() {
+		if index == 0 && options.header {
+			record.push("result".to_string());
+			writeln!(out, "{}", csv::format_record(&record))?;
+			continue;
+		}
+		let result = match csv::evaluate_record(&record, expr_col, context) {
+			Ok(result) => result,
+			Err(message) if options.fail_fast => {
+				writeln!(err, "{}:{}: {}", source, index + 1, message)?;
+				return Ok(false);
 			}
-			Token::OpenParen => {
-				print!("( ");
+			Err(message) => {
+				success = false;
+				format!("error: {}", message)
 			}
+		};
+		record.push(result);
+		writeln!(out, "{}", csv::format_record(&record))?;
+	}
+	Ok(success)
+}
+
+/// Runs the calculator as configured by `options`. Returns whether every expression evaluated
+/// successfully, or a message for errors that prevent running at all, such as unreadable files.
+fn run(options: &Options) -> Result<bool, String> {
+	if options.help {
+		print!("{}", usage_text());
+		return Ok(true);
+	}
+	let mut context = Context::new();
+	context.set_cache_capacity(options.cache_size.unwrap_or(DEFAULT_CAPACITY));
+	if let Some(size) = options.tape_size {
+		context.tape_mut().set_limit(size);
+	}
+	context.set_budget(options.budget);
+	if let Some(precision) = options.precision {
+		context.display_mut().precision = precision;
+	}
+	if let Some(notation) = options.notation {
+		context.display_mut().notation = notation;
+	}
+	if let Some(durations) = options.durations {
+		context.display_mut().durations = durations;
+	}
+	if options.fraction {
+		context.display_mut().fraction = Some(DEFAULT_MAX_DENOMINATOR);
+	}
+	if let Some(radix) = options.radix {
+		context.display_mut().radix = radix;
+	}
+	if let Some(rounding) = options.rounding {
+		context.set_rounding(rounding);
+	}
+	context.set_uncertainty(options.uncertainty);
+	context.set_money(options.money);
+	context.set_lint(options.lint);
+	context.set_strict(options.strict);
+	context.display_mut().locale = options.locale;
+	if let Some(angle) = options.angle {
+		context.set_angle_mode(angle);
+	}
+	for statement in &options.startup {
+		if let Err(e) = context.execute(statement) {
+			eprintln!("syc: startup expression '{}': {}", statement, e);
 		}
 	}
-	println!();
-
-	let mut solve = VecDeque::new();
-	for tok in output {
-		match tok {
-			Token::NumericLiteral(num) => {
-				solve.push_front(num);
-			},
-			Token::Operator(op) => {
-				if solve.len() < op.argc {
-					return Err(EvalError::NotEnoughArguments);
-				}
-
-				if op.symbol == '+' && op.argc == 1 {
-					/* Nothing to do */
-				} else if op.symbol == '-' && op.argc == 1 {
-					let value = solve.pop_front().unwrap();
-					solve.push_front(-value);
-				} else {
-					let mut args = Vec::with_capacity(op.argc);
-					for _ in 0 .. op.argc {
-						args.push(solve.pop_front().unwrap());
-					}
-					if args.len() < op.argc {
-						return Err(EvalError::NotEnoughArguments);
-					}
-					solve.push_front(op.resolve(&args));
-					args.clear();
-				}
-			},
-			Token::OpenParen => {
-				return Err(EvalError::UnexpectedToken(tok));
+	for define in &options.defines {
+		context.execute(define).map_err(|e| format!("--define {}: {}", define, e))?;
+	}
+	if options.serve_stdio {
+		return serve::serve(io::stdin().lock(), io::stdout().lock(), &mut context)
+			.map(|_| true)
+			.map_err(|e| format!("cannot serve requests: {}", e));
+	}
+	if let Some(path) = &options.csv {
+		let text = fs::read_to_string(path).map_err(|e| format!("cannot read '{}': {}", path, e))?;
+		let mut out: Box<dyn Write> = match &options.out {
+			Some(out_path) => {
+				let file = File::create(out_path).map_err(|e| format!("cannot create '{}': {}", out_path, e))?;
+				Box::new(io::BufWriter::new(file))
 			}
+			None => Box::new(io::stdout().lock())
+		};
+		return run_csv(path, &text, &context, options, &mut out, &mut io::stderr().lock())
+			.and_then(|success| out.flush().map(|_| success))
+			.map_err(|e| format!("cannot write results: {}", e));
+	}
+	let interactive = options.expressions.is_empty() && options.files.is_empty();
+	let dumb = options.dumb_terminal
+		|| env::var_os("TERM").is_some_and(|term| term == "dumb")
+		|| (!io::stdin().is_terminal() && io::stdout().is_terminal());
+	let repl = match options.command {
+		Some(Subcommand::Repl) if !io::stdin().is_terminal() && !dumb => {
+			return Err("'syc repl' needs a terminal, use 'syc run' for piped input".to_string());
+		}
+		Some(Subcommand::Repl) => true,
+		Some(_) => false,
+		None => interactive && (io::stdin().is_terminal() || options.dumb_terminal)
+	};
+	if repl {
+		if options.json {
+			return Err("--json needs expressions, files or piped input".to_string());
 		}
+		let history_path = if options.no_history {
+			None
+		} else {
+			options.history_path.clone().or_else(line_editor::default_history_path)
+		};
+		repl::run(&mut context, repl::Config {
+			history_path,
+			dumb,
+			style: Style::detect(io::stdout().is_terminal() && !dumb, options.no_color),
+			debug: options.verbose,
+			time: options.time,
+			frozen_clock: options.frozen_clock,
+			prompt: options.prompt.clone().unwrap_or_else(|| repl::DEFAULT_PROMPT.to_string()),
+			template: options.template.clone().unwrap_or_default()
+		});
+		return Ok(true);
 	}
 
-	if !solve.is_empty() {
-		Ok(*solve.front().unwrap())
-	} else {
-		Err(EvalError::NoResult)
+	let stdout = io::stdout();
+	let stderr = io::stderr();
+	let mut out = stdout.lock();
+	let mut err = stderr.lock();
+	let styles = Styles {
+		out: Style::detect(out.is_terminal(), options.no_color),
+		err: Style::detect(err.is_terminal(), options.no_color)
+	};
+	if options.command == Some(Subcommand::Sweep) {
+		return run_sweep(&context, options, styles, &mut out).map_err(|e| format!("cannot write results: {}", e));
+	}
+	let mut summary: Option<Box<dyn Write>> = match &options.summary_file {
+		Some(path) => Some(Box::new(File::create(path).map_err(|e| format!("cannot create '{}': {}", path, e))?)),
+		None if options.summary => Some(Box::new(io::stderr())),
+		None => None
+	};
+	let mut summarize = |source: Option<&str>, stats: &BatchStats, context: &Context| match summary.as_mut() {
+		Some(summary) => write_summary(source, stats, context, summary).map_err(|e| format!("cannot write the summary: {}", e)),
+		None => Ok(())
+	};
+	if interactive {
+		let stats = run_lines(None, io::stdin().lock(), &mut context, options, styles, &mut out, &mut err)
+			.map_err(|e| format!("cannot read stdin: {}", e))?;
+		summarize(None, &stats, &context)?;
+		return Ok(stats.errors == 0);
 	}
-}
 
-fn main() {
-	loop {
-		let stdin = io::stdin();
-		let mut expr = String::new();
-		if stdin.read_line(&mut expr).is_ok() {
-			if expr == "end" {
-				break;
-			}
-			match eval(expr.as_str()) {
-				Ok(result) => { println!("{} = {}", expr.trim_end(), result); }
-				Err(err) => { println!("Error: {:?}", err); }
-			}
+	let mut success = run_expressions(&mut context, options, styles, &mut out, &mut err)
+		.map_err(|e| format!("cannot write results: {}", e))?;
+	for name in &options.files {
+		if !success && options.fail_fast {
+			break;
+		}
+		let stats = if name == "-" {
+			run_lines(None, io::stdin().lock(), &mut context, options, styles, &mut out, &mut err)
+				.map_err(|e| format!("cannot read stdin: {}", e))?
 		} else {
-			println!("Input error!");
+			let file = File::open(name).map_err(|e| format!("cannot open '{}': {}", name, e))?;
+			run_lines(Some(name), BufReader::new(file), &mut context, options, styles, &mut out, &mut err)
+				.map_err(|e| format!("cannot read '{}': {}", name, e))?
+		};
+		summarize(Some(name.as_str()).filter(|name| *name != "-"), &stats, &context)?;
+		success &= stats.errors == 0;
+	}
+	Ok(success)
+}
+
+/// Fills in everything the command line left unset from the configuration file.
+fn merge_config(mut options: Options, config: ConfigFile) -> Options {
+	options.precision = options.precision.or(config.precision);
+	options.notation = options.notation.or(config.format);
+	options.angle = options.angle.or(config.angle);
+	options.no_color |= config.color == Some(false);
+	options.prompt = options.prompt.or(config.prompt);
+	options.history_path = config.history;
+	options.startup = config.startup;
+	options
+}
+
+/// Reads the file given with `--config`, or the default one if it exists. Invalid keys are reported
+/// and skipped; only an unreadable `--config` file is an error.
+fn load_config(options: &Options) -> Result<ConfigFile, String> {
+	let (path, explicit) = match &options.config {
+		Some(path) => (PathBuf::from(path), true),
+		None => match config::default_path() {
+			Some(path) => (path, false),
+			None => return Ok(ConfigFile::default())
+		}
+	};
+	let text = match fs::read_to_string(&path) {
+		Ok(text) => text,
+		Err(e) if explicit => return Err(format!("cannot read '{}': {}", path.display(), e)),
+		Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(ConfigFile::default()),
+		Err(e) => {
+			eprintln!("syc: cannot read '{}': {}", path.display(), e);
+			return Ok(ConfigFile::default());
 		}
+	};
+	let (config, warnings) = config::parse(&text);
+	for warning in warnings {
+		eprintln!("syc: {}: {}", path.display(), warning);
+	}
+	Ok(config)
+}
+
+/// Exits with 0 when everything evaluated, 1 for usage errors and unreadable input, and 2 when an
+/// expression failed to evaluate. The REPL always exits with 0.
+fn main() -> ExitCode {
+	let result = parse_args(env::args().skip(1))
+		.map(|mut options| {
+			options.frozen_clock = env::var_os(FROZEN_CLOCK_VAR).is_some_and(|value| !value.is_empty());
+			options
+		})
+		.and_then(|options| load_config(&options).map(|config| merge_config(options, config)))
+		.and_then(|options| run(&options));
+	match result {
+		Ok(true) => ExitCode::SUCCESS,
+		Ok(false) => ExitCode::from(2),
+		Err(message) => {
+			eprintln!("syc: {}", message);
+			ExitCode::from(1)
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use shunting_yard::{AngleMode, Precision};
+
+	/// Runs `input` as the lines of `source`, giving the stats, stdout and stderr.
+	fn run_input(source: Option<&str>, input: &str, options: &Options) -> (BatchStats, String, String) {
+		let (mut out, mut err) = (Vec::new(), Vec::new());
+		let stats = run_lines(source, input.as_bytes(), &mut Context::new(), options, Styles::default(), &mut out, &mut err).unwrap();
+		(stats, String::from_utf8(out).unwrap(), String::from_utf8(err).unwrap())
+	}
+
+	#[test]
+	fn a_file_is_echoed_with_its_errors_by_line() {
+		let input = "x = 2\n\n1 + #\n   \nx * 3\n";
+		let (stats, out, err) = run_input(Some("calc.txt"), input, &Options::default());
+		assert_eq!(out, "x = 2\nx * 3 = 6\n");
+		assert_eq!(err, "calc.txt:3: unexpected character '#'\n");
+		assert_eq!((stats.lines, stats.ok, stats.errors), (5, 2, 1));
+		let options = Options { fail_fast: true, ..Options::default() };
+		let (stats, out, _) = run_input(Some("calc.txt"), input, &options);
+		assert_eq!((out.as_str(), stats.errors), ("x = 2\n", 1));
+	}
+
+	#[test]
+	fn piped_lines_give_bare_results_until_the_end_of_input() {
+		let (stats, out, err) = run_input(None, "1+1\n2 *\n3*2\r\n", &Options::default());
+		assert_eq!(out, "2\n6\n");
+		assert_eq!(err, "2: incomplete expression: expected a value after '*'\n");
+		assert_eq!((stats.lines, stats.ok, stats.errors), (3, 2, 1));
+		let (stats, out, err) = run_input(None, "", &Options::default());
+		assert_eq!((stats.lines, out.as_str(), err.as_str()), (0, "", ""));
+	}
+
+	#[test]
+	fn flags_override_the_config_file() {
+		let config = ConfigFile {
+			precision: Some(Precision::Significant(6)),
+			angle: Some(AngleMode::Degrees),
+			color: Some(false),
+			prompt: Some("calc> ".to_string()),
+			startup: vec!["g = 9.81".to_string()],
+			..ConfigFile::default()
+		};
+		let options = Options { precision: Some(Precision::Full), prompt: Some("> ".to_string()), ..Options::default() };
+		let options = merge_config(options, config.clone());
+		assert_eq!((options.precision, options.angle), (Some(Precision::Full), Some(AngleMode::Degrees)));
+		assert_eq!((options.prompt.as_deref(), options.no_color), (Some("> "), true));
+		assert_eq!(options.startup, ["g = 9.81"]);
+		let options = merge_config(Options::default(), ConfigFile { color: Some(true), ..config });
+		assert_eq!((options.precision, options.no_color), (Some(Precision::Significant(6)), false));
 	}
 }
@@ -0,0 +1,242 @@
+use alloc::collections::VecDeque;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::budget::Budget;
+use crate::strict::report;
+use crate::{format_money, BinOp, Context, EvalError, Rounding, StrictRule, Token, Violation};
+
+/// Cents in a unit of currency.
+const SCALE: i128 = 100;
+/// Larger amounts are refused, a double no longer holds every cent of them.
+const MAX_CENTS: i128 = 1 << 53;
+
+/// Converts `value` to cents, rounding it to two decimals in `context`'s rounding mode. A value
+/// with more decimals is reported by [`StrictRule::MoneyRounding`].
+fn to_cents(value: f64, context: &Context, violations: &mut Vec<Violation>) -> Result<i128, EvalError> {
+	if !value.is_finite() {
+		return Err(EvalError::NotMoney(value.to_string()));
+	}
+	if value.abs() >= (MAX_CENTS / SCALE) as f64 {
+		return Err(EvalError::NotMoney("an amount of 2^53 cents or more".to_string()));
+	}
+	let rounded = context.rounding().round_to(value, 2);
+	let cents = (rounded * SCALE as f64).round() as i128;
+	if cents_to_f64(cents) != value {
+		let detail = format!("{} rounded to {}", value, show(cents, context));
+		report(StrictRule::MoneyRounding, None, Some(detail), context, violations)?;
+	}
+	Ok(cents)
+}
+
+/// A rate used as an amount, 12.5% as 0.13.
+fn round_rate(rate: i128, context: &Context, violations: &mut Vec<Violation>) -> Result<i128, EvalError> {
+	let cents = divide(rate, SCALE, context);
+	if cents * SCALE != rate {
+		let detail = format!("{}% rounded to {}", cents_to_f64(rate), show(cents, context));
+		report(StrictRule::MoneyRounding, None, Some(detail), context, violations)?;
+	}
+	Ok(cents)
+}
+
+fn show(cents: i128, context: &Context) -> String {
+	format_money(cents_to_f64(cents), &context.display().locale)
+}
+
+fn cents_to_f64(cents: i128) -> f64 {
+	cents as f64 / SCALE as f64
+}
+
+/// `numerator / denominator` rounded to a whole number in `context`'s rounding mode.
+fn divide(numerator: i128, denominator: i128, context: &Context) -> i128 {
+	let quotient = numerator / denominator;
+	let remainder = numerator % denominator;
+	if remainder == 0 {
+		return quotient;
+	}
+	// The quotient is truncated, so away from zero means one further from it
+	let away = if (numerator < 0) == (denominator < 0) { 1 } else { -1 };
+	let twice = (remainder * 2).abs();
+	let up = match context.rounding() {
+		Rounding::TowardZero => false,
+		Rounding::AwayFromZero => true,
+		Rounding::HalfUp => twice >= denominator.abs(),
+		Rounding::HalfEven => twice > denominator.abs() || (twice == denominator.abs() && quotient % 2 != 0)
+	};
+	if up { quotient + away } else { quotient }
+}
+
+/// A value on the stack: whole cents, or for `x%` a rate in hundredths of a cent, which is only
+/// rounded when it is used as an amount rather than applied to one.
+#[derive(Copy, Clone)]
+struct Value {
+	units: i128,
+	rate: bool
+}
+
+/// Reduces an RPN queue in money mode: every value is a whole number of cents, so `+` and `-` are
+/// exact. A product or quotient that does not come out in whole cents is rounded, and what was
+/// rounded off is reported by [`StrictRule::MoneyRounding`]: `100 / 3` is 33.33, leaving 0.01.
+/// Functions other than `abs`, `min` and `max`, powers and conversions have no money meaning.
+pub(crate) fn reduce(
+	rpn: VecDeque<Token>, context: &Context, budget: &Budget, violations: &mut Vec<Violation>
+) -> Result<f64, EvalError> {
+	budget.spend(rpn.len())?;
+	let unsupported = |what: String| Err(EvalError::NotMoney(what));
+	let mut solve: Vec<Value> = Vec::with_capacity(rpn.len());
+	for tok in rpn {
+		let argc = match tok {
+			Token::NumericLiteral(num) | Token::Uncertain(num, _) => {
+				solve.push(Value { units: to_cents(num, context, violations)?, rate: false });
+				continue;
+			}
+			Token::Duration(_) => return unsupported("a duration".to_string()),
+			Token::Tag(_) => continue,
+			Token::Operator(op) => op.argc,
+			Token::Function(func) => func.argc,
+			Token::Convert(..) | Token::Percent => 1,
+			Token::PercentChange(_) => 2,
+			Token::OpenParen | Token::CloseParen | Token::Comma | Token::Unit(_) => {
+				return Err(EvalError::UnexpectedToken(tok));
+			}
+		};
+		if solve.len() < argc {
+			return Err(EvalError::NotEnoughArguments);
+		}
+		let args: Vec<Value> = solve.drain(solve.len() - argc ..).collect();
+		let mut amount = |value: Value| if value.rate { round_rate(value.units, context, violations) } else { Ok(value.units) };
+		// The exact result over its scale: products of cents are in ten-thousandths
+		let (exact, scale) = match tok {
+			Token::Operator(op) if argc == 1 => {
+				let units = if op.symbol == '-' { -args[0].units } else { args[0].units };
+				solve.push(Value { units, ..args[0] });
+				continue;
+			}
+			Token::Percent => {
+				solve.push(Value { units: amount(args[0])?, rate: true });
+				continue;
+			}
+			Token::Operator(op) => match (op.op, args[0], args[1]) {
+				(BinOp::Mul, Value { units: lhs, rate: false }, Value { units: rhs, rate: true })
+				| (BinOp::Mul, Value { units: rhs, rate: true }, Value { units: lhs, rate: false }) => (lhs * rhs, SCALE * SCALE),
+				(BinOp::Div, Value { units: lhs, rate: false }, Value { units: rhs, rate: true }) if rhs != 0 => (lhs * SCALE * SCALE, rhs),
+				(BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Div, lhs, rhs) => {
+					let (lhs, rhs) = (amount(lhs)?, amount(rhs)?);
+					match op.op {
+						BinOp::Add => (lhs + rhs, 1),
+						BinOp::Sub => (lhs - rhs, 1),
+						BinOp::Mul => (lhs * rhs, SCALE),
+						_ if rhs == 0 => return Err(EvalError::Domain { function: "/", requirement: "a divisor other than 0" }),
+						_ => (lhs * SCALE, rhs)
+					}
+				}
+				_ => return unsupported(format!("'{}'", op.symbol))
+			},
+			Token::PercentChange(op) => {
+				let base = amount(args[0])?;
+				let change = if args[1].rate { base * args[1].units } else { base * args[1].units * SCALE };
+				let sign = if op.op == BinOp::Sub { -1 } else { 1 };
+				(base * SCALE * SCALE + sign * change, SCALE * SCALE)
+			}
+			Token::Function(func) if matches!(func.name, "abs" | "min" | "max") => {
+				let first = amount(args[0])?;
+				match func.name {
+					"abs" => (first.abs(), 1),
+					"min" => (first.min(amount(args[1])?), 1),
+					_ => (first.max(amount(args[1])?), 1)
+				}
+			}
+			Token::Function(func) => return unsupported(format!("'{}'", func.name)),
+			Token::Convert(..) => return unsupported("convert".to_string()),
+			_ => unreachable!()
+		};
+		let result = divide(exact, scale, context);
+		let remainder = exact - result * scale;
+		if remainder != 0 {
+			let detail = match (tok, args[0], args.get(1)) {
+				// What is left of the dividend, as when splitting a bill
+				(Token::Operator(op), Value { units: lhs, rate: false }, Some(Value { units: rhs, rate: false })) if op.op == BinOp::Div => format!(
+					"{} / {} is {}, leaving {}",
+					show(lhs, context), show(*rhs, context), show(result, context), remainder as f64 / (SCALE * SCALE) as f64
+				),
+				_ => format!("{} rounded to {}", exact as f64 / (scale * SCALE) as f64, show(result, context))
+			};
+			report(StrictRule::MoneyRounding, None, Some(detail), context, violations)?;
+		}
+		if result.abs() >= MAX_CENTS {
+			return Err(EvalError::NotMoney("an amount of 2^53 cents or more".to_string()));
+		}
+		solve.push(Value { units: result, rate: false });
+	}
+	match solve.last() {
+		Some(value) => Ok(cents_to_f64(if value.rate { round_rate(value.units, context, violations)? } else { value.units })),
+		None => Err(EvalError::NoResult)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::vec::Vec;
+	use crate::{Context, EvalError, Rounding, RuleLevel, StrictRule};
+
+	fn money() -> Context {
+		let mut context = Context::new();
+		context.set_money(true);
+		context
+	}
+
+	#[test]
+	fn sums_and_products_are_exact() {
+		let context = money();
+		assert_eq!(context.eval("0.1 + 0.2").unwrap(), 0.3);
+		assert_eq!(context.eval("19.99 * 3").unwrap(), 59.97);
+		assert_eq!(context.eval("1.005 * 3").unwrap(), 3.03);
+		assert_eq!(context.eval("10% of 50").unwrap(), 5.0);
+	}
+
+	#[test]
+	fn a_division_reports_its_remainder() {
+		let mut context = money();
+		assert_eq!(context.execute("100 / 3").unwrap().value(), 33.33);
+		assert_eq!(context.warnings().len(), 1);
+		assert_eq!(context.warnings()[0].rule, StrictRule::MoneyRounding);
+		assert!(context.warnings()[0].detail.as_deref().unwrap().contains("leaving 0.01"));
+		assert_eq!(context.execute("100 / 4").unwrap().value(), 25.0);
+		assert!(context.warnings().is_empty());
+		context.set_rule(StrictRule::MoneyRounding, RuleLevel::Error);
+		assert!(matches!(context.execute("100 / 3"), Err(EvalError::Strict { rule: StrictRule::MoneyRounding, .. })));
+	}
+
+	#[test]
+	fn literals_round_in_the_rounding_mode() {
+		let mut context = money();
+		assert_eq!(context.eval("1.234").unwrap(), 1.23);
+		assert_eq!(context.eval("2.675").unwrap(), 2.68);
+		context.set_rounding(Rounding::TowardZero);
+		assert_eq!(context.eval("2.679").unwrap(), 2.67);
+		assert_eq!(context.eval("-2.679").unwrap(), -2.67);
+	}
+
+	#[test]
+	fn functions_without_a_money_meaning_are_refused() {
+		let context = money();
+		assert!(matches!(context.eval("sqrt(4)"), Err(EvalError::NotMoney(_))));
+		assert!(matches!(context.eval("2 ^ 2"), Err(EvalError::NotMoney(_))));
+		assert_eq!(context.eval("max(abs(-3), 2)").unwrap(), 3.0);
+	}
+
+	#[test]
+	fn every_entry_point_reduces_in_cents() {
+		let mut context = money();
+		let detailed = context.eval_detailed("1.005 * 3").unwrap();
+		assert_eq!(detailed.value, 3.03);
+		assert_eq!(detailed.warnings.iter().map(|violation| violation.rule).collect::<Vec<_>>(), [StrictRule::MoneyRounding]);
+		assert_eq!(context.eval_detailed("100 / 3").unwrap().warnings.len(), 1);
+		let points = context.sweep("x / 3", "x", [1.0, 100.0]);
+		assert_eq!(points.iter().map(|(_, value)| *value.as_ref().unwrap()).collect::<Vec<_>>(), [0.33, 33.33]);
+		assert_eq!(context.execute("1.005 * 3").unwrap().value(), context.eval("1.005 * 3").unwrap());
+		assert!(matches!(context.eval("integrate(x, x, 0, 1)"), Err(EvalError::NotMoney(_))));
+	}
+}
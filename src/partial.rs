@@ -0,0 +1,149 @@
+use alloc::vec::Vec;
+
+use crate::{calculus, Context, EvalError, Function, LexemeKind, CONSTANTS};
+
+/// What an incomplete expression needs before it can be evaluated.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Expectation {
+	/// A value, after an operator, `(` or separator, as in `2 *`.
+	Operand,
+	/// The `(` of a call, after a function name, as in `sqrt`.
+	Call,
+	/// The rest of a name that this much of is unknown, as in `sq` for `sqrt`.
+	Name,
+	/// This many `)`, as in `2 * (3 + 4`.
+	CloseParens(usize)
+}
+
+#[derive(Clone, Debug)]
+pub enum PartialResult {
+	Complete(f64),
+	/// The input could still become an expression. `preview` is its value with the open parentheses
+	/// closed, when that is all it needs.
+	Incomplete { missing: Expectation, preview: Option<f64> },
+	/// No more input would make it an expression.
+	Invalid(EvalError)
+}
+
+/// Evaluates `expression` as it is being typed, for showing a result as it changes. Whether it is
+/// incomplete is decided by where the parser stopped: expecting an operand or the `(` of a call at
+/// the end, or with parentheses still open. An unknown name at the end is incomplete when a known
+/// one starts with it.
+pub fn try_eval_partial(expression: &str, context: &Context) -> PartialResult {
+	let err = match context.eval(expression) {
+		Ok(value) => return PartialResult::Complete(value),
+		Err(err) => err
+	};
+	let incomplete = |missing: Expectation, preview: Option<f64>| PartialResult::Incomplete { missing, preview };
+	// The lexemes are whole, else evaluating would have failed on them
+	let lexemes: Vec<LexemeKind> = context.lexer(expression).filter_map(|lexeme| lexeme.ok()).map(|lexeme| lexeme.kind).collect();
+	let end = expression.trim_end().len();
+	match err {
+		EvalError::NoResult if lexemes.is_empty() => incomplete(Expectation::Operand, None),
+		EvalError::IncompleteExpression(..) => incomplete(Expectation::Operand, None),
+		EvalError::ExpectedCall(_, span) if span.end == end => incomplete(Expectation::Call, None),
+		EvalError::UnknownIdentifier(ref name, span) if span.end == end && is_name_prefix(name, context) => {
+			incomplete(Expectation::Name, None)
+		}
+		EvalError::MismatchedParenthesis(span) if expression[span.start ..].starts_with('(') => {
+			if matches!(lexemes.last(), Some(LexemeKind::OpenParen | LexemeKind::Separator)) {
+				return incomplete(Expectation::Operand, None);
+			}
+			let open = lexemes.iter().fold(0isize, |depth, kind| match kind {
+				LexemeKind::OpenParen => depth + 1,
+				LexemeKind::CloseParen => depth - 1,
+				_ => depth
+			});
+			let open = open.max(1) as usize;
+			let preview = context.eval(&format!("{}{}", expression, ")".repeat(open))).ok();
+			incomplete(Expectation::CloseParens(open), preview)
+		}
+		_ => PartialResult::Invalid(err)
+	}
+}
+
+fn is_name_prefix(prefix: &str, context: &Context) -> bool {
+	let mut names = Function::MAP.iter().map(|(name, _)| *name)
+		.chain(CONSTANTS.iter().map(|(name, _)| *name))
+		.chain(calculus::FORMS.iter().copied())
+		.chain(context.variables().map(|(name, _)| name))
+		.chain(["ans", "of"]);
+	names.any(|name| name.starts_with(prefix))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Span;
+	use Expectation::*;
+
+	/// What is missing from `expression` and its value or preview, which must not be invalid.
+	fn step(expression: &str, context: &Context) -> (Option<Expectation>, Option<f64>) {
+		match try_eval_partial(expression, context) {
+			PartialResult::Complete(value) => (None, Some(value)),
+			PartialResult::Incomplete { missing, preview } => (Some(missing), preview),
+			PartialResult::Invalid(err) => panic!("{:?} is invalid: {:?}", expression, err)
+		}
+	}
+
+	#[test]
+	fn every_prefix_of_an_expression_is_classified() {
+		let expression = "2 * (sqrt(16) + max(1, x)) - pi";
+		let mut context = Context::new();
+		context.set_var("x", 3.0);
+		let expected = [
+			("", Some(Operand), None),
+			("2", None, Some(2.0)),
+			("2 *", Some(Operand), None),
+			("2 * (", Some(Operand), None),
+			("2 * (s", Some(Name), None),
+			("2 * (sqr", Some(Name), None),
+			("2 * (sqrt", Some(Call), None),
+			("2 * (sqrt(", Some(Operand), None),
+			("2 * (sqrt(1", Some(CloseParens(2)), Some(2.0)),
+			("2 * (sqrt(16", Some(CloseParens(2)), Some(8.0)),
+			("2 * (sqrt(16)", Some(CloseParens(1)), Some(8.0)),
+			("2 * (sqrt(16) +", Some(Operand), None),
+			("2 * (sqrt(16) + ma", Some(Name), None),
+			("2 * (sqrt(16) + max", Some(Call), None),
+			// `max` needs two arguments, so closing it gives no preview yet
+			("2 * (sqrt(16) + max(1", Some(CloseParens(2)), None),
+			("2 * (sqrt(16) + max(1,", Some(Operand), None),
+			("2 * (sqrt(16) + max(1, x", Some(CloseParens(2)), Some(14.0)),
+			("2 * (sqrt(16) + max(1, x)", Some(CloseParens(1)), Some(14.0)),
+			("2 * (sqrt(16) + max(1, x))", None, Some(14.0)),
+			("2 * (sqrt(16) + max(1, x)) -", Some(Operand), None),
+			("2 * (sqrt(16) + max(1, x)) - p", Some(Name), None)
+		];
+		for (prefix, missing, value) in expected {
+			assert!(expression.starts_with(prefix));
+			assert_eq!(step(prefix, &context), (missing, value), "{:?}", prefix);
+			assert_eq!(step(&format!("{} ", prefix), &context), (missing, value), "{:?} and a space", prefix);
+		}
+		assert_eq!(step(expression, &context), (None, Some(14.0 - core::f64::consts::PI)));
+	}
+
+	#[test]
+	fn open_parentheses_are_closed_for_the_preview() {
+		let context = Context::new();
+		assert_eq!(step("2*(3+4", &context), (Some(CloseParens(1)), Some(14.0)));
+		assert_eq!(step("2 * (3 + (4", &context), (Some(CloseParens(2)), Some(14.0)));
+		assert_eq!(step("((1)", &context), (Some(CloseParens(1)), Some(1.0)));
+	}
+
+	#[test]
+	fn what_no_more_input_can_fix_is_invalid() {
+		let context = Context::new();
+		let invalid = |expression: &str| match try_eval_partial(expression, &context) {
+			PartialResult::Invalid(err) => err,
+			result => panic!("{:?} is {:?}", expression, result)
+		};
+		assert!(matches!(invalid("2 * (3 + 4))"), EvalError::MismatchedParenthesis(span) if span == Span::new(11, 12)));
+		assert!(matches!(invalid("2 # 3"), EvalError::InvalidCharacter('#', _)));
+		assert!(matches!(invalid("2 3"), EvalError::ExpectedOperator { .. }));
+		assert!(matches!(invalid("1 +* 2"), EvalError::ExpectedOperand { .. }));
+		assert!(matches!(invalid("sqrt 2"), EvalError::ExpectedCall("sqrt", _)));
+		// No name starts with `zz`
+		assert!(matches!(invalid("1 + zz"), EvalError::UnknownIdentifier(..)));
+	}
+}
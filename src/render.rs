@@ -0,0 +1,343 @@
+use std::env;
+
+use std::collections::VecDeque;
+
+use shunting_yard::trace::format_tokens;
+use shunting_yard::{Context, EvalError, Info, Lexeme, LexemeKind, Span, Token, Trace};
+
+const RESET: &str = "\x1b[0m";
+const BOLD_GREEN: &str = "\x1b[1;32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const CYAN: &str = "\x1b[36m";
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Style {
+	pub color: bool
+}
+impl Style {
+	/// Colors are used only on terminals, and never when `NO_COLOR` is set or `--no-color` was passed.
+	pub fn detect(is_terminal: bool, no_color: bool) -> Self {
+		let no_color_env = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+		Self { color: is_terminal && !no_color && !no_color_env }
+	}
+
+	fn paint(&self, code: &str, text: &str) -> String {
+		if self.color {
+			format!("{}{}{}", code, text, RESET)
+		} else {
+			text.to_string()
+		}
+	}
+	pub fn result(&self, text: &str) -> String {
+		self.paint(BOLD_GREEN, text)
+	}
+	pub fn expression(&self, text: &str) -> String {
+		self.paint(CYAN, text)
+	}
+	pub fn error(&self, text: &str) -> String {
+		self.paint(RED, text)
+	}
+	pub fn warning(&self, text: &str) -> String {
+		self.paint(YELLOW, text)
+	}
+}
+
+/// Styles for the two output streams, which may be redirected independently.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Styles {
+	pub out: Style,
+	pub err: Style
+}
+
+/// Renders an error. When it has a span, the source line is shown with the offending part underlined
+/// and the message next to the carets; for input spanning several lines a heading says which line and
+/// column that is.
+pub fn render_error(source: &str, err: &EvalError, style: Style) -> String {
+	let paint = |text: &str| if err.is_incomplete() { style.warning(text) } else { style.error(text) };
+	let Some(span) = err.span().filter(|span| span.start <= source.len() && source.is_char_boundary(span.start)) else {
+		return if err.is_incomplete() { paint(&err.to_string()) } else { paint(&format!("Error: {}", err)) };
+	};
+	let line_start = source[.. span.start].rfind('\n').map_or(0, |i| i + 1);
+	let line_end = source[span.start ..].find('\n').map_or(source.len(), |i| span.start + i);
+	let line = &source[line_start .. line_end];
+	let span = Span::new(span.start - line_start, span.end.clamp(span.start, line_end) - line_start);
+	let (source_line, carets) = render_caret(line, span);
+	let mut text = String::new();
+	if source.contains('\n') {
+		let number = source[.. line_start].matches('\n').count() + 1;
+		let column = line[.. span.start].chars().count() + 1;
+		text.push_str(&paint(&format!("Error on line {}, column {}:", number, column)));
+		text.push('\n');
+	}
+	text.push_str(&source_line);
+	text.push('\n');
+	text.push_str(&format!("{} {}", style.warning(&carets), paint(&err.to_string())));
+	text
+}
+
+/// [`render_error`] followed by the names the error suggests in `context`, if any.
+pub fn render_error_in(source: &str, err: &EvalError, style: Style, context: &Context) -> String {
+	let text = render_error(source, err, style);
+	match err.did_you_mean(context) {
+		Some(question) => format!("{}\n  {}", text, style.warning(&question)),
+		None => text
+	}
+}
+
+/// Returns `line` indented for display and a line of carets under `span`. Tabs before the span are
+/// kept in the caret line so it stays aligned whatever the tab width.
+pub fn render_caret(line: &str, span: Span) -> (String, String) {
+	let start = span.start.min(line.len());
+	let padding: String = line.get(.. start).unwrap_or("").chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+	let width = line.get(start .. span.end.min(line.len())).map_or(0, |part| part.chars().count()).max(1);
+	(format!("  {}", line), format!("  {}{}", padding, "^".repeat(width)))
+}
+
+/// Renders the token stream, the RPN queue and each reduction step of an evaluation.
+pub fn render_trace(trace: &Trace) -> String {
+	let mut text = format!("tokens: {}\nrpn:    {}", format_tokens(&trace.tokens), format_tokens(&trace.rpn));
+	for step in &trace.steps {
+		text.push_str(&format!("\n  {}", step));
+	}
+	text
+}
+
+/// Renders a trace for teaching: the tokens, how the shunting-yard algorithm moved each of them,
+/// the RPN queue and each reduction of its stack, as far as the evaluation got.
+pub fn render_explanation(trace: &Trace) -> String {
+	let mut text = format!("tokens: {}\nshunting yard:", format_tokens(&trace.tokens));
+	for event in &trace.events {
+		text.push_str(&format!("\n  {}", event));
+	}
+	// Parsing stopped at an error
+	if trace.rpn.is_empty() {
+		return text;
+	}
+	text.push_str(&format!("\nrpn:    {}\nevaluation:", format_tokens(&trace.rpn)));
+	for step in &trace.steps {
+		let args: Vec<String> = step.args.iter().map(f64::to_string).collect();
+		text.push_str(&format!("\n  pop {}; apply {} → {}", args.join(", "), step.token, step.result));
+	}
+	text
+}
+
+/// One line per lexeme for `:tokens`: its index, byte span, text and what it is, in aligned columns.
+pub fn render_tokens(expression: &str, lexemes: &[Lexeme], context: &Context) -> String {
+	let mut rows = Vec::with_capacity(lexemes.len());
+	for (i, lexeme) in lexemes.iter().enumerate() {
+		let text = &expression[lexeme.span.start .. lexeme.span.end];
+		let previous = i.checked_sub(1).map(|i| lexemes[i].kind);
+		let unary = matches!(previous, None | Some(LexemeKind::Operator(_) | LexemeKind::OpenParen | LexemeKind::Separator));
+		let kind = match lexeme.kind {
+			LexemeKind::Number(value) => format!("number {}", value),
+			LexemeKind::Duration(seconds) => format!("duration {}s", seconds),
+			LexemeKind::TapeRef(index) => format!("tape entry {}", index),
+			LexemeKind::Identifier => match context.info(text) {
+				Info::Variable(_) => "variable".to_string(),
+				Info::Answer(_) => "last result".to_string(),
+				Info::Constant(_) => "constant".to_string(),
+				Info::Function(_) | Info::UserFunction(_) => "function".to_string(),
+				Info::Unknown(_) => "name".to_string()
+			},
+			LexemeKind::Operator('-') if unary => "unary minus".to_string(),
+			LexemeKind::Operator('+') if unary => "unary plus".to_string(),
+			LexemeKind::Operator(_) => "operator".to_string(),
+			LexemeKind::OpenParen => "open paren".to_string(),
+			LexemeKind::CloseParen => "close paren".to_string(),
+			LexemeKind::Separator => "separator".to_string(),
+			LexemeKind::String => "string".to_string(),
+			LexemeKind::Percent => "percent".to_string(),
+			LexemeKind::Form => "form".to_string(),
+			LexemeKind::Placeholder => "placeholder".to_string(),
+			LexemeKind::Tag => "tag".to_string()
+		};
+		rows.push([i.to_string(), format!("{}..{}", lexeme.span.start, lexeme.span.end), text.replace('\n', " "), kind]);
+	}
+	columns(&rows)
+}
+
+/// One line per token of the postfix queue for `:rpn`: its index, the token and what it is.
+pub fn render_rpn(rpn: &VecDeque<Token>) -> String {
+	let rows: Vec<[String; 3]> = rpn.iter().enumerate().map(|(i, token)| {
+		let kind = match token {
+			Token::NumericLiteral(_) => "number".to_string(),
+			Token::Operator(op) if op.argc == 1 && op.symbol == '-' => "unary minus".to_string(),
+			Token::Operator(op) if op.argc == 1 => "unary plus".to_string(),
+			Token::Operator(op) => format!("operator, {} operands", op.argc),
+			Token::Function(func) if func.argc == 1 => "function, 1 argument".to_string(),
+			Token::Function(func) => format!("function, {} arguments", func.argc),
+			Token::OpenParen | Token::CloseParen | Token::Comma => "punctuation".to_string(),
+			Token::Unit(_) => "unit".to_string(),
+			Token::Convert(..) => "conversion".to_string(),
+			Token::Uncertain(..) => "uncertain number".to_string(),
+			Token::Duration(_) => "duration".to_string(),
+			Token::Percent => "percent".to_string(),
+			Token::PercentChange(_) => "percent change".to_string(),
+			Token::Tag(_) => "tag".to_string()
+		};
+		[i.to_string(), token.to_string(), kind]
+	}).collect();
+	columns(&rows)
+}
+
+/// The rows as lines, each column padded to its widest cell and separated by two spaces.
+fn columns<const N: usize>(rows: &[[String; N]]) -> String {
+	let mut widths = [0; N];
+	for row in rows {
+		for (width, cell) in widths.iter_mut().zip(row) {
+			*width = (*width).max(cell.chars().count());
+		}
+	}
+	let mut text = String::new();
+	for row in rows {
+		let mut line = String::new();
+		for (cell, width) in row.iter().zip(widths) {
+			line.push_str(&format!("{:<width$}  ", cell, width = width));
+		}
+		text.push_str(line.trim_end());
+		text.push('\n');
+	}
+	text
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const COLOR: Style = Style { color: true };
+
+	#[test]
+	fn errors_are_rendered_with_carets_under_their_span() {
+		let err = Context::new().eval("2 + foo").unwrap_err();
+		assert_eq!(render_error("2 + foo", &err, Style::default()), "  2 + foo\n      ^^^ unknown identifier 'foo'");
+		assert_eq!(
+			render_error("2 + foo", &err, COLOR),
+			"  2 + foo\n\x1b[33m      ^^^\x1b[0m \x1b[31munknown identifier 'foo'\x1b[0m"
+		);
+		let err = Context::new().eval("1 +\n(2 * )").unwrap_err();
+		assert_eq!(
+			render_error("1 +\n(2 * )", &err, Style::default()),
+			"Error on line 2, column 6:\n  (2 * )\n       ^ expected a value, found ')'"
+		);
+		assert_eq!(render_error("", &EvalError::NoResult, Style::default()), format!("Error: {}", EvalError::NoResult));
+	}
+
+	#[test]
+	fn incomplete_input_is_a_warning() {
+		let err = Context::new().eval("2 +").unwrap_err();
+		assert_eq!(render_error("2 +", &err, COLOR), "  2 +\n\x1b[33m    ^\x1b[0m \x1b[33mincomplete expression: expected a value after '+'\x1b[0m");
+	}
+
+	#[test]
+	fn carets_keep_tabs_and_cover_wide_spans() {
+		assert_eq!(render_caret("\tab c", Span::new(3, 5)), ("  \tab c".to_string(), "  \t  ^^".to_string()));
+		assert_eq!(render_caret("µx", Span::new(0, 2)), ("  µx".to_string(), "  ^".to_string()));
+		assert_eq!(render_caret("1", Span::new(4, 4)).1, "   ^");
+	}
+
+	#[test]
+	fn color_needs_a_terminal_and_no_opt_out() {
+		assert_eq!(Style::detect(false, false), Style::default());
+		assert_eq!(Style::detect(true, true), Style::default());
+		assert_eq!(Style::default().result("3"), "3");
+		assert_eq!(COLOR.result("3"), "\x1b[1;32m3\x1b[0m");
+	}
+
+	#[test]
+	fn each_kind_of_error_points_at_its_span() {
+		let rendered = |source: &str| render_error(source, &Context::new().eval(source).unwrap_err(), Style::default());
+		assert_eq!(rendered("2 + @3"), "  2 + @3\n      ^ unexpected character '@'");
+		assert_eq!(rendered("\t2 + @3"), "  \t2 + @3\n  \t    ^ unexpected character '@'");
+		assert_eq!(rendered("1 + 1.2.3"), "  1 + 1.2.3\n         ^ duplicate decimal point in number");
+		assert_eq!(rendered("2 * value"), "  2 * value\n      ^^^^^ unknown identifier 'value'");
+		assert_eq!(rendered("(2 + 3"), "  (2 + 3\n  ^ mismatched parenthesis");
+		assert_eq!(rendered("2 + 3)"), "  2 + 3)\n       ^ mismatched parenthesis");
+		assert_eq!(rendered("4 *"), "  4 *\n    ^ incomplete expression: expected a value after '*'");
+	}
+
+	fn explained(expression: &str) -> String {
+		let mut trace = Trace::default();
+		let _ = Context::new().eval_traced(expression, &mut trace);
+		render_explanation(&trace)
+	}
+
+	#[test]
+	fn explanations_show_the_parse_and_each_reduction() {
+		assert_eq!(explained("3 + 4 * (2 - 1)"), "\
+tokens: 3 + 4 * ( 2 - 1 )
+shunting yard:
+  output 3
+  push +
+  output 4
+  push *
+  push (
+  output 2
+  push -
+  output 1
+  pop -, closing parenthesis
+  pop ( and drop it, matched by )
+  pop *, end of input
+  pop +, end of input
+rpn:    3 4 2 1 - * +
+evaluation:
+  pop 2, 1; apply - → 1
+  pop 4, 1; apply * → 4
+  pop 3, 4; apply + → 7");
+		assert_eq!(explained("2 ^ 3 ^ 2"), "\
+tokens: 2 ^ 3 ^ 2
+shunting yard:
+  output 2
+  push ^
+  output 3
+  push ^
+  output 2
+  pop ^, end of input
+  pop ^, end of input
+rpn:    2 3 2 ^ ^
+evaluation:
+  pop 3, 2; apply ^ → 9
+  pop 2, 9; apply ^ → 512");
+		assert_eq!(explained("2 +"), "tokens: 2 +\nshunting yard:\n  output 2\n  push +");
+	}
+
+	#[test]
+	fn tokens_are_listed_in_columns_with_what_they_are() {
+		let mut context = Context::new();
+		context.execute("x = 3").unwrap();
+		let tokens = |expression: &str| render_tokens(expression, &context.tokenize(expression).unwrap(), &context);
+		assert_eq!(tokens("-x * ans + pi"), "\
+0  0..1    -    unary minus
+1  1..2    x    variable
+2  3..4    *    operator
+3  5..8    ans  last result
+4  9..10   +    operator
+5  11..13  pi   constant
+");
+		assert_eq!(tokens("max(1, 2)^2"), "\
+0  0..3    max  function
+1  3..4    (    open paren
+2  4..5    1    number 1
+3  5..6    ,    separator
+4  7..8    2    number 2
+5  8..9    )    close paren
+6  9..10   ^    operator
+7  10..11  2    number 2
+");
+		assert_eq!(tokens("y"), "0  0..1  y  name\n");
+	}
+
+	#[test]
+	fn the_rpn_is_listed_without_evaluating() {
+		let rpn = |expression: &str| render_rpn(&Context::new().to_postfix(expression).unwrap());
+		assert_eq!(rpn("1/0"), "0  1  number\n1  0  number\n2  /  operator, 2 operands\n");
+		assert_eq!(rpn("-max(1, 2)^2"), "\
+0  1    number
+1  2    number
+2  max  function, 2 arguments
+3  2    number
+4  ^    operator, 2 operands
+5  -    unary minus
+");
+	}
+}
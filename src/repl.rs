@@ -0,0 +1,629 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+
+use shunting_yard::display::DEFAULT_MAX_DENOMINATOR;
+use shunting_yard::{contain_panic, format_duration, is_incomplete, radix_note, simplify_in, to_dot, to_infix, to_latex, AngleMode, Context, ContextDiff, EvalError, Info, IterationStop, Lint, Locale, EvalOutcome, Notation, Precision, Radix, RuleLevel, StrictRule, Timing, Trace};
+
+use crate::command::{parse_input, Command, Input};
+use crate::completion::complete;
+use crate::help::{help_text, topic_text};
+use crate::line_editor::{LineEditor, ReadResult};
+use crate::render::{render_error, render_error_in, render_explanation, render_rpn, render_tokens, render_trace, Style};
+use crate::result_format::{Fields, Template};
+
+pub const DEFAULT_PROMPT: &str = "> ";
+const CONTINUATION_PROMPT: &str = "... ";
+
+/// An expression evaluated in this session, for `:last` and `:redo`.
+struct Evaluation {
+	index: usize,
+	input: String,
+	/// The result as shown, or the error message.
+	outcome: Result<String, String>
+}
+
+pub struct Config {
+	pub history_path: Option<PathBuf>,
+	/// Plain line reading for a dumb terminal, see [`LineEditor::dumb`]. The style has no color then.
+	pub dumb: bool,
+	pub style: Style,
+	pub debug: bool,
+	pub time: bool,
+	/// Times are shown as 0, see [`Options::frozen_clock`](crate::cli::Options::frozen_clock).
+	pub frozen_clock: bool,
+	pub prompt: String,
+	pub template: Template
+}
+
+pub fn run(context: &mut Context, mut config: Config) {
+	let mut editor = LineEditor::new(config.history_path.take()).dumb(config.dumb);
+	let mut index = 0;
+	// As many evaluations as the tape keeps results
+	let mut log: VecDeque<Evaluation> = VecDeque::new();
+	// What `:save` compares with, after the startup expressions
+	let session = context.snapshot();
+	// Lines of an expression that continues on the next line, joined by newlines
+	let mut pending = String::new();
+	// Lines of a file from `:load`, run before reading more
+	let mut queued: VecDeque<String> = VecDeque::new();
+	loop {
+		let prompt = if pending.is_empty() { config.prompt.as_str() } else { CONTINUATION_PROMPT };
+		let read = match queued.pop_front() {
+			Some(line) => Ok(ReadResult::Line(line)),
+			None => editor.read_line(prompt, &|line, pos| complete(line, pos, context))
+		};
+		let line = match read {
+			Ok(ReadResult::Line(line)) if pending.is_empty() => line,
+			Ok(ReadResult::Line(line)) => format!("{}\n{}", std::mem::take(&mut pending), line),
+			Ok(ReadResult::Interrupted) => {
+				pending.clear();
+				continue;
+			}
+			Ok(ReadResult::Eof) => break,
+			Err(err) => {
+				println!("Input error: {}", err);
+				break;
+			}
+		};
+		if !line.trim_start().starts_with(':') {
+			if let Some(head) = line.trim_end().strip_suffix('\\') {
+				pending = head.to_string();
+				continue;
+			}
+			if is_incomplete(&line) {
+				pending = line;
+				continue;
+			}
+		}
+		match parse_input(&line) {
+			Input::Command(Command::Quit) => break,
+			Input::Empty => {}
+			Input::Command(Command::Help(None)) => print!("{}", help_text()),
+			Input::Command(Command::Help(Some(topic))) => print!("{}", topic_text(&topic)),
+			Input::Command(Command::Info(name)) => print!("{}", info_text(context, &name)),
+			Input::Command(Command::Vars) => {
+				let mut variables: Vec<_> = context.variables().collect();
+				variables.sort_by(|a, b| a.0.cmp(b.0));
+				for (name, value) in variables {
+					let value = match context.var_uncertainty(name) {
+						_ if context.is_duration(name) => format_duration(value, context.display()),
+						Some(sigma) => context.format_uncertain(value, sigma),
+						None => context.format(value)
+					};
+					match context.var_tag(name) {
+						Some(tag) => println!("{} = {} [{}]", name, value, tag),
+						None => println!("{} = {}", name, value)
+					}
+				}
+			}
+			Input::Command(Command::Delete(names)) => {
+				for name in names {
+					if context.remove_var(&name).is_none() {
+						println!("Unknown variable '{}'", name);
+					}
+				}
+			}
+			Input::Command(Command::Debug(Some(enabled))) => config.debug = enabled,
+			Input::Command(Command::Debug(None)) => println!("debug is {}", if config.debug { "on" } else { "off" }),
+			Input::Command(Command::Time(Some(enabled))) => config.time = enabled,
+			Input::Command(Command::Time(None)) => println!("time is {}", if config.time { "on" } else { "off" }),
+			Input::Command(Command::Stats) => match context.cache_stats() {
+				Some(stats) => println!(
+					"cache: {} hits, {} misses, {} of {} entries", stats.hits, stats.misses, stats.entries, stats.capacity
+				),
+				None => println!("cache is off")
+			},
+			Input::Command(Command::Latex(expression)) => match context.to_ast(&expression) {
+				Ok(ast) => println!("{}", to_latex(&ast)),
+				Err(err) => println!("{}", render_error(&expression, &err, config.style))
+			},
+			Input::Command(Command::Simplify(expression)) => match context.to_ast(&expression) {
+				Ok(ast) => println!("{}", to_infix(&simplify_in(&ast, context))),
+				Err(err) => println!("{}", render_error(&expression, &err, config.style))
+			},
+			Input::Command(Command::Explain(expression)) => {
+				let mut trace = Trace::default();
+				let result = context.eval_traced(&expression, &mut trace);
+				println!("{}", render_explanation(&trace));
+				match result {
+					Ok(value) => println!("result: {}", context.format(value)),
+					Err(err) => println!("{}", render_error(&expression, &err, config.style))
+				}
+			}
+			Input::Command(Command::Tokens(expression)) => match context.tokenize(&expression) {
+				Ok(lexemes) => print!("{}", render_tokens(&expression, &lexemes, context)),
+				Err(err) => println!("{}", render_error(&expression, &err, config.style))
+			},
+			Input::Command(Command::Rpn(expression)) => match context.to_postfix(&expression) {
+				Ok(rpn) => print!("{}", render_rpn(&rpn)),
+				Err(err) => println!("{}", render_error(&expression, &err, config.style))
+			},
+			Input::Command(Command::Canonical(expression)) => match context.to_canonical_string(&expression) {
+				Ok(canonical) => println!("{}", canonical),
+				Err(err) => println!("{}", render_error(&expression, &err, config.style))
+			},
+			Input::Command(Command::Tape) if context.tape().is_empty() => println!("the tape is empty"),
+			Input::Command(Command::Tape) => print!("{}", tape_text(context)),
+			Input::Command(Command::ClearTape) => context.tape_mut().clear(),
+			Input::Command(Command::Last(count)) => print!("{}", last_text(&log, count, config.style)),
+			Input::Command(Command::Redo(number)) => match log.iter().find(|evaluation| evaluation.index == number) {
+				Some(evaluation) => {
+					let input = evaluation.input.clone();
+					index += 1;
+					let outcome = evaluate(context, &input, &config, index, |context, trace, timing| {
+						context.execute_instrumented(&input, trace, timing)
+					});
+					remember(&mut log, Evaluation { index, input, outcome }, context.tape().limit());
+				}
+				None if log.is_empty() => println!("nothing has been evaluated yet"),
+				None => println!("there is no evaluation #{}, :last lists them", number)
+			},
+			Input::Command(Command::Save(path)) => {
+				let diff = context.diff(session.context());
+				match fs::write(&path, save_text(context, session.context(), &diff)) {
+					Ok(()) if diff.removed.is_empty() => println!(
+						"saved {} variable(s) and {} template(s) to '{}'",
+						diff.added.len() + diff.changed.len(), diff.templates.len(), path
+					),
+					Ok(()) => println!(
+						"saved {} variable(s) and {} template(s) to '{}', deleting {} cannot be saved",
+						diff.added.len() + diff.changed.len(), diff.templates.len(), path, diff.removed.join(", ")
+					),
+					Err(err) => println!("cannot write '{}': {}", path, err)
+				}
+			}
+			Input::Command(Command::Load(path)) => match fs::read_to_string(&path) {
+				Ok(text) => queued.extend(text.lines().map(str::to_string)),
+				Err(err) => println!("cannot read '{}': {}", path, err)
+			},
+			Input::Command(Command::DefineTemplate { name, body }) => {
+				if let Err(err) = context.define_template(&name, &body) {
+					println!("{}", render_error(&body, &err, config.style));
+				}
+			}
+			Input::Command(Command::Templates) => {
+				let mut templates = context.templates().peekable();
+				if templates.peek().is_none() {
+					println!("there are no templates");
+				}
+				for (name, body) in templates {
+					println!("{} = {}", name, body);
+				}
+			}
+			Input::Command(Command::UseTemplate { name, values }) => match context.template(&name) {
+				Some(body) => {
+					let body = body.to_string();
+					let values: Vec<(&str, &str)> = values.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+					index += 1;
+					let outcome = evaluate(context, &body, &config, index, |context, trace, timing| {
+						context.use_template_instrumented(&name, &values, trace, timing)
+					});
+					remember(&mut log, Evaluation { index, input: body, outcome }, context.tape().limit());
+				}
+				None => println!("{}", render_error(&name, &EvalError::UnknownTemplate(name.clone()), config.style))
+			},
+			Input::Command(Command::SaveTape(path)) => match fs::write(&path, tape_text(context)) {
+				Ok(()) => println!("wrote {} entries to {}", context.tape().len(), path),
+				Err(err) => println!("cannot write '{}': {}", path, err)
+			},
+			Input::Command(Command::Dot { expression, path }) => match (context.to_ast(&expression), path) {
+				(Ok(ast), None) => print!("{}", to_dot(&ast)),
+				(Ok(ast), Some(path)) => match fs::write(&path, to_dot(&ast)) {
+					Ok(()) => println!("wrote {}", path),
+					Err(err) => println!("cannot write '{}': {}", path, err)
+				},
+				(Err(err), _) => println!("{}", render_error(&expression, &err, config.style))
+			},
+			Input::Command(Command::Uncertainty(Some(on))) => context.set_uncertainty(on),
+			Input::Command(Command::Uncertainty(None)) => {
+				println!("uncertainty is {}", if context.uncertainty() { "on" } else { "off" });
+			}
+			Input::Command(Command::Strict(Some(RuleLevel::Allow))) => context.set_strict(false),
+			Input::Command(Command::Strict(Some(level))) => {
+				for rule in StrictRule::ALL {
+					context.set_rule(rule, level);
+				}
+			}
+			Input::Command(Command::Lint(on)) => context.set_lint(on),
+			Input::Command(Command::AllowLint(lint, allowed)) => context.allow_lint(lint, allowed),
+			Input::Command(Command::ShowLints) => {
+				println!("linting is {}", if context.lint_enabled() { "on" } else { "off" });
+				for lint in Lint::ALL {
+					println!("{}: {}", lint.name(), if context.is_lint_allowed(lint) { "allow" } else { "warn" });
+				}
+			}
+			Input::Command(Command::Strict(None)) => {
+				for rule in StrictRule::ALL {
+					println!("{}: {}", rule.description(), context.rule(rule).name());
+				}
+			}
+			Input::Command(Command::Tolerance(Some(tolerance))) => context.set_tolerance(tolerance),
+			Input::Command(Command::Tolerance(None)) => println!("tolerance is {:e}", context.tolerance()),
+			Input::Command(Command::TermLimit(Some(limit))) => context.set_term_limit(limit),
+			Input::Command(Command::TermLimit(None)) => println!("sums and products may have {} terms", context.term_limit()),
+			Input::Command(Command::Budget(budget)) => context.set_budget(budget),
+			Input::Command(Command::ShowBudget) => match context.budget() {
+				Some(budget) => println!("evaluations may take {} operations", budget),
+				None => println!("evaluations are not limited")
+			},
+			Input::Command(Command::Rounding(Some(rounding))) => context.set_rounding(rounding),
+			Input::Command(Command::Rounding(None)) => println!("rounding is {}", context.rounding().name()),
+			Input::Command(Command::Mode(Some(mode))) => context.set_angle_mode(mode),
+			Input::Command(Command::Money(on)) => context.set_money(on),
+			Input::Command(Command::Mode(None)) => {
+				println!("mode is {}{}", mode_name(context.angle_mode()), if context.money() { ", money" } else { "" });
+			}
+			Input::Command(Command::Decimal(decimal)) => {
+				let grouping = context.display().locale.grouping();
+				set_locale(context, decimal, grouping);
+			}
+			Input::Command(Command::Grouping(grouping)) => {
+				let decimal = context.display().locale.decimal();
+				set_locale(context, decimal, grouping);
+			}
+			Input::Command(Command::Fraction(max)) => context.display_mut().fraction = max,
+			Input::Command(Command::ToggleFraction) => {
+				let display = context.display_mut();
+				display.fraction = if display.fraction.is_some() { None } else { Some(DEFAULT_MAX_DENOMINATOR) };
+				match display.fraction {
+					Some(max) => println!("fraction is on, with denominators up to {}", max),
+					None => println!("fraction is off")
+				}
+			}
+			Input::Command(Command::ShowLocale) => {
+				let locale = context.display().locale;
+				let grouping = match locale.grouping() {
+					None => "off",
+					Some(' ') => "space",
+					Some('.') => "point",
+					Some('_') => "underscore",
+					Some(_) => "apostrophe"
+				};
+				println!("decimal is {}, grouping is {}", if locale.decimal() == ',' { "comma" } else { "point" }, grouping);
+			}
+			Input::Command(Command::Iterate { rounds, start, tolerance, expression }) => {
+				if let Some(start) = start {
+					context.set_answer(start);
+				}
+				iterate(context, &expression, rounds, tolerance, config.style);
+			}
+			Input::Command(Command::SetPrompt(prompt)) => config.prompt = prompt,
+			Input::Command(Command::SetTemplate(template)) => config.template = template,
+			Input::Command(Command::Precision(Some(precision))) => context.display_mut().precision = precision,
+			Input::Command(Command::Precision(None)) => match context.display().precision {
+				Precision::Significant(digits) => println!("precision is {} significant digits", digits),
+				Precision::Figures(figures) => println!("precision is exactly {} significant figures", figures),
+				Precision::Full => println!("precision is full")
+			},
+			Input::Command(Command::SigFig(Some(figures))) => {
+				let display = context.display_mut();
+				if let Notation::Fixed(decimals) = display.notation {
+					println!("showing {} significant figures instead of {} decimals, format is now auto", figures, decimals);
+					display.notation = Notation::Auto;
+				}
+				display.precision = Precision::Figures(figures);
+			}
+			Input::Command(Command::ShowSigFig) => match context.display().precision {
+				Precision::Figures(figures) => println!("sigfig is {}", figures),
+				_ => println!("sigfig is off")
+			},
+			Input::Command(Command::SigFig(None)) => {
+				if let Precision::Figures(_) = context.display().precision {
+					context.display_mut().precision = Precision::default();
+				}
+			}
+			Input::Command(Command::Format(Some(notation))) => {
+				let display = context.display_mut();
+				if let (Notation::Fixed(decimals), Precision::Figures(figures)) = (notation, display.precision) {
+					println!("showing {} decimals instead of {} significant figures, sigfig is now off", decimals, figures);
+					display.precision = Precision::default();
+				}
+				display.notation = notation;
+			}
+			Input::Command(Command::Format(None)) => match context.display().notation {
+				Notation::Auto => println!("format is auto"),
+				Notation::Fixed(decimals) => println!("format is fixed with {} decimals", decimals),
+				Notation::Scientific => println!("format is sci"),
+				Notation::Engineering => println!("format is eng")
+			},
+			Input::Command(Command::Durations(style)) => context.display_mut().durations = style,
+			Input::Command(Command::PercentHelpers(on)) => {
+				let display = context.display_mut();
+				display.percent_helpers = on.unwrap_or(!display.percent_helpers);
+				println!("percent helpers are {}", if display.percent_helpers { "on" } else { "off" });
+			}
+			Input::Command(Command::Output(Some(radix))) => context.display_mut().radix = radix,
+			Input::Command(Command::Output(None)) => println!("output is {}", match context.display().radix {
+				Radix::Decimal => "dec",
+				Radix::Hexadecimal => "hex",
+				Radix::Binary => "bin",
+				Radix::Octal => "oct",
+				Radix::Hex64 => "hex64"
+			}),
+			Input::Command(Command::Invalid(message)) => println!("{}", message),
+			Input::Command(Command::Unknown(name)) => {
+				println!("Unknown command ':{}', type :help for a list of commands", name);
+			}
+			Input::Expression(expr) => {
+				editor.add_history(&expr.replace('\n', " "));
+				index += 1;
+				let outcome = evaluate(context, expr, &config, index, |context, trace, timing| {
+					context.execute_instrumented(expr, trace, timing)
+				});
+				remember(&mut log, Evaluation { index, input: expr.to_string(), outcome }, context.tape().limit());
+			}
+		}
+	}
+	if let Err(err) = editor.save_history() {
+		eprintln!("syc: could not save history: {}", err);
+	}
+}
+
+/// One `#N  input = result` line per entry on the tape.
+fn tape_text(context: &Context) -> String {
+	let mut text = String::new();
+	for entry in context.tape().entries() {
+		text.push_str(&format!("#{}  {} = {}\n", entry.index, entry.input.replace('\n', " "), context.format(entry.value)));
+	}
+	text
+}
+
+/// The angle mode as a `:mode` line when it changed since `session`, the variables added or changed
+/// as assignments that evaluate back to them, values in full, then the templates defined as
+/// `:template` lines.
+fn save_text(context: &Context, session: &Context, diff: &ContextDiff) -> String {
+	let decimal = context.display().locale.decimal().to_string();
+	let mut names: Vec<&String> = diff.added.iter().chain(&diff.changed).collect();
+	names.sort();
+	let mut text = String::new();
+	if context.angle_mode() != session.angle_mode() {
+		text.push_str(&format!(":mode {}\n", mode_name(context.angle_mode())));
+	}
+	for name in names {
+		let Some(value) = context.get_var(name) else {
+			continue;
+		};
+		let suffix = if context.is_duration(name) { "s" } else { "" };
+		let tag = context.var_tag(name).map(|tag| format!(" [{}]", tag)).unwrap_or_default();
+		text.push_str(&format!("{} = {}{}{}\n", name, value.to_string().replace('.', &decimal), suffix, tag));
+	}
+	for name in &diff.templates {
+		if let Some(body) = context.template(name) {
+			text.push_str(&format!(":template {} = {}\n", name, body));
+		}
+	}
+	text
+}
+
+/// The argument of `:mode` that sets `mode`.
+fn mode_name(mode: AngleMode) -> &'static str {
+	match mode {
+		AngleMode::Radians => "rad",
+		AngleMode::Degrees => "deg"
+	}
+}
+
+fn remember(log: &mut VecDeque<Evaluation>, evaluation: Evaluation, limit: usize) {
+	log.push_back(evaluation);
+	while log.len() > limit {
+		log.pop_front();
+	}
+}
+
+/// What `:info` shows about `name`.
+fn info_text(context: &Context, name: &str) -> String {
+	let indexes = |indexes: &[usize]| indexes.iter().map(|index| format!("${}", index)).collect::<Vec<_>>().join(", ");
+	match context.info(name) {
+		Info::Variable(info) => {
+			let value = match info.sigma {
+				_ if info.duration => format_duration(info.value, context.display()),
+				Some(sigma) => context.format_uncertain(info.value, sigma),
+				None => context.format(info.value)
+			};
+			let assigned = match (info.assigned, info.reads.as_slice()) {
+				(Some(index), []) => format!("assigned at ${}", index),
+				(Some(index), reads) => format!("assigned at ${} from {}", index, reads.join(", ")),
+				(None, _) => "assigned before the tape begins".to_string()
+			};
+			let used = match info.used_by.as_slice() {
+				[] => "not used on the tape".to_string(),
+				used_by => format!("used by {}", indexes(used_by))
+			};
+			let mut text = format!("{} = {}\n  {}\n  {}\n", name, value, assigned, used);
+			if !info.functions.is_empty() {
+				text += &format!("  read by {}\n", info.functions.join(", "));
+			}
+			text
+		}
+		Info::Answer(value) => format!("ans = {}\n  the last result\n", context.format(value)),
+		Info::Constant(value) => format!("{} = {}\n  a constant\n", name, context.format(value)),
+		Info::Function(func) => {
+			let argc = if func.argc == 1 { "1 argument".to_string() } else { format!("{} arguments", func.argc) };
+			format!("{}, {}\n  {}\n  example: {}\n", func.name, argc, func.description, func.example)
+		}
+		Info::UserFunction(info) => {
+			let reads = match info.reads.as_slice() {
+				[] => "reads only its parameters".to_string(),
+				reads => format!("reads {}", reads.join(", "))
+			};
+			format!("{}({}) = {}\n  {}\n", name, info.params.join(", "), info.body, reads)
+		}
+		Info::Unknown(close) if close.is_empty() => format!("unknown name '{}'\n", name),
+		Info::Unknown(close) => format!("unknown name '{}', did you mean {}?\n", name, close.join(", "))
+	}
+}
+
+/// The last `count` evaluations, oldest first. An evaluation repeated with the same result is only
+/// listed the last time.
+fn last_text(log: &VecDeque<Evaluation>, count: usize, style: Style) -> String {
+	let mut shown: Vec<&Evaluation> = Vec::new();
+	for evaluation in log.iter().rev() {
+		if shown.len() == count {
+			break;
+		}
+		if !shown.iter().any(|later| later.input == evaluation.input && later.outcome == evaluation.outcome) {
+			shown.push(evaluation);
+		}
+	}
+	let mut text = String::new();
+	for evaluation in shown.into_iter().rev() {
+		let input = evaluation.input.replace('\n', " ");
+		match &evaluation.outcome {
+			Ok(result) => text.push_str(&format!("#{}  {} = {}\n", evaluation.index, input, style.result(result))),
+			Err(message) => text.push_str(&format!("#{}  {}  {}\n", evaluation.index, input, style.error(message)))
+		}
+	}
+	text
+}
+
+fn set_locale(context: &mut Context, decimal: char, grouping: Option<char>) {
+	match Locale::new(decimal, grouping) {
+		Ok(locale) => context.display_mut().locale = locale,
+		Err(message) => println!("{}", message)
+	}
+}
+
+fn iterate(context: &mut Context, expression: &str, rounds: usize, tolerance: Option<f64>, style: Style) {
+	let iteration = match context.iterate(expression, rounds, tolerance) {
+		Ok(iteration) => iteration,
+		Err(err) => {
+			println!("{}", render_error(expression, &err, style));
+			return;
+		}
+	};
+	for (i, value) in iteration.values.iter().enumerate() {
+		println!("{}: {}", i + 1, style.result(&context.format(*value)));
+	}
+	match iteration.stop {
+		IterationStop::Completed => {}
+		IterationStop::Converged => println!("converged after {} rounds", iteration.values.len()),
+		IterationStop::NonFinite => println!("{}", style.warning("stopped, the value is no longer finite"))
+	}
+}
+
+/// Evaluates and prints `expr` with `run`, returning its result as shown or the error message.
+fn evaluate(
+	context: &mut Context, expr: &str, config: &Config, index: usize,
+	run: impl FnOnce(&mut Context, Option<&mut Trace>, Option<&mut Timing>) -> Result<EvalOutcome, EvalError>
+) -> Result<String, String> {
+	let style = config.style;
+	let mut trace = Trace::default();
+	let mut timing = Timing::default();
+	for warning in context.lint(expr) {
+		println!("{}", style.warning(&format!("lint: {}", warning)));
+	}
+	let result = contain_panic(|| run(context, config.debug.then_some(&mut trace), config.time.then_some(&mut timing)));
+	if config.debug {
+		println!("{}", render_trace(&trace));
+	}
+	if config.frozen_clock {
+		timing = timing.without_durations();
+	}
+	let time = if config.time { format!("{}µs", (timing.parse + timing.eval).as_micros()) } else { String::new() };
+	let shown = match result {
+		Ok(outcome) => {
+			let value = outcome.value();
+			let (input, result) = match outcome {
+				EvalOutcome::Value(value) => (expr.replace('\n', " "), context.format_result(value)),
+				EvalOutcome::Assigned(name, value) | EvalOutcome::Solved(name, value) => (name, context.format_result(value)),
+				EvalOutcome::Multiple(values) => (expr.replace('\n', " "), context.format_list(values)),
+				EvalOutcome::AssignedMultiple(assigned) => {
+					let (names, values): (Vec<String>, Vec<f64>) = assigned.into_iter().unzip();
+					(names.join(", "), context.format_list(values))
+				}
+			};
+			println!("{}", config.template.render(&Fields {
+				input: &style.expression(&input),
+				result: &style.result(&result),
+				index,
+				time: &time
+			}));
+			if let Some(note) = radix_note(value, context.display()) {
+				println!("{}", style.warning(&note));
+			}
+			for violation in context.warnings() {
+				println!("{}", style.warning(&format!("warning: {}", violation)));
+			}
+			Ok(result)
+		}
+		Err(err) => {
+			println!("{}", render_error_in(expr, &err, style, context));
+			Err(err.to_string())
+		}
+	};
+	if config.time {
+		eprintln!("{}", timing);
+	}
+	shown
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn info_is_one_line_per_fact() {
+		let mut context = Context::new();
+		context.execute("r = 2").unwrap();
+		context.execute("r * 3").unwrap();
+		assert_eq!(info_text(&context, "r"), "r = 2\n  assigned at $1\n  used by $2\n");
+		assert_eq!(info_text(&context, "ans"), "ans = 6\n  the last result\n");
+		assert_eq!(info_text(&context, "pi"), format!("pi = {}\n  a constant\n", context.format(std::f64::consts::PI)));
+		assert!(info_text(&context, "sqrt").starts_with("sqrt, 1 argument\n  "));
+		context.register_fn("area", &["w", "h"], "(w * h) * r").unwrap();
+		context.register_fn("square", &["w"], "area(w, w)").unwrap();
+		context.register_fn("half", &["v"], "v / 2").unwrap();
+		assert_eq!(info_text(&context, "r"), "r = 2\n  assigned at $1\n  used by $2\n  read by area, square\n");
+		assert_eq!(info_text(&context, "area"), "area(w, h) = w*h*r\n  reads r\n");
+		assert_eq!(info_text(&context, "half"), "half(v) = v/2\n  reads only its parameters\n");
+		assert_eq!(info_text(&context, "zzzzzz"), "unknown name 'zzzzzz'\n");
+		assert!(info_text(&context, "rr").starts_with("unknown name 'rr', did you mean r, "));
+	}
+
+	#[test]
+	fn the_tape_is_saved_one_line_per_entry() {
+		let mut context = Context::new();
+		context.execute("2+2").unwrap();
+		context.execute("(ans *\n3)").unwrap();
+		assert_eq!(tape_text(&context), "#1  2+2 = 4\n#2  (ans * 3) = 12\n");
+		context.tape_mut().clear();
+		assert_eq!(tape_text(&context), "");
+	}
+
+	fn evaluation(index: usize, input: &str, outcome: Result<&str, &str>) -> Evaluation {
+		Evaluation { index, input: input.to_string(), outcome: outcome.map(str::to_string).map_err(str::to_string) }
+	}
+
+	#[test]
+	fn the_last_evaluations_are_listed_once_each() {
+		let mut log = VecDeque::new();
+		for (index, input, outcome) in [(1, "x * 2", Ok("4")), (2, "x +", Err("incomplete")), (3, "x * 2", Ok("4")), (4, "x * 2", Ok("6"))] {
+			remember(&mut log, evaluation(index, input, outcome), 3);
+		}
+		assert_eq!(log.iter().map(|evaluation| evaluation.index).collect::<Vec<_>>(), [2, 3, 4]);
+		assert_eq!(last_text(&log, 10, Style::default()), "#2  x +  incomplete\n#3  x * 2 = 4\n#4  x * 2 = 6\n");
+		assert_eq!(last_text(&log, 1, Style::default()), "#4  x * 2 = 6\n");
+		remember(&mut log, evaluation(5, "x * 2", Ok("6")), 3);
+		assert_eq!(last_text(&log, 2, Style::default()), "#3  x * 2 = 4\n#5  x * 2 = 6\n");
+		assert_eq!(last_text(&VecDeque::new(), 5, Style::default()), "");
+	}
+
+	#[test]
+	fn a_save_holds_what_changed_this_session() {
+		let mut context = Context::new();
+		context.execute("kept = 1").unwrap();
+		let session = context.snapshot();
+		assert_eq!(save_text(&context, session.context(), &context.diff(session.context())), "");
+		context.set_angle_mode(AngleMode::Degrees);
+		context.execute("kept = 2.5").unwrap();
+		context.execute("wait = 90min").unwrap();
+		context.execute("price = 3 [usd]").unwrap();
+		context.define_template("bmi", "?w / ?h^2").unwrap();
+		let diff = context.diff(session.context());
+		assert_eq!(
+			save_text(&context, session.context(), &diff),
+			":mode deg\nkept = 2.5\nprice = 3 [usd]\nwait = 5400s\n:template bmi = ?w / ?h^2\n"
+		);
+	}
+}
@@ -0,0 +1,120 @@
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Placeholder {
+	Input,
+	Result,
+	Index,
+	Time
+}
+
+const PLACEHOLDERS: [(&str, Placeholder); 4] = [
+	("input", Placeholder::Input),
+	("result", Placeholder::Result),
+	("index", Placeholder::Index),
+	("time", Placeholder::Time)
+];
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+	Literal(String),
+	Placeholder(Placeholder)
+}
+
+/// A result line such as `{input} => {result}`. `{{` and `}}` stand for literal braces.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+	parts: Vec<Part>
+}
+
+/// The values substituted into a [`Template`]. `time` is empty unless timing is on.
+pub struct Fields<'a> {
+	pub input: &'a str,
+	pub result: &'a str,
+	pub index: usize,
+	pub time: &'a str
+}
+
+impl Template {
+	pub fn parse(text: &str) -> Result<Self, String> {
+		let mut parts = Vec::new();
+		let mut literal = String::new();
+		let mut chars = text.chars();
+		while let Some(c) = chars.next() {
+			match c {
+				'{' if chars.as_str().starts_with('{') => {
+					chars.next();
+					literal.push('{');
+				}
+				'}' if chars.as_str().starts_with('}') => {
+					chars.next();
+					literal.push('}');
+				}
+				'{' => {
+					let Some((name, rest)) = chars.as_str().split_once('}') else {
+						return Err("unclosed '{' in template".to_string());
+					};
+					let Some((_, placeholder)) = PLACEHOLDERS.iter().find(|(known, _)| *known == name) else {
+						let known: Vec<String> = PLACEHOLDERS.iter().map(|(known, _)| format!("{{{}}}", known)).collect();
+						return Err(format!("unknown placeholder '{{{}}}', expected one of {}", name, known.join(", ")));
+					};
+					if !literal.is_empty() {
+						parts.push(Part::Literal(std::mem::take(&mut literal)));
+					}
+					parts.push(Part::Placeholder(*placeholder));
+					chars = rest.chars();
+				}
+				'}' => return Err("unmatched '}' in template, use '}}' for a literal brace".to_string()),
+				_ => literal.push(c)
+			}
+		}
+		if !literal.is_empty() {
+			parts.push(Part::Literal(literal));
+		}
+		Ok(Self { parts })
+	}
+
+	pub fn render(&self, fields: &Fields) -> String {
+		let mut text = String::new();
+		for part in &self.parts {
+			match part {
+				Part::Literal(literal) => text.push_str(literal),
+				Part::Placeholder(Placeholder::Input) => text.push_str(fields.input),
+				Part::Placeholder(Placeholder::Result) => text.push_str(fields.result),
+				Part::Placeholder(Placeholder::Index) => text.push_str(&fields.index.to_string()),
+				Part::Placeholder(Placeholder::Time) => text.push_str(fields.time)
+			}
+		}
+		text
+	}
+}
+impl Default for Template {
+	fn default() -> Self {
+		Self { parts: vec![
+			Part::Placeholder(Placeholder::Input),
+			Part::Literal(" = ".to_string()),
+			Part::Placeholder(Placeholder::Result)
+		] }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn fields() -> Fields<'static> {
+		Fields { input: "2 + 2", result: "4", index: 3, time: "" }
+	}
+
+	#[test]
+	fn placeholders_are_filled() {
+		assert_eq!(Template::default().render(&fields()), "2 + 2 = 4");
+		assert_eq!(Template::parse("${index}: {input} => {result}{time}").unwrap().render(&fields()), "$3: 2 + 2 => 4");
+		assert_eq!(Template::parse("{{{result}}}").unwrap().render(&fields()), "{4}");
+	}
+
+	#[test]
+	fn malformed_templates_are_refused() {
+		assert!(Template::parse("{input").unwrap_err().contains("unclosed"));
+		assert!(Template::parse("{value}").unwrap_err().contains("unknown placeholder '{value}'"));
+		assert!(Template::parse("a } b").unwrap_err().contains("unmatched"));
+	}
+}
@@ -0,0 +1,169 @@
+use alloc::collections::BTreeSet;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+
+use crate::{calculus, Context, Function, CONSTANTS};
+
+/// Operations one evaluation of a [`Context::sandboxed`] may spend.
+pub const SANDBOX_BUDGET: u64 = 100_000;
+/// How deeply an expression in a [`Context::sandboxed`] may nest, enough for four forms within each
+/// other.
+pub const SANDBOX_NESTING_LIMIT: usize = 64;
+/// How many characters a lexeme in a [`Context::sandboxed`] may have.
+pub const SANDBOX_TOKEN_LIMIT: usize = 256;
+
+impl Context {
+	/// A context for evaluating untrusted input: the constants, functions and calculus forms are
+	/// [protected](Context::protect), and evaluations are limited to [`SANDBOX_BUDGET`]
+	/// operations, [`SANDBOX_NESTING_LIMIT`] levels and lexemes of [`SANDBOX_TOKEN_LIMIT`]
+	/// characters. Variables set with [`Context::set_var_protected`] are kept from the input too.
+	pub fn sandboxed() -> Self {
+		let mut context = Self::new();
+		let names = CONSTANTS.iter().map(|(name, _)| *name)
+			.chain(Function::MAP.iter().map(|(name, _)| *name))
+			.chain(calculus::FORMS.iter().copied());
+		for name in names {
+			context.protect(name);
+		}
+		context.set_budget(Some(SANDBOX_BUDGET));
+		context.set_nesting_limit(SANDBOX_NESTING_LIMIT);
+		context.set_token_limit(SANDBOX_TOKEN_LIMIT);
+		context
+	}
+	/// A copy that exposes only the variables, templates, functions, constants and calculus forms
+	/// named in `names`, for evaluating input that must not see the rest; any other is an unknown
+	/// identifier. `let` is always there. The tape and the last result are left behind; settings,
+	/// limits and protections are kept.
+	pub fn sandbox(&self, names: &[&str]) -> Context {
+		let mut sandbox = self.clone();
+		let exposed = |name: &String| names.contains(&name.as_str());
+		let visible = names.iter().map(|name| name.to_string()).filter(|name| self.visible.as_ref().is_none_or(|visible| visible.contains(name)));
+		sandbox.visible = Some(Arc::new(visible.collect::<BTreeSet<_>>()));
+		Arc::make_mut(&mut sandbox.functions).retain(|name, _| exposed(name));
+		Arc::make_mut(&mut sandbox.variables).retain(|name, _| exposed(name));
+		Arc::make_mut(&mut sandbox.sigmas).retain(|name, _| exposed(name));
+		Arc::make_mut(&mut sandbox.durations).retain(exposed);
+		Arc::make_mut(&mut sandbox.tags).retain(|name, _| exposed(name));
+		Arc::make_mut(&mut sandbox.templates).retain(|name, _| exposed(name));
+		sandbox.placeholders.clear();
+		sandbox.tape_mut().clear();
+		sandbox.answer = None;
+		sandbox.answer_sigma = None;
+		sandbox.answer_duration = false;
+		sandbox.answer_tag = None;
+		sandbox.answer_percent = false;
+		sandbox.answer_base = None;
+		if let Some(cache) = sandbox.cache.as_mut() {
+			cache.clear();
+		}
+		sandbox
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{EvalError, EvalOutcome};
+
+	fn protected(context: &mut Context, statement: &str) -> String {
+		match context.execute(statement) {
+			Err(EvalError::Protected(name)) => name,
+			result => panic!("{} gave {:?}", statement, result)
+		}
+	}
+
+	#[test]
+	fn protected_names_are_read_but_never_assigned() {
+		let mut context = Context::new();
+		context.set_var_protected("rate", 0.25);
+		context.set_var("a", 1.0);
+		assert_eq!(context.eval("rate * 4").unwrap(), 1.0);
+		assert_eq!(protected(&mut context, "rate = 2"), "rate");
+		assert_eq!(protected(&mut context, "a, rate = 5, 6"), "rate");
+		assert_eq!(context.get_var("a"), Some(1.0));
+		assert_eq!(protected(&mut context, "ans = 1"), "ans");
+		assert!(matches!(context.eval("sum_over(rate, 1, 3, rate)"), Err(EvalError::Protected(name)) if name == "rate"));
+		assert_eq!(context.get_var("rate"), Some(0.25));
+		// The program itself still can
+		context.set_var("rate", 0.5);
+		assert_eq!(context.remove_var("rate"), Some(0.5));
+		assert!(context.is_protected("rate"));
+	}
+
+	#[test]
+	fn the_sandboxed_profile_protects_every_built_in_name() {
+		let mut context = Context::sandboxed();
+		for (statement, name) in [("pi = 3", "pi"), ("e = 2", "e"), ("sin = 1", "sin"), ("x, sqrt = 1, 2", "sqrt"), ("sum_over = 1", "sum_over")] {
+			assert_eq!(protected(&mut context, statement), name);
+		}
+		assert!(matches!(context.eval("integrate(pi, pi, 0, 1)"), Err(EvalError::Protected(name)) if name == "pi"));
+		assert_eq!(context.get_var("x"), None);
+		assert_eq!(context.eval("sin(pi / 2) * 2").unwrap(), 2.0);
+	}
+
+	#[test]
+	fn the_sandboxed_profile_evaluates_ordinary_arithmetic_within_its_limits() {
+		let mut context = Context::sandboxed();
+		assert_eq!(context.eval("1 + 2 * 3 - 4 / 2").unwrap(), 5.0);
+		assert_eq!(context.eval("sqrt(16) + max(1, 2)^3 + 10%").unwrap(), 13.2);
+		assert!(matches!(context.execute("total = 19.99 * 3").unwrap(), EvalOutcome::Assigned(name, _) if name == "total"));
+		assert_eq!(context.eval("total / 3").unwrap(), 19.99);
+		assert_eq!(context.eval("sum_over(k, 1, 100, k)").unwrap(), 5050.0);
+		let deep = format!("{}1{}", "(".repeat(SANDBOX_NESTING_LIMIT + 1), ")".repeat(SANDBOX_NESTING_LIMIT + 1));
+		assert!(matches!(context.eval(&deep), Err(EvalError::TooDeep { limit: SANDBOX_NESTING_LIMIT, .. })));
+		let long = "9".repeat(SANDBOX_TOKEN_LIMIT + 1);
+		assert!(matches!(context.eval(&long), Err(EvalError::TokenTooLong { limit: SANDBOX_TOKEN_LIMIT, .. })));
+		assert!(matches!(context.eval("sum_over(k, 1, 1e6, k)"), Err(EvalError::BudgetExceeded { limit, .. }) if limit == SANDBOX_BUDGET));
+	}
+
+	#[test]
+	fn a_sandbox_exposes_only_the_names_it_is_given() {
+		let mut context = Context::new();
+		context.set_var("price", 20.0);
+		context.set_var("secret", 42.0);
+		context.set_var_protected("tax", 0.2);
+		context.define_template("with_tax", "?net * (1 + tax)").unwrap();
+		context.define_template("leak", "secret").unwrap();
+		context.execute("secret * 2").unwrap();
+		let mut sandbox = context.sandbox(&["price", "tax", "with_tax"]);
+		assert_eq!(sandbox.eval("price * (1 + tax)").unwrap(), 24.0);
+		assert!(matches!(sandbox.eval("secret"), Err(EvalError::UnknownIdentifier(name, _)) if name == "secret"));
+		assert!(sandbox.eval("ans").is_err() && sandbox.eval("$1").is_err());
+		assert_eq!(sandbox.template("leak"), None);
+		assert_eq!(sandbox.use_template("with_tax", &[("net", "price")]).unwrap().value(), 24.0);
+		assert_eq!(protected(&mut sandbox, "tax = 0"), "tax");
+		assert_eq!(context.get_var("secret"), Some(42.0));
+	}
+
+	#[test]
+	fn a_protected_function_is_called_but_never_shadowed() {
+		let mut context = Context::new();
+		context.register_fn_protected("fee", &["x"], "x / 10").unwrap();
+		assert_eq!(context.eval("fee(100) + 1").unwrap(), 11.0);
+		assert_eq!(protected(&mut context, "fee = 1"), "fee");
+		assert_eq!(protected(&mut context, "let fee = 1 in fee"), "fee");
+		assert!(matches!(context.eval("sum_over(fee, 1, 3, fee)"), Err(EvalError::Protected(name)) if name == "fee"));
+		assert_eq!(context.eval("fee(50)").unwrap(), 5.0);
+	}
+
+	#[test]
+	fn a_sandbox_hides_the_functions_and_constants_it_is_not_given() {
+		let mut context = Context::new();
+		context.set_cache_capacity(8);
+		context.register_fn("area", &["w", "h"], "w * h").unwrap();
+		context.register_fn("leak", &[], "1").unwrap();
+		assert_eq!(context.eval("sin(0)").unwrap(), 0.0);
+		let sandbox = context.sandbox(&["sqrt", "area", "sum_over"]);
+		assert_eq!(sandbox.eval("sqrt(16) + area(2, 3) * 2").unwrap(), 16.0);
+		assert_eq!(sandbox.eval("sum_over(k, 1, 3, let j = k in j)").unwrap(), 6.0);
+		for (input, name, start) in [("sin(0)", "sin", 0), ("2 * pi", "pi", 4), ("1 + leak()", "leak", 4), ("product_over(k, 1, 3, k)", "product_over", 0)] {
+			let err = sandbox.eval(input).unwrap_err();
+			assert!(matches!(&err, EvalError::UnknownIdentifier(found, span) if found == name && span.start == start), "{}: {:?}", input, err);
+		}
+		// A sandbox of a sandbox sees no more than it
+		let inner = sandbox.sandbox(&["sqrt", "cos"]);
+		assert_eq!(inner.eval("sqrt(4)").unwrap(), 2.0);
+		assert!(matches!(inner.eval("cos(0)"), Err(EvalError::UnknownIdentifier(name, _)) if name == "cos"));
+		assert_eq!(context.eval("cos(0) + leak()").unwrap(), 2.0);
+	}
+}
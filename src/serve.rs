@@ -0,0 +1,154 @@
+use std::io::{self, BufRead, Write};
+
+use shunting_yard::{clean_line, Context, EvalOutcome};
+
+use crate::json::{self, Value};
+
+/// `--serve-stdio`: answers one JSON request per line of `reader`, such as
+/// `{"id":1,"expr":"x*2","vars":{"x":21}}`, with one response line on `writer`. `vars` only apply to
+/// that request, while assignments made by `expr` persist into later ones.
+pub fn serve<R: BufRead, W: Write>(reader: R, mut writer: W, context: &mut Context) -> io::Result<()> {
+	for (index, line) in reader.lines().enumerate() {
+		let line = line?;
+		let line = clean_line(&line, index == 0);
+		if line.trim().is_empty() {
+			continue;
+		}
+		writeln!(writer, "{}", respond(line, context))?;
+		writer.flush()?;
+	}
+	Ok(())
+}
+
+fn respond(line: &str, context: &mut Context) -> String {
+	let request = match json::parse(line) {
+		Ok(request) => request,
+		Err(message) => {
+			let id = json::find_member(line, "id").unwrap_or(Value::Null);
+			return invalid_request(&id, &format!("malformed JSON: {}", message));
+		}
+	};
+	let id = request.get("id").cloned().unwrap_or(Value::Null);
+	if !matches!(request, Value::Object(_)) {
+		return invalid_request(&id, "a request must be an object");
+	}
+	let Some(Value::String(expr)) = request.get("expr") else {
+		return invalid_request(&id, "\"expr\" must be a string");
+	};
+	let mut vars = Vec::new();
+	match request.get("vars") {
+		None | Some(Value::Null) => {}
+		Some(Value::Object(members)) => {
+			for (name, value) in members {
+				match value {
+					Value::Number(value) => vars.push((name.as_str(), *value)),
+					_ => return invalid_request(&id, &format!("variable '{}' must be a number", name))
+				}
+			}
+		}
+		Some(_) => return invalid_request(&id, "\"vars\" must be an object")
+	}
+
+	let saved: Vec<(&str, Option<f64>)> = vars.iter().map(|(name, _)| (*name, context.get_var(name))).collect();
+	for (name, value) in &vars {
+		context.set_var(name, *value);
+	}
+	let result = context.execute(expr);
+	let assigned: Vec<&str> = match &result {
+		Ok(EvalOutcome::Assigned(name, _)) => vec![name.as_str()],
+		Ok(EvalOutcome::AssignedMultiple(assigned)) => assigned.iter().map(|(name, _)| name.as_str()).collect(),
+		_ => Vec::new()
+	};
+	for (name, previous) in saved.into_iter().rev().filter(|(name, _)| !assigned.contains(name)) {
+		match previous {
+			Some(value) => context.set_var(name, value),
+			None => {
+				context.remove_var(name);
+			}
+		}
+	}
+
+	let id = id.render();
+	match result {
+		Ok(EvalOutcome::Value(value)) => format!("{{\"id\":{},\"ok\":true,\"value\":{}}}", id, json::number(value)),
+		Ok(EvalOutcome::Assigned(name, value) | EvalOutcome::Solved(name, value)) => format!(
+			"{{\"id\":{},\"ok\":true,\"name\":{},\"value\":{}}}", id, json::quote(&name), json::number(value)
+		),
+		Ok(EvalOutcome::Multiple(values)) => format!(
+			"{{\"id\":{},\"ok\":true,\"values\":{}}}", id, json::array(values.into_iter().map(json::number))
+		),
+		Ok(EvalOutcome::AssignedMultiple(assigned)) => format!(
+			"{{\"id\":{},\"ok\":true,\"names\":{},\"values\":{}}}", id,
+			json::array(assigned.iter().map(|(name, _)| json::quote(name))),
+			json::array(assigned.iter().map(|(_, value)| json::number(*value)))
+		),
+		Err(err) => format!("{{\"id\":{},\"ok\":false,\"error\":{}}}", id, json::render_error(&err, context))
+	}
+}
+
+fn invalid_request(id: &Value, message: &str) -> String {
+	format!(
+		"{{\"id\":{},\"ok\":false,\"error\":{{\"kind\":\"InvalidRequest\",\"code\":null,\"pos\":null,\"message\":{}}}}}",
+		id.render(), json::quote(message)
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn responses(requests: &str, context: &mut Context) -> Vec<Value> {
+		let mut out = Vec::new();
+		serve(requests.as_bytes(), &mut out, context).unwrap();
+		String::from_utf8(out).unwrap().lines().map(|line| json::parse(line).unwrap()).collect()
+	}
+
+	fn error_kind(response: &Value) -> Option<&Value> {
+		response.get("error").and_then(|error| error.get("kind"))
+	}
+
+	#[test]
+	fn requests_are_answered_in_order() {
+		let mut context = Context::new();
+		let requests = concat!(
+			"{\"id\":1,\"expr\":\"x*2\",\"vars\":{\"x\":21}}\n",
+			"\n",
+			"{\"id\":\"b\",\"expr\":\"x\"}\n",
+			"{\"id\":3,\"expr\":\"y = 5\"}\n",
+			"{\"id\":4,\"expr\":\"y + 1\"}\n",
+			"{\"id\":5,\"expr\":\"2 *\"}\n"
+		);
+		let responses = responses(requests, &mut context);
+		assert_eq!(responses.len(), 5);
+		assert_eq!(responses[0].render(), r#"{"id":1,"ok":true,"value":42}"#);
+		assert_eq!(responses[1].get("id"), Some(&Value::String("b".to_string())));
+		assert_eq!(error_kind(&responses[1]), Some(&Value::String("UnknownIdentifier".to_string())));
+		assert_eq!(responses[2].render(), r#"{"id":3,"ok":true,"name":"y","value":5}"#);
+		assert_eq!(responses[3].get("value"), Some(&Value::Number(6.0)));
+		assert_eq!(error_kind(&responses[4]), Some(&Value::String("IncompleteExpression".to_string())));
+	}
+
+	#[test]
+	fn request_variables_do_not_outlive_the_request() {
+		let mut context = Context::new();
+		context.set_var("x", 1.0);
+		let answered = responses("{\"id\":1,\"expr\":\"x + y\",\"vars\":{\"x\":2,\"y\":3}}\n{\"id\":2,\"expr\":\"x\"}\n", &mut context);
+		assert_eq!((answered[0].get("value"), answered[1].get("value")), (Some(&Value::Number(5.0)), Some(&Value::Number(1.0))));
+		assert_eq!(context.get_var("y"), None);
+		responses("{\"id\":3,\"expr\":\"x = x * 10\",\"vars\":{\"x\":4}}\n", &mut context);
+		assert_eq!(context.get_var("x"), Some(40.0));
+	}
+
+	#[test]
+	fn malformed_requests_keep_their_id_when_it_can_be_read() {
+		let mut context = Context::new();
+		let requests = "{\"id\":7,\"expr\":\"1 +\n{\"expr\": 3, \"id\": 8}\n[1, 2]\nnot json\n{\"id\":9,\"expr\":\"1\",\"vars\":{\"x\":\"a\"}}\n";
+		let responses = responses(requests, &mut context);
+		let ids: Vec<String> = responses.iter().map(|response| response.get("id").unwrap().render()).collect();
+		assert_eq!(ids, ["7", "8", "null", "null", "9"]);
+		for response in &responses {
+			assert_eq!(response.get("ok"), Some(&Value::Boolean(false)));
+			assert_eq!(error_kind(response), Some(&Value::String("InvalidRequest".to_string())));
+		}
+	}
+}
@@ -0,0 +1,141 @@
+use alloc::boxed::Box;
+
+use crate::{BinOp, Context, Expr, Operator};
+
+/// Cleans up `expr` without changing its value: subtrees without names are folded into numbers,
+/// signs that cancel are removed and so are `+ 0`, `- 0`, `* 1` and `/ 1`. Names stay, so `2*3 + x`
+/// becomes `6 + x`. Print the result with [`crate::to_infix`] for the fewest parentheses.
+pub fn simplify(expr: &Expr) -> Expr {
+	simplify_in(expr, &Context::new())
+}
+
+/// Like [`simplify`], folding with the angle mode and rounding of `context`.
+pub fn simplify_in(expr: &Expr, context: &Context) -> Expr {
+	let simplified = match expr {
+		Expr::Number(_) | Expr::Name(..) | Expr::Var(_) => return expr.clone(),
+		Expr::Unary(op, value) => match simplify_in(value, context) {
+			value if op.op == BinOp::Add && !is_percent(&value) => value,
+			Expr::Unary(inner, value) if op.op == BinOp::Sub && inner.op == BinOp::Sub && !is_percent(&value) => *value,
+			value => Expr::Unary(*op, Box::new(value))
+		},
+		Expr::Binary(op, lhs, rhs) => identity(*op, simplify_in(lhs, context), simplify_in(rhs, context)),
+		Expr::Call(func, args) => Expr::Call(*func, args.iter().map(|arg| simplify_in(arg, context)).collect()),
+		Expr::Convert { value, from, to } => Expr::Convert { value: Box::new(simplify_in(value, context)), from, to },
+		// A percentage must stay one, `x + 10%` means something else than `x + 0.1`
+		Expr::Percent(value) => return Expr::Percent(Box::new(simplify_in(value, context)))
+	};
+	if has_names(&simplified) || is_percent(&simplified) {
+		return simplified;
+	}
+	match simplified.eval_in(context) {
+		value if value.is_finite() => number(value),
+		_ => simplified
+	}
+}
+
+/// Drops an operand that leaves the other unchanged. The one that remains may not be a percentage,
+/// which would turn an enclosing sum into a percent change.
+fn identity(op: Operator, lhs: Expr, rhs: Expr) -> Expr {
+	let is = |expr: &Expr, n: f64| matches!(expr, Expr::Number(value) if *value == n);
+	match op.op {
+		BinOp::Add | BinOp::Sub if is(&rhs, 0.0) && !is_percent(&lhs) => lhs,
+		BinOp::Add if is(&lhs, 0.0) && !is_percent(&rhs) => rhs,
+		BinOp::Mul | BinOp::Div if is(&rhs, 1.0) && !is_percent(&lhs) => lhs,
+		BinOp::Mul if is(&lhs, 1.0) && !is_percent(&rhs) => rhs,
+		// Only where the other operand cannot be infinite or NaN
+		BinOp::Mul if (is(&lhs, 0.0) && is_polynomial(&rhs)) || (is(&rhs, 0.0) && is_polynomial(&lhs)) => Expr::Number(0.0),
+		_ => Expr::Binary(op, Box::new(lhs), Box::new(rhs))
+	}
+}
+
+/// A folded value. Negative values keep their sign as an operator, so the infix reads back the same.
+fn number(value: f64) -> Expr {
+	if value.is_sign_negative() {
+		let minus = Operator { argc: 1, precedence: 255, ..Operator::by_char('-').unwrap() };
+		Expr::Unary(minus, Box::new(Expr::Number(-value)))
+	} else {
+		Expr::Number(value)
+	}
+}
+
+fn is_percent(expr: &Expr) -> bool {
+	matches!(expr, Expr::Percent(_))
+}
+
+fn has_names(expr: &Expr) -> bool {
+	match expr {
+		Expr::Number(_) => false,
+		Expr::Name(..) | Expr::Var(_) => true,
+		Expr::Unary(_, value) | Expr::Percent(value) | Expr::Convert { value, .. } => has_names(value),
+		Expr::Binary(_, lhs, rhs) => has_names(lhs) || has_names(rhs),
+		Expr::Call(_, args) => args.iter().any(has_names)
+	}
+}
+
+/// Sums and products of finite numbers and names, which stay finite for finite values.
+fn is_polynomial(expr: &Expr) -> bool {
+	match expr {
+		Expr::Number(value) => value.is_finite(),
+		Expr::Name(..) | Expr::Var(_) => true,
+		Expr::Unary(_, value) => is_polynomial(value),
+		Expr::Binary(op, lhs, rhs) => matches!(op.op, BinOp::Add | BinOp::Sub | BinOp::Mul) && is_polynomial(lhs) && is_polynomial(rhs),
+		_ => false
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::string::String;
+	use super::*;
+	use crate::to_infix;
+
+	fn simplified(expression: &str) -> String {
+		let mut context = Context::new();
+		context.set_var("x", 5.0);
+		context.set_var("y", 2.0);
+		to_infix(&simplify(&context.to_ast(expression).unwrap()))
+	}
+
+	#[test]
+	fn constants_fold_and_names_stay() {
+		assert_eq!(simplified("2*3 + x"), "6+x");
+		assert_eq!(simplified("(2 + 3) * x ^ (1 + 1)"), "5*x^2");
+		assert_eq!(simplified("-(2 * 3) + x"), "-6+x");
+		assert_eq!(simplified("2 - 5"), "-3");
+		// Folding to infinity would lose the expression
+		assert_eq!(simplified("1/0 + x"), "1/0+x");
+	}
+
+	#[test]
+	fn identities_and_double_signs_are_removed() {
+		for expression in ["--x", "+x", "x + 0", "0 + x", "x - 0", "x * 1", "1 * x", "x / 1"] {
+			assert_eq!(simplified(expression), "x", "{}", expression);
+		}
+		assert_eq!(simplified("(x + y) * (2 - 1)"), "x+y");
+		assert_eq!(simplified("0 * x"), "0");
+		// sqrt(x) may be NaN, which 0 * NaN keeps
+		assert_eq!(simplified("0 * sqrt(x)"), "0*sqrt(x)");
+	}
+
+	#[test]
+	fn percentages_stay_percentages() {
+		assert_eq!(simplified("x + 10%"), "x+10%");
+		// 0 + 10% is 10% of 0, and once multiplied a percentage is a plain number
+		assert_eq!(simplified("x + (0 + 10%)"), "x");
+		assert_eq!(simplified("x + 10% * 1"), "x+0.1");
+		assert_eq!(simplified("x - +10%"), "x-0.1");
+	}
+
+	#[test]
+	fn undefined_names_stay_names() {
+		let context = Context::new();
+		let simplified = |expression: &str| to_infix(&simplify(&context.to_ast(expression).unwrap()));
+		assert_eq!(simplified("x*1 + 0"), "x");
+		assert_eq!(simplified("2*3 + z * (4 - 3)"), "6+z");
+		assert_eq!(simplified("0 * (a + b)"), "0");
+		let ast = simplify(&context.to_ast("x*1 + 2*3").unwrap());
+		let mut defined = Context::new();
+		defined.set_var("x", 4.0);
+		assert_eq!((ast.eval_in(&defined), ast.eval().is_nan()), (10.0, true));
+	}
+}
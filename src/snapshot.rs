@@ -0,0 +1,166 @@
+use alloc::collections::BTreeSet;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::Context;
+
+/// The state of a [`Context`] at one point, to go back to with [`Context::restore`]. Variables,
+/// their uncertainties and kinds, the functions of the program, templates and the tape are shared
+/// with the context until either changes them, so taking a snapshot costs the same however many
+/// there are. The settings and the expression cache, which hold at most its capacity of entries,
+/// are copied.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+	context: Context
+}
+impl Snapshot {
+	/// The context as it was, for reading it or comparing with [`Context::diff`].
+	pub fn context(&self) -> &Context {
+		&self.context
+	}
+}
+
+/// The variables, functions and templates that differ between two contexts, each list sorted by
+/// name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ContextDiff {
+	pub added: Vec<String>,
+	/// Variables in both whose value, uncertainty, tag or being a duration differs.
+	pub changed: Vec<String>,
+	pub removed: Vec<String>,
+	/// Functions defined, redefined or removed with [`Context::register_fn`] and
+	/// [`Context::remove_fn`].
+	pub functions: Vec<String>,
+	/// Templates defined or redefined.
+	pub templates: Vec<String>
+}
+impl ContextDiff {
+	pub fn is_empty(&self) -> bool {
+		self.added.is_empty() && self.changed.is_empty() && self.removed.is_empty() && self.functions.is_empty() && self.templates.is_empty()
+	}
+}
+
+impl Context {
+	pub fn snapshot(&self) -> Snapshot {
+		Snapshot { context: self.clone() }
+	}
+	/// Puts back everything as it was when `snapshot` was taken, settings and tape included.
+	pub fn restore(&mut self, snapshot: Snapshot) {
+		*self = snapshot.context;
+	}
+	/// What changed from `earlier` to this context: a variable is added when only this context has
+	/// it. Comparing with a context that shares its variables returns at once.
+	pub fn diff(&self, earlier: &Context) -> ContextDiff {
+		let mut diff = ContextDiff::default();
+		if !Arc::ptr_eq(&self.functions, &earlier.functions) {
+			let names: BTreeSet<&str> = self.functions().chain(earlier.functions()).map(|(name, _)| name).collect();
+			diff.functions = names.into_iter()
+				.filter(|name| self.function(name) != earlier.function(name))
+				.map(str::to_string)
+				.collect();
+		}
+		if !Arc::ptr_eq(&self.templates, &earlier.templates) {
+			diff.templates = self.templates()
+				.filter(|(name, body)| earlier.template(name) != Some(body))
+				.map(|(name, _)| name.to_string())
+				.collect();
+		}
+		if Arc::ptr_eq(&self.variables, &earlier.variables)
+			&& Arc::ptr_eq(&self.sigmas, &earlier.sigmas)
+			&& Arc::ptr_eq(&self.durations, &earlier.durations)
+			&& Arc::ptr_eq(&self.tags, &earlier.tags)
+		{
+			return diff;
+		}
+		for (name, value) in self.variables() {
+			match earlier.get_var(name) {
+				None => diff.added.push(name.to_string()),
+				Some(before) if before.to_bits() != value.to_bits()
+					|| earlier.var_uncertainty(name) != self.var_uncertainty(name)
+					|| earlier.is_duration(name) != self.is_duration(name)
+					|| earlier.var_tag(name) != self.var_tag(name) => diff.changed.push(name.to_string()),
+				Some(_) => {}
+			}
+		}
+		diff.removed = earlier.variables().map(|(name, _)| name).filter(|name| self.get_var(name).is_none()).map(str::to_string).collect();
+		diff.added.sort();
+		diff.changed.sort();
+		diff.removed.sort();
+		diff
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::AngleMode;
+
+	#[test]
+	fn restoring_undoes_every_change() {
+		let mut context = Context::new();
+		context.execute("x = 2").unwrap();
+		context.register_fn("double", &["v"], "2 * v").unwrap();
+		context.define_template("double", "?v * 2").unwrap();
+		let snapshot = context.snapshot();
+		context.execute("x = 3").unwrap();
+		context.execute("y = x * 2").unwrap();
+		context.remove_fn("double");
+		context.register_fn("half", &["v"], "v / 2").unwrap();
+		context.remove_template("double");
+		context.set_angle_mode(AngleMode::Degrees);
+		context.tape_mut().clear();
+		context.restore(snapshot);
+		assert_eq!((context.get_var("x"), context.get_var("y")), (Some(2.0), None));
+		assert_eq!((context.eval("double(4)").ok(), context.function("half")), (Some(8.0), None));
+		assert_eq!(context.template("double"), Some("?v * 2"));
+		assert_eq!(context.angle_mode(), AngleMode::Radians);
+		assert_eq!(context.tape().len(), 1);
+		assert_eq!(context.answer(), Some(2.0));
+	}
+
+	#[test]
+	fn a_snapshot_shares_what_has_not_changed() {
+		let mut context = Context::new();
+		context.execute("x = 2").unwrap();
+		let snapshot = context.snapshot();
+		assert!(Arc::ptr_eq(&context.variables, &snapshot.context().variables));
+		context.set_angle_mode(AngleMode::Degrees);
+		assert!(Arc::ptr_eq(&context.variables, &snapshot.context().variables));
+		context.execute("x = 5").unwrap();
+		assert!(!Arc::ptr_eq(&context.variables, &snapshot.context().variables));
+		assert_eq!(snapshot.context().get_var("x"), Some(2.0));
+	}
+
+	#[test]
+	fn a_diff_lists_added_changed_and_removed_names() {
+		let mut context = Context::new();
+		for statement in ["kept = 1", "changed = 2", "gone = 3", "tagged = 4"] {
+			context.execute(statement).unwrap();
+		}
+		context.register_fn("same", &["v"], "v").unwrap();
+		context.register_fn("redefined", &["v"], "v").unwrap();
+		context.register_fn("dropped", &[], "1").unwrap();
+		let before = context.snapshot();
+		assert!(context.diff(before.context()).is_empty());
+		for statement in ["changed = 20", "b = 1", "a = 2", "tagged = 4 [kg]", "kept = 1"] {
+			context.execute(statement).unwrap();
+		}
+		context.remove_var("gone");
+		context.register_fn("same", &["v"], "v").unwrap();
+		context.register_fn("redefined", &["v"], "v + 1").unwrap();
+		context.register_fn("new", &[], "2").unwrap();
+		context.remove_fn("dropped");
+		context.define_template("t", "?x + 1").unwrap();
+		let diff = context.diff(before.context());
+		assert_eq!(diff, ContextDiff {
+			added: vec!["a".to_string(), "b".to_string()],
+			changed: vec!["changed".to_string(), "tagged".to_string()],
+			removed: vec!["gone".to_string()],
+			functions: vec!["dropped".to_string(), "new".to_string(), "redefined".to_string()],
+			templates: vec!["t".to_string()]
+		});
+		assert!(!diff.is_empty());
+		assert_eq!(before.context().diff(&context).removed, ["a", "b"]);
+	}
+}
@@ -0,0 +1,149 @@
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{Context, EvalError, EvalOutcome};
+
+/// Where the residual of an equation is evaluated beyond 0 and 1 to make sure it is linear. They
+/// are irregular so that periodic or stepped functions are unlikely to pass by chance.
+const PROBES: [f64; 2] = [2.5, -7.25];
+
+/// The equation of a statement `solve LHS = RHS`, with its offset in the statement.
+pub(crate) fn equation(statement: &str) -> Option<(&str, usize)> {
+	let trimmed = statement.trim_start();
+	let rest = trimmed.strip_prefix("solve")?;
+	rest.starts_with(char::is_whitespace).then(|| (rest, statement.len() - rest.len()))
+}
+
+impl Context {
+	/// Solves `LHS = RHS` for the one name in it that has no value, which must appear linearly. The
+	/// solution becomes `ans`; the unknown is left without a value, so it can be solved for again.
+	/// The residual `LHS - RHS` is evaluated at a few points to find its slope and check that it is a
+	/// straight line.
+	pub fn solve(&mut self, equation: &str) -> Result<EvalOutcome, EvalError> {
+		let (lhs, rhs) = match equation.split_once('=') {
+			Some((lhs, rhs)) if !rhs.contains('=') => (lhs, rhs),
+			_ => return Err(EvalError::ExpectedEquation)
+		};
+		let rhs_offset = lhs.len() + 1;
+		// The residual, and the larger side to judge rounding against
+		let residual = |context: &Context| -> Result<(f64, f64), EvalError> {
+			let left = context.eval(lhs)?;
+			let right = context.eval(rhs).map_err(|err| err.offset(rhs_offset))?;
+			Ok((left - right, left.abs().max(right.abs())))
+		};
+		// Every name that has no value is found by giving it one and trying again
+		let mut unknowns: Vec<String> = Vec::new();
+		let first = loop {
+			match residual(self) {
+				Err(EvalError::UnknownIdentifier(name, _)) if !unknowns.contains(&name) => {
+					self.set_var(&name, 0.0);
+					unknowns.push(name);
+				}
+				result => break result
+			}
+		};
+		let Some(unknown) = unknowns.first().cloned() else {
+			let (residual, scale) = first?;
+			return Err(if is_zero(residual, scale) { EvalError::InfiniteSolutions } else { EvalError::NoSolution });
+		};
+		let mut at = |x: f64| {
+			self.set_var(&unknown, x);
+			residual(self)
+		};
+		let solved = match unknowns.len() {
+			1 => first.and_then(|(intercept, mut scale)| {
+				let (value, size) = at(1.0)?;
+				let slope = value - intercept;
+				scale = scale.max(size);
+				for x in PROBES {
+					let (value, size) = at(x)?;
+					let expected = slope * x + intercept;
+					scale = scale.max(size);
+					if !(value - expected).abs().le(&(1e-9 * size.max(expected.abs()))) {
+						return Err(EvalError::Nonlinear(unknown.clone()));
+					}
+				}
+				match (is_zero(slope, scale), is_zero(intercept, scale)) {
+					(true, true) => return Err(EvalError::InfiniteSolutions),
+					(true, false) => return Err(EvalError::NoSolution),
+					_ => {}
+				}
+				// The slope between 0 and 1 loses digits for a distant solution, so take it again from there
+				let mut x = -intercept / slope;
+				for _ in 0 .. 3 {
+					let (value, _) = at(x)?;
+					if value == 0.0 || value == intercept {
+						break;
+					}
+					x *= intercept / (intercept - value);
+				}
+				Ok(x)
+			}),
+			_ => Err(EvalError::MultipleUnknowns(unknowns.clone()))
+		};
+		for name in &unknowns {
+			self.remove_var(name);
+		}
+		let value = solved?;
+		self.answer = Some(value);
+		self.answer_sigma = None;
+		self.answer_duration = false;
+		self.answer_tag = None;
+		self.answer_percent = false;
+		self.answer_base = None;
+		self.tape_mut().push(format!("solve{}", equation).trim(), value);
+		Ok(EvalOutcome::Solved(unknown, value))
+	}
+}
+
+/// Whether `value` is zero up to the rounding of numbers as large as `scale`.
+fn is_zero(value: f64, scale: f64) -> bool {
+	value.abs() <= 1e-14 * scale
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::string::ToString;
+	use super::*;
+
+	fn solved(context: &mut Context, statement: &str) -> Result<(String, f64), EvalError> {
+		match context.execute(statement)? {
+			EvalOutcome::Solved(name, value) => Ok((name, value)),
+			outcome => panic!("{}: {:?}", statement, outcome)
+		}
+	}
+
+	#[test]
+	fn a_linear_unknown_is_solved_for() {
+		let mut context = Context::new();
+		assert_eq!(solved(&mut context, "solve 2*x + 3 = 7").unwrap(), ("x".to_string(), 2.0));
+		assert_eq!(context.get_var("x"), None);
+		assert_eq!(context.execute("ans").unwrap().value(), 2.0);
+		assert_eq!(solved(&mut context, "solve 3*x = x + 4").unwrap().1, 2.0);
+		context.execute("a = 4").unwrap();
+		assert_eq!(solved(&mut context, "solve a*t - 1 = 0").unwrap(), ("t".to_string(), 0.25));
+		assert!((solved(&mut context, "solve (x - 1e6) / 3 = 0").unwrap().1 - 1e6).abs() < 1e-6);
+		assert_eq!(equation("solve x = 1"), Some((" x = 1", 5)));
+		assert_eq!(equation("solver = 1"), None);
+	}
+
+	#[test]
+	fn nonlinear_and_several_unknowns_are_refused() {
+		let mut context = Context::new();
+		assert!(matches!(solved(&mut context, "solve x*x = 4"), Err(EvalError::Nonlinear(name)) if name == "x"));
+		assert!(matches!(solved(&mut context, "solve sin(x) = 0.5"), Err(EvalError::Nonlinear(_))));
+		assert!(matches!(solved(&mut context, "solve x + y = 1"), Err(EvalError::MultipleUnknowns(names)) if names == ["x", "y"]));
+		assert!(matches!(solved(&mut context, "solve 2 + 3"), Err(EvalError::ExpectedEquation)));
+		assert!(matches!(solved(&mut context, "solve x = 1 = 2"), Err(EvalError::ExpectedEquation)));
+		assert_eq!((context.get_var("x"), context.get_var("y")), (None, None));
+	}
+
+	#[test]
+	fn degenerate_equations_have_no_or_infinite_solutions() {
+		let mut context = Context::new();
+		assert!(matches!(solved(&mut context, "solve x = x + 1"), Err(EvalError::NoSolution)));
+		assert!(matches!(solved(&mut context, "solve x = x"), Err(EvalError::InfiniteSolutions)));
+		assert!(matches!(solved(&mut context, "solve 0 = 0"), Err(EvalError::InfiniteSolutions)));
+		assert!(matches!(solved(&mut context, "solve 1 = 2"), Err(EvalError::NoSolution)));
+	}
+}
@@ -0,0 +1,315 @@
+use alloc::collections::VecDeque;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::binding::{self, Let};
+use crate::calculus::form_name;
+use crate::{Context, EvalError, Lexeme, LexemeKind, Span, Token};
+
+/// A convenience that makes a result harder to audit, which strict mode rejects.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum StrictRule {
+	/// `ans` for the previous result.
+	Answer,
+	/// `of` for multiplication, as in `20% of 150`.
+	Of,
+	/// `150 + 10%` as 165 rather than 150.1.
+	PercentChange,
+	/// Infinite and NaN results, such as `1/0`.
+	NonFinite,
+	/// A literal with more digits than a float keeps, such as `12345678901234567890`. Unlike the
+	/// others this rule warns by default.
+	InexactLiteral,
+	/// A display tag lost to an operation that cannot keep it, such as `2 [usd] + 1 [eur]`. It warns
+	/// by default too.
+	DroppedTag,
+	/// In money mode, a value rounded to whole cents, such as `100 / 3`. It warns by default too.
+	MoneyRounding
+}
+impl StrictRule {
+	pub const ALL: [StrictRule; 7] = [
+		StrictRule::Answer, StrictRule::Of, StrictRule::PercentChange, StrictRule::NonFinite, StrictRule::InexactLiteral,
+		StrictRule::DroppedTag, StrictRule::MoneyRounding
+	];
+
+	pub fn description(self) -> &'static str {
+		match self {
+			Self::Answer => "'ans' for the previous result",
+			Self::Of => "'of' for multiplication",
+			Self::PercentChange => "adding or subtracting a percentage as a percent change",
+			Self::NonFinite => "an infinite or NaN result",
+			Self::InexactLiteral => "a literal that cannot be represented exactly",
+			Self::DroppedTag => "a tag dropped by an operation",
+			Self::MoneyRounding => "an amount of money rounded to cents"
+		}
+	}
+	/// The level before any is set and after strict mode is turned off.
+	pub fn default_level(self) -> RuleLevel {
+		match self {
+			Self::InexactLiteral | Self::DroppedTag | Self::MoneyRounding => RuleLevel::Warn,
+			_ => RuleLevel::Allow
+		}
+	}
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum RuleLevel {
+	#[default]
+	Allow,
+	/// Evaluates as usual and records a [`Violation`], see [`Context::warnings`].
+	Warn,
+	/// Fails with [`EvalError::Strict`].
+	Error
+}
+impl RuleLevel {
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Allow => "allow",
+			Self::Warn => "warn",
+			Self::Error => "error"
+		}
+	}
+}
+
+/// A use of a convenience whose rule is at [`RuleLevel::Warn`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+	pub rule: StrictRule,
+	/// Where in the statement, for the rules about what was written.
+	pub span: Option<Span>,
+	/// What happened in particular, shown instead of the description of the rule.
+	pub detail: Option<String>
+}
+impl Display for Violation {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let what = self.detail.as_deref().unwrap_or(self.rule.description());
+		match self.span {
+			Some(span) => write!(f, "{} at position {}", what, span.start),
+			None => write!(f, "{}", what)
+		}
+	}
+}
+
+/// Checks what `expression`, `offset` into its statement, was written with and what its RPN does,
+/// adding warnings to `violations`.
+pub(crate) fn check_input(
+	expression: &str, offset: usize, rpn: &VecDeque<Token>, context: &Context, violations: &mut Vec<Violation>
+) -> Result<(), EvalError> {
+	// Warning about literals is the default, so it should not mean lexing every expression twice
+	let lossy = context.rule(StrictRule::InexactLiteral) != RuleLevel::Allow && has_long_number(expression, context);
+	// Tags and money are followed through the RPN while evaluating
+	let others = StrictRule::ALL.iter()
+		.filter(|rule| !matches!(rule, StrictRule::InexactLiteral | StrictRule::DroppedTag | StrictRule::MoneyRounding))
+		.any(|rule| context.rule(*rule) != RuleLevel::Allow);
+	if !lossy && !others {
+		return Ok(());
+	}
+	scan(expression, offset, context, violations)?;
+	if rpn.iter().any(|tok| matches!(tok, Token::PercentChange(_))) {
+		report(StrictRule::PercentChange, None, None, context, violations)?;
+	}
+	Ok(())
+}
+
+pub(crate) fn check_value(value: f64, context: &Context, violations: &mut Vec<Violation>) -> Result<(), EvalError> {
+	if !value.is_finite() {
+		report(StrictRule::NonFinite, None, None, context, violations)?;
+	}
+	Ok(())
+}
+
+/// The names of `expression`, which starts `offset` into the statement. The arguments of forms,
+/// which are lexed as one lexeme, are scanned too.
+fn scan(expression: &str, offset: usize, context: &Context, violations: &mut Vec<Violation>) -> Result<(), EvalError> {
+	// The expression has been parsed already, so there are no errors to expect
+	for Lexeme { kind, span } in context.lexer(expression).map_while(Result::ok) {
+		let text = &expression[span.start .. span.end];
+		let at = Span::new(span.start + offset, span.end + offset);
+		match kind {
+			LexemeKind::Identifier if text == "ans" && context.get_var(text).is_none() => {
+				report(StrictRule::Answer, Some(at), None, context, violations)?;
+			}
+			LexemeKind::Identifier if text == "of" => report(StrictRule::Of, Some(at), None, context, violations)?,
+			LexemeKind::Number(value) if !is_exact(text, value, context) => {
+				let used = if value.fract() == 0.0 { format!("{:.0}", value) } else { value.to_string() };
+				let detail = format!("literal {} cannot be represented exactly; using {}", text, used);
+				report(StrictRule::InexactLiteral, Some(at), Some(detail), context, violations)?;
+			}
+			LexemeKind::Form if form_name(text) == "let" => {
+				if let Some(Let { value, body, .. }) = binding::split(text, context.display().locale.argument_separator()) {
+					for (start, end) in [value, body] {
+						scan(&text[start .. end], at.start + start, context, violations)?;
+					}
+				}
+			}
+			LexemeKind::Form => {
+				let open = text.find('(').unwrap_or(0);
+				scan(&text[open ..], at.start + open, context, violations)?;
+			}
+			_ => {}
+		}
+	}
+	Ok(())
+}
+
+/// Whether the digits of a number literal are what was parsed from them, either as written or as the
+/// shortest digits that parse to the same value. Up to 15 significant digits always are.
+fn is_exact(text: &str, value: f64, context: &Context) -> bool {
+	let locale = context.display().locale;
+	let typed: String = text.chars()
+		.filter(|c| Some(*c) != locale.grouping())
+		.map(|c| if c == locale.decimal() { '.' } else { c })
+		.collect();
+	// With an exponent only the digits before it count, shown as `{:e}` shows them
+	if let Some((mantissa, _)) = typed.split_once(['e', 'E']) {
+		let shortest = format!("{:e}", value);
+		let (shortest, _) = shortest.split_once('e').unwrap_or_default();
+		let digits = |text: &str| -> String { text.chars().filter(char::is_ascii_digit).skip_while(|c| *c == '0').collect() };
+		let (typed, shortest) = (digits(mantissa), digits(shortest));
+		return typed.len() <= 15 || typed.trim_end_matches('0') == shortest.trim_end_matches('0');
+	}
+	let typed = normalize(&typed);
+	if typed.chars().filter(char::is_ascii_digit).skip_while(|c| *c == '0').count() <= 15 {
+		return true;
+	}
+	typed == normalize(&value.to_string()) || typed == normalize(&format!("{:.1074}", value))
+}
+
+/// Whether `expression` has a number with more than 15 digits, the most that are always exact.
+fn has_long_number(expression: &str, context: &Context) -> bool {
+	let locale = context.display().locale;
+	expression
+		.split(|c: char| !(c.is_ascii_digit() || c == locale.decimal() || Some(c) == locale.grouping()))
+		.any(|number| number.chars().filter(char::is_ascii_digit).count() > 15)
+}
+
+/// `007.50` as `7.5`, `1.0` as `1`.
+fn normalize(digits: &str) -> String {
+	let (integer, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+	let integer = integer.trim_start_matches('0');
+	let fraction = fraction.trim_end_matches('0');
+	format!("{}.{}", if integer.is_empty() { "0" } else { integer }, fraction)
+}
+
+pub(crate) fn report(
+	rule: StrictRule, span: Option<Span>, detail: Option<String>, context: &Context, violations: &mut Vec<Violation>
+) -> Result<(), EvalError> {
+	match context.rule(rule) {
+		RuleLevel::Allow => Ok(()),
+		RuleLevel::Warn => {
+			violations.push(Violation { rule, span, detail });
+			Ok(())
+		}
+		RuleLevel::Error => Err(EvalError::Strict { rule, span })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Locale;
+
+	/// A statement that uses each rule's convenience, and the context it is executed in.
+	fn using(rule: StrictRule) -> (&'static str, Context) {
+		let mut context = Context::new();
+		context.set_answer(4.0);
+		let statement = match rule {
+			StrictRule::Answer => "ans * 2",
+			StrictRule::Of => "20% of 150",
+			StrictRule::PercentChange => "150 + 10%",
+			StrictRule::NonFinite => "1 / 0",
+			StrictRule::InexactLiteral => "12345678901234567890",
+			StrictRule::DroppedTag => "2 [usd] + 1 [eur]",
+			StrictRule::MoneyRounding => {
+				context.set_money(true);
+				"100 / 3"
+			}
+		};
+		(statement, context)
+	}
+
+	#[test]
+	fn each_rule_allows_warns_or_fails() {
+		for rule in StrictRule::ALL {
+			for level in [RuleLevel::Allow, RuleLevel::Warn, RuleLevel::Error] {
+				let (statement, mut context) = using(rule);
+				context.set_rule(rule, level);
+				let result = context.execute(statement);
+				let warned: Vec<StrictRule> = context.warnings().iter().map(|violation| violation.rule).collect();
+				match level {
+					RuleLevel::Allow => assert!(result.is_ok() && warned.is_empty(), "{:?} {:?}", rule, warned),
+					RuleLevel::Warn => assert!(result.is_ok() && warned == [rule], "{:?} {:?}", rule, warned),
+					RuleLevel::Error => assert!(matches!(result, Err(EvalError::Strict { rule: failed, .. }) if failed == rule), "{:?}", rule)
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn rules_only_apply_to_their_convenience() {
+		let mut context = Context::new();
+		context.set_strict(true);
+		assert!(context.is_strict());
+		for statement in ["answer = 2", "answer * 2", "0.5 * 150", "150 + 10", "1 / 4", "1234567890.12345", "2 [usd] + 1 [usd]"] {
+			assert!(context.execute(statement).is_ok(), "{}", statement);
+		}
+		context.set_strict(false);
+		assert!(!context.is_strict());
+		assert_eq!(StrictRule::ALL.map(|rule| context.rule(rule)), StrictRule::ALL.map(StrictRule::default_level));
+	}
+
+	#[test]
+	fn violations_name_the_rule_and_where() {
+		let mut context = Context::new();
+		context.set_rule(StrictRule::Of, RuleLevel::Error);
+		let err = context.execute("x = 20% of 150").unwrap_err();
+		assert!(matches!(err, EvalError::Strict { rule: StrictRule::Of, span: Some(span) } if span == Span::new(8, 10)));
+		assert_eq!(err.to_string(), "'of' for multiplication is not allowed in strict mode");
+		context.set_rule(StrictRule::Answer, RuleLevel::Warn);
+		context.set_answer(1.0);
+		context.execute("sum_over(k, 1, 2, k * ans)").unwrap();
+		assert_eq!(context.warnings()[0].to_string(), "'ans' for the previous result at position 22");
+		let violation = Violation { rule: StrictRule::NonFinite, span: None, detail: None };
+		assert_eq!(violation.to_string(), "an infinite or NaN result");
+	}
+
+	fn literal_warnings(statement: &str) -> Vec<String> {
+		let mut context = Context::new();
+		context.execute(statement).unwrap();
+		context.warnings().iter().map(Violation::to_string).collect()
+	}
+
+	#[test]
+	fn literals_warn_only_when_their_digits_are_lost() {
+		assert_eq!(
+			literal_warnings("12345678901234567890 + 1"),
+			["literal 12345678901234567890 cannot be represented exactly; using 12345678901234567168 at position 0"]
+		);
+		assert_eq!(literal_warnings("x = 1 + 0.12345678901234567"), ["literal 0.12345678901234567 cannot be represented exactly; using 0.12345678901234566 at position 8"]);
+		// 2^60, 2^-3 written out and normal literals are exact
+		for statement in ["1152921504606846976", "0.125000000000000000", "1.50", "007", "123456789012345", "1e300", "2.5e-2"] {
+			assert!(literal_warnings(statement).is_empty(), "{}", statement);
+		}
+		assert_eq!(literal_warnings("1.2345678901234567890e5").len(), 1);
+		let mut context = Context::new();
+		context.set_rule(StrictRule::InexactLiteral, RuleLevel::Allow);
+		context.execute("12345678901234567890").unwrap();
+		assert!(context.warnings().is_empty());
+	}
+
+	#[test]
+	fn literals_are_compared_as_the_locale_writes_them() {
+		assert_eq!(normalize("007.50"), "7.5");
+		assert_eq!(normalize("1.0"), "1.");
+		assert_eq!(normalize("0"), "0.");
+		let mut context = Context::new();
+		context.display_mut().locale = Locale::new(',', Some('.')).unwrap();
+		context.execute("1.152.921.504.606.846.976 + 0,5").unwrap();
+		assert!(context.warnings().is_empty());
+		context.execute("12.345.678.901.234.567.890").unwrap();
+		assert_eq!(context.warnings().len(), 1);
+	}
+}
@@ -0,0 +1,196 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::str::FromStr;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::{Context, EvalError, Expression};
+
+/// The most points a sweep evaluates, over all its ranges together.
+pub const MAX_SWEEP_POINTS: usize = 1_000_000;
+/// How close to the end, in steps, the last point may fall short of it and still be taken as the
+/// end: `0..0.3:0.1` takes just under three steps in doubles.
+const END_TOLERANCE: f64 = 1e-9;
+
+/// The values from `start` to `end` in steps of `step`, written `start..end:step`. The end is
+/// included when a whole number of steps reaches it, and the step is negative for a range that
+/// counts down.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SweepRange {
+	pub start: f64,
+	pub end: f64,
+	pub step: f64
+}
+impl SweepRange {
+	pub fn new(start: f64, end: f64, step: f64) -> Result<Self, String> {
+		if !(start.is_finite() && end.is_finite() && step.is_finite()) {
+			return Err("a range needs finite bounds and step".to_string());
+		}
+		if step == 0.0 && start != end {
+			return Err("the step of a range cannot be 0".to_string());
+		}
+		if start != end && (end - start).signum() != step.signum() {
+			return Err(format!("a step of {} never reaches {} from {}", step, end, start));
+		}
+		let range = Self { start, end, step };
+		if range.points() > MAX_SWEEP_POINTS {
+			return Err(format!("a range of more than {} points", MAX_SWEEP_POINTS));
+		}
+		Ok(range)
+	}
+	/// How many values the range has, at least 1.
+	pub fn points(&self) -> usize {
+		if self.start == self.end {
+			return 1;
+		}
+		let steps = ((self.end - self.start) / self.step + END_TOLERANCE).floor();
+		if steps >= MAX_SWEEP_POINTS as f64 { MAX_SWEEP_POINTS + 1 } else { steps as usize + 1 }
+	}
+	/// Each value is computed from the start rather than added up, so errors do not accumulate; a
+	/// last value within the tolerance of the end is the end.
+	pub fn values(&self) -> impl Iterator<Item = f64> + '_ {
+		let last = self.points() - 1;
+		(0 ..= last).map(move |index| {
+			let value = self.start + index as f64 * self.step;
+			if index == last && (value - self.end).abs() <= END_TOLERANCE * self.step.abs() { self.end } else { value }
+		})
+	}
+}
+impl FromStr for SweepRange {
+	type Err = String;
+
+	/// Accepts `start..end:step`, or `start..end` for steps of 1 toward the end.
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		let invalid = || format!("invalid range '{}', expected START..END:STEP", s);
+		let (start, rest) = s.split_once("..").ok_or_else(invalid)?;
+		let (end, step) = match rest.split_once(':') {
+			Some((end, step)) => (end, Some(step)),
+			None => (rest, None)
+		};
+		let number = |text: &str| text.trim().parse::<f64>().map_err(|_| invalid());
+		let (start, end) = (number(start)?, number(end)?);
+		let step = match step {
+			Some(step) => number(step)?,
+			None if end < start => -1.0,
+			None => 1.0
+		};
+		Self::new(start, end, step)
+	}
+}
+
+impl Context {
+	/// Evaluates `expression` with `var` set to each of `values` in turn. A point that fails does not
+	/// stop the others.
+	pub fn sweep(
+		&self, expression: &str, var: &str, values: impl IntoIterator<Item = f64>
+	) -> Vec<(f64, Result<f64, EvalError>)> {
+		let mut context = self.clone();
+		values.into_iter().map(|value| {
+			context.set_var(var, value);
+			(value, context.eval(expression))
+		}).collect()
+	}
+	/// Evaluates `expression` at every combination of the values of `axes`, the last one changing
+	/// fastest. Each point has the values of the axes in their order.
+	pub fn sweep_grid(&self, expression: &str, axes: &[(&str, SweepRange)]) -> Vec<(Vec<f64>, Result<f64, EvalError>)> {
+		let values: Vec<Vec<f64>> = axes.iter().map(|(_, range)| range.values().collect()).collect();
+		let total = values.iter().map(Vec::len).product();
+		let mut context = self.clone();
+		let mut points = Vec::with_capacity(total);
+		let mut indices = vec![0; axes.len()];
+		for _ in 0 .. total {
+			let point: Vec<f64> = indices.iter().zip(&values).map(|(&index, values)| values[index]).collect();
+			for ((name, _), value) in axes.iter().zip(&point) {
+				context.set_var(name, *value);
+			}
+			points.push((point, context.eval(expression)));
+			for axis in (0 .. indices.len()).rev() {
+				indices[axis] += 1;
+				if indices[axis] < values[axis].len() {
+					break;
+				}
+				indices[axis] = 0;
+			}
+		}
+		points
+	}
+}
+
+impl Expression {
+	/// The expression with `var` set to each of `values` in `context`, see [`Context::sweep`].
+	pub fn sweep(&self, var: &str, values: impl IntoIterator<Item = f64>, context: &Context) -> Vec<(f64, Result<f64, EvalError>)> {
+		context.sweep(self.as_str(), var, values)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn values(range: &str) -> Vec<f64> {
+		range.parse::<SweepRange>().unwrap().values().collect()
+	}
+
+	#[test]
+	fn ranges_include_their_end_within_the_tolerance() {
+		assert_eq!(values("0..5:0.5"), [0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.5, 4.0, 4.5, 5.0]);
+		assert_eq!(values("0..0.3:0.1"), [0.0, 0.1, 0.2, 0.3]);
+		assert_eq!(values("1..4"), [1.0, 2.0, 3.0, 4.0]);
+		assert_eq!(values("0..2.5:1"), [0.0, 1.0, 2.0]);
+		assert_eq!(values("2..2:0"), [2.0]);
+		assert_eq!(values(" -1 .. 1 : 1 "), [-1.0, 0.0, 1.0]);
+	}
+
+	#[test]
+	fn ranges_count_down_with_a_negative_step() {
+		assert_eq!(values("3..0"), [3.0, 2.0, 1.0, 0.0]);
+		assert_eq!(values("1..-1:-0.5"), [1.0, 0.5, 0.0, -0.5, -1.0]);
+		assert_eq!("5..0:-2".parse::<SweepRange>().unwrap().points(), 3);
+	}
+
+	#[test]
+	fn invalid_ranges_are_refused() {
+		let error = |range: &str| range.parse::<SweepRange>().unwrap_err();
+		assert_eq!(error("0-5"), "invalid range '0-5', expected START..END:STEP");
+		assert_eq!(error("0..x:1"), "invalid range '0..x:1', expected START..END:STEP");
+		assert_eq!(error("0..5:0"), "the step of a range cannot be 0");
+		assert_eq!(error("0..5:-1"), "a step of -1 never reaches 5 from 0");
+		assert_eq!(error("0..inf:1"), "a range needs finite bounds and step");
+		assert_eq!(error("0..1000000:1"), "a range of more than 1000000 points");
+		assert!("1..1000000:1".parse::<SweepRange>().is_ok());
+	}
+
+	#[test]
+	fn a_failing_point_does_not_stop_the_others() {
+		let context = Context::new();
+		let points = context.sweep("1 / (x - 2) + y", "x", values("0..4"));
+		assert!(matches!(points[0], (0.0, Err(EvalError::UnknownIdentifier(..)))));
+		let mut context = Context::new();
+		context.set_var("y", 1.0);
+		let points = context.sweep("x^2 - 3*x + 2", "x", values("0..2:0.5"));
+		assert_eq!(points.iter().map(|(x, y)| (*x, *y.as_ref().unwrap())).collect::<Vec<_>>(), [(0.0, 2.0), (0.5, 0.75), (1.0, 0.0), (1.5, -0.25), (2.0, 0.0)]);
+		let points = context.sweep("sqrt(x) + 1 / (x - 2)", "x", values("-1..3"));
+		assert!(matches!(points[0], (-1.0, Ok(value)) if value.is_nan()));
+		assert_eq!(points[1].1.as_ref().unwrap(), &-0.5);
+		assert!(points.iter().all(|(_, result)| result.is_ok()));
+		context.set_rule(crate::StrictRule::NonFinite, crate::RuleLevel::Error);
+		let points = context.sweep("1 / (x - 2)", "x", values("0..4"));
+		assert_eq!(points.iter().filter(|(_, result)| result.is_err()).map(|(x, _)| *x).collect::<Vec<_>>(), [2.0]);
+		assert_eq!(points[4].1.as_ref().unwrap(), &0.5);
+		assert_eq!(context.get_var("x"), None);
+		let expression = Expression::parse("1 / (x - 2)", &context).unwrap();
+		let points = expression.sweep("x", values("0..4"), &context);
+		assert_eq!(points.iter().map(|(x, result)| (*x, result.is_ok())).collect::<Vec<_>>(), [(0.0, true), (1.0, true), (2.0, false), (3.0, true), (4.0, true)]);
+	}
+
+	#[test]
+	fn several_ranges_sweep_every_combination() {
+		let context = Context::new();
+		let axes = [("x", "1..2".parse().unwrap()), ("y", "0..20:10".parse().unwrap())];
+		let points: Vec<(Vec<f64>, f64)> = context.sweep_grid("x + y", &axes).into_iter().map(|(point, result)| (point, result.unwrap())).collect();
+		assert_eq!(points, [
+			(vec![1.0, 0.0], 1.0), (vec![1.0, 10.0], 11.0), (vec![1.0, 20.0], 21.0),
+			(vec![2.0, 0.0], 2.0), (vec![2.0, 10.0], 12.0), (vec![2.0, 20.0], 22.0)
+		]);
+	}
+}
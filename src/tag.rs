@@ -0,0 +1,188 @@
+use alloc::boxed::Box;
+use alloc::collections::{BTreeSet, VecDeque};
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use crate::strict::report;
+use crate::{BinOp, Context, EvalError, StrictRule, Token, Violation};
+
+/// The one copy of `tag`, so that [`Token::Tag`] can stay `Copy`. Each distinct tag is kept for
+/// the rest of the program, and there are only as many as were typed.
+pub(crate) fn intern(tag: &str) -> &'static str {
+	with_tags(|tags| {
+		if let Some(known) = tags.get(tag) {
+			return *known;
+		}
+		let tag: &'static str = Box::leak(tag.to_string().into_boxed_str());
+		tags.insert(tag);
+		tag
+	})
+}
+
+#[cfg(feature = "std")]
+fn with_tags<R>(f: impl FnOnce(&mut BTreeSet<&'static str>) -> R) -> R {
+	use std::sync::Mutex;
+
+	static TAGS: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+	f(&mut TAGS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))
+}
+
+/// Without std there is no mutex, so the set is guarded by a spin lock, held only for a lookup and
+/// an insertion.
+#[cfg(not(feature = "std"))]
+fn with_tags<R>(f: impl FnOnce(&mut BTreeSet<&'static str>) -> R) -> R {
+	use core::cell::UnsafeCell;
+	use core::sync::atomic::{AtomicBool, Ordering};
+
+	struct Tags(UnsafeCell<BTreeSet<&'static str>>);
+	// SAFETY: the set is only reached while LOCKED is held
+	unsafe impl Sync for Tags {}
+
+	static LOCKED: AtomicBool = AtomicBool::new(false);
+	static TAGS: Tags = Tags(UnsafeCell::new(BTreeSet::new()));
+	while LOCKED.compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed).is_err() {
+		core::hint::spin_loop();
+	}
+	// SAFETY: LOCKED is held, so this is the only reference to the set
+	let result = f(unsafe { &mut *TAGS.0.get() });
+	LOCKED.store(false, Ordering::Release);
+	result
+}
+
+/// Follows the display tags of an RPN queue and returns the tag of its result. A tag survives
+/// scaling by untagged values and adding values with the same tag; when an operation has no
+/// sensible tag it is dropped, reported by [`StrictRule::DroppedTag`].
+pub(crate) fn check(rpn: &VecDeque<Token>, context: &Context, violations: &mut Vec<Violation>) -> Result<Option<&'static str>, EvalError> {
+	if !rpn.iter().any(|tok| matches!(tok, Token::Tag(_))) {
+		return Ok(None);
+	}
+	let mut dropped = Vec::new();
+	// The tag of each operand on the stack
+	let mut stack: Vec<Option<&'static str>> = Vec::with_capacity(rpn.len());
+	for tok in rpn {
+		let argc = match tok {
+			Token::NumericLiteral(_) | Token::Uncertain(..) | Token::Duration(_) => {
+				stack.push(None);
+				continue;
+			}
+			Token::Tag(tag) => {
+				stack.pop();
+				stack.push(Some(tag));
+				continue;
+			}
+			Token::Operator(op) => op.argc,
+			Token::Function(func) => func.argc,
+			Token::Convert(..) | Token::Percent => 1,
+			Token::PercentChange(_) => 2,
+			Token::OpenParen | Token::CloseParen | Token::Comma | Token::Unit(_) => 0
+		};
+		if stack.len() < argc {
+			return Err(EvalError::NotEnoughArguments);
+		}
+		let base = stack.len() - argc;
+		let args = &stack[base ..];
+		let mut drop = |what: String| {
+			dropped.push(what);
+			None
+		};
+		let tag = match *tok {
+			Token::Operator(_) | Token::Percent if argc == 1 => args[0],
+			Token::Operator(op) => match (op.op, args[0], args[1]) {
+				(_, None, None) => None,
+				(BinOp::Add | BinOp::Sub | BinOp::PlusMinus, lhs, rhs) if lhs == rhs => lhs,
+				(BinOp::Add | BinOp::PlusMinus, lhs, rhs) => drop(format!("adding {} and {}", described(lhs), described(rhs))),
+				(BinOp::Sub, lhs, rhs) => drop(format!("subtracting {} from {}", described(rhs), described(lhs))),
+				(BinOp::Mul, Some(tag), None) | (BinOp::Mul, None, Some(tag)) | (BinOp::Div, Some(tag), None) => Some(tag),
+				(BinOp::Mul, lhs, rhs) => drop(format!("multiplying {} by {}", described(lhs), described(rhs))),
+				// A ratio of like values is a plain number
+				(BinOp::Div, lhs, rhs) if lhs == rhs => None,
+				(BinOp::Div, lhs, rhs) => drop(format!("dividing {} by {}", described(lhs), described(rhs))),
+				(BinOp::Pow, lhs, rhs) => drop(format!("raising {} to {}", described(lhs), described(rhs)))
+			},
+			Token::PercentChange(_) if args[1].is_none() => args[0],
+			Token::PercentChange(_) => drop(format!("taking {} of {} as a percent change", described(args[1]), described(args[0]))),
+			Token::Function(_) if args.iter().all(Option::is_none) => None,
+			Token::Function(func) if matches!(func.name, "abs" | "max" | "min" | "round" | "floor" | "ceil")
+				&& args.iter().all(|tag| *tag == args[0]) => args[0],
+			Token::Function(func) => drop(format!("passing {} to '{}'", described(args.iter().copied().flatten().next()), func.name)),
+			Token::Convert(..) if args[0].is_none() => None,
+			Token::Convert(..) => drop(format!("converting {}", described(args[0]))),
+			_ => None
+		};
+		stack.truncate(base);
+		stack.push(tag);
+	}
+	for what in dropped {
+		report(StrictRule::DroppedTag, None, Some(format!("tag dropped, {}", what)), context, violations)?;
+	}
+	Ok(stack.last().copied().flatten())
+}
+
+fn described(tag: Option<&str>) -> String {
+	match tag {
+		Some(tag) => format!("[{}]", tag),
+		None => "an untagged value".to_string()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::RuleLevel;
+
+	/// What `statement` shows after `price = 19.99 [usd]` and `fee = 2 [eur]`, with the details of
+	/// the tags it dropped.
+	fn shown(statement: &str) -> (String, Vec<String>) {
+		let mut context = Context::new();
+		context.execute("price = 19.99 [usd]").unwrap();
+		context.execute("fee = 2 [eur]").unwrap();
+		let value = context.execute(statement).unwrap().value();
+		let dropped = context.warnings().iter().map(|violation| violation.to_string()).collect();
+		(context.format_result(value), dropped)
+	}
+
+	#[test]
+	fn tags_survive_scaling_and_like_sums() {
+		assert_eq!(shown("price * 3"), ("59.97 [usd]".to_string(), vec![]));
+		assert_eq!(shown("3 * price").0, "59.97 [usd]");
+		assert_eq!(shown("price / 2").0, "9.995 [usd]");
+		assert_eq!(shown("price + 1 [usd]").0, "20.99 [usd]");
+		assert_eq!(shown("price - price").0, "0 [usd]");
+		assert_eq!(shown("-price").0, "-19.99 [usd]");
+		assert_eq!(shown("max(price, 5 [usd])").0, "19.99 [usd]");
+		assert_eq!(shown("(price + 0.01 [usd]) * 3").0, "60 [usd]");
+		assert_eq!(shown("price / 1 [usd]").0, "19.99");
+		assert_eq!(shown("1 + 2").0, "3");
+	}
+
+	#[test]
+	fn unlike_tags_and_operations_without_a_tag_drop_it() {
+		let dropped = |statement: &str| {
+			let (text, dropped) = shown(statement);
+			assert!(!text.contains('['), "{}", statement);
+			dropped
+		};
+		assert_eq!(dropped("price + fee"), ["tag dropped, adding [usd] and [eur]"]);
+		assert_eq!(dropped("price - 1"), ["tag dropped, subtracting an untagged value from [usd]"]);
+		assert_eq!(dropped("price * fee"), ["tag dropped, multiplying [usd] by [eur]"]);
+		assert_eq!(dropped("2 / price"), ["tag dropped, dividing an untagged value by [usd]"]);
+		assert_eq!(dropped("price ^ 2"), ["tag dropped, raising [usd] to an untagged value"]);
+		assert_eq!(dropped("sqrt(price)"), ["tag dropped, passing [usd] to 'sqrt'"]);
+	}
+
+	#[test]
+	fn variables_keep_their_tag_and_dropping_can_fail() {
+		let mut context = Context::new();
+		context.execute("price = 19.99 [usd]").unwrap();
+		context.execute("total = price * 3").unwrap();
+		assert_eq!(context.var_tag("total"), Some("usd"));
+		context.execute("price * 2").unwrap();
+		let value = context.execute("ans + 1 [usd]").unwrap().value();
+		assert_eq!(context.format_result(value), "40.98 [usd]");
+		context.set_rule(StrictRule::DroppedTag, RuleLevel::Error);
+		assert!(matches!(context.execute("price + 1"), Err(EvalError::Strict { rule: StrictRule::DroppedTag, .. })));
+		context.set_rule(StrictRule::DroppedTag, RuleLevel::Allow);
+		context.execute("price + 1").unwrap();
+		assert!(context.warnings().is_empty());
+	}
+}
@@ -0,0 +1,115 @@
+use alloc::collections::VecDeque;
+use alloc::string::{String, ToString};
+
+pub const DEFAULT_LIMIT: usize = 1000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TapeEntry {
+	/// What `$index` refers to, counting from 1.
+	pub index: usize,
+	pub input: String,
+	pub value: f64
+}
+
+/// The numbered results of a session, readable in expressions as `$1`, `$2`, … Once the tape is
+/// full the oldest entry is dropped; the numbers of the others do not change.
+#[derive(Clone, Debug)]
+pub struct Tape {
+	entries: VecDeque<TapeEntry>,
+	limit: usize,
+	next: usize
+}
+impl Default for Tape {
+	fn default() -> Self {
+		Self { entries: VecDeque::new(), limit: DEFAULT_LIMIT, next: 1 }
+	}
+}
+impl Tape {
+	/// Records a result and returns its index. Nothing is kept with a limit of 0.
+	pub fn push(&mut self, input: &str, value: f64) -> usize {
+		let index = self.next;
+		self.next += 1;
+		if self.limit > 0 {
+			if self.entries.len() == self.limit {
+				self.entries.pop_front();
+			}
+			self.entries.push_back(TapeEntry { index, input: input.to_string(), value });
+		}
+		index
+	}
+	pub fn get(&self, index: usize) -> Option<f64> {
+		let first = self.entries.front()?.index;
+		let offset = index.checked_sub(first)?;
+		self.entries.get(offset).map(|entry| entry.value)
+	}
+	pub fn entries(&self) -> impl Iterator<Item = &TapeEntry> {
+		self.entries.iter()
+	}
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+	pub fn is_empty(&self) -> bool {
+		self.entries.is_empty()
+	}
+	/// Empties the tape and numbers the next result 1 again.
+	pub fn clear(&mut self) {
+		self.entries.clear();
+		self.next = 1;
+	}
+	pub fn limit(&self) -> usize {
+		self.limit
+	}
+	/// Drops the oldest entries that no longer fit.
+	pub fn set_limit(&mut self, limit: usize) {
+		self.limit = limit;
+		while self.entries.len() > limit {
+			self.entries.pop_front();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::vec::Vec;
+	use super::*;
+	use crate::{Context, EvalError};
+
+	#[test]
+	fn results_are_referenced_by_number() {
+		let mut context = Context::new();
+		context.execute("2+2").unwrap();
+		context.execute("ans*3").unwrap();
+		assert_eq!(context.eval("$1 + $2").unwrap(), 16.0);
+		let entries: Vec<(usize, &str, f64)> = context.tape().entries().map(|entry| (entry.index, entry.input.as_str(), entry.value)).collect();
+		assert_eq!(entries, [(1, "2+2", 4.0), (2, "ans*3", 12.0)]);
+		assert!(matches!(context.eval("$3"), Err(EvalError::MissingTapeEntry(3, _))));
+		assert_eq!(context.eval("$3").unwrap_err().to_string(), "there is no result $3 on the tape");
+	}
+
+	#[test]
+	fn the_oldest_entries_are_evicted_and_keep_their_numbers() {
+		let mut tape = Tape::default();
+		tape.set_limit(2);
+		for value in [10.0, 20.0, 30.0] {
+			tape.push("x", value);
+		}
+		assert_eq!((tape.get(1), tape.get(2), tape.get(3), tape.get(4)), (None, Some(20.0), Some(30.0), None));
+		tape.set_limit(1);
+		assert_eq!((tape.len(), tape.get(3)), (1, Some(30.0)));
+		tape.clear();
+		assert!(tape.is_empty());
+		assert_eq!(tape.push("y", 1.0), 1);
+		tape.set_limit(0);
+		assert_eq!((tape.push("z", 2.0), tape.len()), (2, 0));
+	}
+
+	#[test]
+	fn evicted_entries_are_errors_naming_their_number() {
+		let mut context = Context::new();
+		context.tape_mut().set_limit(1);
+		context.execute("1").unwrap();
+		context.execute("2").unwrap();
+		assert!(matches!(context.eval("$1"), Err(EvalError::MissingTapeEntry(1, _))));
+		assert_eq!(context.eval("$2 * 2").unwrap(), 4.0);
+	}
+}
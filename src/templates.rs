@@ -0,0 +1,107 @@
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+
+use crate::{Context, EvalError, EvalOutcome, Lexeme, LexemeKind, Timing, Trace};
+
+/// The names of the placeholders in `body`, without their `?`, each once in the order they first
+/// appear.
+pub fn placeholders(body: &str, context: &Context) -> Result<Vec<String>, EvalError> {
+	let mut names: Vec<String> = Vec::new();
+	for lexeme in context.lexer(body) {
+		let Lexeme { kind, span } = lexeme?;
+		let name = &body[span.start + 1 .. span.end];
+		if kind == LexemeKind::Placeholder && !names.iter().any(|known| known == name) {
+			names.push(name.to_string());
+		}
+	}
+	Ok(names)
+}
+
+impl Context {
+	/// Stores `body`, an expression with placeholders such as `?w / ?h^2`, as the template `name`,
+	/// replacing any template of that name. The body is only checked to lex.
+	pub fn define_template(&mut self, name: &str, body: &str) -> Result<(), EvalError> {
+		placeholders(body, self)?;
+		Arc::make_mut(&mut self.templates).insert(name.to_string(), body.trim().to_string());
+		Ok(())
+	}
+	pub fn remove_template(&mut self, name: &str) -> Option<String> {
+		if !self.templates.contains_key(name) {
+			return None;
+		}
+		Arc::make_mut(&mut self.templates).remove(name)
+	}
+	pub fn template(&self, name: &str) -> Option<&str> {
+		self.templates.get(name).map(String::as_str)
+	}
+	/// Every template with its body, by name.
+	pub fn templates(&self) -> impl Iterator<Item = (&str, &str)> {
+		self.templates.iter().map(|(name, body)| (name.as_str(), body.as_str()))
+	}
+
+	/// Evaluates the template `name` with `values` for its placeholders, as [`Context::execute`]
+	/// does its body. Each value is an expression, evaluated first and put into the body as a number,
+	/// so `?h^2` with `h = 1 + 0.8` squares 1.8. Every placeholder without a value is reported.
+	pub fn use_template(&mut self, name: &str, values: &[(&str, &str)]) -> Result<EvalOutcome, EvalError> {
+		self.use_template_instrumented(name, values, None, None)
+	}
+	pub fn use_template_instrumented(
+		&mut self, name: &str, values: &[(&str, &str)], trace: Option<&mut Trace>, timing: Option<&mut Timing>
+	) -> Result<EvalOutcome, EvalError> {
+		let body = self.template(name).ok_or_else(|| EvalError::UnknownTemplate(name.to_string()))?.to_string();
+		let missing: Vec<String> = placeholders(&body, self)?
+			.into_iter()
+			.filter(|placeholder| !values.iter().any(|(name, _)| name == placeholder))
+			.collect();
+		if !missing.is_empty() {
+			return Err(EvalError::UnfilledPlaceholders(missing));
+		}
+		let mut filled = Vec::with_capacity(values.len());
+		for (name, value) in values {
+			filled.push((name.to_string(), self.eval(value)?));
+		}
+		self.placeholders = filled.into_iter().collect();
+		let outcome = self.execute_instrumented(&body, trace, timing);
+		self.placeholders.clear();
+		outcome
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::vec::Vec;
+	use crate::{Context, EvalError};
+
+	#[test]
+	fn a_template_is_defined_and_used() {
+		let mut context = Context::new();
+		context.define_template("bmi", "?w / (?h)^2").unwrap();
+		assert_eq!(context.templates().collect::<Vec<_>>(), [("bmi", "?w / (?h)^2")]);
+		let bmi = context.use_template("bmi", &[("w", "81"), ("h", "1.8")]).unwrap().value();
+		assert!((bmi - 25.0).abs() < 1e-12);
+		assert_eq!(context.answer(), Some(bmi));
+		assert_eq!(context.remove_template("bmi").as_deref(), Some("?w / (?h)^2"));
+		assert!(matches!(context.use_template("bmi", &[]), Err(EvalError::UnknownTemplate(name)) if name == "bmi"));
+	}
+
+	#[test]
+	fn every_missing_placeholder_is_reported() {
+		let mut context = Context::new();
+		context.define_template("box", "?w * ?h * ?d + ?w").unwrap();
+		let missing = match context.use_template("box", &[("h", "2")]) {
+			Err(EvalError::UnfilledPlaceholders(missing)) => missing,
+			other => panic!("{:?}", other)
+		};
+		assert_eq!(missing, ["w", "d"]);
+	}
+
+	#[test]
+	fn a_value_is_substituted_as_one_operand() {
+		let mut context = Context::new();
+		context.define_template("square", "?h^2").unwrap();
+		assert_eq!(context.use_template("square", &[("h", "1 + 2")]).unwrap().value(), 9.0);
+		context.define_template("negate", "-?x").unwrap();
+		assert_eq!(context.use_template("negate", &[("x", "2 - 5")]).unwrap().value(), 3.0);
+	}
+}
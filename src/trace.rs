@@ -0,0 +1,175 @@
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::{self, Display};
+use core::time::Duration;
+
+use crate::{BinOp, Operator, Token};
+
+/// One application of an operator or function while reducing the RPN queue.
+#[derive(Clone, Debug)]
+pub struct Step {
+	pub token: Token,
+	/// Operands in source order, e.g. `[4, 2]` for `4 * 2`.
+	pub args: Vec<f64>,
+	pub result: f64
+}
+impl Display for Step {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "apply {} to ", self.token)?;
+		for (i, arg) in self.args.iter().enumerate() {
+			if i > 0 {
+				write!(f, ", ")?;
+			}
+			write!(f, "{}", arg)?;
+		}
+		write!(f, " → {}", self.result)
+	}
+}
+
+/// Why the parser popped a token off the holding stack.
+#[derive(Copy, Clone, Debug)]
+pub enum Rule {
+	/// The incoming operator does not bind tighter than the one on top of the stack.
+	Precedence(Operator),
+	CloseParen,
+	Separator,
+	End
+}
+
+/// What the shunting-yard algorithm did with a token.
+#[derive(Clone, Debug)]
+pub enum Event {
+	/// An operand went straight to the output.
+	Output(Token),
+	/// An operator, function or parenthesis waits on the holding stack.
+	Push(Token),
+	/// A token left the holding stack; parentheses are dropped, everything else goes to the output.
+	Pop(Token, Rule)
+}
+impl Display for Event {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Event::Output(tok) => write!(f, "output {}", tok),
+			Event::Push(tok) => write!(f, "push {}", tok),
+			Event::Pop(Token::OpenParen, _) => write!(f, "pop ( and drop it, matched by )"),
+			Event::Pop(tok, Rule::Precedence(op)) if op.op == BinOp::Pow => write!(f, "pop {}, it binds tighter than ^", tok),
+			Event::Pop(tok, Rule::Precedence(op)) => write!(f, "pop {}, it binds at least as tightly as {}", tok, op.symbol),
+			Event::Pop(tok, Rule::CloseParen) => write!(f, "pop {}, closing parenthesis", tok),
+			Event::Pop(tok, Rule::Separator) => write!(f, "pop {}, argument separator", tok),
+			Event::Pop(tok, Rule::End) => write!(f, "pop {}, end of input", tok)
+		}
+	}
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Trace {
+	pub tokens: Vec<Token>,
+	/// How the parser moved each token, in order.
+	pub events: Vec<Event>,
+	pub rpn: Vec<Token>,
+	pub steps: Vec<Step>
+}
+
+/// How long the two phases of an evaluation took, and how much they had to work with.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Timing {
+	/// Tokenizing and converting to RPN.
+	pub parse: Duration,
+	/// Reducing the RPN queue to a value.
+	pub eval: Duration,
+	pub tokens: usize,
+	pub rpn_len: usize
+}
+impl Timing {
+	/// The same counts with no time taken, for output that does not change between runs.
+	pub fn without_durations(self) -> Self {
+		Self { parse: Duration::ZERO, eval: Duration::ZERO, ..self }
+	}
+}
+
+/// Measures one phase into a [`Timing`]. Only `std` has a clock, so without it every phase takes no
+/// time; the clock is not read either when nothing is timed.
+#[cfg(feature = "std")]
+pub(crate) struct Stopwatch(Option<std::time::Instant>);
+#[cfg(feature = "std")]
+impl Stopwatch {
+	pub(crate) fn start(timed: bool) -> Self {
+		Self(timed.then(std::time::Instant::now))
+	}
+	pub(crate) fn elapsed(&self) -> Duration {
+		self.0.map_or(Duration::ZERO, |start| start.elapsed())
+	}
+}
+#[cfg(not(feature = "std"))]
+pub(crate) struct Stopwatch;
+#[cfg(not(feature = "std"))]
+impl Stopwatch {
+	pub(crate) fn start(_timed: bool) -> Self {
+		Self
+	}
+	pub(crate) fn elapsed(&self) -> Duration {
+		Duration::ZERO
+	}
+}
+impl Display for Timing {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(
+			f, "parse: {}µs ({} tokens), eval: {}µs ({} in rpn)",
+			self.parse.as_micros(), self.tokens, self.eval.as_micros(), self.rpn_len
+		)
+	}
+}
+
+pub fn format_tokens(tokens: &[Token]) -> String {
+	tokens.iter().map(Token::to_string).collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::Context;
+
+	#[test]
+	fn a_trace_records_tokens_rpn_and_each_step() {
+		let mut trace = Trace::default();
+		assert_eq!(Context::new().eval_traced("4 * (3 - 1)", &mut trace).unwrap(), 8.0);
+		assert_eq!(format_tokens(&trace.tokens), "4 * ( 3 - 1 )");
+		assert_eq!(format_tokens(&trace.rpn), "4 3 1 - *");
+		let steps: Vec<String> = trace.steps.iter().map(Step::to_string).collect();
+		assert_eq!(steps, ["apply - to 3, 1 → 2", "apply * to 4, 2 → 8"]);
+		let events: Vec<String> = trace.events.iter().map(Event::to_string).collect();
+		assert_eq!(events.last().map(String::as_str), Some("pop *, end of input"));
+		assert!(events.contains(&"pop ( and drop it, matched by )".to_string()));
+	}
+
+	#[test]
+	fn timing_counts_grow_with_the_expression() {
+		let mut context = Context::new();
+		let mut short = Timing::default();
+		context.execute_timed("1 + 2", &mut short).unwrap();
+		assert_eq!((short.tokens, short.rpn_len), (3, 3));
+		let mut long = Timing::default();
+		context.execute_timed("(1 + 2) * sin(3) - 4", &mut long).unwrap();
+		assert!(long.tokens > short.tokens && long.rpn_len > short.rpn_len);
+		assert!(long.tokens >= long.rpn_len);
+		let timing = Timing { parse: Duration::from_micros(3), eval: Duration::from_micros(4), tokens: 3, rpn_len: 3 };
+		assert_eq!(timing.to_string(), "parse: 3µs (3 tokens), eval: 4µs (3 in rpn)");
+		assert_eq!(timing.without_durations().to_string(), "parse: 0µs (3 tokens), eval: 0µs (3 in rpn)");
+	}
+
+	#[test]
+	fn only_a_given_trace_is_recorded_into() {
+		let mut context = Context::new();
+		let mut trace = Trace::default();
+		let untraced = context.execute_instrumented("x = 3 + 4 * 2", None, None).unwrap();
+		let traced = context.execute_instrumented("x = 3 + 4 * 2", Some(&mut trace), None).unwrap();
+		assert_eq!((untraced.value(), traced.value()), (11.0, 11.0));
+		assert_eq!((trace.tokens.len(), trace.rpn.len(), trace.steps.len()), (5, 5, 2));
+		context.execute("1 + 1").unwrap();
+		assert_eq!(context.eval("1 + 1").unwrap(), 2.0);
+		assert_eq!(trace.steps.len(), 2);
+		// A second traced evaluation starts over rather than appending
+		context.eval_traced("1 + 1", &mut trace).unwrap();
+		assert_eq!((trace.tokens.len(), trace.steps.len()), (3, 1));
+	}
+}
@@ -0,0 +1,193 @@
+use alloc::collections::VecDeque;
+use alloc::vec::Vec;
+use core::f64::consts;
+
+#[cfg(not(any(feature = "std", test)))]
+use crate::float::Float;
+use crate::budget::Budget;
+use crate::{Angle, AngleMode, BinOp, EvalError, Function, Rounding, Token};
+
+/// A value with its standard uncertainty, `12.3 ± 0.2`. Uncertainties are propagated to first
+/// order, treating every operand as independent of the others.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Uncertain {
+	pub value: f64,
+	pub sigma: f64
+}
+impl Uncertain {
+	pub fn new(value: f64, sigma: f64) -> Self {
+		Self { value, sigma: sigma.abs() }
+	}
+	pub fn exact(value: f64) -> Self {
+		Self { value, sigma: 0.0 }
+	}
+
+	pub fn apply(op: BinOp, lhs: Self, rhs: Self) -> Self {
+		let (a, b) = (lhs.value, rhs.value);
+		match op {
+			BinOp::Add => Self::new(a + b, lhs.sigma.hypot(rhs.sigma)),
+			BinOp::Sub => Self::new(a - b, lhs.sigma.hypot(rhs.sigma)),
+			BinOp::Mul => Self::new(a * b, (b * lhs.sigma).hypot(a * rhs.sigma)),
+			BinOp::Div => Self::new(a / b, (lhs.sigma / b).hypot(a * rhs.sigma / (b * b))),
+			BinOp::Pow => {
+				let value = a.powf(b);
+				// Each term is left out when exact, a negative base only has a power rule
+				let mut sigma = 0.0;
+				if lhs.sigma != 0.0 {
+					sigma = b * a.powf(b - 1.0) * lhs.sigma;
+				}
+				if rhs.sigma != 0.0 {
+					sigma = sigma.hypot(value * a.ln() * rhs.sigma);
+				}
+				Self::new(value, sigma)
+			}
+			BinOp::PlusMinus => Self::new(a, lhs.sigma.hypot(b))
+		}
+	}
+}
+
+/// The derivative of a one-argument function at `x`, with angles in radians, for the functions that
+/// propagate uncertainty.
+fn derivative(name: &str, x: f64) -> Option<f64> {
+	Some(match name {
+		"abs" => x.signum(),
+		"sqrt" => 0.5 / x.sqrt(),
+		"exp" => x.exp(),
+		"ln" => 1.0 / x,
+		"log" => 1.0 / (x * consts::LN_10),
+		"sin" => x.cos(),
+		"cos" => -x.sin(),
+		"tan" => 1.0 / (x.cos() * x.cos()),
+		"asin" => 1.0 / (1.0 - x * x).sqrt(),
+		"acos" => -1.0 / (1.0 - x * x).sqrt(),
+		"atan" => 1.0 / (1.0 + x * x),
+		_ => return None
+	})
+}
+
+/// Exact arguments give an exact result from any function; otherwise the function needs a rule.
+fn call(func: Function, args: &[Uncertain], mode: AngleMode, rounding: Rounding) -> Result<Uncertain, EvalError> {
+	let values: Vec<f64> = args.iter().map(|arg| arg.value).collect();
+	func.check_domain(&values)?;
+	let value = match func.name {
+		"round" => rounding.round(values[0]),
+		_ => func.resolve_in(&values, mode)
+	};
+	if args.iter().all(|arg| arg.sigma == 0.0) {
+		return Ok(Uncertain::exact(value));
+	}
+	let x = args[0].value;
+	let slope = match (mode, func.angle) {
+		(AngleMode::Degrees, Angle::Argument) => derivative(func.name, x.to_radians()).map(f64::to_radians),
+		(AngleMode::Degrees, Angle::Result) => derivative(func.name, x).map(f64::to_degrees),
+		_ => derivative(func.name, x)
+	};
+	match slope {
+		Some(slope) => Ok(Uncertain::new(value, slope * args[0].sigma)),
+		None => Err(EvalError::NoPropagationRule(func.name))
+	}
+}
+
+/// Evaluates an RPN queue like [`crate::eval_postfix`], carrying an uncertainty with every value.
+pub(crate) fn reduce(output: VecDeque<Token>, mode: AngleMode, rounding: Rounding, budget: &Budget) -> Result<Uncertain, EvalError> {
+	budget.spend(output.len())?;
+	let mut solve: Vec<Uncertain> = Vec::with_capacity(output.len());
+	for tok in output {
+		let argc = match tok {
+			Token::NumericLiteral(num) | Token::Duration(num) => {
+				solve.push(Uncertain::exact(num));
+				continue;
+			}
+			Token::Uncertain(value, sigma) => {
+				solve.push(Uncertain::new(value, sigma));
+				continue;
+			}
+			Token::Operator(op) => op.argc,
+			Token::Function(func) => func.argc,
+			Token::Convert(..) | Token::Percent | Token::Tag(_) => 1,
+			Token::PercentChange(_) => 2,
+			Token::OpenParen | Token::CloseParen | Token::Comma | Token::Unit(_) => {
+				return Err(EvalError::UnexpectedToken(tok));
+			}
+		};
+		if solve.len() < argc {
+			return Err(EvalError::NotEnoughArguments);
+		}
+		let base = solve.len() - argc;
+		let args = &solve[base ..];
+		let result = match tok {
+			Token::Operator(op) if op.symbol == '+' && argc == 1 => args[0],
+			Token::Operator(op) if op.symbol == '-' && argc == 1 => Uncertain::new(-args[0].value, args[0].sigma),
+			Token::Operator(op) => Uncertain::apply(op.op, args[0], args[1]),
+			Token::Function(func) => call(func, args, mode, rounding)?,
+			Token::Convert(from, to) => Uncertain::new(from.convert(args[0].value, to), args[0].sigma * from.scale / to.scale),
+			Token::Percent => Uncertain::new(args[0].value / 100.0, args[0].sigma / 100.0),
+			Token::Tag(_) => args[0],
+			Token::PercentChange(op) => Uncertain::apply(op.op, args[0], Uncertain::apply(BinOp::Mul, args[0], args[1])),
+			_ => unreachable!()
+		};
+		solve.truncate(base);
+		solve.push(result);
+	}
+
+	solve.last().copied().ok_or(EvalError::NoResult)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{Context, EvalOutcome};
+
+	fn close(actual: f64, expected: f64) -> bool {
+		(actual - expected).abs() <= 1e-12 * expected.abs().max(1.0)
+	}
+
+	#[test]
+	fn each_operator_propagates_in_quadrature() {
+		let (a, b) = (Uncertain::new(12.3, 0.2), Uncertain::new(4.0, 0.1));
+		let cases = [
+			(BinOp::Add, 16.3, (0.2f64 * 0.2 + 0.1 * 0.1).sqrt()),
+			(BinOp::Sub, 8.3, (0.2f64 * 0.2 + 0.1 * 0.1).sqrt()),
+			(BinOp::Mul, 49.2, 49.2 * ((0.2f64 / 12.3).powi(2) + (0.1f64 / 4.0).powi(2)).sqrt()),
+			(BinOp::Div, 3.075, 3.075 * ((0.2f64 / 12.3).powi(2) + (0.1f64 / 4.0).powi(2)).sqrt())
+		];
+		for (op, value, sigma) in cases {
+			let result = Uncertain::apply(op, a, b);
+			assert!(close(result.value, value) && close(result.sigma, sigma), "{:?}: {:?}", op, result);
+		}
+		let exact = Uncertain::apply(BinOp::Mul, Uncertain::exact(2.0), Uncertain::exact(3.0));
+		assert_eq!(exact, Uncertain::exact(6.0));
+	}
+
+	#[test]
+	fn results_are_shown_with_two_significant_figures_of_uncertainty() {
+		let mut context = Context::new();
+		context.set_uncertainty(true);
+		let cases = [
+			("(12.3 ± 0.2) * (4.0 ± 0.1)", "49.2 ± 1.5"),
+			("(12.3 +- 0.2) + (4.0 +- 0.1)", "16.30 ± 0.22"),
+			("(10 ± 1) - (4 ± 2)", "6.0 ± 2.2"),
+			("(10 ± 1) / (4 ± 0.5)", "2.50 ± 0.40"),
+			("(2 ± 0.1) ^ 2 + 1", "5.00 ± 0.40"),
+			("sqrt(4 ± 0.2)", "2.000 ± 0.050"),
+			("2 * 3", "6")
+		];
+		for (expression, shown) in cases {
+			let Ok(EvalOutcome::Value(value)) = context.execute(expression) else { panic!("{}", expression) };
+			assert_eq!(context.format_result(value), shown, "{}", expression);
+		}
+	}
+
+	#[test]
+	fn uncertainties_need_the_mode_and_a_rule() {
+		let mut context = Context::new();
+		assert!(matches!(context.execute("12.3 ± 0.2"), Err(EvalError::UncertaintyOff(_))));
+		context.set_uncertainty(true);
+		assert!(matches!(context.execute("max(1 ± 0.1, 2)"), Err(EvalError::NoPropagationRule("max"))));
+		assert!(matches!(context.execute("max(1, 2)"), Ok(EvalOutcome::Value(value)) if value == 2.0));
+		context.execute("x = 5 ± 0.5").unwrap();
+		assert_eq!(context.var_uncertainty("x"), Some(0.5));
+		context.execute("x * 2").unwrap();
+		assert_eq!(context.answer_uncertainty(), Some(1.0));
+	}
+}
@@ -0,0 +1,139 @@
+use core::fmt::{self, Display};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dimension {
+	Length,
+	Mass,
+	Temperature,
+	Time,
+	Data
+}
+impl Display for Dimension {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(match self {
+			Self::Length => "length",
+			Self::Mass => "mass",
+			Self::Temperature => "temperature",
+			Self::Time => "time",
+			Self::Data => "data size"
+		})
+	}
+}
+
+/// A unit as an affine map to the base unit of its dimension: `base = value * scale + offset`.
+/// Only temperatures have an offset.
+#[derive(Debug, PartialEq)]
+pub struct Unit {
+	pub names: &'static [&'static str],
+	pub dimension: Dimension,
+	pub scale: f64,
+	pub offset: f64
+}
+impl Unit {
+	pub fn name(&self) -> &'static str {
+		self.names[0]
+	}
+	pub fn convert(&self, value: f64, to: &Unit) -> f64 {
+		(value * self.scale + self.offset - to.offset) / to.scale
+	}
+}
+
+const fn unit(names: &'static [&'static str], dimension: Dimension, scale: f64) -> Unit {
+	Unit { names, dimension, scale, offset: 0.0 }
+}
+
+/// Base units are metres, kilograms, kelvin, seconds and bytes. Names are case-sensitive.
+pub const UNITS: &[Unit] = &[
+	unit(&["m", "metre", "meter"], Dimension::Length, 1.0),
+	unit(&["km"], Dimension::Length, 1e3),
+	unit(&["cm"], Dimension::Length, 1e-2),
+	unit(&["mm"], Dimension::Length, 1e-3),
+	unit(&["um", "µm"], Dimension::Length, 1e-6),
+	unit(&["nm"], Dimension::Length, 1e-9),
+	unit(&["in", "inch"], Dimension::Length, 0.0254),
+	unit(&["ft", "foot", "feet"], Dimension::Length, 0.3048),
+	unit(&["yd", "yard"], Dimension::Length, 0.9144),
+	unit(&["mi", "mile"], Dimension::Length, 1609.344),
+	unit(&["nmi"], Dimension::Length, 1852.0),
+	unit(&["kg"], Dimension::Mass, 1.0),
+	unit(&["g"], Dimension::Mass, 1e-3),
+	unit(&["mg"], Dimension::Mass, 1e-6),
+	unit(&["t", "tonne"], Dimension::Mass, 1e3),
+	unit(&["lb", "pound"], Dimension::Mass, 0.45359237),
+	unit(&["oz", "ounce"], Dimension::Mass, 0.028349523125),
+	unit(&["st", "stone"], Dimension::Mass, 6.35029318),
+	unit(&["K", "k", "kelvin"], Dimension::Temperature, 1.0),
+	Unit { names: &["C", "c", "celsius"], dimension: Dimension::Temperature, scale: 1.0, offset: 273.15 },
+	Unit { names: &["F", "f", "fahrenheit"], dimension: Dimension::Temperature, scale: 5.0 / 9.0, offset: 273.15 - 32.0 * 5.0 / 9.0 },
+	unit(&["s", "sec"], Dimension::Time, 1.0),
+	unit(&["ns"], Dimension::Time, 1e-9),
+	unit(&["us", "µs"], Dimension::Time, 1e-6),
+	unit(&["ms"], Dimension::Time, 1e-3),
+	unit(&["min"], Dimension::Time, 60.0),
+	unit(&["h", "hour"], Dimension::Time, 3600.0),
+	unit(&["d", "day"], Dimension::Time, 86400.0),
+	unit(&["wk", "week"], Dimension::Time, 604800.0),
+	unit(&["yr", "year"], Dimension::Time, 31557600.0),
+	unit(&["B", "byte"], Dimension::Data, 1.0),
+	unit(&["bit"], Dimension::Data, 0.125),
+	unit(&["kB", "KB"], Dimension::Data, 1e3),
+	unit(&["MB"], Dimension::Data, 1e6),
+	unit(&["GB"], Dimension::Data, 1e9),
+	unit(&["TB"], Dimension::Data, 1e12),
+	unit(&["KiB"], Dimension::Data, 1024.0),
+	unit(&["MiB"], Dimension::Data, 1048576.0),
+	unit(&["GiB"], Dimension::Data, 1073741824.0),
+	unit(&["TiB"], Dimension::Data, 1099511627776.0)
+];
+
+pub fn find_unit(name: &str) -> Option<&'static Unit> {
+	UNITS.iter().find(|unit| unit.names.contains(&name))
+}
+
+#[cfg(test)]
+mod tests {
+	use alloc::string::ToString;
+	use super::*;
+	use crate::{Context, EvalError};
+
+	fn convert(value: f64, from: &str, to: &str) -> f64 {
+		find_unit(from).unwrap().convert(value, find_unit(to).unwrap())
+	}
+
+	fn close(a: f64, b: f64) -> bool {
+		(a - b).abs() <= 1e-9 * b.abs().max(1.0)
+	}
+
+	#[test]
+	fn each_dimension_converts() {
+		assert!(close(convert(100.0, "km", "mi"), 62.1371192237334));
+		assert!(close(convert(1.0, "ft", "in"), 12.0));
+		assert!(close(convert(1.0, "lb", "g"), 453.59237));
+		assert!(close(convert(2.0, "h", "min"), 120.0));
+		assert!(close(convert(1.0, "GiB", "MB"), 1073.741824));
+		assert!(close(convert(1.0, "B", "bit"), 8.0));
+	}
+
+	#[test]
+	fn temperatures_are_affine() {
+		assert!(close(convert(32.0, "F", "C"), 0.0));
+		assert!(close(convert(212.0, "f", "c"), 100.0));
+		assert!(close(convert(-40.0, "C", "F"), -40.0));
+		assert!(close(convert(0.0, "K", "C"), -273.15));
+		assert!(close(convert(300.0, "kelvin", "fahrenheit"), 80.33));
+	}
+
+	#[test]
+	fn conversions_are_called_by_name_and_checked() {
+		let context = Context::new();
+		assert!(close(context.eval("convert(100, \"km\", \"mi\")").unwrap(), 62.1371192237334));
+		assert!(close(context.eval("convert(32, f, c) + 1").unwrap(), 1.0));
+		let err = context.eval("convert(1, km, kg)").unwrap_err();
+		assert!(matches!(err, EvalError::IncompatibleUnits { .. }));
+		assert_eq!(err.to_string(), "cannot convert km (length) to kg (mass)");
+		assert!(matches!(context.eval("convert(1, km, parsec)"), Err(EvalError::UnknownUnit(name, _)) if name == "parsec"));
+		assert!(matches!(context.eval("convert(1, 2, km)"), Err(EvalError::ExpectedUnit(_))));
+		assert!(find_unit("KM").is_none());
+		assert!(UNITS.iter().all(|unit| unit.offset == 0.0 || unit.dimension == Dimension::Temperature));
+	}
+}
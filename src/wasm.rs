@@ -0,0 +1,77 @@
+//! The surface for WebAssembly bindings, built with the `wasm` feature. It only uses types
+//! `wasm-bindgen` can pass to JavaScript, so binding it means adding `#[wasm_bindgen]` to each item
+//! and converting [`ErrorInfo`] into a `JsValue`. Nothing here reads a clock, spawns a thread or
+//! touches a terminal, which `wasm32-unknown-unknown` lacks.
+
+use alloc::string::{String, ToString};
+
+use crate::{Context, EvalError, Span};
+
+/// Why an evaluation failed: the message of the error and the part of the input it is about, if
+/// any.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorInfo {
+	pub message: String,
+	pub span: Option<Span>
+}
+impl From<EvalError> for ErrorInfo {
+	fn from(err: EvalError) -> Self {
+		Self { message: err.to_string(), span: err.span() }
+	}
+}
+
+/// Evaluates `expression` in a fresh context.
+pub fn evaluate(expression: &str) -> Result<f64, ErrorInfo> {
+	Ok(Context::new().eval(expression)?)
+}
+
+/// A context kept between calls, so that variables and `ans` persist.
+#[derive(Clone, Debug, Default)]
+pub struct Calculator {
+	context: Context
+}
+impl Calculator {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Evaluates an expression or assignment, giving its value.
+	pub fn eval(&mut self, statement: &str) -> Result<f64, ErrorInfo> {
+		Ok(self.context.execute(statement)?.value())
+	}
+
+	pub fn set_var(&mut self, name: &str, value: f64) {
+		self.context.set_var(name, value);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn expressions_evaluate_to_numbers() {
+		assert_eq!(evaluate("2 + 3 * 4"), Ok(14.0));
+		assert_eq!(evaluate("max(1, sqrt(16)) ^ 2"), Ok(16.0));
+		assert_eq!(evaluate("(1 + 2) * pi / pi"), Ok(3.0));
+	}
+
+	#[test]
+	fn an_error_carries_its_message_and_span() {
+		let err = evaluate("2 * ").unwrap_err();
+		assert_eq!(err.message, EvalError::IncompleteExpression('*', Span::at(2, '*')).to_string());
+		assert_eq!(err.span, Some(Span::at(2, '*')));
+		let err = evaluate("1 + y").unwrap_err();
+		assert_eq!((err.message.as_str(), err.span), ("unknown identifier 'y'", Some(Span::new(4, 5))));
+	}
+
+	#[test]
+	fn a_calculator_keeps_its_variables_between_calls() {
+		let mut calculator = Calculator::new();
+		calculator.set_var("rate", 0.5);
+		assert_eq!(calculator.eval("x = 8 * rate"), Ok(4.0));
+		assert_eq!(calculator.eval("x * 2"), Ok(8.0));
+		assert_eq!(calculator.eval("ans + x"), Ok(12.0));
+		assert!(Calculator::new().eval("x").is_err());
+	}
+}
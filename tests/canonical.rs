@@ -0,0 +1,66 @@
+use shunting_yard::{Context, Expression, Locale};
+
+const CORPUS: &str = include_str!("golden/canonical.txt");
+
+fn context(locale: char) -> Context {
+	let mut context = Context::new();
+	if locale == ',' {
+		context.display_mut().locale = Locale::new(',', Some('.')).unwrap();
+	}
+	context.set_var("x", 2.5);
+	context.set_var("y", 4.0);
+	context
+}
+
+/// The RPN of `expression`, written out since tokens cannot be compared.
+fn rpn(context: &Context, expression: &str) -> String {
+	format!("{:?}", context.to_postfix(expression).unwrap())
+}
+
+/// Every input parses to the RPN its canonical text parses to in the default locale, which is what
+/// makes the text safe to store.
+#[test]
+fn the_corpus_normalizes_to_its_golden_text() {
+	let mut canonical: Option<(&str, f64)> = None;
+	for line in CORPUS.lines().filter(|line| !line.is_empty() && !line.starts_with('#')) {
+		let (marker, rest) = line.split_at(1);
+		let rest = rest.strip_prefix(' ').unwrap_or(rest);
+		match marker {
+			"=" => {
+				let context = context('.');
+				assert_eq!(context.to_canonical_string(rest).unwrap(), rest, "{}", line);
+				canonical = Some((rest, context.eval(rest).unwrap()));
+			}
+			"." | "," => {
+				let (text, value) = canonical.expect("an input before any canonical text");
+				let context = context(marker.chars().next().unwrap());
+				assert_eq!(context.to_canonical_string(rest).unwrap(), text, "{}", line);
+				assert_eq!(context.eval(rest).unwrap(), value, "{}", line);
+				assert_eq!(rpn(&context, rest), rpn(&self::context('.'), text), "{}", line);
+			}
+			"!" => {
+				let (input, kind) = rest.rsplit_once(' ').unwrap();
+				assert_eq!(context('.').to_canonical_string(input).map_err(|err| err.kind()), Err(kind), "{}", line);
+			}
+			_ => panic!("unknown line '{}'", line)
+		}
+	}
+}
+
+#[test]
+fn names_need_not_be_defined() {
+	let context = Context::new();
+	assert_eq!(context.to_canonical_string("$4+?w/ans*z").unwrap(), "$4 + ?w / ans * z");
+	assert!(context.eval("$4+?w/ans*z").is_err());
+}
+
+#[test]
+fn a_stored_expression_reads_back_the_same_in_any_locale() {
+	let typed = context(',');
+	let expression = Expression::parse("x*2,5 + max(y; 1)", &typed).unwrap();
+	let text = expression.to_canonical_string(&typed).unwrap();
+	assert_eq!(text, "x * 2.5 + max(y, 1)");
+	let stored = Expression::parse(&text, &context('.')).unwrap();
+	assert_eq!(stored.eval(&context('.')).unwrap(), expression.eval(&typed).unwrap());
+	assert_eq!(rpn(&context('.'), stored.as_str()), rpn(&typed, expression.as_str()));
+}
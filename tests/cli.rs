@@ -0,0 +1,142 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+#[allow(dead_code)]
+#[path = "../src/json.rs"]
+mod json;
+
+use json::Value;
+
+/// Runs `syc` with `args` and `stdin`, without a config file. Output is piped, so it has no colors.
+fn syc(args: &[&str], stdin: &str) -> Output {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_syc"))
+		.args(args)
+		.env("XDG_CONFIG_HOME", env!("CARGO_TARGET_TMPDIR"))
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+	child.wait_with_output().unwrap()
+}
+
+fn stdout(output: &Output) -> &str {
+	std::str::from_utf8(&output.stdout).unwrap()
+}
+
+fn stderr(output: &Output) -> &str {
+	std::str::from_utf8(&output.stderr).unwrap()
+}
+
+#[test]
+fn every_expression_is_evaluated_despite_errors() {
+	let output = syc(&["-e", "1 +", "-e", "x", "-e", "2 * 3"], "");
+	assert_eq!(stdout(&output), "6\n");
+	assert!(stderr(&output).contains("incomplete expression"));
+	assert!(stderr(&output).contains("unknown identifier 'x'"));
+	assert_eq!(output.status.code(), Some(2));
+	let output = syc(&["-e", "1 + 1", "-e", "2 * 3"], "");
+	assert_eq!((stdout(&output), output.status.code()), ("2\n6\n", Some(0)));
+}
+
+#[test]
+fn fail_fast_stops_at_the_first_error() {
+	let output = syc(&["--fail-fast", "-e", "1", "-e", "x", "-e", "2 * 3"], "");
+	assert_eq!(stdout(&output), "1\n");
+	assert!(!stderr(&output).contains("2 * 3"));
+	assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn global_flags_go_on_either_side_of_the_subcommand() {
+	for args in [["--money", "eval", "1.005 * 3"], ["eval", "--money", "1.005 * 3"]] {
+		let output = syc(&args, "");
+		assert_eq!((stdout(&output), output.status.code()), ("3.03\n", Some(0)), "{:?}", args);
+	}
+	let output = syc(&["--degrees", "run", "-"], "sin(90)\n");
+	assert_eq!(stdout(&output), "1\n");
+	let output = syc(&["--jobs", "2", "eval", "1"], "");
+	assert!(stderr(&output).contains("'--jobs' cannot be used with 'syc eval'"));
+	assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn fixed_takes_its_decimals_as_the_next_argument() {
+	assert_eq!(stdout(&syc(&["--format", "fixed", "3", "-e", "1/3"], "")), "0.333\n");
+	assert_eq!(stdout(&syc(&["--format", "fixed 4", "-e", "1/3"], "")), "0.3333\n");
+	assert_eq!(stdout(&syc(&["--format", "fixed", "-e", "1/3"], "")), "0.33\n");
+	assert_eq!(stdout(&syc(&["--format", "fixed", "-"], "1/3\n")), "0.33\n");
+	let output = syc(&["--format", "fixed", "30", "-e", "1"], "");
+	assert!(stderr(&output).contains("expected 0 to 17"));
+}
+
+#[test]
+fn a_csv_header_is_passed_through() {
+	let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("header.csv");
+	std::fs::write(&path, "a,b,expr\n2,3,c1*c2\n4,x,c1+c2\n").unwrap();
+	let path = path.to_str().unwrap();
+	let output = syc(&["--csv", path, "--expr-col", "3", "--header"], "");
+	assert_eq!(stdout(&output), "a,b,expr,result\n2,3,c1*c2,6\n4,x,c1+c2,error: cell c2 is not a number\n");
+	assert_eq!(output.status.code(), Some(2));
+	let output = syc(&["--csv", path, "--expr-col", "3", "--header", "--fail-fast"], "");
+	assert_eq!(stdout(&output), "a,b,expr,result\n2,3,c1*c2,6\n");
+	assert!(stderr(&output).ends_with(":3: cell c2 is not a number\n"));
+	let output = syc(&["--csv", path, "--expr-col", "3"], "");
+	assert!(stdout(&output).starts_with("a,b,expr,error: "));
+}
+
+#[test]
+fn json_lines_parse_with_their_fields() {
+	let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("json.calc");
+	std::fs::write(&path, "2*(3+4)\n2*(\n").unwrap();
+	let runs = [
+		syc(&["--json", "-e", "2*(3+4)", "-e", "2*("], ""),
+		syc(&["--json", path.to_str().unwrap()], ""),
+		syc(&["--json"], "2*(3+4)\n2*(\n")
+	];
+	for output in &runs {
+		let lines: Vec<Value> = stdout(output).lines().map(|line| json::parse(line).unwrap()).collect();
+		assert_eq!(lines.len(), 2);
+		assert_eq!(lines[0].get("input"), Some(&Value::String("2*(3+4)".to_string())));
+		assert_eq!(lines[0].get("ok"), Some(&Value::Boolean(true)));
+		assert_eq!(lines[0].get("value"), Some(&Value::Number(14.0)));
+		assert_eq!(lines[1].get("ok"), Some(&Value::Boolean(false)));
+		let error = lines[1].get("error").unwrap();
+		assert_eq!(error.get("kind"), Some(&Value::String("MismatchedParenthesis".to_string())));
+		assert_eq!(error.get("pos"), Some(&Value::Number(2.0)));
+		assert!(matches!(error.get("message"), Some(Value::String(message)) if !message.is_empty()));
+		assert_eq!(output.status.code(), Some(2));
+	}
+	let output = syc(&["--json", "repl"], "");
+	assert!(stderr(&output).contains("'--json' cannot be used with 'syc repl'"));
+}
+
+#[test]
+fn exit_codes_tell_usage_errors_from_failed_expressions() {
+	let code = |args: &[&str], stdin: &str| syc(args, stdin).status.code();
+	assert_eq!(code(&["-e", "2 * 3"], ""), Some(0));
+	assert_eq!(code(&["-e", "2 *"], ""), Some(2));
+	assert_eq!(code(&["-"], "1\n2 +\n3\n"), Some(2));
+	assert_eq!(code(&["--fail-fast", "-"], "x\n1\n"), Some(2));
+	assert_eq!(code(&["--no-such-flag"], ""), Some(1));
+	assert_eq!(code(&["--precision"], ""), Some(1));
+	assert_eq!(code(&["no/such/file.calc"], ""), Some(1));
+	assert_eq!(code(&["repl", "--dumb-terminal", "--no-history"], "x\n:quit\n"), Some(0));
+	assert_eq!(code(&["repl", "--dumb-terminal", "--no-history"], "1 +\n"), Some(0));
+}
+
+#[test]
+fn a_summary_leaves_stdout_to_the_results() {
+	let input = "10\n1 +\nx = -5\nsqrt(\n2 * 3\n";
+	let output = syc(&["run", "--summary"], input);
+	assert_eq!(stdout(&output), "10\n6\n");
+	let summary: Vec<&str> = stderr(&output).lines().skip(2).collect();
+	assert_eq!(&summary[.. 2], ["5 lines, 3 ok, 2 failed", "min -5, max 10, mean 3.66666666667"]);
+	assert!(summary[2].starts_with("total time ") && summary[3 ..].len() == 3, "{:?}", summary);
+	let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("summary.txt");
+	let output = syc(&["run", "--summary-file", path.to_str().unwrap()], input);
+	assert_eq!(stdout(&output), "10\n6\n");
+	assert_eq!(stderr(&output).lines().count(), 2);
+	assert!(std::fs::read_to_string(&path).unwrap().starts_with("5 lines, 3 ok, 2 failed\n"));
+}
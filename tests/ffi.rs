@@ -0,0 +1,77 @@
+//! Calls the C interface through declarations as a C program would see them, from `syc.h`.
+
+use std::ffi::{c_char, CStr};
+use std::ptr;
+
+use shunting_yard::ffi::*;
+
+#[repr(C)]
+struct Opaque {
+	_private: [u8; 0]
+}
+
+extern "C" {
+	#[link_name = "syc_eval"]
+	fn eval(expr: *const c_char, out: *mut f64) -> i32;
+	#[link_name = "syc_last_error_message"]
+	fn last_error_message() -> *const c_char;
+	#[link_name = "syc_context_new"]
+	fn context_new() -> *mut Opaque;
+	#[link_name = "syc_context_free"]
+	fn context_free(ctx: *mut Opaque);
+	#[link_name = "syc_context_set_var"]
+	fn context_set_var(ctx: *mut Opaque, name: *const c_char, value: f64) -> i32;
+	#[link_name = "syc_context_eval"]
+	fn context_eval(ctx: *mut Opaque, expr: *const c_char, out: *mut f64) -> i32;
+}
+
+fn last_message() -> String {
+	unsafe { CStr::from_ptr(last_error_message()) }.to_str().unwrap().to_string()
+}
+
+#[test]
+fn expressions_evaluate_to_a_value_or_a_code() {
+	let mut out = 0.0;
+	unsafe {
+		assert_eq!(eval(c"2 * (3 + 4)".as_ptr(), &mut out), SYC_OK);
+		assert_eq!(out, 14.0);
+		assert_eq!(eval(c"2 * (".as_ptr(), &mut out), SYC_ERR_MISMATCHED_PARENTHESIS);
+		assert_eq!(last_message(), "mismatched parenthesis");
+		assert_eq!(eval(c"2 +".as_ptr(), &mut out), SYC_ERR_INCOMPLETE_EXPRESSION);
+		assert_eq!(eval(c"sqr(4)".as_ptr(), &mut out), SYC_ERR_UNKNOWN_IDENTIFIER);
+		assert!(last_message().starts_with("unknown identifier 'sqr'"));
+		assert_eq!(eval(c"1 $ 2".as_ptr(), &mut out), SYC_ERR_INVALID_CHARACTER);
+		assert_eq!(out, 14.0);
+	}
+}
+
+#[test]
+fn bad_arguments_have_their_own_codes() {
+	let mut out = 0.0;
+	let invalid = b"1 + \xff\0";
+	unsafe {
+		assert_eq!(eval(invalid.as_ptr().cast(), &mut out), SYC_ERR_INVALID_UTF8);
+		assert!(last_message().starts_with("invalid UTF-8"));
+		assert_eq!(eval(ptr::null(), &mut out), SYC_ERR_NULL_POINTER);
+		assert_eq!(eval(c"1".as_ptr(), ptr::null_mut()), SYC_ERR_NULL_POINTER);
+		assert_eq!(context_set_var(ptr::null_mut(), c"x".as_ptr(), 1.0), SYC_ERR_NULL_POINTER);
+		context_free(ptr::null_mut());
+	}
+}
+
+#[test]
+fn variables_persist_in_a_context() {
+	let mut out = 0.0;
+	unsafe {
+		let ctx = context_new();
+		assert_eq!(context_set_var(ctx, c"x".as_ptr(), 21.0), SYC_OK);
+		assert_eq!(context_eval(ctx, c"x * 2".as_ptr(), &mut out), SYC_OK);
+		assert_eq!(out, 42.0);
+		assert_eq!(context_eval(ctx, c"y = ans + 1".as_ptr(), &mut out), SYC_OK);
+		assert_eq!(context_eval(ctx, c"y".as_ptr(), &mut out), SYC_OK);
+		assert_eq!(out, 43.0);
+		assert_eq!(context_set_var(ctx, c"2x".as_ptr(), 1.0), SYC_ERR_INVALID_NAME);
+		assert_eq!(context_eval(ctx, c"z".as_ptr(), &mut out), SYC_ERR_UNKNOWN_IDENTIFIER);
+		context_free(ctx);
+	}
+}
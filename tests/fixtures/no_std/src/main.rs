@@ -0,0 +1,14 @@
+use std::io::{self, BufRead};
+
+use shunting_yard::Context;
+
+/// Prints the bits of the value of every line, or the error it gives.
+fn main() {
+	let context = Context::new();
+	for line in io::stdin().lock().lines() {
+		match context.eval(&line.unwrap()) {
+			Ok(value) => println!("{:x}", value.to_bits()),
+			Err(err) => println!("error: {}", err)
+		}
+	}
+}
@@ -0,0 +1,194 @@
+//! Runs `syc` on the scenarios in `tests/golden/cli`, one per `.txt` file, and compares everything
+//! it writes exactly. A scenario is made of lines starting with a marker and a space:
+//!
+//! - `$ ARGS`: the arguments, split at spaces except within double quotes
+//! - `< LINE`: a line of stdin
+//! - `> LINE`: a line the run writes to stdout
+//! - `! LINE`: a line the run writes to stderr
+//! - `? CODE`: the exit code, 0 when left out
+//! - `# TEXT`: a comment
+//!
+//! A last line written without a line ending is compared as if it had one. The runs have no
+//! config file, history or colors, and their times are all 0. With `SYC_BLESS` set the scenarios
+//! are rewritten with what the runs wrote instead of compared.
+
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+const SCENARIOS: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/cli");
+
+#[derive(Debug, Default, PartialEq)]
+struct Scenario {
+	comments: Vec<String>,
+	args: String,
+	stdin: String,
+	stdout: String,
+	stderr: String,
+	code: i32
+}
+
+fn parse(text: &str) -> Result<Scenario, String> {
+	let mut scenario = Scenario::default();
+	for (number, line) in text.lines().enumerate() {
+		let (marker, rest) = line.split_at(line.len().min(1));
+		let rest = rest.strip_prefix(' ').unwrap_or(rest);
+		match marker {
+			"#" => scenario.comments.push(rest.to_string()),
+			"$" => scenario.args = rest.to_string(),
+			"<" => writeln!(scenario.stdin, "{}", rest).unwrap(),
+			">" => writeln!(scenario.stdout, "{}", rest).unwrap(),
+			"!" => writeln!(scenario.stderr, "{}", rest).unwrap(),
+			"?" => scenario.code = rest.parse().map_err(|_| format!("line {}: invalid exit code '{}'", number + 1, rest))?,
+			"" => {}
+			_ => return Err(format!("line {}: unknown marker '{}'", number + 1, marker))
+		}
+	}
+	Ok(scenario)
+}
+
+/// The scenario file that `scenario` was read from, with its lines in the usual order.
+fn write(scenario: &Scenario) -> String {
+	let mut text = String::new();
+	for comment in &scenario.comments {
+		writeln!(text, "# {}", comment).unwrap();
+	}
+	writeln!(text, "$ {}", scenario.args).unwrap();
+	for (marker, lines) in [('<', &scenario.stdin), ('>', &scenario.stdout), ('!', &scenario.stderr)] {
+		for line in lines.lines() {
+			writeln!(text, "{} {}", marker, line).unwrap();
+		}
+	}
+	if scenario.code != 0 {
+		writeln!(text, "? {}", scenario.code).unwrap();
+	}
+	// A line is kept without its trailing space, which the marker's space stands for
+	text.lines().map(|line| format!("{}\n", if line.len() == 2 { line.trim_end() } else { line })).collect()
+}
+
+fn split_args(args: &str) -> Vec<String> {
+	let mut split = Vec::new();
+	let mut current: Option<String> = None;
+	let mut quoted = false;
+	for c in args.chars() {
+		match c {
+			'"' => {
+				quoted = !quoted;
+				current.get_or_insert_with(String::new);
+			}
+			' ' if !quoted => split.extend(current.take()),
+			_ => current.get_or_insert_with(String::new).push(c)
+		}
+	}
+	split.extend(current);
+	split
+}
+
+/// Runs the scenario's arguments and stdin, giving what was written and the exit code.
+fn run(scenario: &Scenario, home: &Path) -> Scenario {
+	let mut child = Command::new(env!("CARGO_BIN_EXE_syc"))
+		.args(split_args(&scenario.args))
+		.current_dir(SCENARIOS)
+		.env("HOME", home)
+		.env("XDG_CONFIG_HOME", home)
+		.env("XDG_STATE_HOME", home)
+		.env("NO_COLOR", "1")
+		.env("SYC_FROZEN_CLOCK", "1")
+		.env_remove("TERM")
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(scenario.stdin.as_bytes()).unwrap();
+	let output = child.wait_with_output().unwrap();
+	let text = |bytes: &[u8]| {
+		let mut text = String::from_utf8_lossy(bytes).into_owned();
+		if !text.is_empty() && !text.ends_with('\n') {
+			text.push('\n');
+		}
+		text
+	};
+	Scenario {
+		comments: scenario.comments.clone(),
+		args: scenario.args.clone(),
+		stdin: scenario.stdin.clone(),
+		stdout: text(&output.stdout),
+		stderr: text(&output.stderr),
+		code: output.status.code().unwrap_or(-1)
+	}
+}
+
+/// The lines of `expected` and `actual` side by side from the first one that differs, `-` for the
+/// expected line and `+` for the one written.
+fn diff(name: &str, expected: &str, actual: &str, report: &mut String) {
+	if expected == actual {
+		return;
+	}
+	let (expected, actual): (Vec<&str>, Vec<&str>) = (expected.lines().collect(), actual.lines().collect());
+	let first = expected.iter().zip(&actual).take_while(|(expected, actual)| expected == actual).count();
+	writeln!(report, "  {} differs from line {}:", name, first + 1).unwrap();
+	for line in &expected[first ..] {
+		writeln!(report, "    - {}", line).unwrap();
+	}
+	for line in &actual[first ..] {
+		writeln!(report, "    + {}", line).unwrap();
+	}
+}
+
+fn scenarios() -> Vec<PathBuf> {
+	let mut paths: Vec<PathBuf> = fs::read_dir(SCENARIOS).unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| path.extension().is_some_and(|extension| extension == "txt"))
+		.collect();
+	paths.sort();
+	paths
+}
+
+#[test]
+fn scenarios_write_what_they_expect() {
+	let home = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden-home");
+	fs::create_dir_all(&home).unwrap();
+	let bless = env::var_os("SYC_BLESS").is_some_and(|value| !value.is_empty());
+	let paths = scenarios();
+	assert!(!paths.is_empty(), "no scenarios in {}", SCENARIOS);
+	let mut report = String::new();
+	for path in &paths {
+		let name = path.file_name().unwrap().to_string_lossy();
+		let expected = match parse(&fs::read_to_string(path).unwrap()) {
+			Ok(scenario) => scenario,
+			Err(message) => {
+				writeln!(report, "{}: {}", name, message).unwrap();
+				continue;
+			}
+		};
+		let actual = run(&expected, &home);
+		if bless {
+			fs::write(path, write(&actual)).unwrap();
+			continue;
+		}
+		if actual != expected {
+			writeln!(report, "{} ($ {}):", name, expected.args).unwrap();
+			diff("stdout", &expected.stdout, &actual.stdout, &mut report);
+			diff("stderr", &expected.stderr, &actual.stderr, &mut report);
+			if actual.code != expected.code {
+				writeln!(report, "  exit code {} instead of {}", actual.code, expected.code).unwrap();
+			}
+		}
+	}
+	assert!(report.is_empty(), "{} scenarios, rerun with SYC_BLESS=1 to accept the output\n{}", paths.len(), report);
+}
+
+#[test]
+fn scenarios_are_read_as_written() {
+	let scenario = parse("# one\n$ -e \"1 + 2\" --no-color\n< x\n> 3\n>\n! warning\n? 2\n").unwrap();
+	assert_eq!(split_args(&scenario.args), ["-e", "1 + 2", "--no-color"]);
+	assert_eq!((scenario.stdin.as_str(), scenario.stdout.as_str(), scenario.stderr.as_str()), ("x\n", "3\n\n", "warning\n"));
+	assert_eq!(scenario.code, 2);
+	assert_eq!(parse(&write(&scenario)), Ok(scenario));
+	assert_eq!(split_args("\"\" a"), ["", "a"]);
+	assert!(parse("% 1").is_err());
+}
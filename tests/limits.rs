@@ -0,0 +1,80 @@
+//! Pathological inputs are refused as soon as a limit is passed, without reading or buffering the
+//! rest of them.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use shunting_yard::lexer::DEFAULT_TOKEN_LIMIT;
+use shunting_yard::{Context, EvalError, Span, DEFAULT_NESTING_LIMIT};
+
+/// Keeps the largest allocation made since it was last reset.
+struct Largest;
+
+static LARGEST: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Largest {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		LARGEST.fetch_max(layout.size(), Ordering::Relaxed);
+		unsafe { System.alloc(layout) }
+	}
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		unsafe { System.dealloc(ptr, layout) }
+	}
+	unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, size: usize) -> *mut u8 {
+		LARGEST.fetch_max(size, Ordering::Relaxed);
+		unsafe { System.realloc(ptr, layout, size) }
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: Largest = Largest;
+
+/// The allocations of one test are only its own.
+static SERIAL: Mutex<()> = Mutex::new(());
+
+/// Evaluates the input `make` builds, which must fail within a second and allocating no more than
+/// 1 MiB at once, however long the input.
+fn refused(make: impl FnOnce() -> String) -> EvalError {
+	let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+	let (context, input) = (Context::new(), make());
+	LARGEST.store(0, Ordering::Relaxed);
+	let start = Instant::now();
+	let err = context.eval(&input).unwrap_err();
+	let (elapsed, largest) = (start.elapsed(), LARGEST.load(Ordering::Relaxed));
+	assert!(elapsed < Duration::from_secs(1), "took {:?}", elapsed);
+	assert!(largest <= 1024 * 1024, "allocated {} bytes at once", largest);
+	err
+}
+
+#[test]
+fn a_long_literal_is_refused_at_the_limit() {
+	let err = refused(|| format!("1 + {}", "9".repeat(10 * 1024 * 1024)));
+	let limit = DEFAULT_TOKEN_LIMIT;
+	assert!(matches!(err, EvalError::TokenTooLong { span, len, limit: 4096 } if span == Span::new(4, 5 + limit) && len == limit + 1), "{:?}", err);
+}
+
+#[test]
+fn a_long_name_is_refused_at_the_limit() {
+	let err = refused(|| "x".repeat(1024 * 1024));
+	assert!(matches!(err, EvalError::TokenTooLong { limit: DEFAULT_TOKEN_LIMIT, .. }), "{:?}", err);
+}
+
+#[test]
+fn runs_of_parentheses_and_signs_are_too_deep() {
+	for run in ["(", "-", "+", "-+"] {
+		let err = refused(|| format!("{}1", run.repeat(1024 * 1024 / run.len())));
+		assert!(matches!(err, EvalError::TooDeep { limit: DEFAULT_NESTING_LIMIT, .. }), "{}: {:?}", run, err);
+	}
+}
+
+#[test]
+fn inputs_within_the_limits_still_evaluate() {
+	let _serial = SERIAL.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+	let context = Context::new();
+	assert_eq!(context.eval(&"1".repeat(DEFAULT_TOKEN_LIMIT)).unwrap(), "1".repeat(DEFAULT_TOKEN_LIMIT).parse::<f64>().unwrap());
+	let nested = format!("{}1{}", "(".repeat(100), ")".repeat(100));
+	assert_eq!(context.eval(&nested).unwrap(), 1.0);
+	assert_eq!(context.eval(&format!("{}1", "-".repeat(100))).unwrap(), 1.0);
+}
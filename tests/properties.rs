@@ -0,0 +1,389 @@
+//! Differential checks over random expressions from a seeded generator: evaluating directly, through
+//! the tree, through the RPN and through the tree written back as infix must agree, NaN included.
+//! A failing expression is shrunk before it is reported. Values are also compared with a separate
+//! evaluator that follows the usual precedence rules, which those paths could otherwise share a
+//! mistake in, and simplified expressions are checked against their originals.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use shunting_yard::{eval_postfix, simplify, to_infix, Context, EvalError, Function};
+
+const CASES: usize = 4000;
+const MAX_DEPTH: u32 = 6;
+
+/// xorshift64*, so that every run generates the same cases.
+struct Rng(u64);
+impl Rng {
+	fn next(&mut self) -> u64 {
+		self.0 ^= self.0 >> 12;
+		self.0 ^= self.0 << 25;
+		self.0 ^= self.0 >> 27;
+		self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+	}
+	fn below(&mut self, n: usize) -> usize {
+		(self.next() % n as u64) as usize
+	}
+	fn pick<T: Copy>(&mut self, items: &[T]) -> T {
+		items[self.below(items.len())]
+	}
+}
+
+/// An expression of the grammar, kept as a tree so that failures can be shrunk.
+#[derive(Clone, Debug)]
+enum Gen {
+	Number(&'static str),
+	Sign(char, Box<Gen>),
+	Binary(char, Box<Gen>, Box<Gen>),
+	Paren(Box<Gen>),
+	Call(&'static str, Vec<Gen>),
+	Percent(Box<Gen>)
+}
+const NUMBERS: [&str; 12] = ["0", "1", "2", "3", "7", "10", "0.5", "2.25", "1e3", "2.5e-2", "1E2", "100"];
+
+impl Gen {
+	fn random(rng: &mut Rng, depth: u32) -> Gen {
+		Gen::random_from(rng, depth, &NUMBERS)
+	}
+	/// A random expression whose leaves are picked from `leaves`, which may be names.
+	fn random_from(rng: &mut Rng, depth: u32, leaves: &[&'static str]) -> Gen {
+		const OPERATORS: [char; 5] = ['+', '-', '*', '/', '^'];
+		const UNARY: [&str; 9] = ["abs", "sqrt", "sin", "cos", "exp", "ln", "floor", "ceil", "round"];
+		const BINARY: [&str; 2] = ["max", "min"];
+		if depth == 0 || rng.below(4) == 0 {
+			return Gen::Number(rng.pick(leaves));
+		}
+		let sub = |rng: &mut Rng| Box::new(Gen::random_from(rng, depth - 1, leaves));
+		match rng.below(10) {
+			0 => Gen::Sign(if rng.below(3) == 0 { '+' } else { '-' }, sub(rng)),
+			1 => Gen::Paren(sub(rng)),
+			2 => Gen::Call(rng.pick(&UNARY), vec![*sub(rng)]),
+			3 => Gen::Call(rng.pick(&BINARY), vec![*sub(rng), *sub(rng)]),
+			4 => Gen::Percent(Box::new(Gen::Number(rng.pick(leaves)))),
+			_ => Gen::Binary(rng.pick(&OPERATORS), sub(rng), sub(rng))
+		}
+	}
+
+	fn write(&self, out: &mut String) {
+		match self {
+			Gen::Number(text) => out.push_str(text),
+			Gen::Sign(sign, value) => {
+				out.push(*sign);
+				value.write(out);
+			}
+			Gen::Binary(op, lhs, rhs) => {
+				lhs.write(out);
+				out.push(' ');
+				out.push(*op);
+				out.push(' ');
+				rhs.write(out);
+			}
+			Gen::Paren(value) => {
+				out.push('(');
+				value.write(out);
+				out.push(')');
+			}
+			Gen::Call(name, args) => {
+				out.push_str(name);
+				out.push('(');
+				for (i, arg) in args.iter().enumerate() {
+					if i > 0 {
+						out.push_str(", ");
+					}
+					arg.write(out);
+				}
+				out.push(')');
+			}
+			Gen::Percent(value) => {
+				value.write(out);
+				out.push('%');
+			}
+		}
+	}
+
+	fn text(&self) -> String {
+		let mut out = String::new();
+		self.write(&mut out);
+		out
+	}
+
+	/// Smaller expressions to try in place of this one: its parts, 1, and this one with one part
+	/// shrunk.
+	fn shrinks(&self) -> Vec<Gen> {
+		let mut shrinks = Vec::new();
+		if !matches!(self, Gen::Number(_)) {
+			shrinks.push(Gen::Number("1"));
+		}
+		match self {
+			Gen::Number(_) => {}
+			Gen::Sign(sign, value) => {
+				shrinks.push((**value).clone());
+				shrinks.extend(value.shrinks().into_iter().map(|value| Gen::Sign(*sign, Box::new(value))));
+			}
+			Gen::Paren(value) => {
+				shrinks.push((**value).clone());
+				shrinks.extend(value.shrinks().into_iter().map(|value| Gen::Paren(Box::new(value))));
+			}
+			Gen::Percent(value) => {
+				shrinks.extend(value.shrinks().into_iter().map(|value| Gen::Percent(Box::new(value))));
+			}
+			Gen::Binary(op, lhs, rhs) => {
+				shrinks.push((**lhs).clone());
+				shrinks.push((**rhs).clone());
+				shrinks.extend(lhs.shrinks().into_iter().map(|lhs| Gen::Binary(*op, Box::new(lhs), rhs.clone())));
+				shrinks.extend(rhs.shrinks().into_iter().map(|rhs| Gen::Binary(*op, lhs.clone(), Box::new(rhs))));
+			}
+			Gen::Call(name, args) => {
+				shrinks.extend(args.iter().cloned());
+				for (i, arg) in args.iter().enumerate() {
+					for smaller in arg.shrinks() {
+						let mut args = args.clone();
+						args[i] = smaller;
+						shrinks.push(Gen::Call(name, args));
+					}
+				}
+			}
+		}
+		shrinks
+	}
+}
+
+/// Recursive descent over the generated grammar without `%`: `+` and `-` bind loosest and `*` and
+/// `/` next, all from the left, then signs, then `^` from the right, so `-2 ^ 2` is `-(2 ^ 2)`.
+struct Reference<'a> {
+	rest: &'a str
+}
+impl Reference<'_> {
+	fn eval(text: &str) -> Option<f64> {
+		let mut reference = Reference { rest: text };
+		let value = reference.sum()?;
+		reference.rest.trim().is_empty().then_some(value)
+	}
+
+	fn eat(&mut self, c: char) -> bool {
+		self.rest = self.rest.trim_start();
+		match self.rest.strip_prefix(c) {
+			Some(rest) => {
+				self.rest = rest;
+				true
+			}
+			None => false
+		}
+	}
+
+	fn sum(&mut self) -> Option<f64> {
+		let mut value = self.product()?;
+		loop {
+			if self.eat('+') {
+				value += self.product()?;
+			} else if self.eat('-') {
+				value -= self.product()?;
+			} else {
+				return Some(value);
+			}
+		}
+	}
+
+	fn product(&mut self) -> Option<f64> {
+		let mut value = self.signed()?;
+		loop {
+			if self.eat('*') {
+				value *= self.signed()?;
+			} else if self.eat('/') {
+				value /= self.signed()?;
+			} else {
+				return Some(value);
+			}
+		}
+	}
+
+	fn signed(&mut self) -> Option<f64> {
+		if self.eat('-') {
+			return self.signed().map(|value| -value);
+		}
+		if self.eat('+') {
+			return self.signed();
+		}
+		let base = self.atom()?;
+		if self.eat('^') {
+			return Some(base.powf(self.signed()?));
+		}
+		Some(base)
+	}
+
+	fn atom(&mut self) -> Option<f64> {
+		if self.eat('(') {
+			let value = self.sum()?;
+			return self.eat(')').then_some(value);
+		}
+		self.rest = self.rest.trim_start();
+		let end = self.rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '.').unwrap_or(self.rest.len());
+		// An exponent may have a sign, `2.5e-2`
+		let end = match self.rest[.. end].ends_with(['e', 'E']) && self.rest[end ..].starts_with(['-', '+']) {
+			true => end + 1 + self.rest[end + 1 ..].find(|c: char| !c.is_ascii_digit()).unwrap_or(self.rest.len() - end - 1),
+			false => end
+		};
+		let (word, rest) = self.rest.split_at(end);
+		self.rest = rest;
+		if let Ok(value) = word.parse() {
+			return Some(value);
+		}
+		let func = Function::by_name(word)?;
+		let mut args = Vec::new();
+		if !self.eat('(') {
+			return None;
+		}
+		loop {
+			args.push(self.sum()?);
+			if self.eat(')') {
+				break;
+			}
+			if !self.eat(',') {
+				return None;
+			}
+		}
+		(args.len() == func.argc).then(|| func.resolve(&args))
+	}
+}
+
+/// Without std the library's float functions are its own, whose large powers can differ from std's
+/// in the last few digits.
+fn same(a: f64, b: f64) -> bool {
+	let error = if cfg!(feature = "std") { 0.0 } else { 1e-12 };
+	a == b || (a.is_nan() && b.is_nan()) || (a - b).abs() <= error * b.abs()
+}
+
+/// What is wrong with `expression`, if anything.
+fn check(expression: &str, context: &Context) -> Option<String> {
+	let run = panic::catch_unwind(AssertUnwindSafe(|| -> Result<Option<String>, EvalError> {
+		let value = context.eval(expression)?;
+		let ast = context.to_ast(expression)?;
+		let tree = ast.eval();
+		let postfix = eval_postfix(context.to_postfix(expression)?)?;
+		let infix = to_infix(&ast);
+		let written = context.eval(&infix);
+		Ok(match written {
+			_ if !same(tree, value) => Some(format!("the tree gives {} instead of {}", tree, value)),
+			_ if !same(postfix, value) => Some(format!("the RPN gives {} instead of {}", postfix, value)),
+			Ok(written) if same(written, value) => None,
+			Ok(written) => Some(format!("'{}' gives {} instead of {}", infix, written, value)),
+			Err(err) => Some(format!("'{}' fails: {}", infix, err))
+		})
+	}));
+	match run {
+		Ok(Ok(problem)) => problem,
+		Ok(Err(_)) => None,
+		Err(_) => Some("panicked".to_string())
+	}
+}
+
+/// The smallest expression from `expr` that still fails, by taking the first shrink that fails until
+/// none does.
+fn shrink(mut expr: Gen, context: &Context) -> (Gen, String) {
+	let mut problem = check(&expr.text(), context).unwrap();
+	'smaller: loop {
+		for smaller in expr.shrinks() {
+			if let Some(found) = check(&smaller.text(), context) {
+				(expr, problem) = (smaller, found);
+				continue 'smaller;
+			}
+		}
+		return (expr, problem);
+	}
+}
+
+#[test]
+fn every_path_agrees_on_random_expressions() {
+	let hook = panic::take_hook();
+	panic::set_hook(Box::new(|_| {}));
+	let context = Context::new();
+	let mut rng = Rng(0x5eed_1234_abcd_9876);
+	let mut failure = None;
+	let mut evaluated = 0;
+	for _ in 0 .. CASES {
+		let expr = Gen::random(&mut rng, MAX_DEPTH);
+		evaluated += context.eval(&expr.text()).is_ok() as usize;
+		if check(&expr.text(), &context).is_some() {
+			failure = Some(shrink(expr, &context));
+			break;
+		}
+	}
+	panic::set_hook(hook);
+	if let Some((expr, problem)) = failure {
+		panic!("'{}': {}", expr.text(), problem);
+	}
+	assert!(evaluated > CASES / 2, "only {} of {} expressions evaluated", evaluated, CASES);
+}
+
+#[test]
+fn random_bytes_never_panic() {
+	const BYTES: &[u8] = b"0123456789.,eE+-*/^%() $?xyz_\"'\t\n\xc2\xb1\xef\xbc\x90abcdefghijklmnopqrstuvwxyz=;:#@!";
+	let context = Context::new();
+	let mut rng = Rng(0x0ddb_a11c_afe0_0042);
+	for _ in 0 .. CASES {
+		let len = rng.below(24);
+		let bytes: Vec<u8> = (0 .. len).map(|_| rng.pick(BYTES)).collect();
+		let text = String::from_utf8_lossy(&bytes).into_owned();
+		let run = panic::catch_unwind(AssertUnwindSafe(|| {
+			let _ = context.eval(&text);
+			let _ = context.to_ast(&text).map(|ast| to_infix(&ast));
+			let _ = context.to_canonical_string(&text);
+		}));
+		assert!(run.is_ok(), "{:?} panicked", text);
+	}
+}
+
+#[test]
+fn failures_shrink_to_a_smaller_expression() {
+	let expr = Gen::Binary('+', Box::new(Gen::Call("sqrt", vec![Gen::Number("2")])), Box::new(Gen::Number("3")));
+	let shrinks: Vec<String> = expr.shrinks().iter().map(Gen::text).collect();
+	assert_eq!(&shrinks[.. 3], ["1", "sqrt(2)", "3"]);
+	assert!(shrinks.contains(&"1 + 3".to_string()));
+	assert!(shrinks.iter().all(|text| text.len() < expr.text().len()));
+}
+
+#[test]
+fn values_follow_the_usual_precedence_rules() {
+	let context = Context::new();
+	let mut rng = Rng(0x00c0_ffee_1234_5678);
+	let mut compared = 0;
+	for _ in 0 .. CASES {
+		let text = Gen::random(&mut rng, MAX_DEPTH).text();
+		if text.contains('%') {
+			continue;
+		}
+		let (Ok(value), Some(expected)) = (context.eval(&text), Reference::eval(&text)) else {
+			continue;
+		};
+		assert!(same(value, expected), "'{}' gives {} instead of {}", text, value, expected);
+		compared += 1;
+	}
+	assert!(compared > CASES / 4, "only {} of {} expressions compared", compared, CASES);
+	for (text, expected) in [("1 - 2 + 3", 2.0), ("8 / 2 * 4", 16.0), ("2 - 3 - 4", -5.0), ("-2 ^ 2", -4.0), ("2 ^ 3 ^ 2", 512.0)] {
+		assert_eq!((context.eval(text).unwrap(), Reference::eval(text)), (expected, Some(expected)), "{}", text);
+	}
+}
+
+#[test]
+fn simplifying_never_changes_the_value() {
+	const LEAVES: [&str; 10] = ["0", "1", "2", "0.5", "10", "2.5e-2", "x", "y", "x", "y"];
+	const BINDINGS: [f64; 8] = [-3.5, -1.0, 0.0, 0.25, 1.0, 2.0, 10.0, 1e3];
+	let close = |a: f64, b: f64| same(a, b) || (a - b).abs() <= 1e-12 * a.abs().max(b.abs());
+	let mut rng = Rng(0x51e0_1f7a_0bad_cafe);
+	let mut compared = 0;
+	for _ in 0 .. CASES {
+		let mut context = Context::new();
+		context.set_var("x", rng.pick(&BINDINGS));
+		context.set_var("y", rng.pick(&BINDINGS));
+		let text = Gen::random_from(&mut rng, MAX_DEPTH, &LEAVES).text();
+		let Ok(ast) = context.to_ast(&text) else {
+			continue;
+		};
+		let (value, simplified) = (ast.eval_in(&context), simplify(&ast));
+		let infix = to_infix(&simplified);
+		assert!(close(simplified.eval_in(&context), value), "'{}' gives {} but '{}' gives {}", text, value, infix, simplified.eval_in(&context));
+		let written = context.eval(&infix).unwrap_or_else(|err| panic!("'{}' from '{}' fails: {}", infix, text, err));
+		assert!(close(written, value), "'{}' gives {} but '{}' reads back as {}", text, value, infix, written);
+		compared += 1;
+	}
+	assert!(compared > CASES / 2, "only {} of {} expressions compared", compared, CASES);
+}
@@ -0,0 +1,63 @@
+//! Inputs 100k levels deep fail with an error instead of overflowing the stack, and the walks of
+//! a tree within the nesting limit all finish.
+
+use shunting_yard::{simplify, to_ast, to_dot, to_infix, to_latex, Context, EvalError, Expr, DEFAULT_NESTING_LIMIT};
+
+const DEPTH: usize = 100_000;
+
+/// `1 + 1 + ...` with `terms` terms, a flat input whose tree is `terms` levels deep.
+fn sum(terms: usize) -> String {
+	vec!["1"; terms].join(" + ")
+}
+
+#[test]
+fn deep_inputs_are_refused_by_every_entry_point() {
+	let context = Context::new();
+	assert!(matches!(to_ast(&sum(DEPTH), &context), Err(EvalError::RecursionLimit { limit: 1024 })));
+	assert!(matches!(context.compile(&sum(DEPTH)), Err(EvalError::RecursionLimit { .. })));
+	let parens = format!("{}1{}", "(".repeat(DEPTH), ")".repeat(DEPTH));
+	let signs = format!("{}1", "-".repeat(DEPTH));
+	for input in [&parens, &signs] {
+		assert!(matches!(to_ast(input, &context), Err(EvalError::TooDeep { limit: 1024, .. })), "{}", &input[.. 8]);
+		assert!(matches!(context.eval(input), Err(EvalError::TooDeep { .. })));
+		assert!(matches!(context.to_canonical_string(input), Err(EvalError::TooDeep { .. })));
+	}
+	let forms = format!("{}k{}", "sum_over(k, 1, 1, ".repeat(5000), ")".repeat(5000));
+	assert!(matches!(context.eval(&forms), Err(EvalError::TooDeep { .. })));
+	assert!(matches!(context.to_canonical_string(&forms), Err(EvalError::TooDeep { .. })));
+}
+
+#[test]
+fn a_deep_tree_still_evaluates_without_recursing() {
+	let context = Context::new();
+	assert_eq!(context.eval(&sum(DEPTH)).unwrap(), DEPTH as f64);
+	assert_eq!(context.to_canonical_string(&sum(DEPTH)).unwrap(), sum(DEPTH));
+}
+
+#[test]
+fn every_walk_of_a_tree_at_the_limit_finishes() {
+	let context = Context::new();
+	let terms = DEFAULT_NESTING_LIMIT - 1;
+	let ast = to_ast(&sum(terms), &context).unwrap();
+	let flat = sum(terms).replace(' ', "");
+	assert_eq!((to_infix(&ast), to_latex(&ast)), (flat.clone(), flat));
+	assert!(to_dot(&ast).starts_with("digraph"));
+	assert!(matches!(simplify(&ast), Expr::Number(value) if value == terms as f64));
+	assert_eq!(context.compile(&sum(terms)).unwrap().eval(&[]).unwrap(), terms as f64);
+	let signs = format!("{}1", "-".repeat(terms - 1));
+	let ast = to_ast(&signs, &context).unwrap();
+	assert!(to_infix(&ast).starts_with("-(-(") && to_latex(&ast).starts_with("-(-("));
+	let dot = to_dot(&ast);
+	assert!(dot.contains("\tn1021 -> n1022;\n") && dot.ends_with("\tn0 -> n1;\n}\n"));
+	assert!(matches!(simplify(&ast), Expr::Number(value) if value == 1.0));
+}
+
+#[test]
+fn the_nesting_limit_is_the_one_knob() {
+	let mut context = Context::new();
+	context.set_nesting_limit(10);
+	assert!(matches!(to_ast(&sum(20), &context), Err(EvalError::RecursionLimit { limit: 10 })));
+	assert!(matches!(context.eval(&format!("{}1", "(".repeat(20))), Err(EvalError::TooDeep { limit: 10, .. })));
+	context.set_nesting_limit(2000);
+	assert!(to_ast(&sum(1500), &context).is_ok());
+}